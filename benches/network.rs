@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use tokio::runtime::Runtime;
+use yave::assets::Identifier;
+use yave::network::compression::{self, DEFAULT_THRESHOLD};
+use yave::network::crypto::{Handshake, Role};
+use yave::network::transport::{Transport, UdpTransport};
+use yave::network::Packet;
+use yave::world::chunk::{Block, Chunk};
+use yave::world::movement::MovementInput;
+
+/// Packets sent in one iteration of the throughput benchmarks. Few enough to fit in the socket
+/// buffers, so none is dropped on localhost.
+const BATCH: u32 = 256;
+
+/// Build a chunk with hills of grass on top of dirt and stone, with air above.
+fn terrain(chunk_x: i64, chunk_y: i64) -> Chunk {
+    let mut chunk = Chunk::new(chunk_x, chunk_y);
+
+    for block in chunk.blocks.iter_mut() {
+        let (x, y, z) = (block.x() as i64, block.y() as i64, block.z() as i64);
+        let height = 8 + ((x + chunk_x * 16) * 3 + (z + chunk_y * 16) * 5).rem_euclid(5);
+
+        let (name, solid) = if y > height {
+            ("air", false)
+        } else if y == height {
+            ("grass", true)
+        } else if y > height - 3 {
+            ("dirt", true)
+        } else {
+            ("stone", true)
+        };
+
+        *block = Block::new(
+            x as u16,
+            y as u16,
+            z as u16,
+            Identifier::new("base", name),
+            solid,
+        );
+    }
+
+    chunk
+}
+
+/// Size of a chunk packet in the previous wire format, which repeated the block id for every run.
+fn string_runs_size(chunk: &Chunk) -> usize {
+    let mut size = 1 + 8 + 8 + 8;
+    let mut last: Option<&str> = None;
+
+    for block in chunk.blocks.iter() {
+        if last != Some(block.id.as_str()) {
+            size += 8 + block.id.len() + 4;
+            last = Some(block.id.as_str());
+        }
+    }
+
+    size
+}
+
+pub fn chunk_size(c: &mut Criterion) {
+    let chunks: Vec<Chunk> = (-2..2)
+        .flat_map(|x| (-2..2).map(move |y| terrain(x, y)))
+        .collect();
+
+    let packets: Vec<Vec<u8>> = chunks
+        .iter()
+        .map(|chunk| {
+            Packet::Chunk {
+                x: chunk.x,
+                y: chunk.y,
+                data: chunk.compress(),
+            }
+            .encode()
+            .unwrap()
+        })
+        .collect();
+
+    let before: usize = chunks.iter().map(string_runs_size).sum();
+    let palette: usize = packets.iter().map(|packet| packet.len()).sum();
+    let compressed: usize = packets
+        .iter()
+        .map(|packet| {
+            compression::compress(packet, Some(DEFAULT_THRESHOLD))
+                .unwrap()
+                .len()
+        })
+        .sum();
+
+    println!(
+        "Bytes per chunk: {} with string runs, {} with a palette, {} with a palette and compression",
+        before / chunks.len(),
+        palette / chunks.len(),
+        compressed / chunks.len()
+    );
+
+    c.bench_function("Chunk packet encoding", |b| {
+        b.iter(|| {
+            Packet::Chunk {
+                x: chunks[0].x,
+                y: chunks[0].y,
+                data: chunks[0].compress(),
+            }
+            .encode()
+            .unwrap();
+        })
+    });
+
+    c.bench_function("Chunk packet compression", |b| {
+        b.iter(|| {
+            compression::compress(&packets[0], Some(DEFAULT_THRESHOLD)).unwrap();
+        })
+    });
+}
+
+/// Send a batch of input packets from a client to a server on localhost and receive them all.
+fn send_batch(client: &UdpTransport, server: &UdpTransport) {
+    let server_addr = server.local_addr().unwrap();
+
+    for sequence in 0..BATCH {
+        let packet = Packet::Input {
+            sequence,
+            input: MovementInput::default(),
+        };
+        client.send_to(packet, &server_addr).unwrap();
+    }
+
+    for _ in 0..BATCH {
+        server.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+}
+
+pub fn throughput(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let _runtime = runtime.enter();
+
+    let mut group = c.benchmark_group("UDP packets");
+    group.throughput(Throughput::Elements(BATCH as u64));
+
+    let server = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let client = UdpTransport::connect(server.local_addr().unwrap()).unwrap();
+    group.bench_function("plain", |b| b.iter(|| send_batch(&client, &server)));
+
+    let client_handshake = Handshake::new();
+    let server_handshake = Handshake::new();
+    let (client_key, server_key) = (client_handshake.public_key(), server_handshake.public_key());
+    server.set_session(
+        client.local_addr().unwrap(),
+        Some(server_handshake.finish(client_key, Role::Server)),
+    );
+    client.set_session(
+        server.local_addr().unwrap(),
+        Some(client_handshake.finish(server_key, Role::Client)),
+    );
+    group.bench_function("encrypted", |b| b.iter(|| send_batch(&client, &server)));
+
+    group.finish();
+}
+
+criterion_group!(benches, chunk_size, throughput);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use yave::network::Packet;
+
+fuzz_target!(|data: &[u8]| {
+    // Decoding is strict, so anything that is accepted must encode back to the same bytes.
+    if let Ok(packet) = Packet::decode(data) {
+        assert_eq!(packet.encode().unwrap(), data);
+    }
+});
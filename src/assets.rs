@@ -1,16 +1,25 @@
+#[cfg(feature = "graphics")]
 use crate::client::renderer::{PipelineBundle, RenderPipelineDescription, Renderer};
+#[cfg(feature = "graphics")]
 use crate::client::voxel::{BlockDescription, VoxelVertex};
+#[cfg(feature = "graphics")]
 use log::{error, info};
+#[cfg(feature = "graphics")]
 use std::collections::HashMap;
 use std::fmt::{Display, Error, Formatter};
+#[cfg(feature = "graphics")]
 use std::fs;
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
+#[cfg(feature = "graphics")]
 use wgpu::{
     BindGroupLayout, BindGroupLayoutEntry, Face, FragmentState, FrontFace, MultisampleState,
     PolygonMode, PrimitiveState, PrimitiveTopology, ShaderModule, ShaderSource, VertexState,
 };
 
+/// Directory the assets are read from, with a directory per namespace.
+pub const ASSETS_DIR: &str = "assets";
+
 /// An identifier is a structure used to identify objects in game like entities, textures, shaders and everything else
 #[derive(Debug, Clone, PartialEq)]
 pub struct Identifier {
@@ -31,7 +40,7 @@ impl Identifier {
 
 impl Eq for Identifier {}
 
-#[allow(clippy::derive_hash_xor_eq)]
+#[allow(clippy::derived_hash_with_manual_eq)]
 impl Hash for Identifier {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.namespace.hash(state);
@@ -49,15 +58,19 @@ impl FromStr for Identifier {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let split: Vec<&str> = s.split(':').collect();
-        Ok(Identifier::new(
-            split.get(0).unwrap(),
-            split.get(1).unwrap(),
-        ))
+        match s.split_once(':') {
+            Some((namespace, name))
+                if !namespace.is_empty() && !name.is_empty() && !name.contains(':') =>
+            {
+                Ok(Identifier::new(namespace, name))
+            }
+            _ => Err(Error),
+        }
     }
 }
 
 /// The asset manager holds game assets like textures, shaders, models and so on
+#[cfg(feature = "graphics")]
 pub struct AssetManager {
     /// All the shaders beign loaded at startup
     shaders: HashMap<Identifier, ShaderModule>,
@@ -67,6 +80,7 @@ pub struct AssetManager {
     bind_group_layouts: HashMap<Identifier, BindGroupLayout>,
 }
 
+#[cfg(feature = "graphics")]
 impl AssetManager {
     pub fn new(renderer: &Renderer) -> Self {
         let mut shaders = HashMap::new();
@@ -117,7 +131,7 @@ impl AssetManager {
             bind_group_layouts.insert(id, transform_bind_group);
         }
 
-        for namespace in fs::read_dir("assets").unwrap() {
+        for namespace in fs::read_dir(ASSETS_DIR).unwrap() {
             let namespace = namespace.unwrap();
 
             for block in fs::read_dir(namespace.path().join("blocks")).unwrap() {
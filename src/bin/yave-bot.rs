@@ -0,0 +1,144 @@
+use log::{error, info};
+use std::time::{Duration, Instant};
+use std::{process, thread};
+use yave::client::bot::{Bot, BotStats};
+use yave::client::status::{self, STATUS_TIMEOUT};
+use yave::client::Credentials;
+use yave::network::conditioner::{ConditionedTransport, NetworkConditions};
+use yave::network::transport::UdpTransport;
+
+/// Time between two updates of the bots.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Time between two bots connecting, so the server doesn't get every login at once.
+const CONNECT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Connects bots to a server and reports how it keeps up with them.
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut addr = String::from("localhost:25000");
+    let mut count = 10;
+    let mut duration = Duration::from_secs(60);
+    let mut prefix = String::from("bot");
+    let mut password = None;
+    let mut encrypt = false;
+    let mut path_size = 32.;
+
+    for (i, arg) in args.iter().enumerate() {
+        let value = || match args.get(i + 1) {
+            Some(value) => value.clone(),
+            None => {
+                error!("Missing value for {arg}");
+                process::exit(1);
+            }
+        };
+        let number = |value: String| match value.parse() {
+            Ok(number) => number,
+            Err(_) => {
+                error!("Invalid value for {arg}: {value}");
+                process::exit(1);
+            }
+        };
+
+        match arg.as_str() {
+            "--connect" => addr = value(),
+            "--bots" => count = number(value()) as usize,
+            "--duration" => duration = Duration::from_secs_f64(number(value())),
+            "--name" => prefix = value(),
+            "--password" => password = Some(value()),
+            "--encrypt" => encrypt = true,
+            "--path-size" => path_size = number(value()),
+            _ => (),
+        }
+    }
+
+    let conditions = match NetworkConditions::from_args(&args) {
+        Ok(conditions) => conditions,
+        Err(e) => {
+            error!("{e}");
+            process::exit(1);
+        }
+    };
+
+    info!(
+        "Connecting {count} bots to {addr} for {} s",
+        duration.as_secs()
+    );
+
+    let start = Instant::now();
+    let mut bots: Vec<Bot> = Vec::with_capacity(count);
+    let mut last_connect: Option<Instant> = None;
+
+    while start.elapsed() < duration {
+        if bots.len() < count && last_connect.is_none_or(|last| last.elapsed() >= CONNECT_INTERVAL)
+        {
+            let index = bots.len();
+            let credentials = Credentials {
+                username: format!("{prefix}{index}"),
+                password: password.clone(),
+            };
+
+            let bot = UdpTransport::connect(&addr).and_then(|transport| {
+                let transport = ConditionedTransport::wrap(transport, conditions);
+                Bot::connect(transport, credentials, encrypt, path(index, path_size))
+            });
+            match bot {
+                Ok(bot) => bots.push(bot),
+                Err(e) => {
+                    error!("Cannot connect to {addr}: {e}");
+                    process::exit(1);
+                }
+            }
+            last_connect = Some(Instant::now());
+        }
+
+        for bot in bots.iter_mut() {
+            if let Err(e) = bot.update() {
+                error!("Bot {} cannot send to the server: {e}", bot.username());
+            }
+        }
+
+        thread::sleep(UPDATE_INTERVAL);
+    }
+
+    let joined = bots.iter().filter(|bot| bot.joined()).count();
+    println!("{joined}/{} bots joined", bots.len());
+    for bot in bots.iter() {
+        if let Some(reason) = bot.disconnected() {
+            println!("{} was disconnected: {reason}", bot.username());
+        }
+    }
+
+    let mut stats = BotStats::default();
+    for bot in bots.iter() {
+        stats.merge(bot.stats());
+    }
+    println!("{stats}");
+
+    match status::query_addr(&addr, STATUS_TIMEOUT) {
+        Ok(status) => println!(
+            "Server: {}/{} players online, {} ms status latency",
+            status.online,
+            status.max_players,
+            status.latency.as_millis()
+        ),
+        Err(e) => error!("Cannot get the status of {addr}: {e}"),
+    }
+}
+
+/// A square the bot walks around, each bot in its own place so they load different chunks.
+fn path(index: usize, size: f64) -> Vec<[f64; 3]> {
+    let x = (index % 16) as f64 * 16.;
+    let z = (index / 16) as f64 * 16. + 10.;
+
+    vec![
+        [x, 0., z],
+        [x + size, 0., z],
+        [x + size, 0., z + size],
+        [x, 0., z + size],
+    ]
+}
@@ -0,0 +1,111 @@
+use log::error;
+use std::process;
+use std::time::Duration;
+use yave::client::bot::Bot;
+use yave::client::Credentials;
+use yave::network::record::Recording;
+use yave::network::replay::ReplayTransport;
+use yave::network::Packet;
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+
+/// Recorded time released between two updates of a replayed client.
+const CLIENT_STEP: Duration = Duration::from_millis(10);
+
+/// Plays a recording made with --record back into a server, or into a headless client with
+/// --client, and tells what it sent.
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+
+    let path = match args.get(1) {
+        Some(path) if !path.starts_with("--") => path,
+        _ => {
+            error!("Usage: yave-replay <recording> [--client] [server flags]");
+            process::exit(1);
+        }
+    };
+
+    let recording = match Recording::read(path) {
+        Ok(recording) => recording,
+        Err(e) => {
+            error!("Cannot read the recording {path}: {e}");
+            process::exit(1);
+        }
+    };
+
+    let transport = ReplayTransport::new(&recording);
+    let replay = transport.replay();
+
+    if args.iter().any(|arg| arg == "--client") {
+        // The bot logs in with the name the recorded client used.
+        let username = recording
+            .sent()
+            .find_map(|packet| match &packet.packet {
+                Packet::Connection { user, .. } => Some(user.clone()),
+                _ => None,
+            })
+            .unwrap_or_else(|| String::from("replay"));
+
+        let credentials = Credentials {
+            username,
+            password: None,
+        };
+        let mut bot = match Bot::connect(transport, credentials, false, Vec::new()) {
+            Ok(bot) => bot,
+            Err(e) => {
+                error!("Cannot start the client: {e}");
+                process::exit(1);
+            }
+        };
+
+        let mut time = Duration::ZERO;
+        while !replay.finished() {
+            time += CLIENT_STEP;
+            replay.advance(time);
+            if let Err(e) = bot.update() {
+                error!("The client cannot send: {e}");
+            }
+        }
+
+        match bot.disconnected() {
+            Some(reason) => println!("The client was disconnected: {reason}"),
+            None => println!(
+                "The client joined: {}, {} chunks loaded at {:?}",
+                bot.joined(),
+                bot.loaded_chunks(),
+                bot.position()
+            ),
+        }
+        println!("{}", bot.stats());
+    } else {
+        // The replayed server starts from an empty world unless told otherwise, so replaying
+        // never changes a real one.
+        let dir = std::env::temp_dir().join(format!("yave-replay-{}", process::id()));
+        let mut config = ServerConfig {
+            world_dir: dir.join("world"),
+            lists_dir: dir,
+            ..Default::default()
+        };
+        let flags: Vec<String> = args[2..]
+            .iter()
+            .filter(|arg| *arg != "--client")
+            .cloned()
+            .collect();
+        if let Err(e) = config.apply_args(&flags) {
+            error!("{e}");
+            process::exit(1);
+        }
+
+        let mut game = Game::new(transport, config);
+        let ticks = game.replay(&replay);
+
+        println!(
+            "Replayed {} packets in {ticks} ticks, the server sent {} packets ({} recorded)",
+            recording.received().count(),
+            replay.take_sent().len(),
+            recording.sent().count()
+        );
+    }
+}
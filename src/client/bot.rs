@@ -0,0 +1,447 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::ErrorKind;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+
+use crate::client::prediction::Prediction;
+use crate::client::{Credentials, ServerInfo};
+use crate::network::crypto::{Handshake, Role};
+use crate::network::transport::Transport;
+use crate::network::{split, Packet, SocketSender};
+use crate::world::chunk::Chunk;
+use crate::world::movement::{MovementInput, INPUTS_PER_SECOND, MOVEMENT_SPEED};
+use crate::world::physics::{Body, STEP_HEIGHT};
+use crate::world::Chunks;
+
+/// What a bot measured about the server since it connected.
+#[derive(Debug, Clone, Default)]
+pub struct BotStats {
+    /// Chunks received, including the ones sent again.
+    pub chunks: u64,
+    /// Time between entering a chunk and receiving each chunk that came into view.
+    pub chunk_latencies: Vec<Duration>,
+    /// Snapshots received. The server sends one every tick.
+    pub snapshots: u64,
+    /// Snapshots the server sent, from the ticks between the first and the latest one.
+    pub expected_snapshots: u64,
+    /// Ticks between the first and the latest snapshot.
+    pub ticks: u64,
+    /// Time between the first and the latest snapshot.
+    pub ticks_time: Duration,
+}
+
+impl BotStats {
+    /// Add the measures of another bot.
+    pub fn merge(&mut self, other: &BotStats) {
+        self.chunks += other.chunks;
+        self.chunk_latencies.extend(&other.chunk_latencies);
+        self.snapshots += other.snapshots;
+        self.expected_snapshots += other.expected_snapshots;
+        self.ticks += other.ticks;
+        self.ticks_time += other.ticks_time;
+    }
+
+    pub fn average_chunk_latency(&self) -> Option<Duration> {
+        if self.chunk_latencies.is_empty() {
+            return None;
+        }
+
+        let total: Duration = self.chunk_latencies.iter().sum();
+        Some(total / self.chunk_latencies.len() as u32)
+    }
+
+    pub fn max_chunk_latency(&self) -> Option<Duration> {
+        self.chunk_latencies.iter().max().copied()
+    }
+
+    /// Snapshots that never arrived.
+    pub fn lost_snapshots(&self) -> u64 {
+        self.expected_snapshots.saturating_sub(self.snapshots)
+    }
+
+    /// Fraction of the snapshots lost, from 0 to 1.
+    pub fn packet_loss(&self) -> f64 {
+        if self.expected_snapshots == 0 {
+            return 0.;
+        }
+
+        self.lost_snapshots() as f64 / self.expected_snapshots as f64
+    }
+
+    /// Ticks the server ran every second, as seen from the snapshots.
+    pub fn tick_rate(&self) -> Option<f64> {
+        if self.ticks_time.is_zero() {
+            return None;
+        }
+
+        Some(self.ticks as f64 / self.ticks_time.as_secs_f64())
+    }
+}
+
+impl Display for BotStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Chunks: {} received", self.chunks)?;
+        if let (Some(average), Some(max)) = (self.average_chunk_latency(), self.max_chunk_latency())
+        {
+            write!(
+                f,
+                ", latency {} ms average, {} ms max",
+                average.as_millis(),
+                max.as_millis()
+            )?;
+        }
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "Snapshots: {} received, {} lost ({:.1}%)",
+            self.snapshots,
+            self.lost_snapshots(),
+            self.packet_loss() * 100.
+        )?;
+
+        match self.tick_rate() {
+            Some(tick_rate) => write!(f, "Server ticks: {tick_rate:.1} per second"),
+            None => write!(f, "Server ticks: unknown"),
+        }
+    }
+}
+
+/// A headless client for load testing. It connects like a player, keeps the chunks it receives to
+/// walk on them and walks along its path over and over, without a window or a GPU.
+pub struct Bot {
+    sender: SocketSender,
+    packets: Receiver<Packet>,
+    credentials: Credentials,
+    handshake: Option<Handshake>,
+    server_info: ServerInfo,
+    disconnected: Option<String>,
+    prediction: Prediction,
+    /// Points the bot walks to one after the other, starting again from the first. It walks on
+    /// the ground whatever their height, and only jumps towards points more than a step above it.
+    path: Vec<[f64; 3]>,
+    waypoint: usize,
+    last_update: Instant,
+    chunks: Chunks,
+    /// Network ids of the entities other than players the server sent.
+    entities: HashSet<u32>,
+    /// World the server last moved the bot to.
+    world: Option<String>,
+    /// Chunk the bot is in and when it entered it.
+    chunk: ((i64, i64), Instant),
+    /// Tick and arrival of the first and the latest snapshot.
+    first_snapshot: Option<(u64, Instant)>,
+    latest_snapshot: Option<(u64, Instant)>,
+    stats: BotStats,
+}
+
+impl Bot {
+    /// Start connecting to the server the transport is connected to. Packets are received on a
+    /// separate thread, and handled by `update`.
+    pub fn connect(
+        transport: impl Transport + 'static,
+        credentials: Credentials,
+        encrypt: bool,
+        path: Vec<[f64; 3]>,
+    ) -> io::Result<Self> {
+        let (mut sender, mut receiver) = split(transport);
+
+        let handshake = if encrypt {
+            let handshake = Handshake::new();
+            sender.send(Packet::KeyExchange {
+                public_key: handshake.public_key(),
+            })?;
+            Some(handshake)
+        } else {
+            sender.send(Packet::Connection {
+                user: credentials.username.clone(),
+                compression: true,
+            })?;
+            None
+        };
+
+        let (packet_sender, packets) = mpsc::channel();
+        thread::spawn(move || loop {
+            match receiver.recv_packet_from() {
+                Ok((packet, _peer)) => {
+                    if packet_sender.send(packet).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::ConnectionAborted => break,
+                Err(e) => debug!("Dropping invalid packet from the server: {e}"),
+            }
+        });
+
+        let position = [0., 16., 10.];
+        let now = Instant::now();
+
+        Ok(Self {
+            sender,
+            packets,
+            credentials,
+            handshake,
+            server_info: ServerInfo::default(),
+            disconnected: None,
+            prediction: Prediction::new(position),
+            path,
+            waypoint: 0,
+            last_update: now,
+            chunks: Chunks::default(),
+            entities: HashSet::new(),
+            world: None,
+            chunk: (chunk_of(position), now),
+            first_snapshot: None,
+            latest_snapshot: None,
+            stats: BotStats::default(),
+        })
+    }
+
+    /// Handle the packets received since the last update, then walk for the time that elapsed.
+    pub fn update(&mut self) -> io::Result<()> {
+        while let Ok(packet) = self.packets.try_recv() {
+            self.handle_packet(packet)?;
+        }
+
+        let now = Instant::now();
+        let delta = now - self.last_update;
+        self.last_update = now;
+
+        if !self.joined() {
+            return Ok(());
+        }
+
+        let input = self.steer();
+        for (sequence, input) in self.prediction.update(delta, input, &self.chunks) {
+            self.sender.send(Packet::Input { sequence, input })?;
+        }
+
+        let chunk = chunk_of(self.prediction.position());
+        if chunk != self.chunk.0 {
+            self.chunk = (chunk, now);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the server welcomed the bot and didn't disconnect it since.
+    pub fn joined(&self) -> bool {
+        self.server_info.id.is_some() && self.disconnected.is_none()
+    }
+
+    /// Network id of the bot, once the server welcomed it.
+    pub fn id(&self) -> Option<u32> {
+        self.server_info.id
+    }
+
+    pub fn username(&self) -> &str {
+        &self.credentials.username
+    }
+
+    /// Reason the server gave for disconnecting the bot.
+    pub fn disconnected(&self) -> Option<&str> {
+        self.disconnected.as_deref()
+    }
+
+    /// Predicted position of the bot.
+    pub fn position(&self) -> [f64; 3] {
+        self.prediction.position()
+    }
+
+    /// Number of chunks currently loaded.
+    pub fn loaded_chunks(&self) -> usize {
+        self.chunks.chunks.len()
+    }
+
+    /// Number of entities other than players currently in range.
+    pub fn loaded_entities(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// World the server last moved the bot to, none while it is still in the one it joined.
+    pub fn world(&self) -> Option<&str> {
+        self.world.as_deref()
+    }
+
+    pub fn stats(&self) -> &BotStats {
+        &self.stats
+    }
+
+    fn handle_packet(&mut self, packet: Packet) -> io::Result<()> {
+        match packet {
+            Packet::KeyExchange { public_key } => {
+                if let (Some(handshake), Ok(addr)) =
+                    (self.handshake.take(), self.sender.peer_addr())
+                {
+                    self.sender
+                        .set_session(addr, Some(handshake.finish(public_key, Role::Client)));
+                    self.sender.send(Packet::Connection {
+                        user: self.credentials.username.clone(),
+                        compression: true,
+                    })?;
+                }
+            }
+            Packet::Compression { threshold } => {
+                if let Ok(addr) = self.sender.peer_addr() {
+                    self.sender.set_compression(addr, Some(threshold));
+                }
+            }
+            Packet::AuthChallenge {
+                salt,
+                iterations,
+                nonce,
+            } => {
+                let answer = self.credentials.answer(&salt, iterations, &nonce);
+                if let Packet::Disconnect { reason } = &answer {
+                    self.disconnected = Some(reason.clone());
+                }
+                self.sender.send(answer)?;
+            }
+            Packet::Welcome { id, tick_rate } => {
+                info!("Bot {} joined", self.credentials.username);
+                self.server_info.id = Some(id);
+                self.server_info.tick_rate = tick_rate;
+            }
+            Packet::Disconnect { reason } => {
+                warn!(
+                    "Bot {} disconnected by the server: {reason}",
+                    self.credentials.username
+                );
+                self.disconnected = Some(reason);
+            }
+            Packet::PlayerState {
+                sequence,
+                x,
+                y,
+                z,
+                velocity,
+            } => {
+                let body = Body {
+                    position: [x, y, z],
+                    velocity,
+                };
+                self.prediction.reconcile(sequence, body, &self.chunks);
+            }
+            Packet::Chunk { x, y, data } => {
+                self.stats.chunks += 1;
+                let chunk = Chunk::decompress(&data, x, y);
+                // Chunks sent again because they changed were already in view.
+                match self.chunks.get_chunk_mut(x, y) {
+                    Some(loaded) => *loaded = chunk,
+                    None => {
+                        self.chunks.chunks.push(chunk);
+                        self.stats.chunk_latencies.push(self.chunk.1.elapsed());
+                    }
+                }
+            }
+            Packet::UnloadChunk { x, y } => {
+                self.chunks
+                    .chunks
+                    .retain(|chunk| chunk.x != x || chunk.y != y);
+            }
+            Packet::ChangeWorld { name } => {
+                // The chunks of the new world are as late as the ones of a chunk just entered.
+                self.chunks.chunks.clear();
+                self.entities.clear();
+                self.chunk.1 = Instant::now();
+                self.world = Some(name);
+            }
+            Packet::SpawnEntity { id, .. } => {
+                self.entities.insert(id);
+            }
+            Packet::DespawnEntity { id } => {
+                self.entities.remove(&id);
+            }
+            Packet::Snapshot { tick, .. } => {
+                self.record_snapshot(tick);
+                self.sender.send(Packet::SnapshotAck { tick })?;
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn record_snapshot(&mut self, tick: u64) {
+        let now = Instant::now();
+        self.stats.snapshots += 1;
+
+        match self.latest_snapshot {
+            // Snapshots arriving late were already counted as expected.
+            Some((latest, _)) if tick <= latest => return,
+            Some((latest, _)) => self.stats.expected_snapshots += tick - latest,
+            None => {
+                self.stats.expected_snapshots += 1;
+                self.first_snapshot = Some((tick, now));
+            }
+        }
+        self.latest_snapshot = Some((tick, now));
+
+        if let Some((first, time)) = self.first_snapshot {
+            self.stats.ticks = tick - first;
+            self.stats.ticks_time = now - time;
+        }
+    }
+
+    /// Input moving the bot towards the next point of its path.
+    fn steer(&mut self) -> MovementInput {
+        if self.path.is_empty() {
+            return MovementInput::default();
+        }
+
+        let reach = MOVEMENT_SPEED / INPUTS_PER_SECOND as f64;
+        let position = self.prediction.position();
+
+        let mut target = self.path[self.waypoint];
+        if distance(position, target) < reach {
+            self.waypoint = (self.waypoint + 1) % self.path.len();
+            target = self.path[self.waypoint];
+        }
+
+        let (dx, dy, dz) = (
+            target[0] - position[0],
+            target[1] - position[1],
+            target[2] - position[2],
+        );
+        let horizontal = distance(position, target);
+
+        // Slow down near the target instead of walking past it.
+        MovementInput {
+            forward: (horizontal / reach).min(1.) as f32,
+            right: 0.,
+            up: if dy > STEP_HEIGHT { 1. } else { 0. },
+            yaw: dz.atan2(dx) as f32,
+            pitch: 0.,
+            flying: false,
+        }
+    }
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        if self.disconnected.is_none() {
+            let _ = self.sender.send(Packet::Disconnect {
+                reason: String::from("Quit"),
+            });
+        }
+
+        self.sender.close();
+    }
+}
+
+fn chunk_of(position: [f64; 3]) -> (i64, i64) {
+    (
+        (position[0] / 16.).floor() as i64,
+        (position[2] / 16.).floor() as i64,
+    )
+}
+
+/// Horizontal distance between two points.
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
@@ -3,7 +3,6 @@ use crate::client::renderer::Renderer;
 use cgmath::{perspective, Deg, InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3};
 use thunderdome::Index;
 use wgpu::BufferUsages;
-use winit::event::{ElementState, VirtualKeyCode};
 use winit::window::Window;
 
 pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
@@ -30,12 +29,13 @@ impl Camera {
         }
     }
 
+    /// Unit vector the camera looks along.
+    pub fn direction(&self) -> Vector3<f32> {
+        Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.0.sin()).normalize()
+    }
+
     pub fn create_matrix(&self) -> Matrix4<f32> {
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.0.sin()).normalize(),
-            Vector3::unit_y(),
-        )
+        Matrix4::look_to_rh(self.position, self.direction(), Vector3::unit_y())
     }
 }
 
@@ -93,84 +93,6 @@ impl Default for CameraUniform {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct CameraController {
-    amount_left: f32,
-    amount_right: f32,
-    amount_down: f32,
-    amount_up: f32,
-    amount_back: f32,
-    amount_forward: f32,
-    rotate_horizontal: f32,
-    rotate_vertical: f32,
-    speed: f32,
-    sensitivity: f32,
-}
-
-impl CameraController {
-    pub fn new(speed: f32, sensitivity: f32) -> Self {
-        Self {
-            amount_left: 0.,
-            amount_right: 0.,
-            amount_down: 0.,
-            amount_up: 0.,
-            amount_back: 0.,
-            amount_forward: 0.,
-            rotate_horizontal: 0.,
-            rotate_vertical: 0.,
-            speed,
-            sensitivity,
-        }
-    }
-
-    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) {
-        let amount = if let ElementState::Pressed = state {
-            1.
-        } else {
-            0.
-        };
-        match key {
-            VirtualKeyCode::W => self.amount_forward = amount,
-            VirtualKeyCode::A => self.amount_left = amount,
-            VirtualKeyCode::S => self.amount_back = amount,
-            VirtualKeyCode::D => self.amount_right = amount,
-            VirtualKeyCode::Space => self.amount_up = amount,
-            VirtualKeyCode::LShift => self.amount_down = amount,
-            _ => (),
-        }
-    }
-
-    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
-    }
-
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: f32) {
-        let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
-        let forward = Vector3::new(yaw_cos, 0., yaw_sin).normalize();
-        let right = Vector3::new(-yaw_sin, 0., yaw_cos).normalize();
-
-        camera.position += forward * (self.amount_forward - self.amount_back) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
-
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
-
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
-
-        self.rotate_horizontal = 0.;
-        self.rotate_vertical = 0.;
-
-        let max_pitch: Rad<f32> = Deg(90.).into();
-
-        if camera.pitch < -max_pitch {
-            camera.pitch = -max_pitch;
-        } else if camera.pitch > max_pitch {
-            camera.pitch = max_pitch;
-        }
-    }
-}
-
 #[derive(Debug, Clone, Copy)]
 pub struct CameraBundle {
     pub camera: Camera,
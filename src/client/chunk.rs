@@ -33,6 +33,10 @@ impl ChunkMesh {
             let y = block.y() as u16;
             let z = block.z() as u16;
 
+            if block.transparent() {
+                continue;
+            }
+
             if y == 15 {
                 vertices.append(
                     &mut BlockFace::new(Direction::Top, x as u32, y as u32, z as u32)
@@ -181,6 +185,7 @@ pub struct ChunkIndices {
 }
 
 impl ChunkIndices {
+    #[allow(clippy::identity_op)]
     pub fn new(renderer: &mut Renderer) -> Self {
         let mut indices = Vec::new();
 
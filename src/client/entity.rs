@@ -0,0 +1,11 @@
+use bevy_ecs::prelude::Component;
+
+/// An entity that is not a player. The server spawns and despawns it as it comes within and goes
+/// out of the tracking range.
+#[derive(Debug, Clone, Component)]
+pub struct NetworkEntity {
+    /// Network id of the entity on the server.
+    pub id: u32,
+    /// Id of its type, like `base:wanderer`.
+    pub kind: String,
+}
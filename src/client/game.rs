@@ -1,60 +1,90 @@
 use crate::assets::{AssetManager, Identifier};
-use crate::client::camera::{CameraBundle, CameraController};
+use crate::client::camera::CameraBundle;
 use crate::client::chunk::ChunkMesh;
-use crate::client::player::{Player, PlayerController};
+use crate::client::entity::NetworkEntity;
+use crate::client::interpolation::{
+    tick_time, InterpolationBuffer, InterpolationSettings, ServerClock,
+};
+use crate::client::player::{
+    LocalPlayer, Look, MovementMode, Player, PlayerInput, PlayerNames, Position, Velocity, View,
+    THIRD_PERSON_DISTANCE,
+};
+use crate::client::prediction::Prediction;
 use crate::client::renderer::Renderer;
 use crate::client::transform::TransformBundle;
 use crate::client::voxel::VoxelVertex;
-use crate::client::ServerEvent;
+use crate::client::{Credentials, PendingConnection, Running, ServerEvent, ServerInfo};
+use crate::network::crypto::{Handshake, Role};
+use crate::network::snapshot::{Snapshot, SnapshotHistory};
+use crate::network::transport::Transport;
 use crate::network::Packet;
-use crate::network::{split_socket, SocketSender};
+use crate::network::{split, SocketSender, MAX_CHAT_LENGTH};
 use crate::world::chunk::Chunk;
+use crate::world::physics::{Body, EYE_HEIGHT};
+use crate::world::Chunks;
 use crate::{DeltaTime, KeyboardEvent, MouseMotion};
 use bevy_ecs::event::{EventReader, Events};
-use bevy_ecs::prelude::{Commands, Entity, Query, ResMut, Schedule, SystemStage, Without};
-use bevy_ecs::schedule::Stage;
+use bevy_ecs::prelude::{
+    Commands, Entity, Or, Query, ResMut, Schedule, SystemStage, With, Without,
+};
+use bevy_ecs::schedule::{ParallelSystemDescriptorCoercion, Stage};
 use bevy_ecs::system::Res;
 use bevy_ecs::world::World;
-use log::{error, info};
-use std::net::UdpSocket;
-use std::sync::{Arc, Mutex};
+use cgmath::{Point3, Rad};
+use log::{error, info, warn};
+use std::collections::HashSet;
+use std::f32::consts::FRAC_PI_2;
+use std::io::BufRead;
+use std::io::ErrorKind;
+use std::sync::mpsc;
 use std::time::{Duration, Instant};
+use std::{io, thread};
 use wgpu::{BufferUsages, IndexFormat, SurfaceError};
 use winit::error::OsError;
 use winit::event::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
+use winit::platform::run_return::EventLoopExtRunReturn;
 use winit::window::{Window, WindowBuilder};
 
 use super::chunk::ChunkIndices;
 
+/// Filter of the players and the entities, drawn at their transform.
+type Drawn = Or<(With<Player>, With<NetworkEntity>, With<LocalPlayer>)>;
+
+/// Components of the local player moved by the inputs.
+type LocalMovement<'a> = (
+    &'a mut Look,
+    &'a MovementMode,
+    &'a mut Prediction,
+    &'a mut Position,
+    &'a mut Velocity,
+);
+
 pub struct Game;
 
 impl Game {
-    pub async fn run(addr: String, username: String) -> Result<(), OsError> {
+    pub async fn run(
+        transport: impl Transport + 'static,
+        username: String,
+        password: Option<String>,
+        encrypt: bool,
+        interpolation: InterpolationSettings,
+    ) -> Result<(), OsError> {
         Chunk::new(0, 0);
 
-        let event_loop = EventLoop::new();
+        let mut event_loop = EventLoop::new();
         let window = WindowBuilder::new().with_title("yave").build(&event_loop)?;
 
         let mut main_schedule = Schedule::default();
 
         let mut setup_schedule = Schedule::default();
 
-        let world = Arc::new(Mutex::new(World::new()));
+        let mut world = World::new();
 
-        world
-            .lock()
-            .unwrap()
-            .insert_resource(Events::<KeyboardEvent>::default());
-        world
-            .lock()
-            .unwrap()
-            .insert_resource(Events::<MouseMotion>::default());
+        world.insert_resource(Events::<KeyboardEvent>::default());
+        world.insert_resource(Events::<MouseMotion>::default());
 
-        world
-            .lock()
-            .unwrap()
-            .insert_resource(Events::<ServerEvent>::default());
+        world.insert_resource(Events::<ServerEvent>::default());
 
         setup_schedule.add_stage("setup", SystemStage::parallel().with_system(Game::setup));
         main_schedule.add_stage(
@@ -67,10 +97,19 @@ impl Game {
         main_schedule.add_stage(
             "main_loop",
             SystemStage::parallel()
-                .with_system(Game::update)
+                .with_system(Game::move_player.label("movement"))
+                .with_system(Game::attach_camera.label("camera").after("movement"))
+                .with_system(Game::update.after("interpolation").after("camera"))
                 .with_system(Game::handle_keyboard)
                 .with_system(Game::handle_mouse)
                 .with_system(Game::handle_packets)
+                .with_system(Game::handle_connection)
+                .with_system(Game::handle_snapshots.label("snapshots"))
+                .with_system(
+                    Game::interpolate_players
+                        .label("interpolation")
+                        .after("snapshots"),
+                )
                 .with_system(Game::update_chunks),
         );
         main_schedule.add_stage(
@@ -78,128 +117,174 @@ impl Game {
             SystemStage::parallel().with_system(Game::render),
         );
 
-        world.lock().unwrap().insert_resource(window);
+        world.insert_resource(window);
 
-        let renderer = Renderer::new(world.lock().unwrap().get_resource::<Window>().unwrap());
+        let renderer = Renderer::new(world.get_resource::<Window>().unwrap());
 
-        world.lock().unwrap().insert_resource(renderer);
+        world.insert_resource(renderer);
 
-        let assets = AssetManager::new(world.lock().unwrap().get_resource::<Renderer>().unwrap());
+        let assets = AssetManager::new(world.get_resource::<Renderer>().unwrap());
 
-        world.lock().unwrap().insert_resource(assets);
+        world.insert_resource(assets);
 
-        world
-            .lock()
-            .unwrap()
-            .insert_resource(DeltaTime(Duration::from_secs_f32(0.0)));
+        world.insert_resource(DeltaTime(Duration::from_secs_f32(0.0)));
 
-        setup_schedule.run(&mut world.lock().unwrap());
+        world.insert_resource(interpolation);
+        world.insert_resource(Running(true));
+        world.insert_resource(Credentials {
+            username: username.clone(),
+            password,
+        });
 
-        let world_clone = world.clone();
+        setup_schedule.run(&mut world);
 
-        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let (mut sender, mut receiver) = split(transport);
 
-        socket.connect(addr).unwrap();
+        info!("Connecting");
 
-        let (mut sender, mut receiver) = split_socket(socket);
+        if encrypt {
+            let handshake = Handshake::new();
+            let key_exchange = Packet::KeyExchange {
+                public_key: handshake.public_key(),
+            };
+            if let Err(e) = sender.send(key_exchange) {
+                error!("Cannot connect to the server: {e}");
+                return Ok(());
+            }
 
-        info!("Connecting");
+            world.insert_resource(PendingConnection {
+                username,
+                handshake: Some(handshake),
+            });
+        } else {
+            let connection = Packet::Connection {
+                user: username,
+                compression: true,
+            };
+            if let Err(e) = sender.send(connection) {
+                error!("Cannot connect to the server: {e}");
+                return Ok(());
+            }
+        }
 
-        sender.send(Packet::Connection { user: username }).unwrap();
+        // Spawn network thread to listen for packets, frames take them from the channel.
+        let (packet_sender, packets) = mpsc::channel();
+        thread::spawn(move || loop {
+            match receiver.recv_packet_from() {
+                Ok((packet, _peer)) => {
+                    if packet_sender.send(packet).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::ConnectionAborted => break,
+                Err(e) => warn!("Dropping invalid packet from the server: {e}"),
+            }
+        });
 
-        world.lock().unwrap().insert_resource(sender);
+        // There is no chat box yet, lines typed in the terminal are sent to the chat.
+        let mut chat_sender = sender.clone();
+        world.insert_resource(sender);
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if line.trim().is_empty() {
+                    continue;
+                }
 
-        // Spawn network thread to listen for packets.
-        tokio::spawn(async move {
-            let world = world_clone;
+                if line.len() > MAX_CHAT_LENGTH {
+                    warn!("Chat messages can't be longer than {MAX_CHAT_LENGTH} bytes");
+                    continue;
+                }
 
-            loop {
-                let mut data = vec![0u8; std::mem::size_of::<Packet>()];
-                if let Ok((size, _peer)) = receiver.recv_from(&mut data) {
-                    data.resize(size, 0);
-                    if let Ok(packet) = Packet::decode(data) {
-                        let mut world = world.lock().unwrap();
-                        let mut client_events =
-                            world.get_resource_mut::<Events<ServerEvent>>().unwrap();
-                        client_events.send(ServerEvent { packet });
-                    }
+                if let Err(e) = chat_sender.send(Packet::ChatMessage { message: line }) {
+                    error!("Cannot send a chat message: {e}");
                 }
             }
         });
 
         let mut last_frame_time = Instant::now();
 
-        event_loop.run(move |e, _, control_flow| {
+        // Returns once the window is closed, so whatever runs besides the client can be stopped.
+        event_loop.run_return(move |e, _, control_flow| {
             *control_flow = ControlFlow::Poll;
 
-            let world = world.clone();
-
             match e {
-                Event::WindowEvent { window_id, event } => {
-                    let mut world = world.lock().unwrap();
-                    if window_id == world.get_resource::<Window>().unwrap().id() {
-                        match event {
-                            WindowEvent::Resized(new_size) => {
-                                let mut renderer = world.get_resource_mut::<Renderer>().unwrap();
-                                renderer.surface_config.width = new_size.width;
-                                renderer.surface_config.height = new_size.height;
-
-                                renderer
-                                    .surface
-                                    .configure(&renderer.device, &renderer.surface_config);
-                            }
-                            WindowEvent::CloseRequested => {
+                Event::WindowEvent { window_id, event }
+                    if window_id == world.get_resource::<Window>().unwrap().id() =>
+                {
+                    match event {
+                        WindowEvent::Resized(new_size) => {
+                            let mut renderer = world.get_resource_mut::<Renderer>().unwrap();
+                            renderer.surface_config.width = new_size.width;
+                            renderer.surface_config.height = new_size.height;
+
+                            renderer
+                                .surface
+                                .configure(&renderer.device, &renderer.surface_config);
+                        }
+                        WindowEvent::CloseRequested => {
+                            *control_flow = ControlFlow::Exit;
+                        }
+                        WindowEvent::KeyboardInput { input, .. } => {
+                            let mut window_events =
+                                world.get_resource_mut::<Events<KeyboardEvent>>().unwrap();
+                            window_events.send(KeyboardEvent { input });
+                            if let KeyboardInput {
+                                virtual_keycode: Some(VirtualKeyCode::Escape),
+                                state: ElementState::Released,
+                                ..
+                            } = input
+                            {
                                 *control_flow = ControlFlow::Exit;
                             }
-                            WindowEvent::KeyboardInput { input, .. } => {
-                                let mut window_events =
-                                    world.get_resource_mut::<Events<KeyboardEvent>>().unwrap();
-                                window_events.send(KeyboardEvent { input });
-                                if let KeyboardInput {
-                                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                                    state: ElementState::Released,
-                                    ..
-                                } = input
-                                {
-                                    *control_flow = ControlFlow::Exit;
-                                }
-                            }
-                            WindowEvent::Focused(is) => {
-                                let window = world.get_resource_mut::<Window>().unwrap();
-                                if let Err(e) = window.set_cursor_grab(is) {
-                                    error!("{e}");
-                                }
-                                window.set_cursor_visible(!is);
+                        }
+                        WindowEvent::Focused(is) => {
+                            let window = world.get_resource_mut::<Window>().unwrap();
+                            if let Err(e) = window.set_cursor_grab(is) {
+                                error!("{e}");
                             }
-                            _ => (),
+                            window.set_cursor_visible(!is);
                         }
+                        _ => (),
                     }
                 }
                 Event::DeviceEvent {
                     event: DeviceEvent::MouseMotion { delta },
                     ..
                 } => {
-                    let mut world = world.lock().unwrap();
                     let mut mouse_events = world.get_resource_mut::<Events<MouseMotion>>().unwrap();
                     mouse_events.send(MouseMotion { delta });
                 }
                 Event::MainEventsCleared => {
-                    world
-                        .lock()
-                        .unwrap()
-                        .get_resource::<Window>()
-                        .unwrap()
-                        .request_redraw();
+                    world.get_resource::<Window>().unwrap().request_redraw();
+                }
+                Event::LoopDestroyed => {
+                    let mut sender = world.get_resource_mut::<SocketSender>().unwrap();
+                    let _ = sender.send(Packet::Disconnect {
+                        reason: String::from("Quit"),
+                    });
                 }
                 Event::RedrawRequested(_) => {
                     let delta_time = Instant::now() - last_frame_time;
                     last_frame_time = Instant::now();
-                    world.lock().unwrap().insert_resource(DeltaTime(delta_time));
-                    main_schedule.run(&mut world.lock().unwrap());
+                    world.insert_resource(DeltaTime(delta_time));
+
+                    let mut server_events =
+                        world.get_resource_mut::<Events<ServerEvent>>().unwrap();
+                    for packet in packets.try_iter() {
+                        server_events.send(ServerEvent { packet });
+                    }
+
+                    main_schedule.run(&mut world);
+
+                    if !world.get_resource::<Running>().unwrap().0 {
+                        *control_flow = ControlFlow::Exit;
+                    }
                 }
                 _ => (),
             }
         });
+
+        Ok(())
     }
 
     pub fn setup(
@@ -209,24 +294,85 @@ impl Game {
         window: Res<Window>,
     ) {
         commands.insert_resource(CameraBundle::new(&window, &mut renderer, &assets));
-        commands.insert_resource(CameraController::new(2., 0.5));
 
-        commands.insert_resource(PlayerController::new(2., 0.5));
+        // The server corrects the position once it placed the player.
+        let position = [0., 16., 10.];
+        commands
+            .spawn()
+            .insert(LocalPlayer)
+            .insert(Position(position))
+            .insert(Velocity::default())
+            .insert(Look {
+                yaw: -FRAC_PI_2,
+                pitch: 0.,
+            })
+            .insert(MovementMode::default())
+            .insert(View::default())
+            .insert(Prediction::new(position))
+            .insert(TransformBundle::new(
+                (position[0] as f32, position[1] as f32, position[2] as f32),
+                &mut renderer,
+                &assets,
+            ));
+        commands.insert_resource(PlayerInput::new(0.004));
+        commands.insert_resource(Chunks::default());
+
+        commands.insert_resource(SnapshotHistory::default());
+        commands.insert_resource(PlayerNames::default());
+        commands.insert_resource(ServerClock::default());
+        commands.insert_resource(ServerInfo::default());
 
         commands.insert_resource(ChunkIndices::new(&mut renderer));
     }
 
-    pub fn update(
+    /// Turn the local player with the mouse, then predict its movement and send the inputs of
+    /// the ticks that elapsed to the server.
+    pub fn move_player(
         delta_time: Res<DeltaTime>,
+        (mut input, chunks): (ResMut<PlayerInput>, Res<Chunks>),
+        (mut sender, mut running): (ResMut<SocketSender>, ResMut<Running>),
+        mut players: Query<LocalMovement, With<LocalPlayer>>,
+    ) {
+        for (mut look, mode, mut prediction, mut position, mut velocity) in players.iter_mut() {
+            input.turn(&mut look);
+
+            let movement = input.input(&look, *mode);
+            for (sequence, input) in prediction.update(delta_time.0, movement, &chunks) {
+                send(&mut sender, &mut running, Packet::Input { sequence, input });
+            }
+
+            position.0 = prediction.render_position();
+            velocity.0 = prediction.body().velocity;
+        }
+    }
+
+    /// Place the camera at the eyes of the local player, or behind them in third person, and
+    /// its model at its feet.
+    pub fn attach_camera(
         mut camera_bundle: ResMut<CameraBundle>,
-        mut camera_controller: ResMut<CameraController>,
-        renderer: Res<Renderer>,
-        mut sender: ResMut<SocketSender>,
-        mut player_controller: ResMut<PlayerController>,
-        mut players: Query<(&Player, &mut TransformBundle)>,
+        mut players: Query<(&Position, &Look, &View, &mut TransformBundle), With<LocalPlayer>>,
     ) {
-        camera_controller.update_camera(&mut camera_bundle.camera, delta_time.0.as_secs_f32());
+        for (position, look, view, mut transform_bundle) in players.iter_mut() {
+            let [x, y, z] = position.0;
+
+            let camera = &mut camera_bundle.camera;
+            camera.yaw = Rad(look.yaw);
+            camera.pitch = Rad(look.pitch);
+            camera.position = Point3::new(x as f32, (y + EYE_HEIGHT) as f32, z as f32);
+            if *view == View::ThirdPerson {
+                camera.position -= camera.direction() * THIRD_PERSON_DISTANCE;
+            }
+
+            transform_bundle.transform.position = (x as f32, y as f32, z as f32).into();
+            transform_bundle.transform.rotation = (look.pitch, look.yaw, 0.).into();
+        }
+    }
 
+    pub fn update(
+        mut camera_bundle: ResMut<CameraBundle>,
+        renderer: Res<Renderer>,
+        mut players: Query<&mut TransformBundle, Drawn>,
+    ) {
         let bundle_clone = *camera_bundle;
 
         camera_bundle
@@ -239,23 +385,7 @@ impl Game {
             bytemuck::cast_slice(&[camera_bundle.camera_uniform]),
         );
 
-        sender
-            .send(Packet::Movement {
-                delta_x: camera_bundle.camera.position.x as f64,
-                delta_y: camera_bundle.camera.position.y as f64,
-                delta_z: camera_bundle.camera.position.z as f64,
-            })
-            .unwrap();
-
-        *player_controller = PlayerController::new(2., 0.5);
-
-        for (player, mut transform_bundle) in players.iter_mut() {
-            sender
-                .send(Packet::PositionRequest {
-                    name: player.name.clone(),
-                })
-                .unwrap();
-
+        for mut transform_bundle in players.iter_mut() {
             let transform = transform_bundle.transform;
 
             transform_bundle.transform_uniform.update(transform);
@@ -270,44 +400,83 @@ impl Game {
     pub fn handle_packets(
         mut commands: Commands,
         mut events: EventReader<ServerEvent>,
-        mut renderer: ResMut<Renderer>,
-        assets: Res<AssetManager>,
-        mut players: Query<(&Player, &mut TransformBundle)>,
+        (mut renderer, assets): (ResMut<Renderer>, Res<AssetManager>),
+        (mut players, mut loaded): (Query<&mut Prediction, With<LocalPlayer>>, ResMut<Chunks>),
+        mut names: ResMut<PlayerNames>,
         chunks: Query<(Entity, &Chunk)>,
+        entities: Query<(Entity, &NetworkEntity)>,
     ) {
         for event in events.iter() {
             match &event.packet {
-                Packet::Connection { user } => {
-                    commands
-                        .spawn()
-                        .insert(Player { name: user.clone() })
-                        .insert(TransformBundle::new((0., 0., 10.), &mut renderer, &assets));
+                Packet::PlayerState {
+                    sequence,
+                    x,
+                    y,
+                    z,
+                    velocity,
+                } => {
+                    let body = Body {
+                        position: [*x, *y, *z],
+                        velocity: *velocity,
+                    };
+                    for mut prediction in players.iter_mut() {
+                        prediction.reconcile(*sequence, body, &loaded);
+                    }
+                }
+                Packet::PlayerJoined { id, name } => {
+                    names.names.insert(*id, name.clone());
                 }
                 Packet::OnlinePlayers { players } => {
                     for player in players {
-                        commands
-                            .spawn()
-                            .insert(Player {
-                                name: player.name.clone(),
-                            })
-                            .insert(TransformBundle::new(
-                                (player.x, player.y, player.z),
-                                &mut renderer,
-                                &assets,
-                            ));
+                        names.names.insert(player.id, player.name.clone());
+                    }
+                }
+                Packet::ChangeWorld { name } => {
+                    info!("Moved to world {name}");
+                    for (entity, _chunk) in chunks.iter() {
+                        commands.entity(entity).despawn();
+                    }
+                    loaded.chunks.clear();
+                    for (entity, _network_entity) in entities.iter() {
+                        commands.entity(entity).despawn();
                     }
                 }
-                Packet::PlayerPosition { x, y, z, name } => {
-                    for (player, mut transform_bundle) in players.iter_mut() {
-                        if player.name == name.clone() {
-                            transform_bundle.transform.position =
-                                (*x as f32, *y as f32, *z as f32).into();
+                Packet::SpawnEntity { id, kind, x, y, z } => {
+                    commands
+                        .spawn()
+                        .insert(NetworkEntity {
+                            id: *id,
+                            kind: kind.clone(),
+                        })
+                        .insert(TransformBundle::new(
+                            (*x as f32, *y as f32, *z as f32),
+                            &mut renderer,
+                            &assets,
+                        ))
+                        .insert(InterpolationBuffer::default());
+                }
+                Packet::DespawnEntity { id } => {
+                    for (entity, network_entity) in entities.iter() {
+                        if network_entity.id == *id {
+                            commands.entity(entity).despawn();
                         }
                     }
                 }
-                Packet::Chunk { x, y, groups } => {
-                    let chunk = Chunk::decompress(groups, *x, *y);
+                Packet::Chunk { x, y, data } => {
+                    // A chunk is sent again when it changes, the new one replaces the old one.
+                    for (entity, chunk) in chunks.iter() {
+                        if chunk.x == *x && chunk.y == *y {
+                            commands.entity(entity).despawn();
+                        }
+                    }
 
+                    let chunk = Chunk::decompress(data, *x, *y);
+
+                    // The prediction walks on a copy, the entity is drawn.
+                    loaded
+                        .chunks
+                        .retain(|loaded| loaded.x != *x || loaded.y != *y);
+                    loaded.chunks.push(chunk.clone());
                     commands.spawn().insert(chunk);
                     info!("Got chunk");
                 }
@@ -317,6 +486,199 @@ impl Game {
                             commands.entity(entity).despawn();
                         }
                     }
+                    loaded.chunks.retain(|chunk| chunk.x != *x || chunk.y != *y);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Rebuild the snapshots sent by the server, acknowledge them and buffer the states of the
+    /// other players and the entities they contain. Players show up when snapshots start having
+    /// them and go away when they stop, as they join, leave or change worlds. Other entities come
+    /// and go with the SpawnEntity and DespawnEntity packets.
+    pub fn handle_snapshots(
+        mut commands: Commands,
+        (mut events, mut sender, mut running): (
+            EventReader<ServerEvent>,
+            ResMut<SocketSender>,
+            ResMut<Running>,
+        ),
+        (mut history, mut clock): (ResMut<SnapshotHistory>, ResMut<ServerClock>),
+        (mut renderer, assets): (ResMut<Renderer>, Res<AssetManager>),
+        (server_info, names): (Res<ServerInfo>, Res<PlayerNames>),
+        mut players: Query<(Entity, &Player, &mut InterpolationBuffer)>,
+        mut others: Query<(&NetworkEntity, &mut InterpolationBuffer), Without<Player>>,
+    ) {
+        // Entities are spawned at the end of the frame, several snapshots can have new players.
+        let mut spawned = HashSet::new();
+
+        for event in events.iter() {
+            if let Packet::Snapshot {
+                tick,
+                baseline,
+                entities,
+                removed,
+            } = &event.packet
+            {
+                // Snapshots arriving late are useless, a newer one has already been applied.
+                if history.latest().is_some_and(|latest| latest.tick >= *tick) {
+                    continue;
+                }
+
+                let baseline = match baseline {
+                    Some(baseline) => match history.get(*baseline) {
+                        Some(snapshot) => Some(snapshot),
+                        None => continue,
+                    },
+                    None => None,
+                };
+
+                let snapshot = match Snapshot::apply(*tick, baseline, entities, removed) {
+                    Some(snapshot) => snapshot,
+                    None => continue,
+                };
+
+                let time = tick_time(*tick, server_info.tick_rate);
+                clock.update(time, Instant::now());
+
+                for (entity, player, mut buffer) in players.iter_mut() {
+                    match snapshot.entities.get(&player.id) {
+                        Some(state) => buffer.push(time, *state),
+                        None => commands.entity(entity).despawn(),
+                    }
+                    spawned.insert(player.id);
+                }
+
+                for (network_entity, mut buffer) in others.iter_mut() {
+                    if let Some(state) = snapshot.entities.get(&network_entity.id) {
+                        buffer.push(time, *state);
+                    }
+                }
+
+                for (id, state) in snapshot.entities.iter() {
+                    // Players are spawned once their name is known. The local player already has
+                    // its own entity, moved by the prediction.
+                    let name = match names.names.get(id) {
+                        Some(name) if !spawned.contains(id) && server_info.id != Some(*id) => name,
+                        _ => continue,
+                    };
+                    spawned.insert(*id);
+
+                    let [x, y, z] = state.position;
+                    let mut buffer = InterpolationBuffer::default();
+                    buffer.push(time, *state);
+                    commands
+                        .spawn()
+                        .insert(Player {
+                            id: *id,
+                            name: name.clone(),
+                        })
+                        .insert(TransformBundle::new(
+                            (x as f32, y as f32, z as f32),
+                            &mut renderer,
+                            &assets,
+                        ))
+                        .insert(buffer);
+                }
+
+                send(
+                    &mut sender,
+                    &mut running,
+                    Packet::SnapshotAck { tick: *tick },
+                );
+
+                history.push(snapshot);
+            }
+        }
+    }
+
+    /// Move the other players and the entities to where they were `delay` ago according to the
+    /// buffered states.
+    pub fn interpolate_players(
+        settings: Res<InterpolationSettings>,
+        clock: Res<ServerClock>,
+        mut players: Query<(&mut InterpolationBuffer, &mut TransformBundle)>,
+    ) {
+        let time = match clock.now(Instant::now()) {
+            Some(now) => now.saturating_sub(settings.delay),
+            None => return,
+        };
+
+        for (mut buffer, mut transform_bundle) in players.iter_mut() {
+            if let Some(state) = buffer.sample(time, settings.max_extrapolation) {
+                transform_bundle.transform.position = (
+                    state.position[0] as f32,
+                    state.position[1] as f32,
+                    state.position[2] as f32,
+                )
+                    .into();
+                transform_bundle.transform.rotation =
+                    (state.rotation[1], state.rotation[0], 0.).into();
+            }
+
+            buffer.discard_before(time);
+        }
+    }
+
+    /// Handle the packets that set up the connection: the key exchange and compression.
+    pub fn handle_connection(
+        mut events: EventReader<ServerEvent>,
+        mut sender: ResMut<SocketSender>,
+        mut pending: Option<ResMut<PendingConnection>>,
+        mut server_info: ResMut<ServerInfo>,
+        (credentials, mut running): (Res<Credentials>, ResMut<Running>),
+    ) {
+        for event in events.iter() {
+            match &event.packet {
+                Packet::KeyExchange { public_key } => {
+                    let pending = match pending.as_mut() {
+                        Some(pending) => pending,
+                        None => continue,
+                    };
+
+                    if let (Some(handshake), Ok(addr)) =
+                        (pending.handshake.take(), sender.peer_addr())
+                    {
+                        sender.set_session(addr, Some(handshake.finish(*public_key, Role::Client)));
+
+                        info!("Encrypted session established");
+
+                        let connection = Packet::Connection {
+                            user: pending.username.clone(),
+                            compression: true,
+                        };
+                        send(&mut sender, &mut running, connection);
+                    }
+                }
+                Packet::Disconnect { reason } => {
+                    error!("Disconnected by the server: {reason}");
+                }
+                Packet::AuthChallenge {
+                    salt,
+                    iterations,
+                    nonce,
+                } => {
+                    let answer = credentials.answer(salt, *iterations, nonce);
+                    send(&mut sender, &mut running, answer);
+                }
+                Packet::SystemMessage { message } => {
+                    info!("{message}");
+                }
+                Packet::Chat {
+                    sender: name,
+                    message,
+                } => {
+                    info!("<{name}> {message}");
+                }
+                Packet::Welcome { id, tick_rate } => {
+                    server_info.id = Some(*id);
+                    server_info.tick_rate = *tick_rate;
+                }
+                Packet::Compression { threshold } => {
+                    if let Ok(addr) = sender.peer_addr() {
+                        sender.set_compression(addr, Some(*threshold));
+                    }
                 }
                 _ => (),
             }
@@ -330,7 +692,7 @@ impl Game {
         assets: Res<AssetManager>,
     ) {
         for (entity, chunk) in no_mesh.iter() {
-            let mesh = ChunkMesh::build(&mut renderer, &chunk);
+            let mesh = ChunkMesh::build(&mut renderer, chunk);
 
             commands
                 .entity(entity)
@@ -343,10 +705,11 @@ impl Game {
         }
     }
 
+    /// Keep track of the keys held, and toggle the movement mode with F and the view with F5.
     pub fn handle_keyboard(
         mut events: EventReader<KeyboardEvent>,
-        mut camera_controller: ResMut<CameraController>,
-        mut player_controller: ResMut<PlayerController>,
+        mut input: ResMut<PlayerInput>,
+        mut players: Query<(&mut MovementMode, &mut View), With<LocalPlayer>>,
     ) {
         for event in events.iter() {
             if let KeyboardInput {
@@ -355,20 +718,26 @@ impl Game {
                 ..
             } = event.input
             {
-                camera_controller.process_keyboard(keycode, state);
-                player_controller.process_keyboard(keycode, state);
+                input.process_keyboard(keycode, state);
+
+                // Held keys repeat their presses, the release only comes once.
+                if state != ElementState::Released {
+                    continue;
+                }
+                for (mut mode, mut view) in players.iter_mut() {
+                    match keycode {
+                        VirtualKeyCode::F => mode.toggle(),
+                        VirtualKeyCode::F5 => view.toggle(),
+                        _ => (),
+                    }
+                }
             }
         }
     }
 
-    pub fn handle_mouse(
-        mut events: EventReader<MouseMotion>,
-        mut camera_controller: ResMut<CameraController>,
-        mut player_controller: ResMut<PlayerController>,
-    ) {
+    pub fn handle_mouse(mut events: EventReader<MouseMotion>, mut input: ResMut<PlayerInput>) {
         for event in events.iter() {
-            camera_controller.process_mouse(event.delta.0, event.delta.1);
-            player_controller.process_mouse(event.delta.0, event.delta.1);
+            input.process_mouse(event.delta.0, event.delta.1);
         }
     }
 
@@ -376,7 +745,7 @@ impl Game {
         mut renderer: ResMut<Renderer>,
         assets: Res<AssetManager>,
         camera_bundle: Res<CameraBundle>,
-        players: Query<(&Player, &TransformBundle)>,
+        players: Query<(&TransformBundle, Option<&View>), Drawn>,
         chunks: Query<(&Chunk, &ChunkMesh, &TransformBundle)>,
         chunk_indices: Res<ChunkIndices>,
     ) {
@@ -444,7 +813,12 @@ impl Game {
                     .set_index_buffer(renderer.buffers[indices].slice(..), IndexFormat::Uint16);
                 render_pass.set_vertex_buffer(0, renderer.buffers[mesh.buffer].slice(..));
 
-                for (_player, transform_bundle) in players.iter() {
+                for (transform_bundle, view) in players.iter() {
+                    // The local player doesn't see itself in first person.
+                    if view == Some(&View::FirstPerson) {
+                        continue;
+                    }
+
                     render_pass.set_bind_group(
                         1,
                         renderer.get_bind_group(transform_bundle.bind_group),
@@ -482,3 +856,18 @@ impl Game {
         }
     }
 }
+
+/// Send a packet to the server. A packet that doesn't fit in the send queue is dropped like the
+/// network would drop it, any other error stops the client.
+fn send(sender: &mut SocketSender, running: &mut Running, packet: Packet) {
+    match sender.send(packet) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+            warn!("Dropping a packet to the server: {e}")
+        }
+        Err(e) => {
+            error!("Cannot send packets to the server anymore: {e}");
+            running.0 = false;
+        }
+    }
+}
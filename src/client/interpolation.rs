@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+use bevy_ecs::prelude::Component;
+
+use crate::network::snapshot::EntityState;
+use crate::server::DEFAULT_TICK_RATE;
+
+/// Number of states kept per entity, enough to cover a second of snapshots at the default tick
+/// rate.
+const BUFFER_SIZE: usize = DEFAULT_TICK_RATE as usize;
+
+/// Difference between the estimated and the received server time above which the clock is reset
+/// instead of slowly corrected, like after a long freeze.
+const CLOCK_RESET: Duration = Duration::from_millis(500);
+
+/// How much of the difference between the estimated and the received server time is corrected
+/// with every snapshot.
+const CLOCK_CORRECTION: f64 = 0.1;
+
+/// How remote entities are rendered from the states received from the server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterpolationSettings {
+    /// How far in the past remote entities are rendered. A longer delay hides more late or lost
+    /// snapshots, but shows other players further behind where they are.
+    pub delay: Duration,
+    /// How long an entity keeps moving with its last known velocity when no newer state arrived.
+    pub max_extrapolation: Duration,
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(100),
+            max_extrapolation: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Server time of the start of a tick.
+pub fn tick_time(tick: u64, tick_rate: u32) -> Duration {
+    Duration::from_secs_f64(tick as f64 / tick_rate as f64)
+}
+
+/// Estimate of the current server time, kept from the ticks of received snapshots.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServerClock {
+    /// Server time at the instant of the last update.
+    reference: Option<(Duration, Instant)>,
+}
+
+impl ServerClock {
+    /// Correct the clock with a server time received at `now`. Small differences are smoothed out
+    /// so network jitter doesn't make entities jump back and forth.
+    pub fn update(&mut self, server_time: Duration, now: Instant) {
+        let estimate = match self.now(now) {
+            Some(estimate) => estimate,
+            None => {
+                self.reference = Some((server_time, now));
+                return;
+            }
+        };
+
+        let error = server_time.as_secs_f64() - estimate.as_secs_f64();
+        let corrected = if error.abs() > CLOCK_RESET.as_secs_f64() {
+            server_time.as_secs_f64()
+        } else {
+            estimate.as_secs_f64() + error * CLOCK_CORRECTION
+        };
+
+        self.reference = Some((Duration::from_secs_f64(corrected.max(0.)), now));
+    }
+
+    /// Estimated server time at `now`.
+    pub fn now(&self, now: Instant) -> Option<Duration> {
+        self.reference
+            .map(|(server_time, instant)| server_time + now.saturating_duration_since(instant))
+    }
+}
+
+/// Recent states of a remote entity with the server time they were taken at.
+#[derive(Debug, Clone, Default, Component)]
+pub struct InterpolationBuffer {
+    states: VecDeque<(Duration, EntityState)>,
+}
+
+impl InterpolationBuffer {
+    /// Add a state, ignoring it if it is older than the newest one.
+    pub fn push(&mut self, time: Duration, state: EntityState) {
+        if self
+            .states
+            .back()
+            .is_some_and(|(latest, _state)| *latest >= time)
+        {
+            return;
+        }
+
+        if self.states.len() == BUFFER_SIZE {
+            self.states.pop_front();
+        }
+
+        self.states.push_back((time, state));
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// State of the entity at `time`, interpolated between the states around it. Past the newest
+    /// state the entity is moved with its last velocity for at most `max_extrapolation`, then
+    /// stays where it is until a new state arrives.
+    pub fn sample(&self, time: Duration, max_extrapolation: Duration) -> Option<EntityState> {
+        let (first_time, first) = self.states.front()?;
+        if time <= *first_time {
+            return Some(*first);
+        }
+
+        let (last_time, last) = self.states.back()?;
+        if time >= *last_time {
+            let ahead = (time - *last_time).min(max_extrapolation).as_secs_f64();
+            return Some(extrapolate(last, ahead));
+        }
+
+        let next = self.states.iter().position(|(t, _state)| *t > time)?;
+        let (from_time, from) = &self.states[next - 1];
+        let (to_time, to) = &self.states[next];
+
+        let t = (time - *from_time).as_secs_f64() / (*to_time - *from_time).as_secs_f64();
+        Some(interpolate(from, to, t))
+    }
+
+    /// Drop the states that can't be used anymore to sample times after `time`.
+    pub fn discard_before(&mut self, time: Duration) {
+        while self.states.len() > 1 && self.states[1].0 <= time {
+            self.states.pop_front();
+        }
+    }
+}
+
+/// Linear interpolation between two states, `t` going from 0 to 1.
+pub fn interpolate(from: &EntityState, to: &EntityState, t: f64) -> EntityState {
+    let lerp = |a: f64, b: f64| a + (b - a) * t;
+    let tf = t as f32;
+
+    EntityState {
+        position: [
+            lerp(from.position[0], to.position[0]),
+            lerp(from.position[1], to.position[1]),
+            lerp(from.position[2], to.position[2]),
+        ],
+        rotation: [
+            lerp_angle(from.rotation[0], to.rotation[0], tf),
+            from.rotation[1] + (to.rotation[1] - from.rotation[1]) * tf,
+        ],
+        velocity: [
+            from.velocity[0] + (to.velocity[0] - from.velocity[0]) * tf,
+            from.velocity[1] + (to.velocity[1] - from.velocity[1]) * tf,
+            from.velocity[2] + (to.velocity[2] - from.velocity[2]) * tf,
+        ],
+    }
+}
+
+/// Move a state along its velocity for `seconds`.
+pub fn extrapolate(state: &EntityState, seconds: f64) -> EntityState {
+    EntityState {
+        position: [
+            state.position[0] + state.velocity[0] as f64 * seconds,
+            state.position[1] + state.velocity[1] as f64 * seconds,
+            state.position[2] + state.velocity[2] as f64 * seconds,
+        ],
+        ..*state
+    }
+}
+
+/// Interpolate between two angles in radians going the short way around.
+fn lerp_angle(from: f32, to: f32, t: f32) -> f32 {
+    let mut difference = (to - from) % (2. * PI);
+    if difference > PI {
+        difference -= 2. * PI;
+    } else if difference < -PI {
+        difference += 2. * PI;
+    }
+
+    from + difference * t
+}
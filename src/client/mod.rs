@@ -1,14 +1,91 @@
+use log::error;
+
+use crate::network::auth::{self, MAX_ITERATIONS, NONCE_SIZE, SALT_SIZE};
+use crate::network::crypto::Handshake;
 use crate::network::Packet;
+use crate::server::DEFAULT_TICK_RATE;
 
+pub mod bot;
+#[cfg(feature = "graphics")]
 pub mod camera;
+#[cfg(feature = "graphics")]
 pub mod chunk;
+#[cfg(feature = "graphics")]
+pub mod entity;
+#[cfg(feature = "graphics")]
 pub mod game;
+pub mod interpolation;
+#[cfg(feature = "graphics")]
 pub mod player;
+pub mod prediction;
+#[cfg(feature = "graphics")]
 pub mod renderer;
+pub mod status;
+#[cfg(feature = "graphics")]
 pub mod transform;
+#[cfg(feature = "graphics")]
 pub mod voxel;
 
 /// This is an event sent by the network handler in the client when the server sends a new packet.
 pub struct ServerEvent {
     pub packet: Packet,
 }
+
+/// What the server told about itself and the local player when it accepted the connection.
+pub struct ServerInfo {
+    /// Network id of the local player, None until the server welcomed it.
+    pub id: Option<u32>,
+    pub tick_rate: u32,
+}
+
+impl Default for ServerInfo {
+    fn default() -> Self {
+        Self {
+            id: None,
+            tick_rate: DEFAULT_TICK_RATE,
+        }
+    }
+}
+
+/// Whether the client keeps running. Cleared when packets can't be sent to the server anymore,
+/// the window then closes.
+pub struct Running(pub bool);
+
+/// Who the local player logs in as. The password is only needed by servers with accounts.
+pub struct Credentials {
+    pub username: String,
+    pub password: Option<String>,
+}
+
+impl Credentials {
+    /// Answer an authentication challenge of the server with the proof of the password, or give
+    /// up with a Disconnect packet when there is no password or it would take too long.
+    pub fn answer(
+        &self,
+        salt: &[u8; SALT_SIZE],
+        iterations: u32,
+        nonce: &[u8; NONCE_SIZE],
+    ) -> Packet {
+        let reason = if iterations > MAX_ITERATIONS {
+            error!("The server asks for too many password rounds");
+            "Too many password rounds"
+        } else if let Some(password) = &self.password {
+            let key = auth::derive_key(password, salt, iterations);
+            let proof = auth::prove(&key, nonce, &self.username);
+            return Packet::AuthResponse { proof };
+        } else {
+            error!("The server needs a password, start the game with --password");
+            "No password"
+        };
+
+        Packet::Disconnect {
+            reason: String::from(reason),
+        }
+    }
+}
+
+/// Connection to the server waiting for the key exchange to finish before the username is sent.
+pub struct PendingConnection {
+    pub username: String,
+    pub handshake: Option<Handshake>,
+}
@@ -1,58 +1,151 @@
+use crate::world::movement::MovementInput;
 use bevy_ecs::prelude::Component;
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
 use winit::event::{ElementState, VirtualKeyCode};
 
-pub struct PlayerController {
-    pub amount_left: f32,
-    pub amount_right: f32,
-    pub amount_down: f32,
-    pub amount_up: f32,
-    pub amount_back: f32,
-    pub amount_forward: f32,
-    pub rotate_horizontal: f32,
-    pub rotate_vertical: f32,
-    pub speed: f32,
+/// Distance in blocks from the eyes of the local player to the camera in third person.
+pub const THIRD_PERSON_DISTANCE: f32 = 4.;
+
+/// The player controlled by this client. It is moved by the prediction, the server only
+/// corrects it.
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct LocalPlayer;
+
+/// Position of the feet of the local player, between the last two predicted inputs.
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct Position(pub [f64; 3]);
+
+/// Vertical velocity of the local player in blocks per second, positive upwards.
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct Velocity(pub f64);
+
+/// Where the local player looks.
+#[derive(Debug, Clone, Copy, Default, Component)]
+pub struct Look {
+    /// Yaw in radians.
+    pub yaw: f32,
+    /// Pitch in radians, from -PI/2 to PI/2.
+    pub pitch: f32,
+}
+
+impl Look {
+    /// Turn by the given angles in radians, without looking further than straight up or down.
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-FRAC_PI_2, FRAC_PI_2);
+    }
+}
+
+/// How the local player moves, toggled with F.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Component)]
+pub enum MovementMode {
+    #[default]
+    Walking,
+    Flying,
+}
+
+impl MovementMode {
+    pub fn toggle(&mut self) {
+        *self = match self {
+            MovementMode::Walking => MovementMode::Flying,
+            MovementMode::Flying => MovementMode::Walking,
+        };
+    }
+}
+
+/// Where the camera shows the local player from, toggled with F5.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Component)]
+pub enum View {
+    /// From its eyes, the player itself isn't drawn.
+    #[default]
+    FirstPerson,
+    /// From `THIRD_PERSON_DISTANCE` behind its eyes.
+    ThirdPerson,
+}
+
+impl View {
+    pub fn toggle(&mut self) {
+        *self = match self {
+            View::FirstPerson => View::ThirdPerson,
+            View::ThirdPerson => View::FirstPerson,
+        };
+    }
+}
+
+/// Keys held by the player and mouse movement not applied yet. Keys stay held across frames
+/// until they are released.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlayerInput {
+    pub forward: bool,
+    pub back: bool,
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+    /// Mouse movement since the look was last turned.
+    pub mouse: (f64, f64),
+    /// Radians turned by unit of mouse movement.
     pub sensitivity: f32,
 }
-impl PlayerController {
-    pub fn new(speed: f32, sensitivity: f32) -> Self {
+
+impl PlayerInput {
+    pub fn new(sensitivity: f32) -> Self {
         Self {
-            amount_left: 0.,
-            amount_right: 0.,
-            amount_down: 0.,
-            amount_up: 0.,
-            amount_back: 0.,
-            amount_forward: 0.,
-            rotate_horizontal: 0.,
-            rotate_vertical: 0.,
-            speed,
             sensitivity,
+            ..Default::default()
         }
     }
 
     pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) {
-        let amount = if let ElementState::Pressed = state {
-            1.
-        } else {
-            0.
-        };
+        let pressed = state == ElementState::Pressed;
         match key {
-            VirtualKeyCode::W => self.amount_forward = amount,
-            VirtualKeyCode::A => self.amount_left = amount,
-            VirtualKeyCode::S => self.amount_back = amount,
-            VirtualKeyCode::D => self.amount_right = amount,
-            VirtualKeyCode::Space => self.amount_up = amount,
-            VirtualKeyCode::LShift => self.amount_down = amount,
+            VirtualKeyCode::W => self.forward = pressed,
+            VirtualKeyCode::A => self.left = pressed,
+            VirtualKeyCode::S => self.back = pressed,
+            VirtualKeyCode::D => self.right = pressed,
+            VirtualKeyCode::Space => self.up = pressed,
+            VirtualKeyCode::LShift => self.down = pressed,
             _ => (),
         }
     }
 
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+        self.mouse.0 += mouse_dx;
+        self.mouse.1 += mouse_dy;
+    }
+
+    /// Turn the look by the mouse movement since the last call.
+    pub fn turn(&mut self, look: &mut Look) {
+        let (dx, dy) = std::mem::take(&mut self.mouse);
+        look.turn(dx as f32 * self.sensitivity, -dy as f32 * self.sensitivity);
+    }
+
+    /// Movement input for the keys currently held, looking in the given direction.
+    pub fn input(&self, look: &Look, mode: MovementMode) -> MovementInput {
+        let axis = |positive: bool, negative: bool| positive as i8 as f32 - negative as i8 as f32;
+
+        MovementInput {
+            forward: axis(self.forward, self.back),
+            right: axis(self.right, self.left),
+            up: axis(self.up, self.down),
+            yaw: look.yaw,
+            pitch: look.pitch,
+            flying: mode == MovementMode::Flying,
+        }
     }
 }
 
 #[derive(Debug, Clone, Component)]
 pub struct Player {
+    /// Network id of the player entity on the server.
+    pub id: u32,
     pub name: String,
 }
+
+/// Names of the online players by network id, known from the join packets. Players only get an
+/// entity while snapshots have them, when they are in the same world.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerNames {
+    pub names: HashMap<u32, String>,
+}
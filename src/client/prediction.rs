@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bevy_ecs::prelude::Component;
+
+use crate::world::movement::{MovementInput, INPUTS_PER_SECOND};
+use crate::world::physics::Body;
+use crate::world::Chunks;
+
+/// Maximum number of inputs waiting for the server to simulate them. If the server stops
+/// answering the oldest ones are forgotten.
+const MAX_PENDING_INPUTS: usize = 4 * INPUTS_PER_SECOND as usize;
+
+/// Maximum number of inputs simulated in one frame, so a long frame doesn't flood the server.
+const MAX_INPUTS_PER_FRAME: usize = 5;
+
+/// Client side prediction of the local player. Inputs are simulated against the chunks the client
+/// has as soon as they are sent and kept until the server acknowledges them, then the
+/// authoritative state is taken and the inputs the server didn't simulate yet are replayed on top
+/// of it.
+#[derive(Debug, Clone, Component)]
+pub struct Prediction {
+    body: Body,
+    previous: [f64; 3],
+    pending: VecDeque<(u32, MovementInput)>,
+    sequence: u32,
+    /// Sequence number of the last input acknowledged by the server.
+    acknowledged: u32,
+    /// Time since the last simulated input.
+    accumulator: Duration,
+}
+
+impl Prediction {
+    pub fn new(position: [f64; 3]) -> Self {
+        Self {
+            body: Body::new(position),
+            previous: position,
+            pending: VecDeque::new(),
+            sequence: 0,
+            acknowledged: 0,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Advance by a frame, simulating `input` once for every input interval that elapsed. Returns
+    /// the inputs to send to the server with their sequence numbers.
+    pub fn update(
+        &mut self,
+        delta: Duration,
+        input: MovementInput,
+        chunks: &Chunks,
+    ) -> Vec<(u32, MovementInput)> {
+        let interval = Duration::from_secs_f64(1. / INPUTS_PER_SECOND as f64);
+        self.accumulator += delta;
+
+        let mut inputs = Vec::new();
+        while self.accumulator >= interval {
+            self.accumulator -= interval;
+
+            if inputs.len() == MAX_INPUTS_PER_FRAME {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+
+            inputs.push(self.apply(input, chunks));
+        }
+
+        inputs
+    }
+
+    /// Simulate an input, returning it with its sequence number.
+    pub fn apply(&mut self, input: MovementInput, chunks: &Chunks) -> (u32, MovementInput) {
+        let input = input.clamped();
+        self.sequence += 1;
+
+        self.previous = self.body.position;
+        self.body.step(&input, chunks);
+
+        if self.pending.len() == MAX_PENDING_INPUTS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((self.sequence, input));
+
+        (self.sequence, input)
+    }
+
+    /// Take the authoritative state of the player after the server simulated the inputs up to
+    /// `sequence`, and replay the newer ones.
+    pub fn reconcile(&mut self, sequence: u32, body: Body, chunks: &Chunks) {
+        // States can arrive out of order, and a state acknowledging inputs that were never sent
+        // can't be trusted.
+        if sequence < self.acknowledged || sequence > self.sequence {
+            return;
+        }
+        self.acknowledged = sequence;
+
+        while self
+            .pending
+            .front()
+            .is_some_and(|(pending, _input)| *pending <= sequence)
+        {
+            self.pending.pop_front();
+        }
+
+        let mut predicted = body;
+        for (_sequence, input) in self.pending.iter() {
+            predicted.step(input, chunks);
+        }
+
+        // The previous position is moved by the same error so the interpolation between the two
+        // doesn't jump.
+        for (previous, (predicted, position)) in self
+            .previous
+            .iter_mut()
+            .zip(predicted.position.iter().zip(self.body.position.iter()))
+        {
+            *previous += predicted - position;
+        }
+        self.body = predicted;
+    }
+
+    /// Predicted state after the last simulated input.
+    pub fn body(&self) -> Body {
+        self.body
+    }
+
+    /// Predicted position after the last simulated input.
+    pub fn position(&self) -> [f64; 3] {
+        self.body.position
+    }
+
+    /// Number of inputs not acknowledged by the server yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Position to render, interpolated between the last two inputs so movement is smooth at any
+    /// frame rate.
+    pub fn render_position(&self) -> [f64; 3] {
+        let interval = 1. / INPUTS_PER_SECOND as f64;
+        let t = (self.accumulator.as_secs_f64() / interval).min(1.);
+
+        [
+            self.previous[0] + (self.body.position[0] - self.previous[0]) * t,
+            self.previous[1] + (self.body.position[1] - self.previous[1]) * t,
+            self.previous[2] + (self.body.position[2] - self.previous[2]) * t,
+        ]
+    }
+}
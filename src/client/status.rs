@@ -0,0 +1,133 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand_core::{OsRng, RngCore};
+
+use crate::network::transport::{Transport, UdpTransport};
+use crate::network::{Packet, PROTOCOL_VERSION};
+
+/// How long to wait for a server to answer a status request by default.
+pub const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a server says about itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerStatus {
+    pub protocol: u32,
+    pub motd: String,
+    pub online: u32,
+    pub max_players: u32,
+    /// Names of some of the online players.
+    pub sample: Vec<String>,
+    /// Time between the request and the answer.
+    pub latency: Duration,
+}
+
+impl ServerStatus {
+    /// Whether this client can join the server.
+    pub fn compatible(&self) -> bool {
+        self.protocol == PROTOCOL_VERSION
+    }
+}
+
+impl Display for ServerStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.motd)?;
+
+        write!(f, "{}/{} players online", self.online, self.max_players)?;
+        if !self.sample.is_empty() {
+            write!(f, ": {}", self.sample.join(", "))?;
+        }
+        let hidden = self.online as usize - self.sample.len().min(self.online as usize);
+        if hidden > 0 {
+            write!(f, " and {hidden} more")?;
+        }
+        writeln!(f)?;
+
+        write!(f, "Protocol {}", self.protocol)?;
+        if !self.compatible() {
+            write!(f, ", incompatible with this client ({PROTOCOL_VERSION})")?;
+        }
+        writeln!(f)?;
+
+        write!(f, "Latency {} ms", self.latency.as_millis())
+    }
+}
+
+/// Ask a server for its status without joining it, waiting at most `timeout` for the answer.
+/// The transport is closed if the server doesn't answer in time.
+pub fn query(
+    transport: &impl Transport,
+    server: SocketAddr,
+    timeout: Duration,
+) -> io::Result<ServerStatus> {
+    let token = OsRng.next_u64();
+    let start = Instant::now();
+    transport.send_to(Packet::StatusRequest { token }, &server)?;
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+
+        scope.spawn(move || {
+            let status = loop {
+                match transport.recv_from() {
+                    Ok((
+                        Packet::Status {
+                            token: received,
+                            protocol,
+                            motd,
+                            online,
+                            max_players,
+                            sample,
+                        },
+                        peer,
+                    )) if received == token && peer == server => {
+                        break Ok(ServerStatus {
+                            protocol,
+                            motd,
+                            online,
+                            max_players,
+                            sample,
+                            latency: start.elapsed(),
+                        })
+                    }
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            ErrorKind::ConnectionAborted | ErrorKind::ConnectionRefused
+                        ) =>
+                    {
+                        break Err(e)
+                    }
+                    // Anything else isn't the answer.
+                    _ => (),
+                }
+            };
+
+            let _ = sender.send(status);
+        });
+
+        match receiver.recv_timeout(timeout) {
+            Ok(status) => status,
+            Err(_) => {
+                transport.close();
+                Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "the server did not answer",
+                ))
+            }
+        }
+    })
+}
+
+/// Ask the server at an address for its status over UDP.
+pub fn query_addr(addr: impl ToSocketAddrs, timeout: Duration) -> io::Result<ServerStatus> {
+    let transport = UdpTransport::connect(addr)?;
+    let server = transport.peer_addr()?;
+
+    query(&transport, server, timeout)
+}
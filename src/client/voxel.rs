@@ -67,6 +67,7 @@ pub struct BlockFace {
 }
 
 impl BlockFace {
+    #[allow(clippy::identity_op)]
     pub fn new(direction: Direction, x: u32, y: u32, z: u32) -> Self {
         match direction {
             Direction::North => Self {
@@ -1,6 +1,8 @@
 extern crate core;
 
+#[cfg(feature = "graphics")]
 use std::time::Duration;
+#[cfg(feature = "graphics")]
 use winit::event::KeyboardInput;
 
 pub mod assets;
@@ -9,12 +11,15 @@ pub mod network;
 pub mod server;
 pub mod world;
 
+#[cfg(feature = "graphics")]
 pub struct DeltaTime(Duration);
 
+#[cfg(feature = "graphics")]
 pub struct KeyboardEvent {
     pub input: KeyboardInput,
 }
 
+#[cfg(feature = "graphics")]
 pub struct MouseMotion {
     pub delta: (f64, f64),
 }
@@ -1,5 +1,19 @@
-use log::info;
+use log::{error, info};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{fs, process, thread};
 use winit::error::OsError;
+use yave::client::interpolation::InterpolationSettings;
+use yave::client::status::{self, STATUS_TIMEOUT};
+use yave::network::conditioner::{ConditionedTransport, NetworkConditions};
+use yave::network::lan::{LanDiscovery, ANNOUNCE_INTERVAL, LAN_GROUP};
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::record::RecordingTransport;
+use yave::network::transport::{Transport, UdpTransport};
+use yave::server::config::{ServerConfig, CONFIG_FILE};
+use yave::server::console;
+use yave::server::game::Game;
 
 #[tokio::main]
 async fn main() -> Result<(), OsError> {
@@ -9,40 +23,235 @@ async fn main() -> Result<(), OsError> {
 
     let mut addr = String::from("localhost:25000");
     let mut username = String::from("singleplayer");
+    let mut password = None;
     let mut dedicated = false;
-    let mut port = String::from("25000");
     let mut remote = false;
+    let mut encrypt = false;
+    let mut interpolation = InterpolationSettings::default();
+    let mut config_path = PathBuf::from(CONFIG_FILE);
+    let mut status_addr = None;
+    let mut discover = false;
+    let mut record = None;
 
-    for (i, arg) in args.iter().enumerate() {
-        if arg == "--connect" {
-            addr = args.get(i + 1).unwrap().clone();
-            remote = true;
+    // Flags of the client are read here, the others are server settings.
+    let mut server_args = Vec::new();
+    let mut flags = args.iter().skip(1);
+    while let Some(arg) = flags.next() {
+        let mut value = || match flags.next() {
+            Some(value) => value.clone(),
+            None => {
+                error!("{arg} needs a value");
+                process::exit(1);
+            }
+        };
+
+        match arg.as_str() {
+            "--connect" => {
+                addr = value();
+                remote = true;
+            }
+            "--username" => username = value(),
+            "--password" => password = Some(value()),
+            "--dedicated" => dedicated = true,
+            "--encrypt" => encrypt = true,
+            "--interpolation-delay" => {
+                let millis = value();
+                match millis.parse() {
+                    Ok(millis) => interpolation.delay = Duration::from_millis(millis),
+                    Err(_) => {
+                        error!("invalid value {millis:?} for {arg}");
+                        process::exit(1);
+                    }
+                }
+            }
+            "--status" => status_addr = Some(value()),
+            "--discover" => discover = true,
+            "--record" => record = Some(PathBuf::from(value())),
+            "--config" => config_path = PathBuf::from(value()),
+            // Read by `NetworkConditions::from_args`.
+            "--network-conditions" => {
+                value();
+            }
+            _ => server_args.push(arg.clone()),
         }
+    }
+
+    // Server settings are only used when a server is started.
+    let starts_server = status_addr.is_none() && !discover && (dedicated || !remote);
+    if let Some(arg) = server_args.first().filter(|_| !starts_server) {
+        error!("unknown argument {arg}");
+        process::exit(1);
+    }
 
-        if arg == "--username" {
-            username = args.get(i + 1).unwrap().clone();
+    let conditions = match NetworkConditions::from_args(&args) {
+        Ok(conditions) => conditions,
+        Err(e) => {
+            error!("{e}");
+            process::exit(1);
         }
+    };
 
-        if arg == "--dedicated" {
-            dedicated = true;
+    // Only print what the server says about itself, without starting the game.
+    if let Some(addr) = status_addr {
+        match status::query_addr(&addr, STATUS_TIMEOUT) {
+            Ok(status) => println!("{status}"),
+            Err(e) => {
+                error!("Cannot get the status of {addr}: {e}");
+                process::exit(1);
+            }
         }
 
-        if arg == "--port" {
-            port = args.get(i + 1).unwrap().clone();
+        return Ok(());
+    }
+
+    // Listen long enough to hear every server announce itself at least once.
+    if discover {
+        let discovery = match LanDiscovery::listen(Ipv4Addr::UNSPECIFIED, LAN_GROUP) {
+            Ok(discovery) => discovery,
+            Err(e) => {
+                error!("Cannot listen for servers on the local network: {e}");
+                process::exit(1);
+            }
+        };
+
+        thread::sleep(ANNOUNCE_INTERVAL * 2);
+
+        let servers = discovery.servers();
+        if servers.is_empty() {
+            println!("No server found on the local network");
         }
+        for server in servers {
+            println!("{server}");
+        }
+
+        return Ok(());
     }
 
     info!("Game starting");
 
-    if !dedicated {
-        if !remote {
-            tokio::spawn(async move { yave::server::game::Game::run(port).unwrap() });
+    if dedicated {
+        let config = ServerConfig::load_or_create(&config_path).and_then(|mut config| {
+            config.apply_args(&server_args)?;
+            Ok(config)
+        });
+
+        let config = match config {
+            Ok(config) => config,
+            Err(e) => {
+                error!(
+                    "Invalid server configuration in {}: {e}",
+                    config_path.display()
+                );
+                process::exit(1);
+            }
+        };
+
+        if let Err(e) = fs::create_dir_all(&config.world_dir) {
+            error!(
+                "Cannot create the world directory {}: {e}",
+                config.world_dir.display()
+            );
+            process::exit(1);
         }
 
-        yave::client::game::Game::run(addr, username).await?;
+        let transport = match UdpTransport::bind(config.addr()) {
+            Ok(transport) => transport,
+            Err(e) => {
+                error!("Cannot listen on {}: {e}", config.addr());
+                process::exit(1);
+            }
+        };
+
+        let game = Game::new(
+            recorded(ConditionedTransport::wrap(transport, conditions), &record),
+            config,
+        );
+        console::spawn(game.console());
+        console::stop_on_signal(game.console());
+
+        if let Err(e) = game.run() {
+            error!("The server did not stop cleanly: {e}");
+            process::exit(1);
+        }
+    } else if remote {
+        let transport = match UdpTransport::connect(&addr) {
+            Ok(transport) => transport,
+            Err(e) => {
+                error!("Cannot connect to {addr}: {e}");
+                process::exit(1);
+            }
+        };
+
+        yave::client::game::Game::run(
+            recorded(ConditionedTransport::wrap(transport, conditions), &record),
+            username,
+            password,
+            encrypt,
+            interpolation,
+        )
+        .await?;
     } else {
-        yave::server::game::Game::run(port).unwrap()
+        // Singleplayer runs the server in the same process, packets never touch a socket.
+        let network = LoopbackNetwork::default();
+        let server_addr = SocketAddr::from(([127, 0, 0, 1], 1));
+        let server = ChannelTransport::bind(&network, server_addr).unwrap();
+        let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2)))
+            .unwrap()
+            .connect(server_addr);
+
+        // Singleplayer doesn't read server.toml, but the server settings can still be changed
+        // from the command line.
+        let mut config = ServerConfig {
+            allow_flight: true,
+            ..Default::default()
+        };
+        if let Err(e) = config.apply_args(&server_args) {
+            error!("{e}");
+            process::exit(1);
+        }
+
+        let game = Game::new(server, config);
+        let console = game.console();
+        let server = thread::spawn(move || game.run());
+
+        let result = yave::client::game::Game::run(
+            recorded(ConditionedTransport::wrap(client, conditions), &record),
+            username,
+            password,
+            encrypt,
+            interpolation,
+        )
+        .await;
+
+        // Stop the server once the window is closed, so the world is saved before exiting.
+        let _ = console.send(String::from("stop"));
+        match server.join() {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => error!("The server did not stop cleanly: {e}"),
+            Err(_) => error!("The server stopped unexpectedly"),
+        }
+
+        result?;
     }
 
     Ok(())
 }
+
+/// Record the packets of the transport to a file, if asked to.
+fn recorded(transport: impl Transport + 'static, path: &Option<PathBuf>) -> Box<dyn Transport> {
+    let path = match path {
+        Some(path) => path,
+        None => return Box::new(transport),
+    };
+
+    match RecordingTransport::new(transport, path) {
+        Ok(transport) => {
+            info!("Recording the network traffic to {}", path.display());
+            Box::new(transport)
+        }
+        Err(e) => {
+            error!("Cannot record to {}: {e}", path.display());
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Size of the salt of a password.
+pub const SALT_SIZE: usize = 16;
+/// Size of the random challenge sent to a client logging in.
+pub const NONCE_SIZE: usize = 32;
+/// Number of PBKDF2 rounds used to derive the key of new passwords.
+pub const DEFAULT_ITERATIONS: u32 = 100_000;
+/// Most PBKDF2 rounds a client accepts to do, so a server can't make it spin forever.
+pub const MAX_ITERATIONS: u32 = 10_000_000;
+
+/// Key derived from a password by the client, from the salt and rounds sent by the server.
+pub fn derive_key(password: &str, salt: &[u8; SALT_SIZE], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, iterations, &mut key);
+
+    key
+}
+
+/// Key the server stores for an account. It only lets the server check proofs: knowing it is not
+/// enough to make one, so a leaked accounts file doesn't let anyone log in.
+pub fn stored_key(key: &[u8; 32]) -> [u8; 32] {
+    Sha256::digest(client_key(key)).into()
+}
+
+/// Answer to a login challenge, proving the client knows the password of the account without
+/// sending it. The name is included so a proof for one account can't be used for another one.
+pub fn prove(key: &[u8; 32], nonce: &[u8; NONCE_SIZE], user: &str) -> [u8; 32] {
+    let client_key = client_key(key);
+    let signature = signature(&Sha256::digest(client_key).into(), nonce, user);
+
+    xor(&client_key, &signature)
+}
+
+/// Check the answer to a login challenge against the stored key, in constant time.
+pub fn verify(
+    stored_key: &[u8; 32],
+    nonce: &[u8; NONCE_SIZE],
+    user: &str,
+    proof: &[u8; 32],
+) -> bool {
+    let client_key = xor(proof, &signature(stored_key, nonce, user));
+    let hash: [u8; 32] = Sha256::digest(client_key).into();
+
+    hash.iter()
+        .zip(stored_key)
+        .fold(0, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+/// Salt sent for a name without an account, so a client can't tell that there is none. It is
+/// the same for every login with the name, as the salt of a real account would be.
+pub fn fake_salt(secret: &[u8; 32], user: &str) -> [u8; SALT_SIZE] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(secret).unwrap();
+    mac.update(b"yave fake salt");
+    mac.update(user.as_bytes());
+
+    let mut salt = [0u8; SALT_SIZE];
+    salt.copy_from_slice(&mac.finalize().into_bytes()[..SALT_SIZE]);
+
+    salt
+}
+
+fn client_key(key: &[u8; 32]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+    mac.update(b"yave client key");
+
+    mac.finalize().into_bytes().into()
+}
+
+fn signature(stored_key: &[u8; 32], nonce: &[u8; NONCE_SIZE], user: &str) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(stored_key).unwrap();
+    mac.update(b"yave login");
+    mac.update(nonce);
+    mac.update(user.as_bytes());
+
+    mac.finalize().into_bytes().into()
+}
+
+fn xor(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (byte, (a, b)) in bytes.iter_mut().zip(a.iter().zip(b)) {
+        *byte = a ^ b;
+    }
+
+    bytes
+}
+
+/// Random bytes for salts and challenges.
+pub fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes
+}
@@ -0,0 +1,62 @@
+use std::io;
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use crate::network::error::DecodeError;
+use crate::network::MAX_PACKET_SIZE;
+
+/// Frame flag of a datagram carrying an encoded packet as it is.
+pub const FRAME_RAW: u8 = 0;
+/// Frame flag of a datagram carrying a zlib compressed packet.
+pub const FRAME_COMPRESSED: u8 = 1;
+
+/// Default size in bytes from which packets are compressed.
+pub const DEFAULT_THRESHOLD: u32 = 256;
+
+/// Wrap an encoded packet in a frame, compressing it if compression is enabled and the packet
+/// is at least `threshold` bytes long.
+pub fn compress(data: &[u8], threshold: Option<u32>) -> io::Result<Vec<u8>> {
+    match threshold {
+        Some(threshold) if data.len() >= threshold as usize => {
+            let mut encoder = ZlibEncoder::new(vec![FRAME_COMPRESSED], flate2::Compression::fast());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        _ => {
+            let mut frame = Vec::with_capacity(data.len() + 1);
+            frame.push(FRAME_RAW);
+            frame.extend_from_slice(data);
+            Ok(frame)
+        }
+    }
+}
+
+/// Unwrap a frame into an encoded packet. Inflated packets can't be bigger than a datagram, so a
+/// small compressed datagram can't be used to make the receiver allocate a lot of memory.
+pub fn decompress(frame: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let (flags, data) = frame.split_first().ok_or(DecodeError::Truncated)?;
+
+    match *flags {
+        FRAME_RAW => Ok(data.to_vec()),
+        FRAME_COMPRESSED => {
+            let mut inflated = Vec::new();
+            ZlibDecoder::new(data)
+                .take(MAX_PACKET_SIZE as u64 + 1)
+                .read_to_end(&mut inflated)
+                .map_err(|_| DecodeError::InvalidCompression)?;
+
+            if inflated.len() > MAX_PACKET_SIZE {
+                return Err(DecodeError::TooLong {
+                    field: "compressed packet",
+                    len: inflated.len() as u64,
+                    max: MAX_PACKET_SIZE as u64,
+                });
+            }
+
+            Ok(inflated)
+        }
+        flags => Err(DecodeError::UnknownFrame(flags)),
+    }
+}
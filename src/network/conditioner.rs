@@ -0,0 +1,438 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::io;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, info};
+use rand_core::{OsRng, RngCore};
+
+use crate::network::crypto::Session;
+use crate::network::transport::Transport;
+use crate::network::Packet;
+
+/// Environment variable with the network conditions, used when --network-conditions isn't given.
+pub const CONDITIONS_VAR: &str = "YAVE_NETWORK_CONDITIONS";
+
+/// Extra delay of a reordered packet, so the packets sent after it overtake it.
+const REORDER_DELAY: Duration = Duration::from_millis(30);
+
+/// Longest time a packet waits for the capped link, like the buffer of a router. Packets that
+/// would wait longer are lost.
+const MAX_QUEUE_DELAY: Duration = Duration::from_secs(1);
+
+/// Bad network conditions to simulate, applied to each direction.
+///
+/// They are written like `latency=100ms,jitter=20ms,loss=5%,reorder=1%,bandwidth=512kbps`, every
+/// setting being optional.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NetworkConditions {
+    /// Delay added to every packet.
+    pub latency: Duration,
+    /// Maximum random variation of the latency, both ways.
+    pub jitter: Duration,
+    /// Fraction of the packets lost, between 0 and 1.
+    pub loss: f64,
+    /// Fraction of the packets arriving after the ones sent after them, between 0 and 1.
+    pub reorder: f64,
+    /// Capacity of the link in kilobits per second, unlimited if `None`.
+    pub bandwidth: Option<u32>,
+}
+
+impl NetworkConditions {
+    /// Conditions given with `--network-conditions <conditions>` on the command line, or in the
+    /// YAVE_NETWORK_CONDITIONS environment variable. `None` when there are none.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        let spec = match args.iter().position(|arg| arg == "--network-conditions") {
+            Some(i) => args
+                .get(i + 1)
+                .cloned()
+                .ok_or_else(|| String::from("Missing value for --network-conditions"))?,
+            None => match std::env::var(CONDITIONS_VAR) {
+                Ok(spec) if !spec.trim().is_empty() => spec,
+                _ => return Ok(None),
+            },
+        };
+
+        spec.parse()
+            .map(Some)
+            .map_err(|e| format!("Invalid network conditions {spec:?}: {e}"))
+    }
+
+    /// Time needed to put `size` bytes on the link.
+    fn transmission_time(&self, size: usize) -> Duration {
+        match self.bandwidth {
+            Some(kbps) => Duration::from_secs_f64(size as f64 * 8. / (kbps as f64 * 1000.)),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+impl FromStr for NetworkConditions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut conditions = Self::default();
+
+        for setting in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got {setting:?}"))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            match key {
+                "latency" => conditions.latency = parse_millis(value)?,
+                "jitter" => conditions.jitter = parse_millis(value)?,
+                "loss" => conditions.loss = parse_percent(value)?,
+                "reorder" => conditions.reorder = parse_percent(value)?,
+                "bandwidth" => {
+                    let kbps = value.strip_suffix("kbps").unwrap_or(value);
+                    match kbps.parse() {
+                        Ok(0) | Err(_) => {
+                            return Err(format!("invalid bandwidth {value:?}, expected kbps"))
+                        }
+                        Ok(kbps) => conditions.bandwidth = Some(kbps),
+                    }
+                }
+                _ => return Err(format!("unknown setting {key:?}")),
+            }
+        }
+
+        Ok(conditions)
+    }
+}
+
+impl fmt::Display for NetworkConditions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "latency={}ms,jitter={}ms,loss={}%,reorder={}%",
+            self.latency.as_millis(),
+            self.jitter.as_millis(),
+            self.loss * 100.,
+            self.reorder * 100.
+        )?;
+
+        if let Some(kbps) = self.bandwidth {
+            write!(f, ",bandwidth={kbps}kbps")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_millis(value: &str) -> Result<Duration, String> {
+    value
+        .strip_suffix("ms")
+        .unwrap_or(value)
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("invalid duration {value:?}, expected milliseconds"))
+}
+
+fn parse_percent(value: &str) -> Result<f64, String> {
+    match value.strip_suffix('%').unwrap_or(value).parse::<f64>() {
+        Ok(percent) if (0. ..=100.).contains(&percent) => Ok(percent / 100.),
+        _ => Err(format!("invalid percentage {value:?}")),
+    }
+}
+
+/// Random number between 0 and 1.
+fn random() -> f64 {
+    (OsRng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Something happening on a link once it is due.
+enum Event<I> {
+    Packet(I),
+    /// Compression or session change, applied once the packets queued before it are sent.
+    Compression(SocketAddr, Option<u32>),
+    Session(SocketAddr, Option<Session>),
+}
+
+struct Scheduled<I> {
+    due: Instant,
+    /// Order of scheduling, to keep the order of events due at the same time.
+    sequence: u64,
+    event: Event<I>,
+}
+
+impl<I> PartialEq for Scheduled<I> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.due, self.sequence) == (other.due, other.sequence)
+    }
+}
+
+impl<I> Eq for Scheduled<I> {}
+
+impl<I> PartialOrd for Scheduled<I> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<I> Ord for Scheduled<I> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.due, self.sequence).cmp(&(other.due, other.sequence))
+    }
+}
+
+struct LinkState<I> {
+    queue: BinaryHeap<Reverse<Scheduled<I>>>,
+    sequence: u64,
+    /// Arrival of the last packet that kept its order, the next ones can't arrive before it.
+    in_order: Instant,
+    /// Latest arrival of everything scheduled.
+    last: Instant,
+    /// When the capped link is done sending the packets before.
+    free_at: Instant,
+    closed: bool,
+}
+
+/// One direction of a conditioned transport, holding packets until they are due.
+struct Link<I> {
+    conditions: NetworkConditions,
+    state: Mutex<LinkState<I>>,
+    changed: Condvar,
+}
+
+impl<I> Link<I> {
+    fn new(conditions: NetworkConditions) -> Self {
+        let now = Instant::now();
+
+        Self {
+            conditions,
+            state: Mutex::new(LinkState {
+                queue: BinaryHeap::new(),
+                sequence: 0,
+                in_order: now,
+                last: now,
+                free_at: now,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Schedule a packet of `size` bytes, unless it is lost.
+    fn send(&self, packet: I, size: usize) {
+        let conditions = &self.conditions;
+        if random() < conditions.loss {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        let start = state.free_at.max(now);
+        if start - now > MAX_QUEUE_DELAY {
+            return;
+        }
+        state.free_at = start + conditions.transmission_time(size);
+
+        let jitter = conditions.jitter.as_secs_f64() * (random() * 2. - 1.);
+        let latency = Duration::from_secs_f64((conditions.latency.as_secs_f64() + jitter).max(0.));
+        let mut due = state.free_at + latency;
+
+        if random() < conditions.reorder {
+            due += REORDER_DELAY;
+        } else {
+            due = due.max(state.in_order);
+            state.in_order = due;
+        }
+
+        self.push(&mut state, due, Event::Packet(packet));
+    }
+
+    /// Schedule a change once everything scheduled before it is done.
+    fn control(&self, event: Event<I>) {
+        let mut state = self.state.lock().unwrap();
+
+        let due = state.last.max(Instant::now());
+        state.in_order = due;
+        self.push(&mut state, due, event);
+    }
+
+    /// Schedule an event without conditions, like an error that must not be lost.
+    fn now(&self, event: Event<I>) {
+        let mut state = self.state.lock().unwrap();
+        let due = Instant::now();
+        self.push(&mut state, due, event);
+    }
+
+    fn push(&self, state: &mut LinkState<I>, due: Instant, event: Event<I>) {
+        state.sequence += 1;
+        state.last = state.last.max(due);
+
+        let sequence = state.sequence;
+        state.queue.push(Reverse(Scheduled {
+            due,
+            sequence,
+            event,
+        }));
+        self.changed.notify_all();
+    }
+
+    /// Wait for the next due event. Once the link is closed, the remaining events are returned
+    /// without waiting if `flush` is set, and `None` once there are none left.
+    fn next(&self, flush: bool) -> Option<Event<I>> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            let now = Instant::now();
+            let due = state.queue.peek().map(|Reverse(scheduled)| scheduled.due);
+
+            match due {
+                _ if state.closed && !flush => return None,
+                Some(due) if due <= now || state.closed => {
+                    return state.queue.pop().map(|Reverse(scheduled)| scheduled.event)
+                }
+                Some(due) => state = self.changed.wait_timeout(state, due - now).unwrap().0,
+                None if state.closed => return None,
+                None => state = self.changed.wait(state).unwrap(),
+            }
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_all();
+    }
+}
+
+type Received = io::Result<(Packet, SocketAddr)>;
+
+/// Transport simulating a bad network around another transport: packets sent and received are
+/// delayed, lost, reordered or slowed down by a capped bandwidth following the conditions.
+///
+/// Two threads move the packets once they are due, one for each direction.
+pub struct ConditionedTransport<T: Transport> {
+    transport: Arc<T>,
+    outgoing: Arc<Link<(Packet, SocketAddr)>>,
+    incoming: Arc<Link<Received>>,
+    /// Session state of the peers once the queued changes are applied.
+    sessions: Mutex<HashMap<SocketAddr, bool>>,
+}
+
+impl<T: Transport + 'static> ConditionedTransport<T> {
+    pub fn new(transport: T, conditions: NetworkConditions) -> Self {
+        let transport = Arc::new(transport);
+        let outgoing = Arc::new(Link::new(conditions));
+        let incoming = Arc::new(Link::new(conditions));
+
+        let (sender, link) = (transport.clone(), outgoing.clone());
+        thread::spawn(move || {
+            // Packets sent before closing still go out.
+            while let Some(event) = link.next(true) {
+                match event {
+                    Event::Packet((packet, addr)) => {
+                        if let Err(e) = sender.send_to(packet, &addr) {
+                            debug!("Cannot send a delayed packet to {addr}: {e}");
+                        }
+                    }
+                    Event::Compression(addr, threshold) => sender.set_compression(addr, threshold),
+                    Event::Session(addr, session) => sender.set_session(addr, session),
+                }
+            }
+        });
+
+        let (receiver, link) = (transport.clone(), incoming.clone());
+        thread::spawn(move || loop {
+            match receiver.recv_from() {
+                Ok((packet, peer)) => {
+                    let size = match link.conditions.bandwidth {
+                        Some(_) => packet.encoded_size().unwrap_or(0),
+                        None => 0,
+                    };
+                    link.send(Ok((packet, peer)), size);
+                }
+                Err(e) if e.kind() == ErrorKind::ConnectionAborted => {
+                    link.close();
+                    break;
+                }
+                Err(e) => link.now(Event::Packet(Err(e))),
+            }
+        });
+
+        Self {
+            transport,
+            outgoing,
+            incoming,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wrap the transport if there are conditions to simulate.
+    pub fn wrap(transport: T, conditions: Option<NetworkConditions>) -> Box<dyn Transport> {
+        match conditions {
+            Some(conditions) => {
+                info!("Simulating network conditions {conditions}");
+                Box::new(Self::new(transport, conditions))
+            }
+            None => Box::new(transport),
+        }
+    }
+}
+
+impl<T: Transport> Transport for ConditionedTransport<T> {
+    fn send_to(&self, packet: Packet, addr: &SocketAddr) -> io::Result<()> {
+        let size = match self.outgoing.conditions.bandwidth {
+            Some(_) => packet.encoded_size()?,
+            None => 0,
+        };
+        self.outgoing.send((packet, *addr), size);
+
+        Ok(())
+    }
+
+    fn recv_from(&self) -> io::Result<(Packet, SocketAddr)> {
+        match self.incoming.next(false) {
+            Some(Event::Packet(received)) => received,
+            _ => Err(io::Error::from(ErrorKind::ConnectionAborted)),
+        }
+    }
+
+    fn close(&self) {
+        self.incoming.close();
+        self.outgoing.close();
+        self.transport.close();
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.peer_addr()
+    }
+
+    fn set_compression(&self, addr: SocketAddr, threshold: Option<u32>) {
+        self.outgoing.control(Event::Compression(addr, threshold));
+    }
+
+    fn set_session(&self, addr: SocketAddr, session: Option<Session>) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(addr, session.is_some());
+        self.outgoing.control(Event::Session(addr, session));
+    }
+
+    fn has_session(&self, addr: &SocketAddr) -> bool {
+        match self.sessions.lock().unwrap().get(addr) {
+            Some(session) => *session,
+            None => self.transport.has_session(addr),
+        }
+    }
+}
+
+impl<T: Transport> Drop for ConditionedTransport<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
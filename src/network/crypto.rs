@@ -0,0 +1,197 @@
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use rand_core::OsRng;
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+use crate::network::error::DecodeError;
+
+/// Frame flag of a datagram carrying an encrypted frame.
+pub const FRAME_ENCRYPTED: u8 = 2;
+
+/// Size of the header of an encrypted frame: the frame flag and the nonce counter.
+const HEADER_SIZE: usize = 1 + 8;
+
+/// Number of nonces behind the highest received one that are still accepted, so datagrams
+/// reordered by the network are not dropped.
+const REPLAY_WINDOW: u64 = 64;
+
+/// Which side of the connection a session belongs to. Each direction uses its own key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+/// One side of a key exchange. The public key is sent to the peer and the handshake is finished
+/// with the public key it sends back.
+pub struct Handshake {
+    secret: EphemeralSecret,
+    public: PublicKey,
+}
+
+impl Handshake {
+    pub fn new() -> Self {
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let public = PublicKey::from(&secret);
+
+        Self { secret, public }
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public.to_bytes()
+    }
+
+    /// Derive the session keys from the peer public key.
+    pub fn finish(self, peer_key: [u8; 32], role: Role) -> Session {
+        let peer = PublicKey::from(peer_key);
+        let shared = self.secret.diffie_hellman(&peer);
+
+        let (client, server) = match role {
+            Role::Client => (self.public, peer),
+            Role::Server => (peer, self.public),
+        };
+
+        let mut salt = [0u8; 64];
+        salt[..32].copy_from_slice(client.as_bytes());
+        salt[32..].copy_from_slice(server.as_bytes());
+
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes());
+        let mut client_key = [0u8; 32];
+        let mut server_key = [0u8; 32];
+        hkdf.expand(b"yave client to server", &mut client_key)
+            .unwrap();
+        hkdf.expand(b"yave server to client", &mut server_key)
+            .unwrap();
+
+        let (send_key, recv_key) = match role {
+            Role::Client => (client_key, server_key),
+            Role::Server => (server_key, client_key),
+        };
+
+        Session {
+            send_cipher: ChaCha20Poly1305::new(Key::from_slice(&send_key)),
+            recv_cipher: ChaCha20Poly1305::new(Key::from_slice(&recv_key)),
+            send_nonce: 0,
+            replay: ReplayWindow::default(),
+        }
+    }
+}
+
+impl Default for Handshake {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An encrypted and authenticated session with a peer. Every datagram is sealed with a new nonce
+/// and datagrams that fail authentication or reuse a nonce are rejected.
+pub struct Session {
+    send_cipher: ChaCha20Poly1305,
+    recv_cipher: ChaCha20Poly1305,
+    send_nonce: u64,
+    replay: ReplayWindow,
+}
+
+impl Session {
+    /// Encrypt a frame into a datagram.
+    pub fn seal(&mut self, frame: &[u8]) -> Vec<u8> {
+        let counter = self.send_nonce;
+        self.send_nonce += 1;
+
+        let mut datagram = Vec::with_capacity(HEADER_SIZE + frame.len() + 16);
+        datagram.push(FRAME_ENCRYPTED);
+        datagram.extend_from_slice(&counter.to_be_bytes());
+
+        let ciphertext = self
+            .send_cipher
+            .encrypt(
+                &nonce(counter),
+                Payload {
+                    msg: frame,
+                    aad: &datagram,
+                },
+            )
+            .unwrap();
+        datagram.extend_from_slice(&ciphertext);
+
+        datagram
+    }
+
+    /// Decrypt a datagram into a frame.
+    pub fn open(&mut self, datagram: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        if datagram.first() != Some(&FRAME_ENCRYPTED) {
+            return Err(DecodeError::Unencrypted);
+        }
+
+        if datagram.len() < HEADER_SIZE {
+            return Err(DecodeError::Truncated);
+        }
+
+        let (header, ciphertext) = datagram.split_at(HEADER_SIZE);
+        let counter = u64::from_be_bytes(header[1..].try_into().unwrap());
+
+        if !self.replay.check(counter) {
+            return Err(DecodeError::Replayed(counter));
+        }
+
+        let frame = self
+            .recv_cipher
+            .decrypt(
+                &nonce(counter),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| DecodeError::Unauthenticated)?;
+
+        // Only authenticated datagrams can move the window, otherwise anyone could make the
+        // receiver drop the real ones.
+        self.replay.update(counter);
+
+        Ok(frame)
+    }
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+/// Sliding window of the nonces received recently.
+#[derive(Debug, Default)]
+struct ReplayWindow {
+    /// Highest nonce received plus one, zero if nothing was received yet.
+    next: u64,
+    /// Bit `n` is set if nonce `next - 1 - n` was received.
+    seen: u64,
+}
+
+impl ReplayWindow {
+    fn check(&self, counter: u64) -> bool {
+        if counter >= self.next {
+            return true;
+        }
+
+        let age = self.next - 1 - counter;
+        age < REPLAY_WINDOW && self.seen & (1 << age) == 0
+    }
+
+    fn update(&mut self, counter: u64) {
+        if counter >= self.next {
+            let shift = counter + 1 - self.next;
+            self.seen = if shift >= REPLAY_WINDOW {
+                0
+            } else {
+                self.seen << shift
+            };
+            self.seen |= 1;
+            self.next = counter + 1;
+        } else {
+            self.seen |= 1 << (self.next - 1 - counter);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Reasons a received datagram can be rejected while decoding it into a packet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// The datagram ended before the packet was complete.
+    Truncated,
+    /// The packet id is not known.
+    UnknownPacket(u8),
+    /// A length prefix is bigger than the maximum allowed for that field.
+    TooLong {
+        field: &'static str,
+        len: u64,
+        max: u64,
+    },
+    /// A string field is not valid UTF-8.
+    InvalidUtf8 { field: &'static str },
+    /// A boolean field is neither 0 nor 1.
+    InvalidBool { field: &'static str },
+    /// A flags field has unknown bits set.
+    InvalidFlags { field: &'static str, flags: u8 },
+    /// A floating point field is NaN or infinite.
+    NonFinite { field: &'static str },
+    /// A block id is not a valid identifier.
+    InvalidIdentifier(String),
+    /// A block group of a chunk points outside of the chunk palette.
+    InvalidPaletteIndex(u16),
+    /// The block groups of a chunk do not add up to a full chunk.
+    InvalidChunkSize(u64),
+    /// There are bytes left after the end of the packet.
+    TrailingBytes(usize),
+    /// The frame flags of a datagram are not known.
+    UnknownFrame(u8),
+    /// A compressed datagram could not be inflated.
+    InvalidCompression,
+    /// A status request was compressed, so it could ask for more than it weighs.
+    CompressedStatusRequest,
+    /// A peer with an encrypted session sent a plaintext datagram.
+    Unencrypted,
+    /// An encrypted datagram was received from a peer without a session.
+    NoSession,
+    /// An encrypted datagram failed authentication.
+    Unauthenticated,
+    /// An encrypted datagram reused a nonce.
+    Replayed(u64),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "packet is truncated"),
+            DecodeError::UnknownPacket(id) => write!(f, "unknown packet id {id}"),
+            DecodeError::TooLong { field, len, max } => {
+                write!(f, "field {field} is too long ({len} > {max})")
+            }
+            DecodeError::InvalidUtf8 { field } => write!(f, "field {field} is not valid UTF-8"),
+            DecodeError::InvalidBool { field } => write!(f, "field {field} is not a boolean"),
+            DecodeError::InvalidFlags { field, flags } => {
+                write!(f, "field {field} has unknown flags {flags:#x}")
+            }
+            DecodeError::NonFinite { field } => write!(f, "field {field} is not a finite number"),
+            DecodeError::InvalidIdentifier(id) => write!(f, "invalid identifier {id:?}"),
+            DecodeError::InvalidPaletteIndex(index) => {
+                write!(f, "palette index {index} is out of bounds")
+            }
+            DecodeError::InvalidChunkSize(size) => write!(f, "chunk has {size} blocks"),
+            DecodeError::TrailingBytes(count) => {
+                write!(f, "{count} trailing bytes after the packet")
+            }
+            DecodeError::UnknownFrame(flags) => write!(f, "unknown frame flags {flags:#x}"),
+            DecodeError::InvalidCompression => write!(f, "invalid compressed data"),
+            DecodeError::CompressedStatusRequest => write!(f, "compressed status request"),
+            DecodeError::Unencrypted => write!(f, "plaintext datagram in an encrypted session"),
+            DecodeError::NoSession => write!(f, "encrypted datagram without a session"),
+            DecodeError::Unauthenticated => write!(f, "datagram failed authentication"),
+            DecodeError::Replayed(nonce) => write!(f, "replayed nonce {nonce}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    /// Packets are decoded from memory, so the only way reading can fail is running out of bytes.
+    fn from(_: io::Error) -> Self {
+        DecodeError::Truncated
+    }
+}
+
+impl From<DecodeError> for io::Error {
+    fn from(e: DecodeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use log::debug;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::network::{Packet, MAX_PACKET_SIZE, PROTOCOL_VERSION};
+
+/// Multicast group servers announce themselves to.
+pub const LAN_GROUP: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 255, 77, 77), 25001);
+/// Time between two announcements of a server.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_millis(1500);
+/// Servers not heard from for this long are removed from the discovered ones.
+pub const SERVER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the discovery thread checks if it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sends the announcements of a server to the LAN multicast group.
+#[derive(Debug)]
+pub struct LanAnnouncer {
+    socket: UdpSocket,
+    group: SocketAddrV4,
+    /// Port players connect to, it can differ from the port announcements are sent from.
+    pub port: u16,
+}
+
+impl LanAnnouncer {
+    /// Announce a server listening on `port`, on the network of `interface` or the default one
+    /// if it is unspecified.
+    pub fn new(interface: Ipv4Addr, group: SocketAddrV4, port: u16) -> io::Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_multicast_if_v4(&interface)?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.bind(&SocketAddr::from((interface, 0)).into())?;
+
+        Ok(Self {
+            socket: socket.into(),
+            group,
+            port,
+        })
+    }
+
+    pub fn announce(&self, announcement: &Packet) -> io::Result<()> {
+        self.socket.send_to(&announcement.encode()?, self.group)?;
+
+        Ok(())
+    }
+}
+
+/// A server found on the LAN.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredServer {
+    /// Address to connect to.
+    pub addr: SocketAddr,
+    pub protocol: u32,
+    pub motd: String,
+    pub online: u32,
+    pub max_players: u32,
+    pub last_seen: Instant,
+}
+
+impl Display for DiscoveredServer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ({}/{} players online)",
+            self.addr, self.motd, self.online, self.max_players
+        )?;
+
+        if self.protocol != PROTOCOL_VERSION {
+            write!(f, ", incompatible protocol {}", self.protocol)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Listens to the announcements of the servers on the LAN from a separate thread, which stops
+/// when this is dropped.
+pub struct LanDiscovery {
+    servers: Arc<Mutex<HashMap<SocketAddr, DiscoveredServer>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LanDiscovery {
+    /// Join the multicast group on the network of `interface`, or the default one if it is
+    /// unspecified. Other programs can listen to the same group at the same time.
+    pub fn listen(interface: Ipv4Addr, group: SocketAddrV4) -> io::Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, group.port())).into())?;
+        socket.join_multicast_v4(group.ip(), &interface)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        let socket: UdpSocket = socket.into();
+
+        let servers = Arc::new(Mutex::new(HashMap::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let servers = servers.clone();
+            let stop = stop.clone();
+
+            thread::spawn(move || {
+                let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+
+                while !stop.load(Ordering::Acquire) {
+                    let (size, source) = match socket.recv_from(&mut buffer) {
+                        Ok(received) => received,
+                        Err(e)
+                            if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                        {
+                            continue
+                        }
+                        Err(e) => {
+                            debug!("Cannot receive LAN announcements: {e}");
+                            continue;
+                        }
+                    };
+
+                    match Packet::decode(&buffer[..size]) {
+                        Ok(Packet::LanAnnouncement {
+                            protocol,
+                            motd,
+                            port,
+                            online,
+                            max_players,
+                        }) => {
+                            let addr = SocketAddr::new(source.ip(), port);
+                            servers.lock().unwrap().insert(
+                                addr,
+                                DiscoveredServer {
+                                    addr,
+                                    protocol,
+                                    motd,
+                                    online,
+                                    max_players,
+                                    last_seen: Instant::now(),
+                                },
+                            );
+                        }
+                        Ok(_) => (),
+                        Err(e) => debug!("Dropping invalid LAN announcement from {source}: {e}"),
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            servers,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Servers heard from recently, sorted by address.
+    pub fn servers(&self) -> Vec<DiscoveredServer> {
+        let mut servers = self.servers.lock().unwrap();
+        servers.retain(|_addr, server| server.last_seen.elapsed() < SERVER_TIMEOUT);
+
+        let mut servers: Vec<DiscoveredServer> = servers.values().cloned().collect();
+        servers.sort_by_key(|server| server.addr);
+
+        servers
+    }
+}
+
+impl Drop for LanDiscovery {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::network::transport::Transport;
+use crate::network::Packet;
+
+/// A packet and the address of the peer that sent it, or `None` when the receiving transport is
+/// closed.
+type Datagram = Option<(Packet, SocketAddr)>;
+
+/// An in-memory network. Transports bound to it exchange packets through channels, without
+/// sockets and without encoding them.
+#[derive(Clone, Default)]
+pub struct LoopbackNetwork {
+    peers: Arc<Mutex<HashMap<SocketAddr, Sender<Datagram>>>>,
+}
+
+/// Transport sending packets to other transports bound to the same LoopbackNetwork.
+pub struct ChannelTransport {
+    network: LoopbackNetwork,
+    addr: SocketAddr,
+    peer: Option<SocketAddr>,
+    receiver: Mutex<Receiver<Datagram>>,
+    closed: AtomicBool,
+}
+
+impl ChannelTransport {
+    /// Bind a transport to an address of the network.
+    pub fn bind(network: &LoopbackNetwork, addr: SocketAddr) -> io::Result<Self> {
+        let mut peers = network.peers.lock().unwrap();
+
+        if peers.contains_key(&addr) {
+            return Err(io::Error::new(
+                ErrorKind::AddrInUse,
+                format!("{addr} is already bound"),
+            ));
+        }
+
+        let (sender, receiver) = channel();
+        peers.insert(addr, sender);
+
+        Ok(Self {
+            network: network.clone(),
+            addr,
+            peer: None,
+            receiver: Mutex::new(receiver),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    /// Set the peer used by SocketSender::send.
+    pub fn connect(mut self, addr: SocketAddr) -> Self {
+        self.peer = Some(addr);
+        self
+    }
+
+    /// Wait for the next packet, giving up after the timeout.
+    pub fn recv_timeout(&self, timeout: Duration) -> io::Result<(Packet, SocketAddr)> {
+        self.receiver
+            .lock()
+            .unwrap()
+            .recv_timeout(timeout)
+            .map_err(|e| match e {
+                RecvTimeoutError::Timeout => io::Error::from(ErrorKind::TimedOut),
+                RecvTimeoutError::Disconnected => io::Error::from(ErrorKind::BrokenPipe),
+            })?
+            .ok_or_else(|| io::Error::from(ErrorKind::ConnectionAborted))
+    }
+}
+
+impl Transport for ChannelTransport {
+    fn send_to(&self, packet: Packet, addr: &SocketAddr) -> io::Result<()> {
+        // Like UDP, packets sent to nobody are lost without errors.
+        if let Some(peer) = self.network.peers.lock().unwrap().get(addr) {
+            let _ = peer.send(Some((packet, self.addr)));
+        }
+
+        Ok(())
+    }
+
+    fn recv_from(&self) -> io::Result<(Packet, SocketAddr)> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(io::Error::from(ErrorKind::ConnectionAborted));
+        }
+
+        self.receiver
+            .lock()
+            .unwrap()
+            .recv()
+            .map_err(|_| io::Error::from(ErrorKind::BrokenPipe))?
+            .ok_or_else(|| {
+                self.closed.store(true, Ordering::Release);
+                io::Error::from(ErrorKind::ConnectionAborted)
+            })
+    }
+
+    fn close(&self) {
+        if let Some(sender) = self.network.peers.lock().unwrap().get(&self.addr) {
+            let _ = sender.send(None);
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.peer
+            .ok_or_else(|| io::Error::from(ErrorKind::NotConnected))
+    }
+}
+
+impl Drop for ChannelTransport {
+    fn drop(&mut self) {
+        self.network.peers.lock().unwrap().remove(&self.addr);
+    }
+}
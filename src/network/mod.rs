@@ -1,31 +1,78 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use pollster::block_on;
 use std::io;
 use std::io::{Cursor, ErrorKind, Read, Write};
 use std::net::{SocketAddr, UdpSocket};
+use std::str::FromStr;
 use std::sync::Arc;
 
-use crate::world::chunk::BlockGroup;
+use crate::assets::Identifier;
+use crate::world::chunk::{BlockGroup, CompressedChunk, CHUNK_BLOCKS};
+use crate::world::movement::MovementInput;
 
-#[derive(Debug, Clone)]
+use self::auth::{NONCE_SIZE, SALT_SIZE};
+use self::crypto::Session;
+use self::error::DecodeError;
+use self::snapshot::EntityDelta;
+use self::transport::{Transport, UdpTransport};
+
+pub mod auth;
+pub mod compression;
+pub mod conditioner;
+pub mod crypto;
+pub mod error;
+pub mod lan;
+pub mod loopback;
+pub mod record;
+pub mod replay;
+pub mod snapshot;
+pub mod transport;
+
+/// Version of the protocol, changed whenever packets change so servers and clients can tell they
+/// can't talk to each other.
+pub const PROTOCOL_VERSION: u32 = 5;
+
+/// Maximum size of a datagram, every packet must fit in one.
+pub const MAX_PACKET_SIZE: usize = 65507;
+/// Maximum length in bytes of a player name.
+pub const MAX_NAME_LENGTH: usize = 32;
+/// Maximum length in bytes of an identifier like a block id.
+pub const MAX_IDENTIFIER_LENGTH: usize = 64;
+/// Maximum number of players in an OnlinePlayers packet.
+pub const MAX_ONLINE_PLAYERS: usize = 1024;
+/// Maximum length in bytes of a disconnection reason.
+pub const MAX_REASON_LENGTH: usize = 256;
+/// Maximum length in bytes of a command line.
+pub const MAX_COMMAND_LENGTH: usize = 256;
+/// Maximum length in bytes of a system message.
+pub const MAX_MESSAGE_LENGTH: usize = 1024;
+/// Maximum length in bytes of a chat message.
+pub const MAX_CHAT_LENGTH: usize = 256;
+/// Maximum length in bytes of the message of the day.
+pub const MAX_MOTD_LENGTH: usize = 256;
+/// Maximum length in bytes of a world name.
+pub const MAX_WORLD_NAME_LENGTH: usize = 32;
+/// Maximum number of player names in a Status packet.
+pub const MAX_STATUS_SAMPLE: usize = 12;
+/// Size of an encoded StatusRequest packet, padded to be bigger than any Status packet so a
+/// request with a spoofed address can't make the server send more than it received.
+pub const STATUS_REQUEST_SIZE: usize = 1024;
+/// Maximum number of entities changed or removed by a Snapshot packet.
+pub const MAX_SNAPSHOT_ENTITIES: usize = 1024;
+
+/// Snapshot delta flag of a changed position.
+const DELTA_POSITION: u8 = 0x1;
+/// Snapshot delta flag of a changed rotation.
+const DELTA_ROTATION: u8 = 0x2;
+/// Snapshot delta flag of a changed velocity.
+const DELTA_VELOCITY: u8 = 0x4;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Packet {
     /// Connection packet. This is sent by the client to the server when a connection is enstablished.
-    Connection { user: String },
-    /// Player movement. This is sent by the client to the server when the position needs to be changed.
-    Movement {
-        delta_x: f64,
-        delta_y: f64,
-        delta_z: f64,
-    },
-    /// Position request. This is sent by the client to the server when it wants to know the new position.
-    PositionRequest { name: String },
-    /// Player position. This is sent by the server to the client when requested.
-    PlayerPosition {
-        x: f64,
-        y: f64,
-        z: f64,
-        name: String,
-    },
+    Connection { user: String, compression: bool },
+    /// Player input. Sent by the client every tick with the movement input of that tick, the
+    /// server simulates it to move the player. Sequence numbers start at 1.
+    Input { sequence: u32, input: MovementInput },
     /// Online player list. Sent by the server to the client when a new client connects.
     OnlinePlayers { players: Vec<OnlinePlayer> },
     /// Unload chunk. Sent by the server to the client when a chunk is unloaded.
@@ -34,13 +81,108 @@ pub enum Packet {
     Chunk {
         x: i64,
         y: i64,
-        groups: Vec<BlockGroup>,
+        data: CompressedChunk,
     },
+    /// Compression. Sent by the server to a client that asked for compression while connecting.
+    /// From now on both sides compress packets bigger than the threshold.
+    Compression { threshold: u32 },
+    /// Key exchange. Sent by the client before connecting to start an encrypted session, and by
+    /// the server as a reply. Everything after it is encrypted. The exchange is not
+    /// authenticated, the server has no identity the client could check, so sessions hide the
+    /// traffic from eavesdroppers but not from someone who can intercept and forward it.
+    KeyExchange { public_key: [u8; 32] },
+    /// Snapshot. Sent by the server to every client each tick with the state of the entities
+    /// around it, as changes from the `baseline` snapshot or in full if there is no baseline.
+    Snapshot {
+        tick: u64,
+        baseline: Option<u64>,
+        entities: Vec<EntityDelta>,
+        removed: Vec<u32>,
+    },
+    /// Snapshot acknowledgement. Sent by the client when it receives a snapshot, so the server
+    /// can use it as the baseline of the next ones.
+    SnapshotAck { tick: u64 },
+    /// Player joined. Sent by the server to the other clients when a player connects.
+    PlayerJoined { id: u32, name: String },
+    /// Player state. Sent by the server to a client every tick with the authoritative position and
+    /// vertical velocity of its player after simulating the inputs up to `sequence`.
+    PlayerState {
+        sequence: u32,
+        x: f64,
+        y: f64,
+        z: f64,
+        velocity: f64,
+    },
+    /// Welcome. Sent by the server to a client when it connects, with the network id of its
+    /// player and the number of ticks the server runs every second.
+    Welcome { id: u32, tick_rate: u32 },
+    /// Disconnect. Sent by the server to a client it refuses or stops serving, and by a client
+    /// when it leaves.
+    Disconnect { reason: String },
+    /// Command. Sent by the client with a command line typed by the player, without the leading
+    /// slash.
+    Command { line: String },
+    /// System message. Sent by the server to show a message from the server to a player, like the
+    /// output of a command.
+    SystemMessage { message: String },
+    /// Chat message. Sent by the client with a message typed by the player. Messages starting
+    /// with a slash are run as commands.
+    ChatMessage { message: String },
+    /// Chat. Sent by the server to every client with a chat message and the name of its sender.
+    Chat { sender: String, message: String },
+    /// Login challenge. Sent by the server when the player has to prove it owns its account, with
+    /// the salt and rounds to derive the key of the account from the password.
+    AuthChallenge {
+        salt: [u8; SALT_SIZE],
+        iterations: u32,
+        nonce: [u8; NONCE_SIZE],
+    },
+    /// Answer to a login challenge. Sent by the client, see `auth::prove`.
+    AuthResponse { proof: [u8; 32] },
+    /// Status request. Sent by anyone wanting to know about a server without joining it, the
+    /// token is sent back in the Status packet. Encoded requests are padded to
+    /// `STATUS_REQUEST_SIZE` bytes and never compressed.
+    StatusRequest { token: u64 },
+    /// Status of the server, answer to a StatusRequest. The sample has the names of some of the
+    /// online players.
+    Status {
+        token: u64,
+        protocol: u32,
+        motd: String,
+        online: u32,
+        max_players: u32,
+        sample: Vec<String>,
+    },
+    /// LAN announcement. Sent by servers to the LAN multicast group so players on the same
+    /// network can find them, with the port they listen on.
+    LanAnnouncement {
+        protocol: u32,
+        motd: String,
+        port: u16,
+        online: u32,
+        max_players: u32,
+    },
+    /// The player was moved to another world. Sent by the server before the chunks of the new
+    /// world, the client drops every chunk it has.
+    ChangeWorld { name: String },
+    /// An entity came within the tracking range of the player. Sent by the server, its state is
+    /// then in the snapshots until a DespawnEntity packet. The kind is the id of its type, like
+    /// `base:wanderer`.
+    SpawnEntity {
+        id: u32,
+        kind: String,
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    /// An entity went out of the tracking range of the player or was removed. Sent by the server.
+    DespawnEntity { id: u32 },
 }
 
 /// Structure used in the OnlinePlayers packet to store information about players.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct OnlinePlayer {
+    pub id: u32,
     pub name: String,
     pub x: f32,
     pub y: f32,
@@ -51,42 +193,42 @@ impl Packet {
     /// Encode a packet into bytes to send it over the internet.
     pub fn encode(&self) -> io::Result<Vec<u8>> {
         let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+
+        Ok(bytes)
+    }
 
+    /// Number of bytes `encode` would return, counted without building the packet in memory.
+    pub fn encoded_size(&self) -> io::Result<usize> {
+        let mut counter = ByteCounter(0);
+        self.write(&mut counter)?;
+
+        Ok(counter.0)
+    }
+
+    fn write(&self, bytes: &mut impl Write) -> io::Result<()> {
         match self {
-            Packet::Connection { user } => {
+            Packet::Connection { user, compression } => {
                 bytes.write_u8(0)?;
-                bytes.write_u64::<BigEndian>(user.len() as u64)?;
-                bytes.write_all(user.as_bytes())?;
+                write_string(bytes, "user", user, MAX_NAME_LENGTH)?;
+                bytes.write_u8(*compression as u8)?;
             }
-            Packet::Movement {
-                delta_x,
-                delta_y,
-                delta_z,
-            } => {
+            Packet::Input { sequence, input } => {
                 bytes.write_u8(1)?;
-                bytes.write_f64::<BigEndian>(*delta_x)?;
-                bytes.write_f64::<BigEndian>(*delta_y)?;
-                bytes.write_f64::<BigEndian>(*delta_z)?;
-            }
-            Packet::PositionRequest { name } => {
-                bytes.write_u8(2)?;
-                bytes.write_u64::<BigEndian>(name.len() as u64)?;
-                bytes.write_all(name.as_bytes())?;
-            }
-            Packet::PlayerPosition { x, y, z, name } => {
-                bytes.write_u8(3)?;
-                bytes.write_f64::<BigEndian>(*x)?;
-                bytes.write_f64::<BigEndian>(*y)?;
-                bytes.write_f64::<BigEndian>(*z)?;
-                bytes.write_u64::<BigEndian>(name.len() as u64)?;
-                bytes.write_all(name.as_bytes())?;
+                bytes.write_u32::<BigEndian>(*sequence)?;
+                bytes.write_f32::<BigEndian>(input.forward)?;
+                bytes.write_f32::<BigEndian>(input.right)?;
+                bytes.write_f32::<BigEndian>(input.up)?;
+                bytes.write_f32::<BigEndian>(input.yaw)?;
+                bytes.write_f32::<BigEndian>(input.pitch)?;
+                bytes.write_u8(input.flying as u8)?;
             }
             Packet::OnlinePlayers { players } => {
                 bytes.write_u8(4)?;
-                bytes.write_u64::<BigEndian>(players.len() as u64)?;
+                write_len(bytes, "players", players.len(), MAX_ONLINE_PLAYERS)?;
                 for player in players {
-                    bytes.write_u64::<BigEndian>(player.name.len() as u64)?;
-                    bytes.write_all(player.name.as_bytes())?;
+                    bytes.write_u32::<BigEndian>(player.id)?;
+                    write_string(bytes, "name", &player.name, MAX_NAME_LENGTH)?;
 
                     bytes.write_f32::<BigEndian>(player.x)?;
                     bytes.write_f32::<BigEndian>(player.y)?;
@@ -98,157 +240,695 @@ impl Packet {
                 bytes.write_i64::<BigEndian>(*x)?;
                 bytes.write_i64::<BigEndian>(*y)?;
             }
-            Packet::Chunk { x, y, groups } => {
+            Packet::Chunk { x, y, data } => {
                 bytes.write_u8(6)?;
                 bytes.write_i64::<BigEndian>(*x)?;
                 bytes.write_i64::<BigEndian>(*y)?;
-                bytes.write_u64::<BigEndian>(groups.len() as u64)?;
-                for group in groups {
-                    bytes.write_u64::<BigEndian>(group.id.len() as u64)?;
-                    bytes.write_all(group.id.as_bytes())?;
-                    bytes.write_u32::<BigEndian>(group.count)?;
+                write_chunk(bytes, data)?;
+            }
+            Packet::Compression { threshold } => {
+                bytes.write_u8(7)?;
+                bytes.write_u32::<BigEndian>(*threshold)?;
+            }
+            Packet::KeyExchange { public_key } => {
+                bytes.write_u8(8)?;
+                bytes.write_all(public_key)?;
+            }
+            Packet::Snapshot {
+                tick,
+                baseline,
+                entities,
+                removed,
+            } => {
+                bytes.write_u8(9)?;
+                bytes.write_u64::<BigEndian>(*tick)?;
+                bytes.write_u8(baseline.is_some() as u8)?;
+                if let Some(baseline) = baseline {
+                    bytes.write_u64::<BigEndian>(*baseline)?;
+                }
+
+                write_len(bytes, "entities", entities.len(), MAX_SNAPSHOT_ENTITIES)?;
+                for delta in entities {
+                    write_delta(bytes, delta)?;
+                }
+
+                write_len(bytes, "removed", removed.len(), MAX_SNAPSHOT_ENTITIES)?;
+                for id in removed {
+                    bytes.write_u32::<BigEndian>(*id)?;
+                }
+            }
+            Packet::SnapshotAck { tick } => {
+                bytes.write_u8(10)?;
+                bytes.write_u64::<BigEndian>(*tick)?;
+            }
+            Packet::PlayerJoined { id, name } => {
+                bytes.write_u8(11)?;
+                bytes.write_u32::<BigEndian>(*id)?;
+                write_string(bytes, "name", name, MAX_NAME_LENGTH)?;
+            }
+            Packet::PlayerState {
+                sequence,
+                x,
+                y,
+                z,
+                velocity,
+            } => {
+                bytes.write_u8(12)?;
+                bytes.write_u32::<BigEndian>(*sequence)?;
+                bytes.write_f64::<BigEndian>(*x)?;
+                bytes.write_f64::<BigEndian>(*y)?;
+                bytes.write_f64::<BigEndian>(*z)?;
+                bytes.write_f64::<BigEndian>(*velocity)?;
+            }
+            Packet::Welcome { id, tick_rate } => {
+                bytes.write_u8(13)?;
+                bytes.write_u32::<BigEndian>(*id)?;
+                bytes.write_u32::<BigEndian>(*tick_rate)?;
+            }
+            Packet::Disconnect { reason } => {
+                bytes.write_u8(14)?;
+                write_string(bytes, "reason", reason, MAX_REASON_LENGTH)?;
+            }
+            Packet::Command { line } => {
+                bytes.write_u8(15)?;
+                write_string(bytes, "line", line, MAX_COMMAND_LENGTH)?;
+            }
+            Packet::SystemMessage { message } => {
+                bytes.write_u8(16)?;
+                write_string(bytes, "message", message, MAX_MESSAGE_LENGTH)?;
+            }
+            Packet::ChatMessage { message } => {
+                bytes.write_u8(17)?;
+                write_string(bytes, "message", message, MAX_CHAT_LENGTH)?;
+            }
+            Packet::Chat { sender, message } => {
+                bytes.write_u8(18)?;
+                write_string(bytes, "sender", sender, MAX_NAME_LENGTH)?;
+                write_string(bytes, "message", message, MAX_CHAT_LENGTH)?;
+            }
+            Packet::AuthChallenge {
+                salt,
+                iterations,
+                nonce,
+            } => {
+                bytes.write_u8(19)?;
+                bytes.write_all(salt)?;
+                bytes.write_u32::<BigEndian>(*iterations)?;
+                bytes.write_all(nonce)?;
+            }
+            Packet::AuthResponse { proof } => {
+                bytes.write_u8(20)?;
+                bytes.write_all(proof)?;
+            }
+            Packet::StatusRequest { token } => {
+                bytes.write_u8(21)?;
+                bytes.write_u64::<BigEndian>(*token)?;
+                bytes.write_all(&[0; STATUS_REQUEST_SIZE - 9])?;
+            }
+            Packet::Status {
+                token,
+                protocol,
+                motd,
+                online,
+                max_players,
+                sample,
+            } => {
+                bytes.write_u8(22)?;
+                bytes.write_u64::<BigEndian>(*token)?;
+                bytes.write_u32::<BigEndian>(*protocol)?;
+                write_string(bytes, "motd", motd, MAX_MOTD_LENGTH)?;
+                bytes.write_u32::<BigEndian>(*online)?;
+                bytes.write_u32::<BigEndian>(*max_players)?;
+                write_len(bytes, "sample", sample.len(), MAX_STATUS_SAMPLE)?;
+                for name in sample {
+                    write_string(bytes, "name", name, MAX_NAME_LENGTH)?;
                 }
             }
+            Packet::LanAnnouncement {
+                protocol,
+                motd,
+                port,
+                online,
+                max_players,
+            } => {
+                bytes.write_u8(23)?;
+                bytes.write_u32::<BigEndian>(*protocol)?;
+                write_string(bytes, "motd", motd, MAX_MOTD_LENGTH)?;
+                bytes.write_u16::<BigEndian>(*port)?;
+                bytes.write_u32::<BigEndian>(*online)?;
+                bytes.write_u32::<BigEndian>(*max_players)?;
+            }
+            Packet::ChangeWorld { name } => {
+                bytes.write_u8(24)?;
+                write_string(bytes, "world", name, MAX_WORLD_NAME_LENGTH)?;
+            }
+            Packet::SpawnEntity { id, kind, x, y, z } => {
+                bytes.write_u8(25)?;
+                bytes.write_u32::<BigEndian>(*id)?;
+                write_string(bytes, "kind", kind, MAX_IDENTIFIER_LENGTH)?;
+                bytes.write_f64::<BigEndian>(*x)?;
+                bytes.write_f64::<BigEndian>(*y)?;
+                bytes.write_f64::<BigEndian>(*z)?;
+            }
+            Packet::DespawnEntity { id } => {
+                bytes.write_u8(26)?;
+                bytes.write_u32::<BigEndian>(*id)?;
+            }
         }
 
-        Ok(bytes)
+        Ok(())
     }
 
     /// Decode a received packet from bytes.
-    pub fn decode(data: Vec<u8>) -> io::Result<Self> {
+    ///
+    /// Decoding is strict: every length prefix is checked against a maximum before anything is
+    /// allocated, strings must be valid UTF-8, numbers must be finite and the packet must use up
+    /// the whole datagram.
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
         let mut cursor = Cursor::new(data);
         let id = cursor.read_u8()?;
 
-        match id {
-            0 => {
-                let size = cursor.read_u64::<BigEndian>()? as usize;
-                let mut buf = vec![0u8; size];
-                cursor.read_exact(&mut buf)?;
-                Ok(Self::Connection {
-                    user: String::from_utf8(buf).unwrap_or_else(|_| String::from("invalid")),
-                })
-            }
-            1 => Ok(Self::Movement {
-                delta_x: cursor.read_f64::<BigEndian>()?,
-                delta_y: cursor.read_f64::<BigEndian>()?,
-                delta_z: cursor.read_f64::<BigEndian>()?,
-            }),
-            2 => {
-                let size = cursor.read_u64::<BigEndian>()? as usize;
-                let mut buf = vec![0u8; size];
-                cursor.read_exact(&mut buf)?;
-                Ok(Self::PositionRequest {
-                    name: String::from_utf8(buf).unwrap_or_else(|_| String::from("invalid")),
-                })
-            }
-            3 => {
-                let x = cursor.read_f64::<BigEndian>()?;
-                let y = cursor.read_f64::<BigEndian>()?;
-                let z = cursor.read_f64::<BigEndian>()?;
-
-                let size = cursor.read_u64::<BigEndian>()? as usize;
-                let mut buf = vec![0u8; size];
-                cursor.read_exact(&mut buf)?;
-                let name = String::from_utf8(buf).unwrap_or_else(|_| String::from("Invalid"));
-
-                Ok(Self::PlayerPosition { x, y, z, name })
-            }
+        let packet = match id {
+            0 => Self::Connection {
+                user: read_string(&mut cursor, "user", MAX_NAME_LENGTH)?,
+                compression: read_bool(&mut cursor, "compression")?,
+            },
+            1 => Self::Input {
+                sequence: cursor.read_u32::<BigEndian>()?,
+                input: MovementInput {
+                    forward: read_f32(&mut cursor, "forward")?,
+                    right: read_f32(&mut cursor, "right")?,
+                    up: read_f32(&mut cursor, "up")?,
+                    yaw: read_f32(&mut cursor, "yaw")?,
+                    pitch: read_f32(&mut cursor, "pitch")?,
+                    flying: read_bool(&mut cursor, "flying")?,
+                },
+            },
             4 => {
-                let len = cursor.read_u64::<BigEndian>()?;
-                let mut players = Vec::with_capacity(len as usize);
+                let len = read_len(&mut cursor, "players", MAX_ONLINE_PLAYERS)?;
+                let mut players = Vec::with_capacity(len);
                 for _ in 0..len {
-                    let name_len = cursor.read_u64::<BigEndian>()?;
-
-                    let mut buf = vec![0u8; name_len as usize];
-                    cursor.read_exact(&mut buf)?;
-
-                    let name = String::from_utf8(buf).unwrap_or_else(|_| String::from("Invalid"));
-                    let x = cursor.read_f32::<BigEndian>()?;
-                    let y = cursor.read_f32::<BigEndian>()?;
-                    let z = cursor.read_f32::<BigEndian>()?;
+                    let id = cursor.read_u32::<BigEndian>()?;
+                    let name = read_string(&mut cursor, "name", MAX_NAME_LENGTH)?;
+                    let x = read_f32(&mut cursor, "x")?;
+                    let y = read_f32(&mut cursor, "y")?;
+                    let z = read_f32(&mut cursor, "z")?;
 
-                    players.push(OnlinePlayer { name, x, y, z });
+                    players.push(OnlinePlayer { id, name, x, y, z });
                 }
 
-                Ok(Self::OnlinePlayers { players })
+                Self::OnlinePlayers { players }
             }
-            5 => Ok(Self::UnloadChunk {
+            5 => Self::UnloadChunk {
                 x: cursor.read_i64::<BigEndian>()?,
                 y: cursor.read_i64::<BigEndian>()?,
-            }),
+            },
             6 => {
                 let x = cursor.read_i64::<BigEndian>()?;
                 let y = cursor.read_i64::<BigEndian>()?;
-                let groups_size = cursor.read_u64::<BigEndian>()?;
 
-                let mut groups = Vec::new();
+                Self::Chunk {
+                    x,
+                    y,
+                    data: read_chunk(&mut cursor)?,
+                }
+            }
+            7 => Self::Compression {
+                threshold: cursor.read_u32::<BigEndian>()?,
+            },
+            8 => {
+                let mut public_key = [0u8; 32];
+                cursor.read_exact(&mut public_key)?;
 
-                for _ in 0..groups_size {
-                    let id_len = cursor.read_u64::<BigEndian>()?;
+                Self::KeyExchange { public_key }
+            }
+            9 => {
+                let tick = cursor.read_u64::<BigEndian>()?;
+                let baseline = if read_bool(&mut cursor, "baseline")? {
+                    Some(cursor.read_u64::<BigEndian>()?)
+                } else {
+                    None
+                };
 
-                    let mut buf = vec![0u8; id_len as usize];
-                    cursor.read_exact(&mut buf)?;
+                let len = read_len(&mut cursor, "entities", MAX_SNAPSHOT_ENTITIES)?;
+                let mut entities = Vec::with_capacity(len);
+                for _ in 0..len {
+                    entities.push(read_delta(&mut cursor)?);
+                }
 
-                    let id = String::from_utf8(buf).unwrap_or_else(|_| String::from("Invalid"));
+                let len = read_len(&mut cursor, "removed", MAX_SNAPSHOT_ENTITIES)?;
+                let mut removed = Vec::with_capacity(len);
+                for _ in 0..len {
+                    removed.push(cursor.read_u32::<BigEndian>()?);
+                }
 
-                    let count = cursor.read_u32::<BigEndian>()?;
+                Self::Snapshot {
+                    tick,
+                    baseline,
+                    entities,
+                    removed,
+                }
+            }
+            10 => Self::SnapshotAck {
+                tick: cursor.read_u64::<BigEndian>()?,
+            },
+            11 => Self::PlayerJoined {
+                id: cursor.read_u32::<BigEndian>()?,
+                name: read_string(&mut cursor, "name", MAX_NAME_LENGTH)?,
+            },
+            12 => Self::PlayerState {
+                sequence: cursor.read_u32::<BigEndian>()?,
+                x: read_f64(&mut cursor, "x")?,
+                y: read_f64(&mut cursor, "y")?,
+                z: read_f64(&mut cursor, "z")?,
+                velocity: read_f64(&mut cursor, "velocity")?,
+            },
+            13 => Self::Welcome {
+                id: cursor.read_u32::<BigEndian>()?,
+                tick_rate: cursor.read_u32::<BigEndian>()?,
+            },
+            14 => Self::Disconnect {
+                reason: read_string(&mut cursor, "reason", MAX_REASON_LENGTH)?,
+            },
+            15 => Self::Command {
+                line: read_string(&mut cursor, "line", MAX_COMMAND_LENGTH)?,
+            },
+            16 => Self::SystemMessage {
+                message: read_string(&mut cursor, "message", MAX_MESSAGE_LENGTH)?,
+            },
+            17 => Self::ChatMessage {
+                message: read_string(&mut cursor, "message", MAX_CHAT_LENGTH)?,
+            },
+            18 => Self::Chat {
+                sender: read_string(&mut cursor, "sender", MAX_NAME_LENGTH)?,
+                message: read_string(&mut cursor, "message", MAX_CHAT_LENGTH)?,
+            },
+            19 => {
+                let mut salt = [0u8; SALT_SIZE];
+                cursor.read_exact(&mut salt)?;
+                let iterations = cursor.read_u32::<BigEndian>()?;
+                let mut nonce = [0u8; NONCE_SIZE];
+                cursor.read_exact(&mut nonce)?;
 
-                    groups.push(BlockGroup { id, count });
+                Self::AuthChallenge {
+                    salt,
+                    iterations,
+                    nonce,
                 }
+            }
+            20 => {
+                let mut proof = [0u8; 32];
+                cursor.read_exact(&mut proof)?;
+
+                Self::AuthResponse { proof }
+            }
+            21 => {
+                let token = cursor.read_u64::<BigEndian>()?;
+
+                let mut padding = [0u8; STATUS_REQUEST_SIZE - 9];
+                cursor.read_exact(&mut padding)?;
 
-                Ok(Self::Chunk { x, y, groups })
+                Self::StatusRequest { token }
             }
-            _ => Err(io::Error::new(
-                ErrorKind::InvalidData,
-                "Trying to decode an invalid packet",
-            )),
+            22 => {
+                let token = cursor.read_u64::<BigEndian>()?;
+                let protocol = cursor.read_u32::<BigEndian>()?;
+                let motd = read_string(&mut cursor, "motd", MAX_MOTD_LENGTH)?;
+                let online = cursor.read_u32::<BigEndian>()?;
+                let max_players = cursor.read_u32::<BigEndian>()?;
+
+                let len = read_len(&mut cursor, "sample", MAX_STATUS_SAMPLE)?;
+                let mut sample = Vec::with_capacity(len);
+                for _ in 0..len {
+                    sample.push(read_string(&mut cursor, "name", MAX_NAME_LENGTH)?);
+                }
+
+                Self::Status {
+                    token,
+                    protocol,
+                    motd,
+                    online,
+                    max_players,
+                    sample,
+                }
+            }
+            23 => Self::LanAnnouncement {
+                protocol: cursor.read_u32::<BigEndian>()?,
+                motd: read_string(&mut cursor, "motd", MAX_MOTD_LENGTH)?,
+                port: cursor.read_u16::<BigEndian>()?,
+                online: cursor.read_u32::<BigEndian>()?,
+                max_players: cursor.read_u32::<BigEndian>()?,
+            },
+            24 => Self::ChangeWorld {
+                name: read_string(&mut cursor, "world", MAX_WORLD_NAME_LENGTH)?,
+            },
+            25 => Self::SpawnEntity {
+                id: cursor.read_u32::<BigEndian>()?,
+                kind: read_string(&mut cursor, "kind", MAX_IDENTIFIER_LENGTH)?,
+                x: read_f64(&mut cursor, "x")?,
+                y: read_f64(&mut cursor, "y")?,
+                z: read_f64(&mut cursor, "z")?,
+            },
+            26 => Self::DespawnEntity {
+                id: cursor.read_u32::<BigEndian>()?,
+            },
+            _ => return Err(DecodeError::UnknownPacket(id)),
+        };
+
+        let remaining = data.len() - cursor.position() as usize;
+        if remaining != 0 {
+            return Err(DecodeError::TrailingBytes(remaining));
         }
+
+        Ok(packet)
     }
 }
 
-/// SocketSender is used with a SocketReceiver to split a UdpSocket into two indipendent parts. This is generated in pair using the split_socket function
+/// Encode a chunk, in the same format as in Chunk packets, to store it on disk.
+pub fn encode_chunk(data: &CompressedChunk) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    write_chunk(&mut bytes, data)?;
+
+    Ok(bytes)
+}
+
+/// Decode a chunk encoded by `encode_chunk`, with the same checks as a received Chunk packet.
+pub fn decode_chunk(data: &[u8]) -> Result<CompressedChunk, DecodeError> {
+    let mut cursor = Cursor::new(data);
+    let chunk = read_chunk(&mut cursor)?;
+
+    let remaining = data.len() - cursor.position() as usize;
+    if remaining != 0 {
+        return Err(DecodeError::TrailingBytes(remaining));
+    }
+
+    Ok(chunk)
+}
+
+/// Write the palette and block groups of a chunk.
+fn write_chunk(bytes: &mut impl Write, data: &CompressedChunk) -> io::Result<()> {
+    write_len(bytes, "palette", data.palette.len(), CHUNK_BLOCKS as usize)?;
+    for id in data.palette.iter() {
+        write_string(bytes, "id", id, MAX_IDENTIFIER_LENGTH)?;
+    }
+    write_len(bytes, "groups", data.groups.len(), CHUNK_BLOCKS as usize)?;
+    for group in data.groups.iter() {
+        bytes.write_u16::<BigEndian>(group.index)?;
+        bytes.write_u16::<BigEndian>(group.count)?;
+    }
+
+    Ok(())
+}
+
+/// Read the palette and block groups of a chunk, checking they make up exactly one chunk.
+fn read_chunk(cursor: &mut Cursor<&[u8]>) -> Result<CompressedChunk, DecodeError> {
+    let palette_size = read_len(cursor, "palette", CHUNK_BLOCKS as usize)?;
+    let mut palette = Vec::with_capacity(palette_size);
+
+    for _ in 0..palette_size {
+        let id = read_string(cursor, "id", MAX_IDENTIFIER_LENGTH)?;
+
+        if Identifier::from_str(&id).is_err() {
+            return Err(DecodeError::InvalidIdentifier(id));
+        }
+
+        palette.push(id);
+    }
+
+    let groups_size = read_len(cursor, "groups", CHUNK_BLOCKS as usize)?;
+    let mut groups = Vec::with_capacity(groups_size);
+    let mut blocks = 0u64;
+
+    for _ in 0..groups_size {
+        let index = cursor.read_u16::<BigEndian>()?;
+
+        if index as usize >= palette.len() {
+            return Err(DecodeError::InvalidPaletteIndex(index));
+        }
+
+        let count = cursor.read_u16::<BigEndian>()?;
+        blocks += count as u64;
+
+        groups.push(BlockGroup { index, count });
+    }
+
+    if blocks != CHUNK_BLOCKS as u64 {
+        return Err(DecodeError::InvalidChunkSize(blocks));
+    }
+
+    Ok(CompressedChunk { palette, groups })
+}
+
+/// Writer that only counts the bytes written to it.
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write a length prefix, refusing lengths the receiving side would reject.
+fn write_len(
+    bytes: &mut impl Write,
+    field: &'static str,
+    len: usize,
+    max: usize,
+) -> io::Result<()> {
+    if len > max {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            DecodeError::TooLong {
+                field,
+                len: len as u64,
+                max: max as u64,
+            },
+        ));
+    }
+
+    bytes.write_u64::<BigEndian>(len as u64)
+}
+
+/// Write a length prefixed UTF-8 string.
+fn write_string(
+    bytes: &mut impl Write,
+    field: &'static str,
+    value: &str,
+    max: usize,
+) -> io::Result<()> {
+    write_len(bytes, field, value.len(), max)?;
+    bytes.write_all(value.as_bytes())
+}
+
+/// Read a length prefix and check it against the maximum before it is used to allocate anything.
+fn read_len(
+    cursor: &mut Cursor<&[u8]>,
+    field: &'static str,
+    max: usize,
+) -> Result<usize, DecodeError> {
+    let len = cursor.read_u64::<BigEndian>()?;
+
+    if len > max as u64 {
+        return Err(DecodeError::TooLong {
+            field,
+            len,
+            max: max as u64,
+        });
+    }
+
+    Ok(len as usize)
+}
+
+/// Read a length prefixed UTF-8 string.
+fn read_string(
+    cursor: &mut Cursor<&[u8]>,
+    field: &'static str,
+    max: usize,
+) -> Result<String, DecodeError> {
+    let len = read_len(cursor, field, max)?;
+    let mut buf = vec![0u8; len];
+    cursor.read_exact(&mut buf)?;
+
+    String::from_utf8(buf).map_err(|_| DecodeError::InvalidUtf8 { field })
+}
+
+/// Write an entity delta, prefixed by flags telling which fields are present.
+fn write_delta(bytes: &mut impl Write, delta: &EntityDelta) -> io::Result<()> {
+    let mut flags = 0;
+    if delta.position.is_some() {
+        flags |= DELTA_POSITION;
+    }
+    if delta.rotation.is_some() {
+        flags |= DELTA_ROTATION;
+    }
+    if delta.velocity.is_some() {
+        flags |= DELTA_VELOCITY;
+    }
+
+    bytes.write_u32::<BigEndian>(delta.id)?;
+    bytes.write_u8(flags)?;
+
+    for value in delta.position.iter().flatten() {
+        bytes.write_f64::<BigEndian>(*value)?;
+    }
+    for value in delta.rotation.iter().flatten() {
+        bytes.write_f32::<BigEndian>(*value)?;
+    }
+    for value in delta.velocity.iter().flatten() {
+        bytes.write_f32::<BigEndian>(*value)?;
+    }
+
+    Ok(())
+}
+
+fn read_delta(cursor: &mut Cursor<&[u8]>) -> Result<EntityDelta, DecodeError> {
+    let id = cursor.read_u32::<BigEndian>()?;
+    let flags = cursor.read_u8()?;
+
+    if flags & !(DELTA_POSITION | DELTA_ROTATION | DELTA_VELOCITY) != 0 {
+        return Err(DecodeError::InvalidFlags {
+            field: "delta",
+            flags,
+        });
+    }
+
+    let position = if flags & DELTA_POSITION != 0 {
+        Some([
+            read_f64(cursor, "x")?,
+            read_f64(cursor, "y")?,
+            read_f64(cursor, "z")?,
+        ])
+    } else {
+        None
+    };
+
+    let rotation = if flags & DELTA_ROTATION != 0 {
+        Some([read_f32(cursor, "yaw")?, read_f32(cursor, "pitch")?])
+    } else {
+        None
+    };
+
+    let velocity = if flags & DELTA_VELOCITY != 0 {
+        Some([
+            read_f32(cursor, "velocity_x")?,
+            read_f32(cursor, "velocity_y")?,
+            read_f32(cursor, "velocity_z")?,
+        ])
+    } else {
+        None
+    };
+
+    Ok(EntityDelta {
+        id,
+        position,
+        rotation,
+        velocity,
+    })
+}
+
+fn read_bool(cursor: &mut Cursor<&[u8]>, field: &'static str) -> Result<bool, DecodeError> {
+    match cursor.read_u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(DecodeError::InvalidBool { field }),
+    }
+}
+
+fn read_f64(cursor: &mut Cursor<&[u8]>, field: &'static str) -> Result<f64, DecodeError> {
+    let value = cursor.read_f64::<BigEndian>()?;
+
+    if !value.is_finite() {
+        return Err(DecodeError::NonFinite { field });
+    }
+
+    Ok(value)
+}
+
+fn read_f32(cursor: &mut Cursor<&[u8]>, field: &'static str) -> Result<f32, DecodeError> {
+    let value = cursor.read_f32::<BigEndian>()?;
+
+    if !value.is_finite() {
+        return Err(DecodeError::NonFinite { field });
+    }
+
+    Ok(value)
+}
+
+/// SocketSender is used with a SocketReceiver to split a Transport into two indipendent parts. This is generated in pair using the split function
+#[derive(Clone)]
 pub struct SocketSender {
-    socket: Arc<tokio::net::UdpSocket>,
+    transport: Arc<dyn Transport>,
 }
 
-/// SocketReceiver is used with a SocketSender to split a UdpSocket into two indipendent parts. This is generated in pair using the split_socket function
+/// SocketReceiver is used with a SocketSender to split a Transport into two indipendent parts. This is generated in pair using the split function
 pub struct SocketReceiver {
-    socket: Arc<tokio::net::UdpSocket>,
+    transport: Arc<dyn Transport>,
 }
 
 impl SocketSender {
     /// Send a packet to the specified socket.
     pub fn send_to(&mut self, packet: Packet, addr: &SocketAddr) -> io::Result<()> {
-        block_on(self.socket.send_to(packet.encode()?.as_slice(), addr))?;
-        Ok(())
+        self.transport.send_to(packet, addr)
     }
 
     /// Send a packet to the connected socket.
     pub fn send(&mut self, packet: Packet) -> io::Result<()> {
-        block_on(self.socket.send(packet.encode()?.as_slice()))?;
-        Ok(())
+        self.transport.send_to(packet, &self.transport.peer_addr()?)
     }
-}
 
-impl SocketReceiver {
-    pub fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        block_on(self.socket.recv_from(buf))
+    /// Address of the connected socket.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.peer_addr()
+    }
+
+    /// Enable or disable compression of the packets sent to a peer.
+    pub fn set_compression(&mut self, addr: SocketAddr, threshold: Option<u32>) {
+        self.transport.set_compression(addr, threshold);
+    }
+
+    /// Start or end an encrypted session with a peer. While a session is active every packet
+    /// sent to the peer is encrypted and plaintext datagrams from it are rejected.
+    pub fn set_session(&mut self, addr: SocketAddr, session: Option<Session>) {
+        self.transport.set_session(addr, session);
     }
 
-    #[allow(dead_code)]
-    pub async fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        block_on(self.socket.recv(buf))
+    /// Check if there is an encrypted session with a peer.
+    pub fn has_session(&self, addr: &SocketAddr) -> bool {
+        self.transport.has_session(addr)
+    }
+
+    /// Close the transport, the receiver stops waiting for packets.
+    pub fn close(&mut self) {
+        self.transport.close();
     }
 }
 
-/// Split a UdpSocket into two indipendent parts, a sender and a receiver.
-pub fn split_socket(socket: UdpSocket) -> (SocketSender, SocketReceiver) {
-    let arc = Arc::new(tokio::net::UdpSocket::from_std(socket).unwrap());
+impl SocketReceiver {
+    /// Wait for the next packet. Datagrams that are not valid packets are reported as InvalidData
+    /// errors.
+    pub fn recv_packet_from(&mut self) -> io::Result<(Packet, SocketAddr)> {
+        self.transport.recv_from()
+    }
+}
+
+/// Split a Transport into two indipendent parts, a sender and a receiver.
+pub fn split(transport: impl Transport + 'static) -> (SocketSender, SocketReceiver) {
+    let transport: Arc<dyn Transport> = Arc::new(transport);
 
     (
         SocketSender {
-            socket: arc.clone(),
+            transport: transport.clone(),
         },
-        SocketReceiver { socket: arc },
+        SocketReceiver { transport },
     )
 }
+
+/// Split a UdpSocket into two indipendent parts, a sender and a receiver.
+pub fn split_socket(socket: UdpSocket) -> (SocketSender, SocketReceiver) {
+    split(UdpTransport::new(socket).unwrap())
+}
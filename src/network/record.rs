@@ -0,0 +1,290 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use log::warn;
+
+use crate::network::crypto::Session;
+use crate::network::transport::Transport;
+use crate::network::Packet;
+
+/// First bytes of a recording.
+const MAGIC: &[u8; 7] = b"YAVEREC";
+/// Version of the recording format, changed when it is not compatible anymore.
+const VERSION: u8 = 1;
+/// Maximum size of a recorded packet. Packets are recorded before compression, so they can be
+/// bigger than a datagram.
+const MAX_RECORDED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Whether a packet was sent or received by the recording side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// A packet seen by a RecordingTransport.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedPacket {
+    /// Time since the recording started.
+    pub time: Duration,
+    pub direction: Direction,
+    /// Peer the packet was sent to or received from.
+    pub peer: SocketAddr,
+    pub packet: Packet,
+}
+
+/// Everything a transport sent and received, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// Address of the recorded transport.
+    pub local: SocketAddr,
+    /// Peer the recorded transport was connected to, for clients.
+    pub peer: Option<SocketAddr>,
+    pub packets: Vec<RecordedPacket>,
+}
+
+impl Recording {
+    /// Read a recording. A recording cut in the middle of a packet, because the program was
+    /// killed while writing it, ends at the last complete packet.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidData, "not a recording"));
+        }
+
+        let version = file.read_u8()?;
+        if version != VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported recording version {version}"),
+            ));
+        }
+
+        let local = read_addr(&mut file)?;
+        let peer = match file.read_u8()? {
+            0 => None,
+            _ => Some(read_addr(&mut file)?),
+        };
+
+        let mut packets = Vec::new();
+        loop {
+            match read_packet(&mut file) {
+                Ok(Some(packet)) => packets.push(packet),
+                Ok(None) => break,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    warn!("The recording is truncated after {} packets", packets.len());
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Self {
+            local,
+            peer,
+            packets,
+        })
+    }
+
+    /// Packets received by the recorded transport, the ones to feed back to reproduce the session.
+    pub fn received(&self) -> impl Iterator<Item = &RecordedPacket> {
+        self.packets
+            .iter()
+            .filter(|packet| packet.direction == Direction::Received)
+    }
+
+    /// Packets sent by the recorded transport.
+    pub fn sent(&self) -> impl Iterator<Item = &RecordedPacket> {
+        self.packets
+            .iter()
+            .filter(|packet| packet.direction == Direction::Sent)
+    }
+}
+
+/// Transport writing every packet sent and received by another transport to a file, with the
+/// time and the peer. Packets are recorded before compression and encryption, and the file is
+/// written after every packet so a crash doesn't lose the end of the session.
+pub struct RecordingTransport<T: Transport> {
+    transport: T,
+    file: Mutex<File>,
+    start: Instant,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Start recording the packets of the transport to a new file.
+    pub fn new(transport: T, path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut header = MAGIC.to_vec();
+        header.write_u8(VERSION)?;
+        write_addr(&mut header, &transport.local_addr()?)?;
+        match transport.peer_addr() {
+            Ok(peer) => {
+                header.write_u8(1)?;
+                write_addr(&mut header, &peer)?;
+            }
+            Err(_) => header.write_u8(0)?,
+        }
+
+        let mut file = File::create(path)?;
+        file.write_all(&header)?;
+
+        Ok(Self {
+            transport,
+            file: Mutex::new(file),
+            start: Instant::now(),
+        })
+    }
+
+    fn record(&self, direction: Direction, peer: &SocketAddr, packet: &Packet) {
+        let entry = encode_entry(self.start.elapsed(), direction, peer, packet);
+
+        let result = entry.and_then(|entry| self.file.lock().unwrap().write_all(&entry));
+        if let Err(e) = result {
+            warn!("Cannot record a packet: {e}");
+        }
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn send_to(&self, packet: Packet, addr: &SocketAddr) -> io::Result<()> {
+        self.record(Direction::Sent, addr, &packet);
+        self.transport.send_to(packet, addr)
+    }
+
+    fn recv_from(&self) -> io::Result<(Packet, SocketAddr)> {
+        let (packet, peer) = self.transport.recv_from()?;
+        self.record(Direction::Received, &peer, &packet);
+
+        Ok((packet, peer))
+    }
+
+    fn close(&self) {
+        self.transport.close();
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.peer_addr()
+    }
+
+    fn set_compression(&self, addr: SocketAddr, threshold: Option<u32>) {
+        self.transport.set_compression(addr, threshold);
+    }
+
+    fn set_session(&self, addr: SocketAddr, session: Option<Session>) {
+        self.transport.set_session(addr, session);
+    }
+
+    fn has_session(&self, addr: &SocketAddr) -> bool {
+        self.transport.has_session(addr)
+    }
+}
+
+fn encode_entry(
+    time: Duration,
+    direction: Direction,
+    peer: &SocketAddr,
+    packet: &Packet,
+) -> io::Result<Vec<u8>> {
+    let packet = packet.encode()?;
+
+    let mut entry = Vec::with_capacity(packet.len() + 40);
+    entry.write_u64::<BigEndian>(time.as_micros() as u64)?;
+    entry.write_u8(match direction {
+        Direction::Sent => 0,
+        Direction::Received => 1,
+    })?;
+    write_addr(&mut entry, peer)?;
+    entry.write_u64::<BigEndian>(packet.len() as u64)?;
+    entry.extend_from_slice(&packet);
+
+    Ok(entry)
+}
+
+/// Read the next packet, or `None` at the end of the recording.
+fn read_packet(file: &mut impl Read) -> io::Result<Option<RecordedPacket>> {
+    let time = match file.read_u64::<BigEndian>() {
+        Ok(time) => Duration::from_micros(time),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let direction = match file.read_u8()? {
+        0 => Direction::Sent,
+        1 => Direction::Received,
+        other => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid packet direction {other}"),
+            ))
+        }
+    };
+    let peer = read_addr(file)?;
+
+    let len = file.read_u64::<BigEndian>()?;
+    if len > MAX_RECORDED_SIZE {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("recorded packet of {len} bytes"),
+        ));
+    }
+    let mut data = vec![0; len as usize];
+    file.read_exact(&mut data)?;
+
+    let packet = Packet::decode(&data).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+    Ok(Some(RecordedPacket {
+        time,
+        direction,
+        peer,
+        packet,
+    }))
+}
+
+fn write_addr(bytes: &mut Vec<u8>, addr: &SocketAddr) -> io::Result<()> {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            bytes.write_u8(4)?;
+            bytes.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            bytes.write_u8(6)?;
+            bytes.extend_from_slice(&ip.octets());
+        }
+    }
+    bytes.write_u16::<BigEndian>(addr.port())
+}
+
+fn read_addr(file: &mut impl Read) -> io::Result<SocketAddr> {
+    let ip = match file.read_u8()? {
+        4 => {
+            let mut octets = [0; 4];
+            file.read_exact(&mut octets)?;
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        6 => {
+            let mut octets = [0; 16];
+            file.read_exact(&mut octets)?;
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        other => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid address family {other}"),
+            ))
+        }
+    };
+
+    Ok(SocketAddr::new(ip, file.read_u16::<BigEndian>()?))
+}
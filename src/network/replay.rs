@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::io;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::network::record::{Direction, RecordedPacket, Recording};
+use crate::network::transport::Transport;
+use crate::network::Packet;
+
+/// Longest time, in the time of the recording, a packet waits for the replayed world to catch up
+/// before it is released anyway. The world can send fewer packets than the recorded one did
+/// when the replay went another way.
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct ReplayState {
+    /// Received packets of the recording not released yet, with the number of packets the
+    /// recorded side sent before receiving each of them.
+    remaining: VecDeque<(usize, RecordedPacket)>,
+    /// Released packets waiting to be received.
+    released: VecDeque<(Packet, SocketAddr)>,
+    /// Whether a thread is waiting in `recv_from`, which means it handled every packet before.
+    waiting: bool,
+    closed: bool,
+    sent: Vec<(Packet, SocketAddr)>,
+    /// Number of packets sent by the replayed world since the start.
+    sent_count: usize,
+}
+
+struct Shared {
+    state: Mutex<ReplayState>,
+    changed: Condvar,
+    /// Time of the last received packet of the recording.
+    duration: Duration,
+}
+
+/// Transport playing back the packets received in a recording, to reproduce a session in a
+/// server or a headless client. Packets are only received once released with `Replay::advance`,
+/// and packets sent are kept instead of going anywhere.
+pub struct ReplayTransport {
+    local: SocketAddr,
+    peer: Option<SocketAddr>,
+    shared: Arc<Shared>,
+}
+
+/// Controls a ReplayTransport from the code driving the replayed world.
+#[derive(Clone)]
+pub struct Replay {
+    shared: Arc<Shared>,
+}
+
+impl ReplayTransport {
+    pub fn new(recording: &Recording) -> Self {
+        let mut sent_before = 0;
+        let mut remaining = VecDeque::new();
+        for packet in &recording.packets {
+            match packet.direction {
+                Direction::Sent => sent_before += 1,
+                Direction::Received => remaining.push_back((sent_before, packet.clone())),
+            }
+        }
+
+        let duration = remaining
+            .back()
+            .map_or(Duration::ZERO, |(_sent_before, packet)| packet.time);
+        let state = ReplayState {
+            remaining,
+            ..Default::default()
+        };
+
+        Self {
+            local: recording.local,
+            peer: recording.peer,
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                changed: Condvar::new(),
+                duration,
+            }),
+        }
+    }
+
+    pub fn replay(&self) -> Replay {
+        Replay {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Replay {
+    /// Release the packets received up to `time` after the start of the recording, and wait until
+    /// the receiving thread handled all of them. A packet is only released once the replayed
+    /// world sent as many packets as the recorded one had when it arrived, so a packet answering
+    /// another one isn't received before the question is asked.
+    ///
+    /// Packets are handed to the world in the same steps whatever the timing of the threads, so
+    /// replaying is deterministic.
+    pub fn advance(&self, time: Duration) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        let mut released = false;
+        while state
+            .remaining
+            .front()
+            .is_some_and(|(sent_before, packet)| {
+                packet.time <= time
+                    && (*sent_before <= state.sent_count || packet.time + MAX_WAIT <= time)
+            })
+        {
+            let (_sent_before, packet) = state.remaining.pop_front().unwrap();
+            state.released.push_back((packet.packet, packet.peer));
+            released = true;
+        }
+
+        if !released {
+            return;
+        }
+
+        state.waiting = false;
+        self.shared.changed.notify_all();
+
+        // The receiving thread is back waiting once it handled every released packet.
+        let handled = |state: &ReplayState| state.released.is_empty() && state.waiting;
+        while !state.closed && !handled(&state) {
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Whether every packet of the recording was released.
+    pub fn finished(&self) -> bool {
+        self.shared.state.lock().unwrap().remaining.is_empty()
+    }
+
+    /// Time of the last received packet of the recording.
+    pub fn duration(&self) -> Duration {
+        self.shared.duration
+    }
+
+    /// Take the packets sent by the replayed world since the last call.
+    pub fn take_sent(&self) -> Vec<(Packet, SocketAddr)> {
+        std::mem::take(&mut self.shared.state.lock().unwrap().sent)
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send_to(&self, packet: Packet, addr: &SocketAddr) -> io::Result<()> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.sent.push((packet, *addr));
+        state.sent_count += 1;
+
+        Ok(())
+    }
+
+    fn recv_from(&self) -> io::Result<(Packet, SocketAddr)> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        loop {
+            if state.closed {
+                return Err(io::Error::from(ErrorKind::ConnectionAborted));
+            }
+
+            if let Some(packet) = state.released.pop_front() {
+                return Ok(packet);
+            }
+
+            state.waiting = true;
+            self.shared.changed.notify_all();
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+
+    fn close(&self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.changed.notify_all();
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.peer
+            .ok_or_else(|| io::Error::from(ErrorKind::NotConnected))
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, VecDeque};
+
+/// Number of snapshots kept to be used as a baseline for deltas. Acknowledgements older than
+/// this can't be used and a full snapshot is sent instead.
+pub const SNAPSHOT_HISTORY: usize = 32;
+
+/// State of an entity as seen by clients.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntityState {
+    pub position: [f64; 3],
+    /// Yaw and pitch in radians.
+    pub rotation: [f32; 2],
+    /// Blocks per second.
+    pub velocity: [f32; 3],
+}
+
+/// Changes of an entity state from a baseline. Fields that didn't change are left out, entities
+/// missing from the baseline have every field set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityDelta {
+    pub id: u32,
+    pub position: Option<[f64; 3]>,
+    pub rotation: Option<[f32; 2]>,
+    pub velocity: Option<[f32; 3]>,
+}
+
+/// State of every entity at a server tick.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub entities: BTreeMap<u32, EntityState>,
+}
+
+impl Snapshot {
+    /// Compute the deltas turning `baseline` into this snapshot, and the entities it removes.
+    /// Without a baseline every entity is sent in full.
+    pub fn diff(&self, baseline: Option<&Snapshot>) -> (Vec<EntityDelta>, Vec<u32>) {
+        let empty = BTreeMap::new();
+        let old = baseline
+            .map(|baseline| &baseline.entities)
+            .unwrap_or(&empty);
+
+        let deltas = self
+            .entities
+            .iter()
+            .filter_map(|(id, state)| {
+                let old = old.get(id);
+                let delta = EntityDelta {
+                    id: *id,
+                    position: changed(old.map(|old| old.position), state.position),
+                    rotation: changed(old.map(|old| old.rotation), state.rotation),
+                    velocity: changed(old.map(|old| old.velocity), state.velocity),
+                };
+
+                if old.is_some() && delta.is_empty() {
+                    None
+                } else {
+                    Some(delta)
+                }
+            })
+            .collect();
+
+        let removed = old
+            .keys()
+            .filter(|id| !self.entities.contains_key(id))
+            .copied()
+            .collect();
+
+        (deltas, removed)
+    }
+
+    /// Rebuild the snapshot of `tick` by applying deltas to a baseline. Returns None if a delta
+    /// is missing fields for an entity that is not in the baseline.
+    pub fn apply(
+        tick: u64,
+        baseline: Option<&Snapshot>,
+        deltas: &[EntityDelta],
+        removed: &[u32],
+    ) -> Option<Self> {
+        let mut entities = baseline
+            .map(|baseline| baseline.entities.clone())
+            .unwrap_or_default();
+
+        for id in removed {
+            entities.remove(id);
+        }
+
+        for delta in deltas {
+            let state = match entities.get(&delta.id) {
+                Some(old) => EntityState {
+                    position: delta.position.unwrap_or(old.position),
+                    rotation: delta.rotation.unwrap_or(old.rotation),
+                    velocity: delta.velocity.unwrap_or(old.velocity),
+                },
+                None => EntityState {
+                    position: delta.position?,
+                    rotation: delta.rotation?,
+                    velocity: delta.velocity?,
+                },
+            };
+
+            entities.insert(delta.id, state);
+        }
+
+        Some(Self { tick, entities })
+    }
+}
+
+impl EntityDelta {
+    pub fn is_empty(&self) -> bool {
+        self.position.is_none() && self.rotation.is_none() && self.velocity.is_none()
+    }
+}
+
+fn changed<T: PartialEq>(old: Option<T>, new: T) -> Option<T> {
+    match old {
+        Some(old) if old == new => None,
+        _ => Some(new),
+    }
+}
+
+/// Recent snapshots, looked up by tick to find the baseline of a delta.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotHistory {
+    snapshots: VecDeque<Snapshot>,
+    /// Last tick acknowledged by the other side.
+    pub acknowledged: Option<u64>,
+}
+
+impl SnapshotHistory {
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == SNAPSHOT_HISTORY {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn get(&self, tick: u64) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|snapshot| snapshot.tick == tick)
+    }
+
+    /// The acknowledged snapshot, if it is still in the history.
+    pub fn baseline(&self) -> Option<&Snapshot> {
+        self.acknowledged.and_then(|tick| self.get(tick))
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    /// Record an acknowledgement, ignoring ones older than the current one since they can arrive
+    /// out of order.
+    pub fn acknowledge(&mut self, tick: u64) {
+        if self
+            .acknowledged
+            .is_none_or(|acknowledged| tick > acknowledged)
+        {
+            self.acknowledged = Some(tick);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError};
+use log::debug;
+use tokio::net::UdpSocket;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{self, error::TrySendError as TokioTrySendError};
+use tokio::sync::Notify;
+
+use crate::network::compression::{self, FRAME_RAW};
+use crate::network::crypto::{Session, FRAME_ENCRYPTED};
+use crate::network::error::DecodeError;
+use crate::network::{Packet, MAX_PACKET_SIZE};
+
+/// A transport moves packets between peers. It is shared by a SocketSender and a SocketReceiver,
+/// so it can be used from the game loop and the network thread at the same time.
+pub trait Transport: Send + Sync {
+    /// Send a packet to a peer.
+    fn send_to(&self, packet: Packet, addr: &SocketAddr) -> io::Result<()>;
+
+    /// Wait for the next packet from any peer. Once the transport is closed this returns a
+    /// ConnectionAborted error.
+    fn recv_from(&self) -> io::Result<(Packet, SocketAddr)>;
+
+    /// Stop receiving packets, waking up a thread waiting in `recv_from`.
+    fn close(&self);
+
+    /// Address other peers use to reach this transport.
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Address of the peer this transport is connected to.
+    fn peer_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Enable or disable compression of the packets sent to a peer.
+    fn set_compression(&self, _addr: SocketAddr, _threshold: Option<u32>) {}
+
+    /// Start or end an encrypted session with a peer.
+    fn set_session(&self, _addr: SocketAddr, _session: Option<Session>) {}
+
+    /// Check if there is an encrypted session with a peer.
+    fn has_session(&self, _addr: &SocketAddr) -> bool {
+        false
+    }
+}
+
+/// A boxed transport, to choose between transports at runtime.
+impl Transport for Box<dyn Transport> {
+    fn send_to(&self, packet: Packet, addr: &SocketAddr) -> io::Result<()> {
+        (**self).send_to(packet, addr)
+    }
+
+    fn recv_from(&self) -> io::Result<(Packet, SocketAddr)> {
+        (**self).recv_from()
+    }
+
+    fn close(&self) {
+        (**self).close()
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        (**self).local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        (**self).peer_addr()
+    }
+
+    fn set_compression(&self, addr: SocketAddr, threshold: Option<u32>) {
+        (**self).set_compression(addr, threshold)
+    }
+
+    fn set_session(&self, addr: SocketAddr, session: Option<Session>) {
+        (**self).set_session(addr, session)
+    }
+
+    fn has_session(&self, addr: &SocketAddr) -> bool {
+        (**self).has_session(addr)
+    }
+}
+
+/// Longest time closing a UDP transport waits for the datagrams already sent to leave.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+/// Most packets waiting in a queue between threads. Packets arriving while a queue is full are
+/// dropped, as the network would drop them, so a flood can't use up the memory of the server.
+pub const QUEUE_SIZE: usize = 4096;
+
+/// A packet received from a peer, or why a datagram couldn't be read.
+type Received = io::Result<(Packet, SocketAddr)>;
+
+/// Encrypted session with each peer. The map is only written when a session starts or ends, each
+/// session is locked on its own so peers don't wait for each other.
+type Sessions = RwLock<HashMap<SocketAddr, Mutex<Session>>>;
+
+/// Work for the task writing to the socket.
+enum Outgoing {
+    Datagram(Vec<u8>, SocketAddr),
+    /// Tell once every datagram queued before is sent.
+    Flush(Sender<()>),
+}
+
+/// Transport sending packets over UDP. Packets are wrapped in a frame, compressed if compression
+/// was negotiated with the peer and encrypted if there is a session with it.
+///
+/// The socket is only used by two tokio tasks, one writing the datagrams queued by `send_to` and
+/// one reading datagrams into a channel for `recv_from`, so sending never waits for the network.
+/// Both channels are lock-free and hold at most `QUEUE_SIZE` packets, and the state of the peers is behind read-write locks that are
+/// only written when it changes. Transports must be created within a tokio runtime.
+pub struct UdpTransport {
+    socket: Arc<UdpSocket>,
+    /// Compression threshold negotiated with each peer. Packets to other peers are not compressed.
+    compression: RwLock<HashMap<SocketAddr, u32>>,
+    /// Encrypted sessions with each peer. While a session is active plaintext datagrams from the
+    /// peer are rejected.
+    sessions: Arc<Sessions>,
+    /// Last error of the sending task for each peer, returned by the next send to it.
+    errors: Arc<RwLock<HashMap<SocketAddr, io::Error>>>,
+    outgoing: mpsc::Sender<Outgoing>,
+    incoming: Receiver<Received>,
+    closed: AtomicBool,
+    /// Stops the receiving task when the transport is closed.
+    close: Arc<Notify>,
+}
+
+impl UdpTransport {
+    pub fn new(socket: std::net::UdpSocket) -> io::Result<Self> {
+        let runtime = Handle::try_current()
+            .map_err(|_| io::Error::other("UDP transports need a tokio runtime"))?;
+        let _runtime = runtime.enter();
+
+        socket.set_nonblocking(true)?;
+        let socket = Arc::new(UdpSocket::from_std(socket)?);
+
+        let sessions = Arc::new(RwLock::new(HashMap::new()));
+        let errors = Arc::new(RwLock::new(HashMap::new()));
+        let close = Arc::new(Notify::new());
+
+        let (outgoing, datagrams) = mpsc::channel(QUEUE_SIZE);
+        runtime.spawn(send_datagrams(socket.clone(), datagrams, errors.clone()));
+
+        let (packets, incoming) = crossbeam_channel::bounded(QUEUE_SIZE);
+        runtime.spawn(receive_packets(
+            socket.clone(),
+            sessions.clone(),
+            packets,
+            close.clone(),
+        ));
+
+        Ok(Self {
+            socket,
+            compression: RwLock::new(HashMap::new()),
+            sessions,
+            errors,
+            outgoing,
+            incoming,
+            closed: AtomicBool::new(false),
+            close,
+        })
+    }
+
+    /// Bind a new UDP socket to the given address.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::new(std::net::UdpSocket::bind(addr)?)
+    }
+
+    /// Bind a new UDP socket to any port and connect it to the given address.
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        Self::new(socket)
+    }
+
+    /// Wait for the next packet, giving up after the timeout.
+    pub fn recv_timeout(&self, timeout: Duration) -> io::Result<(Packet, SocketAddr)> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(io::Error::from(ErrorKind::ConnectionAborted));
+        }
+
+        match self.incoming.recv_timeout(timeout) {
+            Ok(received) => received,
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::from(ErrorKind::TimedOut)),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(io::Error::from(ErrorKind::ConnectionAborted))
+            }
+        }
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send_to(&self, packet: Packet, addr: &SocketAddr) -> io::Result<()> {
+        // Errors are rare, the map is only locked for writing when there is one to return.
+        if self.errors.read().unwrap().contains_key(addr) {
+            if let Some(e) = self.errors.write().unwrap().remove(addr) {
+                return Err(e);
+            }
+        }
+
+        let threshold = self.compression.read().unwrap().get(addr).copied();
+        let mut datagram = compression::compress(&packet.encode()?, threshold)?;
+
+        // The datagram is queued while the session is locked, so datagrams leave in the order of
+        // their nonces. Key exchanges are never encrypted, the peer needs them to start a session.
+        let sessions = self.sessions.read().unwrap();
+        let mut session = sessions.get(addr).map(|session| session.lock().unwrap());
+        if let Some(session) = session.as_mut() {
+            if !matches!(packet, Packet::KeyExchange { .. }) {
+                datagram = session.seal(&datagram);
+            }
+        }
+
+        if datagram.len() > MAX_PACKET_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("a datagram of {} bytes is too big", datagram.len()),
+            ));
+        }
+
+        match self.outgoing.try_send(Outgoing::Datagram(datagram, *addr)) {
+            Ok(()) => Ok(()),
+            Err(TokioTrySendError::Full(_)) => Err(io::Error::new(
+                ErrorKind::WouldBlock,
+                "too many datagrams are waiting to be sent",
+            )),
+            Err(TokioTrySendError::Closed(_)) => Err(io::Error::from(ErrorKind::BrokenPipe)),
+        }
+    }
+
+    fn recv_from(&self) -> io::Result<(Packet, SocketAddr)> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(io::Error::from(ErrorKind::ConnectionAborted));
+        }
+
+        match self.incoming.recv() {
+            Ok(received) => received,
+            Err(_) => Err(io::Error::from(ErrorKind::ConnectionAborted)),
+        }
+    }
+
+    /// Stop receiving, then wait a little for the datagrams already sent to leave so a last
+    /// Disconnect isn't lost when the program exits.
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        // The permit is kept if the task isn't waiting yet, so a receive starting right now ends too.
+        self.close.notify_one();
+
+        // Waiting for room in a full queue counts toward the timeout.
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        let (done, flushed) = crossbeam_channel::bounded(1);
+        let mut flush = Outgoing::Flush(done);
+        loop {
+            match self.outgoing.try_send(flush) {
+                Ok(()) => break,
+                Err(TokioTrySendError::Full(outgoing)) if Instant::now() < deadline => {
+                    flush = outgoing;
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Err(_) => return,
+            }
+        }
+        let _ = flushed.recv_deadline(deadline);
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    fn set_compression(&self, addr: SocketAddr, threshold: Option<u32>) {
+        let mut compression = self.compression.write().unwrap();
+
+        match threshold {
+            Some(threshold) => compression.insert(addr, threshold),
+            None => compression.remove(&addr),
+        };
+    }
+
+    fn set_session(&self, addr: SocketAddr, session: Option<Session>) {
+        let mut sessions = self.sessions.write().unwrap();
+
+        match session {
+            Some(session) => sessions.insert(addr, Mutex::new(session)),
+            None => sessions.remove(&addr),
+        };
+    }
+
+    fn has_session(&self, addr: &SocketAddr) -> bool {
+        self.sessions.read().unwrap().contains_key(addr)
+    }
+}
+
+impl Drop for UdpTransport {
+    fn drop(&mut self) {
+        // The sending task ends by itself once it sent what is left.
+        self.close.notify_one();
+    }
+}
+
+/// Write the queued datagrams until the transport is dropped.
+async fn send_datagrams(
+    socket: Arc<UdpSocket>,
+    mut datagrams: mpsc::Receiver<Outgoing>,
+    errors: Arc<RwLock<HashMap<SocketAddr, io::Error>>>,
+) {
+    let connected = socket.peer_addr().ok();
+
+    while let Some(outgoing) = datagrams.recv().await {
+        match outgoing {
+            Outgoing::Datagram(datagram, addr) => {
+                let sent = if connected == Some(addr) {
+                    socket.send(&datagram).await
+                } else {
+                    socket.send_to(&datagram, addr).await
+                };
+
+                if let Err(e) = sent {
+                    debug!("Cannot send a datagram to {addr}: {e}");
+                    errors.write().unwrap().insert(addr, e);
+                }
+            }
+            Outgoing::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Read datagrams into packets until the transport is closed.
+async fn receive_packets(
+    socket: Arc<UdpSocket>,
+    sessions: Arc<Sessions>,
+    packets: Sender<Received>,
+    close: Arc<Notify>,
+) {
+    let mut buffer = vec![0u8; MAX_PACKET_SIZE];
+
+    loop {
+        let received = tokio::select! {
+            received = socket.recv_from(&mut buffer) => received,
+            _ = close.notified() => break,
+        };
+
+        let packet = received.and_then(|(size, peer)| {
+            let packet = decode(&sessions, &buffer[..size], &peer)?;
+            Ok((packet, peer))
+        });
+
+        match packets.try_send(packet) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => debug!("Dropping a packet, too many are waiting"),
+            // Nobody is receiving anymore.
+            Err(TrySendError::Disconnected(_)) => break,
+        }
+    }
+}
+
+/// Open, decompress and decode a datagram from a peer.
+fn decode(sessions: &Sessions, datagram: &[u8], peer: &SocketAddr) -> io::Result<Packet> {
+    let frame = match sessions.read().unwrap().get(peer) {
+        Some(session) => session.lock().unwrap().open(datagram)?,
+        None if datagram.first() == Some(&FRAME_ENCRYPTED) => {
+            return Err(DecodeError::NoSession.into())
+        }
+        None => datagram.to_vec(),
+    };
+
+    let data = compression::decompress(&frame)?;
+    let packet = Packet::decode(&data)?;
+
+    // The padding of a status request only protects against amplification if it is really sent.
+    if matches!(packet, Packet::StatusRequest { .. }) && frame.first() != Some(&FRAME_RAW) {
+        return Err(DecodeError::CompressedStatusRequest.into());
+    }
+
+    Ok(packet)
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::server::storage::{read_toml, write_toml};
+use crate::server::PermissionLevel;
+
+pub const WHITELIST_FILE: &str = "whitelist.toml";
+pub const OPERATORS_FILE: &str = "ops.toml";
+pub const BANNED_PLAYERS_FILE: &str = "banned-players.toml";
+pub const BANNED_IPS_FILE: &str = "banned-ips.toml";
+
+/// Reason given to players banned without one.
+pub const DEFAULT_BAN_REASON: &str = "Banned by an operator";
+
+/// A list of player names, as stored in the whitelist and operator files. Names are compared
+/// without regard to case, like the names of online players.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct NameList {
+    players: BTreeSet<String>,
+}
+
+impl NameList {
+    fn contains(&self, name: &str) -> bool {
+        self.players
+            .iter()
+            .any(|player| player.eq_ignore_ascii_case(name))
+    }
+
+    fn insert(&mut self, name: &str) -> bool {
+        !self.contains(name) && self.players.insert(name.to_string())
+    }
+
+    fn remove(&mut self, name: &str) -> bool {
+        let count = self.players.len();
+        self.players
+            .retain(|player| !player.eq_ignore_ascii_case(name));
+
+        self.players.len() != count
+    }
+}
+
+/// Who may join the server and who are its operators. Each list is saved to its own TOML file in
+/// the server directory as soon as it changes, missing files are empty lists.
+#[derive(Debug, Clone)]
+pub struct AccessLists {
+    dir: PathBuf,
+    whitelist: NameList,
+    operators: NameList,
+    /// Reason of the ban of each banned name.
+    banned_players: BTreeMap<String, String>,
+    /// Reason of the ban of each banned address.
+    banned_ips: BTreeMap<IpAddr, String>,
+}
+
+impl AccessLists {
+    /// Empty lists saved in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            whitelist: NameList::default(),
+            operators: NameList::default(),
+            banned_players: BTreeMap::new(),
+            banned_ips: BTreeMap::new(),
+        }
+    }
+
+    /// Read the lists saved in `dir`.
+    pub fn load(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let mut lists = Self::new(dir);
+
+        lists.whitelist = read_toml(&lists.dir.join(WHITELIST_FILE))?;
+        lists.operators = read_toml(&lists.dir.join(OPERATORS_FILE))?;
+        lists.banned_players = read_toml(&lists.dir.join(BANNED_PLAYERS_FILE))?;
+        lists.banned_ips = read_toml(&lists.dir.join(BANNED_IPS_FILE))?;
+
+        Ok(lists)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Check if a player may join, returning why it is refused otherwise. The whitelist is only
+    /// checked if it is enabled, bans always are.
+    pub fn check(&self, name: &str, ip: IpAddr, whitelist: bool) -> Result<(), String> {
+        if let Some(reason) = self.banned_ips.get(&ip) {
+            return Err(format!("Your address is banned: {reason}"));
+        }
+
+        if let Some(reason) = self.player_ban(name) {
+            return Err(format!("You are banned: {reason}"));
+        }
+
+        if whitelist && !self.whitelist.contains(name) {
+            return Err(String::from("You are not whitelisted on this server"));
+        }
+
+        Ok(())
+    }
+
+    /// Level of the commands a player may run.
+    pub fn permission(&self, name: &str) -> PermissionLevel {
+        if self.operators.contains(name) {
+            PermissionLevel::Operator
+        } else {
+            PermissionLevel::Player
+        }
+    }
+
+    pub fn whitelist(&self) -> impl Iterator<Item = &str> {
+        self.whitelist.players.iter().map(String::as_str)
+    }
+
+    /// Add a player to the whitelist. Returns false if it already was.
+    pub fn add_to_whitelist(&mut self, name: &str) -> io::Result<bool> {
+        if !self.whitelist.insert(name) {
+            return Ok(false);
+        }
+
+        write_toml(&self.dir.join(WHITELIST_FILE), &self.whitelist)?;
+        Ok(true)
+    }
+
+    /// Remove a player from the whitelist. Returns false if it wasn't in it.
+    pub fn remove_from_whitelist(&mut self, name: &str) -> io::Result<bool> {
+        if !self.whitelist.remove(name) {
+            return Ok(false);
+        }
+
+        write_toml(&self.dir.join(WHITELIST_FILE), &self.whitelist)?;
+        Ok(true)
+    }
+
+    pub fn operators(&self) -> impl Iterator<Item = &str> {
+        self.operators.players.iter().map(String::as_str)
+    }
+
+    /// Make a player an operator. Returns false if it already was.
+    pub fn add_operator(&mut self, name: &str) -> io::Result<bool> {
+        if !self.operators.insert(name) {
+            return Ok(false);
+        }
+
+        write_toml(&self.dir.join(OPERATORS_FILE), &self.operators)?;
+        Ok(true)
+    }
+
+    /// Take the operator permission of a player back. Returns false if it wasn't an operator.
+    pub fn remove_operator(&mut self, name: &str) -> io::Result<bool> {
+        if !self.operators.remove(name) {
+            return Ok(false);
+        }
+
+        write_toml(&self.dir.join(OPERATORS_FILE), &self.operators)?;
+        Ok(true)
+    }
+
+    /// Reason a player name is banned for, if it is, whatever its case.
+    pub fn player_ban(&self, name: &str) -> Option<&str> {
+        self.banned_players
+            .iter()
+            .find(|(banned, _reason)| banned.eq_ignore_ascii_case(name))
+            .map(|(_banned, reason)| reason.as_str())
+    }
+
+    /// Ban a player name, replacing the reason of an existing ban.
+    pub fn ban_player(&mut self, name: &str, reason: &str) -> io::Result<()> {
+        self.banned_players
+            .retain(|banned, _reason| !banned.eq_ignore_ascii_case(name));
+        self.banned_players
+            .insert(name.to_string(), reason.to_string());
+
+        write_toml(&self.dir.join(BANNED_PLAYERS_FILE), &self.banned_players)
+    }
+
+    /// Lift the ban of a player name. Returns false if it wasn't banned.
+    pub fn pardon_player(&mut self, name: &str) -> io::Result<bool> {
+        let count = self.banned_players.len();
+        self.banned_players
+            .retain(|banned, _reason| !banned.eq_ignore_ascii_case(name));
+        if self.banned_players.len() == count {
+            return Ok(false);
+        }
+
+        write_toml(&self.dir.join(BANNED_PLAYERS_FILE), &self.banned_players)?;
+        Ok(true)
+    }
+
+    /// Reason an address is banned for, if it is.
+    pub fn ip_ban(&self, ip: IpAddr) -> Option<&str> {
+        self.banned_ips.get(&ip).map(String::as_str)
+    }
+
+    /// Ban an address, replacing the reason of an existing ban.
+    pub fn ban_ip(&mut self, ip: IpAddr, reason: &str) -> io::Result<()> {
+        self.banned_ips.insert(ip, reason.to_string());
+
+        write_toml(&self.dir.join(BANNED_IPS_FILE), &self.banned_ips)
+    }
+
+    /// Lift the ban of an address. Returns false if it wasn't banned.
+    pub fn pardon_ip(&mut self, ip: IpAddr) -> io::Result<bool> {
+        if self.banned_ips.remove(&ip).is_none() {
+            return Ok(false);
+        }
+
+        write_toml(&self.dir.join(BANNED_IPS_FILE), &self.banned_ips)?;
+        Ok(true)
+    }
+}
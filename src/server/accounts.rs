@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::network::auth::{self, DEFAULT_ITERATIONS, NONCE_SIZE, SALT_SIZE};
+use crate::server::storage::{read_toml, write_private_toml};
+
+pub const ACCOUNTS_FILE: &str = "accounts.toml";
+
+/// How the server makes sure players are who they claim to be.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    /// Anyone can join under any name not already online, for LAN play.
+    #[default]
+    Offline,
+    /// Players need an account on the server and must prove they know its password.
+    Accounts,
+}
+
+impl FromStr for AuthMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "offline" => Ok(AuthMode::Offline),
+            "accounts" => Ok(AuthMode::Accounts),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for AuthMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthMode::Offline => write!(f, "offline"),
+            AuthMode::Accounts => write!(f, "accounts"),
+        }
+    }
+}
+
+/// Account of a player. Only a hash of the key derived from the password is kept, so neither the
+/// password nor a working login proof can be made from the accounts file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "StoredAccount", into = "StoredAccount")]
+pub struct Account {
+    pub salt: [u8; SALT_SIZE],
+    pub iterations: u32,
+    pub stored_key: [u8; 32],
+}
+
+impl Account {
+    /// An account with a new random salt.
+    pub fn new(password: &str, iterations: u32) -> Self {
+        let salt = auth::random();
+
+        Self {
+            salt,
+            iterations,
+            stored_key: auth::stored_key(&auth::derive_key(password, &salt, iterations)),
+        }
+    }
+
+    /// Check the answer of a client to a login challenge.
+    pub fn verify(&self, nonce: &[u8; NONCE_SIZE], user: &str, proof: &[u8; 32]) -> bool {
+        auth::verify(&self.stored_key, nonce, user, proof)
+    }
+}
+
+/// An account as written in the accounts file, with the bytes hex encoded.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StoredAccount {
+    salt: String,
+    iterations: u32,
+    stored_key: String,
+}
+
+impl TryFrom<StoredAccount> for Account {
+    type Error = String;
+
+    fn try_from(stored: StoredAccount) -> Result<Self, Self::Error> {
+        Ok(Self {
+            salt: from_hex(&stored.salt).ok_or("invalid salt")?,
+            iterations: stored.iterations,
+            stored_key: from_hex(&stored.stored_key).ok_or("invalid stored key")?,
+        })
+    }
+}
+
+impl From<Account> for StoredAccount {
+    fn from(account: Account) -> Self {
+        Self {
+            salt: to_hex(&account.salt),
+            iterations: account.iterations,
+            stored_key: to_hex(&account.stored_key),
+        }
+    }
+}
+
+/// Accounts of the players, by name, saved in the accounts file of the server directory as soon
+/// as they change. Only the owner of the server can read the file.
+#[derive(Debug, Clone)]
+pub struct Accounts {
+    path: PathBuf,
+    accounts: BTreeMap<String, Account>,
+    /// Secret the salts sent for names without an account are made from.
+    secret: [u8; 32],
+}
+
+impl Accounts {
+    /// Read the accounts saved in `dir`, none if there is no accounts file.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(ACCOUNTS_FILE);
+        let accounts = read_toml(&path)?;
+
+        Ok(Self {
+            path,
+            accounts,
+            secret: auth::random(),
+        })
+    }
+
+    /// No accounts, saved in `dir` once one is added.
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            path: dir.join(ACCOUNTS_FILE),
+            accounts: BTreeMap::new(),
+            secret: auth::random(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Account> {
+        self.accounts.get(name)
+    }
+
+    /// Salt and password rounds to challenge a client logging in as `name` with. Names without
+    /// an account get made up ones, so the challenge doesn't tell which accounts exist.
+    pub fn challenge(&self, name: &str) -> ([u8; SALT_SIZE], u32) {
+        match self.accounts.get(name) {
+            Some(account) => (account.salt, account.iterations),
+            None => (auth::fake_salt(&self.secret, name), DEFAULT_ITERATIONS),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.accounts.keys().map(String::as_str)
+    }
+
+    /// Create an account or change its password.
+    pub fn register(&mut self, name: &str, password: &str) -> io::Result<()> {
+        self.insert(name, Account::new(password, DEFAULT_ITERATIONS))
+    }
+
+    pub fn insert(&mut self, name: &str, account: Account) -> io::Result<()> {
+        self.accounts.insert(name.to_string(), account);
+
+        write_private_toml(&self.path, &self.accounts)
+    }
+
+    /// Delete an account. Returns false if there was none.
+    pub fn remove(&mut self, name: &str) -> io::Result<bool> {
+        if self.accounts.remove(name).is_none() {
+            return Ok(false);
+        }
+
+        write_private_toml(&self.path, &self.accounts)?;
+        Ok(true)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(bytes)
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_derive::Deserialize;
+
+use crate::assets::Identifier;
+use crate::server::storage::read_toml;
+use crate::world::chunk::AIR;
+
+/// Directory of the blocks in an asset namespace, one TOML file per block.
+pub const BLOCKS_DIR: &str = "blocks";
+
+/// What the server needs to know about a block, read from `blocks/<name>.toml` in an asset
+/// namespace. The textures in the same file are only used by the client.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct BlockDescription {
+    /// Whether players collide with the block.
+    #[serde(default)]
+    pub solid: bool,
+}
+
+/// Blocks that can be placed, by id, air included.
+#[derive(Debug, Clone)]
+pub struct BlockTypes {
+    types: HashMap<Identifier, BlockDescription>,
+}
+
+impl Default for BlockTypes {
+    fn default() -> Self {
+        Self {
+            types: HashMap::from([(AIR.parse().unwrap(), BlockDescription { solid: false })]),
+        }
+    }
+}
+
+impl BlockTypes {
+    /// Read the blocks of every namespace in the assets directory.
+    pub fn load(assets: &Path) -> io::Result<Self> {
+        let mut types = Self::default();
+
+        for namespace in fs::read_dir(assets)? {
+            let namespace = namespace?;
+            let dir = namespace.path().join(BLOCKS_DIR);
+            if !dir.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(dir)? {
+                let path = file?.path();
+                if path.extension().is_none_or(|extension| extension != "toml") {
+                    continue;
+                }
+
+                let id = Identifier::new(
+                    &namespace.file_name().to_string_lossy(),
+                    &path.file_stem().unwrap().to_string_lossy(),
+                );
+                types.register(id, read_toml(&path)?);
+            }
+        }
+
+        Ok(types)
+    }
+
+    /// Add a block, replacing the one with the same id.
+    pub fn register(&mut self, id: Identifier, description: BlockDescription) {
+        self.types.insert(id, description);
+    }
+
+    pub fn get(&self, id: &Identifier) -> Option<&BlockDescription> {
+        self.types.get(id)
+    }
+
+    pub fn contains(&self, id: &Identifier) -> bool {
+        self.types.contains_key(id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &Identifier> {
+        self.types.keys()
+    }
+}
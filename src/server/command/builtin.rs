@@ -0,0 +1,624 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::IpAddr;
+
+use bevy_ecs::prelude::{Entity, Mut, With, World};
+
+use crate::network::Packet;
+use crate::server::access::{AccessLists, DEFAULT_BAN_REASON};
+use crate::server::accounts::Accounts;
+use crate::server::block::BlockTypes;
+use crate::server::command::{
+    Argument, ArgumentKind, Arguments, Command, CommandContext, CommandDispatcher, CommandError,
+    CommandResult,
+};
+use crate::server::config::ServerConfig;
+use crate::server::game::Game;
+use crate::server::queue::{Priority, SendQueue};
+use crate::server::worlds::Worlds;
+use crate::server::{
+    Connection, InWorld, LoadedChunks, PermissionLevel, Player, Position, PreviousPosition, Running,
+};
+use crate::world::chunk::Block;
+
+/// Register the commands every server has.
+pub fn register(dispatcher: &mut CommandDispatcher) {
+    let commands = [
+        Command {
+            name: "help",
+            description: "List the commands you can use",
+            permission: PermissionLevel::Player,
+            arguments: vec![],
+            handler: help,
+        },
+        Command {
+            name: "list",
+            description: "List the online players",
+            permission: PermissionLevel::Player,
+            arguments: vec![],
+            handler: list,
+        },
+        Command {
+            name: "kick",
+            description: "Disconnect a player",
+            permission: PermissionLevel::Operator,
+            arguments: vec![
+                Argument::required("player", ArgumentKind::Player),
+                Argument::optional("reason", ArgumentKind::Text),
+            ],
+            handler: kick,
+        },
+        Command {
+            name: "tp",
+            description: "Teleport a player, relative coordinates start from the player",
+            permission: PermissionLevel::Operator,
+            arguments: vec![
+                Argument::required("player", ArgumentKind::Player),
+                Argument::required("x", ArgumentKind::Coordinate),
+                Argument::required("y", ArgumentKind::Coordinate),
+                Argument::required("z", ArgumentKind::Coordinate),
+            ],
+            handler: tp,
+        },
+        Command {
+            name: "save",
+            description: "Save the world and the players",
+            permission: PermissionLevel::Operator,
+            arguments: vec![],
+            handler: save,
+        },
+        Command {
+            name: "stop",
+            description: "Stop the server",
+            permission: PermissionLevel::Operator,
+            arguments: vec![],
+            handler: stop,
+        },
+        Command {
+            name: "say",
+            description: "Send a message to every player",
+            permission: PermissionLevel::Operator,
+            arguments: vec![Argument::required("message", ArgumentKind::Text)],
+            handler: say,
+        },
+        Command {
+            name: "op",
+            description: "Let a player run operator commands",
+            permission: PermissionLevel::Operator,
+            arguments: vec![Argument::required("player", ArgumentKind::Player)],
+            handler: op,
+        },
+        Command {
+            name: "deop",
+            description: "Take the operator permission of a player back",
+            permission: PermissionLevel::Operator,
+            arguments: vec![Argument::required("player", ArgumentKind::Player)],
+            handler: deop,
+        },
+        Command {
+            name: "ban",
+            description: "Prevent a player from joining and disconnect it",
+            permission: PermissionLevel::Operator,
+            arguments: vec![
+                Argument::required("player", ArgumentKind::Player),
+                Argument::optional("reason", ArgumentKind::Text),
+            ],
+            handler: ban,
+        },
+        Command {
+            name: "ban-ip",
+            description: "Prevent an address, or the address of an online player, from joining",
+            permission: PermissionLevel::Operator,
+            arguments: vec![
+                Argument::required("target", ArgumentKind::Player),
+                Argument::optional("reason", ArgumentKind::Text),
+            ],
+            handler: ban_ip,
+        },
+        Command {
+            name: "pardon",
+            description: "Lift the ban of a player",
+            permission: PermissionLevel::Operator,
+            arguments: vec![Argument::required("player", ArgumentKind::Player)],
+            handler: pardon,
+        },
+        Command {
+            name: "pardon-ip",
+            description: "Lift the ban of an address",
+            permission: PermissionLevel::Operator,
+            arguments: vec![Argument::required("address", ArgumentKind::Text)],
+            handler: pardon_ip,
+        },
+        Command {
+            name: "whitelist",
+            description: "Show or change the players allowed to join when the whitelist is on",
+            permission: PermissionLevel::Operator,
+            arguments: vec![
+                Argument::required("action", ArgumentKind::Choice(&["add", "remove", "list"])),
+                Argument::optional("player", ArgumentKind::Player),
+            ],
+            handler: whitelist,
+        },
+        Command {
+            name: "account",
+            description: "Manage the accounts players need when the server has accounts",
+            permission: PermissionLevel::Console,
+            arguments: vec![
+                Argument::required("action", ArgumentKind::Choice(&["add", "remove", "list"])),
+                Argument::optional("player", ArgumentKind::Player),
+                Argument::optional("password", ArgumentKind::Text),
+            ],
+            handler: account,
+        },
+        Command {
+            name: "setblock",
+            description: "Replace a block",
+            permission: PermissionLevel::Operator,
+            arguments: vec![
+                Argument::required("x", ArgumentKind::Coordinate),
+                Argument::required("y", ArgumentKind::Coordinate),
+                Argument::required("z", ArgumentKind::Coordinate),
+                Argument::required("block", ArgumentKind::Block),
+            ],
+            handler: setblock,
+        },
+        Command {
+            name: "worlds",
+            description: "List the worlds and how many players are in each",
+            permission: PermissionLevel::Player,
+            arguments: vec![],
+            handler: worlds,
+        },
+        Command {
+            name: "world",
+            description: "Move a player to the spawn of another world",
+            permission: PermissionLevel::Operator,
+            arguments: vec![
+                Argument::required("player", ArgumentKind::Player),
+                Argument::required("world", ArgumentKind::World),
+            ],
+            handler: world,
+        },
+        Command {
+            name: "summon",
+            description: "Spawn an entity, relative coordinates start from whoever runs it",
+            permission: PermissionLevel::Operator,
+            arguments: vec![
+                Argument::required("entity", ArgumentKind::EntityType),
+                Argument::required("x", ArgumentKind::Coordinate),
+                Argument::required("y", ArgumentKind::Coordinate),
+                Argument::required("z", ArgumentKind::Coordinate),
+            ],
+            handler: summon,
+        },
+        Command {
+            name: "despawn",
+            description: "Remove an entity that is not a player",
+            permission: PermissionLevel::Operator,
+            arguments: vec![Argument::required("id", ArgumentKind::Integer)],
+            handler: despawn,
+        },
+    ];
+
+    for command in commands {
+        dispatcher.register(command);
+    }
+}
+
+fn help(context: &mut CommandContext, _arguments: &Arguments) -> CommandResult {
+    let lines: Vec<String> = context
+        .dispatcher
+        .available(context.source)
+        .map(|command| format!("{}: {}", command.usage(), command.description))
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+fn list(context: &mut CommandContext, _arguments: &Arguments) -> CommandResult {
+    let world = &mut *context.world;
+    let mut names: Vec<String> = world
+        .query::<&Player>()
+        .iter(world)
+        .map(|player| player.name.name.clone())
+        .collect();
+    names.sort();
+
+    let max_players = world.get_resource::<ServerConfig>().unwrap().max_players;
+
+    Ok(format!(
+        "{} of {max_players} players online: {}",
+        names.len(),
+        names.join(", ")
+    ))
+}
+
+fn kick(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let name = arguments.player("player").unwrap();
+    let entity = Game::find_player(context.world, name)
+        .ok_or_else(|| CommandError::PlayerNotFound(name.to_string()))?;
+    let reason = arguments.text("reason").unwrap_or("Kicked by an operator");
+
+    Game::disconnect(context.world, entity, reason);
+
+    Ok(format!("Kicked {name}: {reason}"))
+}
+
+fn tp(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let name = arguments.player("player").unwrap();
+    let entity = Game::find_player(context.world, name)
+        .ok_or_else(|| CommandError::PlayerNotFound(name.to_string()))?;
+
+    let mut position = context.world.get_mut::<Position>(entity).unwrap();
+    let destination = Position {
+        x: arguments.coordinate("x").unwrap().resolve(position.x),
+        y: arguments.coordinate("y").unwrap().resolve(position.y),
+        z: arguments.coordinate("z").unwrap().resolve(position.z),
+    };
+    *position = destination;
+
+    // A teleport is not a movement, the player doesn't get any velocity from it.
+    context.world.get_mut::<PreviousPosition>(entity).unwrap().0 = destination;
+
+    Ok(format!(
+        "Teleported {name} to {:.2} {:.2} {:.2}",
+        destination.x, destination.y, destination.z
+    ))
+}
+
+fn save(context: &mut CommandContext, _arguments: &Arguments) -> CommandResult {
+    let (chunks, players) = Game::save(context.world)
+        .map_err(|e| CommandError::Failed(format!("Cannot save the world: {e}")))?;
+
+    Ok(format!("Saved {chunks} chunks and {players} players"))
+}
+
+fn stop(context: &mut CommandContext, _arguments: &Arguments) -> CommandResult {
+    context.world.insert_resource(Running(false));
+
+    Ok(String::from("Stopping the server"))
+}
+
+fn say(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let message = format!(
+        "[{}] {}",
+        context.source.name(),
+        arguments.text("message").unwrap()
+    );
+
+    Game::broadcast(context.world, &message);
+
+    Ok(message)
+}
+
+fn setblock(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let origin = context.source.position(context.world);
+    let coordinate =
+        |name, origin: f64| arguments.coordinate(name).unwrap().resolve(origin).floor() as i64;
+    let (x, y, z) = (
+        coordinate("x", origin[0]),
+        coordinate("y", origin[1]),
+        coordinate("z", origin[2]),
+    );
+    let id = arguments.block("block").unwrap();
+
+    if !(0..16).contains(&y) {
+        return Err(CommandError::Failed(format!(
+            "y must be between 0 and 15, not {y}"
+        )));
+    }
+
+    let solid = context
+        .world
+        .get_resource::<BlockTypes>()
+        .unwrap()
+        .get(id)
+        .ok_or_else(|| CommandError::Failed(format!("There is no block {id}")))?
+        .solid;
+
+    let (chunk_x, chunk_y) = (x.div_euclid(16), z.div_euclid(16));
+    let (block_x, block_y, block_z) = (x.rem_euclid(16) as u16, y as u16, z.rem_euclid(16) as u16);
+
+    let world = &mut *context.world;
+    let name = context.source.world(world);
+
+    let mut worlds = world.get_resource_mut::<Worlds>().unwrap();
+    let game_world = worlds
+        .get_mut(&name)
+        .ok_or_else(|| CommandError::WorldNotFound(name.clone()))?;
+    let chunk = game_world.load_chunk(chunk_x, chunk_y);
+    let index = (block_z * 16 * 16 + block_y * 16 + block_x) as usize;
+    chunk.blocks[index] = Block::new(block_x, block_y, block_z, id.clone(), solid);
+    let data = chunk.compress();
+    game_world.chunks.modified.insert((chunk_x, chunk_y));
+
+    // Clients only receive whole chunks, the ones of the world that have it loaded get it again.
+    // A chunk still waiting to be sent is sent with the change instead.
+    let mut clients = world.query::<(&InWorld, &LoadedChunks, &mut SendQueue)>();
+    for (in_world, loaded, mut queue) in clients.iter_mut(world) {
+        if in_world.0 != name
+            || !loaded.chunks.contains(&(chunk_x, chunk_y))
+            || queue.update_chunk(chunk_x, chunk_y, &data)
+        {
+            continue;
+        }
+
+        let packet = Packet::Chunk {
+            x: chunk_x,
+            y: chunk_y,
+            data: data.clone(),
+        };
+        queue.push(packet, Priority::Block);
+    }
+
+    Ok(format!("Placed {id} at {x} {y} {z}"))
+}
+
+fn worlds(context: &mut CommandContext, _arguments: &Arguments) -> CommandResult {
+    let world = &mut *context.world;
+    let mut players: BTreeMap<String, usize> = BTreeMap::new();
+    for in_world in world.query_filtered::<&InWorld, With<Player>>().iter(world) {
+        *players.entry(in_world.0.clone()).or_default() += 1;
+    }
+
+    let lines: Vec<String> = world
+        .get_resource::<Worlds>()
+        .unwrap()
+        .names()
+        .map(|name| {
+            let count = players.get(name).copied().unwrap_or_default();
+            format!("{name}: {count} players")
+        })
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+fn world(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let name = arguments.player("player").unwrap();
+    let entity = Game::find_player(context.world, name)
+        .ok_or_else(|| CommandError::PlayerNotFound(name.to_string()))?;
+    let destination = arguments.world("world").unwrap();
+
+    let worlds = context.world.get_resource::<Worlds>().unwrap();
+    if worlds.get(destination).is_none() {
+        return Err(CommandError::WorldNotFound(destination.to_string()));
+    }
+
+    if context.world.get::<InWorld>(entity).unwrap().0 == destination {
+        return Err(CommandError::Failed(format!(
+            "{name} is already in {destination}"
+        )));
+    }
+
+    Game::move_to_world(context.world, entity, destination);
+
+    Ok(format!("Moved {name} to {destination}"))
+}
+
+fn summon(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let origin = context.source.position(context.world);
+    let position = Position {
+        x: arguments.coordinate("x").unwrap().resolve(origin[0]),
+        y: arguments.coordinate("y").unwrap().resolve(origin[1]),
+        z: arguments.coordinate("z").unwrap().resolve(origin[2]),
+    };
+    let kind = arguments.entity_type("entity").unwrap();
+    let world_name = context.source.world(context.world);
+
+    let id = Game::spawn_entity(context.world, kind, &world_name, position)
+        .ok_or_else(|| CommandError::Failed(format!("There is no entity type {kind}")))?;
+
+    Ok(format!(
+        "Summoned {kind} {} at {:.2} {:.2} {:.2}",
+        id.0, position.x, position.y, position.z
+    ))
+}
+
+fn despawn(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let id = arguments.integer("id").unwrap();
+
+    let removed = u32::try_from(id).is_ok_and(|id| Game::despawn_entity(context.world, id));
+    if !removed {
+        return Err(CommandError::Failed(format!("There is no entity {id}")));
+    }
+
+    Ok(format!("Removed entity {id}"))
+}
+
+fn access(world: &mut World) -> Mut<'_, AccessLists> {
+    world.get_resource_mut::<AccessLists>().unwrap()
+}
+
+fn save_failed(e: io::Error) -> CommandError {
+    CommandError::Failed(format!("Cannot save the access lists: {e}"))
+}
+
+fn op(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let name = arguments.player("player").unwrap();
+
+    if !access(context.world)
+        .add_operator(name)
+        .map_err(save_failed)?
+    {
+        return Err(CommandError::Failed(format!(
+            "{name} is already an operator"
+        )));
+    }
+    if let Some(entity) = Game::find_player(context.world, name) {
+        *context.world.get_mut::<PermissionLevel>(entity).unwrap() = PermissionLevel::Operator;
+        Game::send_message(context.world, entity, "You are now an operator");
+    }
+
+    Ok(format!("Made {name} an operator"))
+}
+
+fn deop(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let name = arguments.player("player").unwrap();
+
+    if !access(context.world)
+        .remove_operator(name)
+        .map_err(save_failed)?
+    {
+        return Err(CommandError::Failed(format!("{name} is not an operator")));
+    }
+    if let Some(entity) = Game::find_player(context.world, name) {
+        *context.world.get_mut::<PermissionLevel>(entity).unwrap() = PermissionLevel::Player;
+        Game::send_message(context.world, entity, "You are no longer an operator");
+    }
+
+    Ok(format!("{name} is no longer an operator"))
+}
+
+fn ban(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let name = arguments.player("player").unwrap();
+    let reason = arguments.text("reason").unwrap_or(DEFAULT_BAN_REASON);
+
+    access(context.world)
+        .ban_player(name, reason)
+        .map_err(save_failed)?;
+
+    if let Some(entity) = Game::find_player(context.world, name) {
+        Game::disconnect(context.world, entity, &format!("You are banned: {reason}"));
+    }
+
+    Ok(format!("Banned {name}: {reason}"))
+}
+
+fn ban_ip(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let target = arguments.player("target").unwrap();
+    let reason = arguments.text("reason").unwrap_or(DEFAULT_BAN_REASON);
+
+    let ip = match target.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => {
+            let entity = Game::find_player(context.world, target)
+                .ok_or_else(|| CommandError::PlayerNotFound(target.to_string()))?;
+            context.world.get::<Connection>(entity).unwrap().peer.ip()
+        }
+    };
+
+    access(context.world)
+        .ban_ip(ip, reason)
+        .map_err(save_failed)?;
+
+    // Everyone playing from the address leaves, not only the target.
+    let players: Vec<Entity> = context
+        .world
+        .query::<(Entity, &Connection)>()
+        .iter(context.world)
+        .filter(|(_entity, connection)| connection.peer.ip() == ip)
+        .map(|(entity, _connection)| entity)
+        .collect();
+    for entity in players.iter() {
+        Game::disconnect(
+            context.world,
+            *entity,
+            &format!("Your address is banned: {reason}"),
+        );
+    }
+
+    Ok(format!(
+        "Banned {ip}: {reason}, disconnected {} players",
+        players.len()
+    ))
+}
+
+fn pardon(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let name = arguments.player("player").unwrap();
+
+    if !access(context.world)
+        .pardon_player(name)
+        .map_err(save_failed)?
+    {
+        return Err(CommandError::Failed(format!("{name} is not banned")));
+    }
+
+    Ok(format!("Lifted the ban of {name}"))
+}
+
+fn pardon_ip(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let address = arguments.text("address").unwrap();
+    let ip = address
+        .parse::<IpAddr>()
+        .map_err(|_| CommandError::Failed(format!("Invalid address {address:?}")))?;
+
+    if !access(context.world).pardon_ip(ip).map_err(save_failed)? {
+        return Err(CommandError::Failed(format!("{ip} is not banned")));
+    }
+
+    Ok(format!("Lifted the ban of {ip}"))
+}
+
+fn whitelist(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let action = arguments.choice("action").unwrap();
+
+    if action == "list" {
+        let access = access(context.world);
+        let names: Vec<&str> = access.whitelist().collect();
+        return Ok(format!(
+            "{} whitelisted players: {}",
+            names.len(),
+            names.join(", ")
+        ));
+    }
+
+    let name = arguments
+        .player("player")
+        .ok_or_else(|| CommandError::MissingArgument {
+            usage: context.dispatcher.get("whitelist").unwrap().usage(),
+            argument: "player",
+        })?;
+
+    let mut access = access(context.world);
+    match action {
+        "add" => match access.add_to_whitelist(name).map_err(save_failed)? {
+            true => Ok(format!("Added {name} to the whitelist")),
+            false => Err(CommandError::Failed(format!(
+                "{name} is already whitelisted"
+            ))),
+        },
+        _ => match access.remove_from_whitelist(name).map_err(save_failed)? {
+            true => Ok(format!("Removed {name} from the whitelist")),
+            false => Err(CommandError::Failed(format!("{name} is not whitelisted"))),
+        },
+    }
+}
+
+/// Accounts can only be managed from the console, so passwords never go through the chat or the
+/// command log.
+fn account(context: &mut CommandContext, arguments: &Arguments) -> CommandResult {
+    let action = arguments.choice("action").unwrap();
+    let usage = || context.dispatcher.get("account").unwrap().usage();
+    let mut accounts = context.world.get_resource_mut::<Accounts>().unwrap();
+
+    if action == "list" {
+        let names: Vec<&str> = accounts.names().collect();
+        return Ok(format!("{} accounts: {}", names.len(), names.join(", ")));
+    }
+
+    let name = arguments
+        .player("player")
+        .ok_or_else(|| CommandError::MissingArgument {
+            usage: usage(),
+            argument: "player",
+        })?;
+    let failed = |e: io::Error| CommandError::Failed(format!("Cannot save the accounts: {e}"));
+
+    if action == "remove" {
+        return match accounts.remove(name).map_err(failed)? {
+            true => Ok(format!("Removed the account of {name}")),
+            false => Err(CommandError::Failed(format!("{name} has no account"))),
+        };
+    }
+
+    let password = arguments
+        .text("password")
+        .ok_or_else(|| CommandError::MissingArgument {
+            usage: usage(),
+            argument: "password",
+        })?;
+    accounts.register(name, password).map_err(failed)?;
+
+    Ok(format!("Set the password of {name}"))
+}
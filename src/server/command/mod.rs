@@ -0,0 +1,570 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use bevy_ecs::prelude::{Entity, World};
+
+use crate::assets::Identifier;
+use crate::server::block::BlockTypes;
+use crate::server::entity::EntityTypes;
+use crate::server::worlds::Worlds;
+use crate::server::{InWorld, PermissionLevel, Player, Position, MAIN_WORLD};
+
+pub mod builtin;
+
+/// Function running a command once its arguments are parsed. It returns the message shown to
+/// whoever ran the command.
+pub type Handler = fn(&mut CommandContext, &Arguments) -> CommandResult;
+
+pub type CommandResult = Result<String, CommandError>;
+
+/// Type of an argument, used to parse it and to suggest values while it is typed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    /// Name of an online player.
+    Player,
+    Integer,
+    /// A block coordinate, either absolute or relative to the player running the command when it
+    /// starts with `~`.
+    Coordinate,
+    /// A block id like `base:stone`.
+    Block,
+    /// Name of a world hosted by the server.
+    World,
+    /// An entity type id like `base:wanderer`.
+    EntityType,
+    /// One of a fixed set of words.
+    Choice(&'static [&'static str]),
+    /// The rest of the line. Only allowed as the last argument.
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argument {
+    pub name: &'static str,
+    pub kind: ArgumentKind,
+    pub optional: bool,
+}
+
+impl Argument {
+    pub fn required(name: &'static str, kind: ArgumentKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    pub fn optional(name: &'static str, kind: ArgumentKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+}
+
+/// Farthest a coordinate argument can be from the origin, in blocks. Positions further away
+/// would overflow chunk coordinates.
+pub const MAX_COORDINATE: f64 = 30_000_000.;
+
+/// A coordinate argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coordinate {
+    pub relative: bool,
+    pub value: f64,
+}
+
+impl Coordinate {
+    /// Absolute value of the coordinate, relative ones being added to `origin`, kept within
+    /// `MAX_COORDINATE`.
+    pub fn resolve(&self, origin: f64) -> f64 {
+        let value = if self.relative {
+            origin + self.value
+        } else {
+            self.value
+        };
+
+        value.clamp(-MAX_COORDINATE, MAX_COORDINATE)
+    }
+}
+
+impl FromStr for Coordinate {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (relative, value) = match s.strip_prefix('~') {
+            Some("") => {
+                return Ok(Self {
+                    relative: true,
+                    value: 0.,
+                })
+            }
+            Some(offset) => (true, offset),
+            None => (false, s),
+        };
+
+        match value.parse::<f64>() {
+            Ok(value) if value.abs() <= MAX_COORDINATE => Ok(Self { relative, value }),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A parsed argument.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Player(String),
+    Integer(i64),
+    Coordinate(Coordinate),
+    Block(Identifier),
+    World(String),
+    EntityType(Identifier),
+    Choice(&'static str),
+    Text(String),
+}
+
+/// Arguments of a command, by name. Optional arguments that were not given are missing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Arguments {
+    values: HashMap<&'static str, Value>,
+}
+
+impl Arguments {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    pub fn player(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(Value::Player(player)) => Some(player),
+            _ => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<i64> {
+        match self.values.get(name) {
+            Some(Value::Integer(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn coordinate(&self, name: &str) -> Option<Coordinate> {
+        match self.values.get(name) {
+            Some(Value::Coordinate(coordinate)) => Some(*coordinate),
+            _ => None,
+        }
+    }
+
+    pub fn block(&self, name: &str) -> Option<&Identifier> {
+        match self.values.get(name) {
+            Some(Value::Block(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn world(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(Value::World(world)) => Some(world),
+            _ => None,
+        }
+    }
+
+    pub fn entity_type(&self, name: &str) -> Option<&Identifier> {
+        match self.values.get(name) {
+            Some(Value::EntityType(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn choice(&self, name: &str) -> Option<&'static str> {
+        match self.values.get(name) {
+            Some(Value::Choice(choice)) => Some(choice),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(Value::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// Who runs a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandSource {
+    /// The server console, allowed to run every command.
+    Console,
+    /// A player, from the chat.
+    Player {
+        entity: Entity,
+        name: String,
+        permission: PermissionLevel,
+    },
+}
+
+impl CommandSource {
+    pub fn name(&self) -> &str {
+        match self {
+            CommandSource::Console => "Server",
+            CommandSource::Player { name, .. } => name,
+        }
+    }
+
+    pub fn permission(&self) -> PermissionLevel {
+        match self {
+            CommandSource::Console => PermissionLevel::Console,
+            CommandSource::Player { permission, .. } => *permission,
+        }
+    }
+
+    /// Position relative coordinates are resolved from, the origin for the console.
+    pub fn position(&self, world: &World) -> [f64; 3] {
+        match self {
+            CommandSource::Console => [0., 0., 0.],
+            CommandSource::Player { entity, .. } => world
+                .get::<Position>(*entity)
+                .map_or([0., 0., 0.], |position| {
+                    [position.x, position.y, position.z]
+                }),
+        }
+    }
+
+    /// World the command acts on when it changes the world, the main one for the console.
+    pub fn world(&self, world: &World) -> String {
+        match self {
+            CommandSource::Console => String::from(MAIN_WORLD),
+            CommandSource::Player { entity, .. } => world
+                .get::<InWorld>(*entity)
+                .map_or_else(|| String::from(MAIN_WORLD), |in_world| in_world.0.clone()),
+        }
+    }
+}
+
+/// What a command runs with.
+pub struct CommandContext<'a> {
+    pub world: &'a mut World,
+    pub source: &'a CommandSource,
+    pub dispatcher: &'a CommandDispatcher,
+}
+
+/// A registered command.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Level needed to run the command.
+    pub permission: PermissionLevel,
+    pub arguments: Vec<Argument>,
+    pub handler: Handler,
+}
+
+impl Command {
+    /// How to use the command, like `tp <player> <x> <y> <z>`. Optional arguments are in square
+    /// brackets.
+    pub fn usage(&self) -> String {
+        let mut usage = String::from(self.name);
+
+        for argument in self.arguments.iter() {
+            if argument.optional {
+                usage.push_str(&format!(" [{}]", argument.name));
+            } else {
+                usage.push_str(&format!(" <{}>", argument.name));
+            }
+        }
+
+        usage
+    }
+}
+
+/// Commands run from the console or the chat, by name.
+#[derive(Debug, Clone, Default)]
+pub struct CommandDispatcher {
+    commands: BTreeMap<&'static str, Command>,
+}
+
+impl CommandDispatcher {
+    /// A dispatcher with the built-in commands registered.
+    pub fn with_builtins() -> Self {
+        let mut dispatcher = Self::default();
+        builtin::register(&mut dispatcher);
+
+        dispatcher
+    }
+
+    /// Add a command. Text arguments take the rest of the line, so they must come last.
+    pub fn register(&mut self, command: Command) {
+        assert!(
+            !self.commands.contains_key(command.name),
+            "command {} is already registered",
+            command.name
+        );
+        assert!(
+            command
+                .arguments
+                .iter()
+                .rev()
+                .skip(1)
+                .all(|argument| argument.kind != ArgumentKind::Text),
+            "text arguments of command {} must come last",
+            command.name
+        );
+        assert!(
+            command
+                .arguments
+                .windows(2)
+                .all(|pair| !pair[0].optional || pair[1].optional),
+            "required arguments of command {} can't follow optional ones",
+            command.name
+        );
+
+        self.commands.insert(command.name, command);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name)
+    }
+
+    /// Commands the source is allowed to run, sorted by name.
+    pub fn available<'a>(&'a self, source: &'a CommandSource) -> impl Iterator<Item = &'a Command> {
+        self.commands
+            .values()
+            .filter(move |command| source.permission() >= command.permission)
+    }
+
+    /// Find the command of a line and parse its arguments, checking the source may run it. A
+    /// leading slash is ignored.
+    pub fn parse(
+        &self,
+        source: &CommandSource,
+        line: &str,
+    ) -> Result<(&Command, Arguments), CommandError> {
+        let line = line.trim();
+        let line = line.strip_prefix('/').unwrap_or(line);
+        let (name, mut rest) = next_word(line);
+
+        let command = self
+            .commands
+            .get(name)
+            .ok_or_else(|| CommandError::UnknownCommand(name.to_string()))?;
+
+        if source.permission() < command.permission {
+            return Err(CommandError::PermissionDenied);
+        }
+
+        let mut arguments = Arguments::default();
+
+        for argument in command.arguments.iter() {
+            let word = if argument.kind == ArgumentKind::Text {
+                let text = rest.trim();
+                rest = "";
+                text
+            } else {
+                let (word, remaining) = next_word(rest);
+                rest = remaining;
+                word
+            };
+
+            if word.is_empty() {
+                if argument.optional {
+                    break;
+                }
+
+                return Err(CommandError::MissingArgument {
+                    usage: command.usage(),
+                    argument: argument.name,
+                });
+            }
+
+            let invalid = || CommandError::InvalidArgument {
+                usage: command.usage(),
+                argument: argument.name,
+                value: word.to_string(),
+            };
+
+            let value = match argument.kind {
+                ArgumentKind::Player => Value::Player(word.to_string()),
+                ArgumentKind::Integer => Value::Integer(word.parse().map_err(|_| invalid())?),
+                ArgumentKind::Coordinate => Value::Coordinate(word.parse().map_err(|_| invalid())?),
+                ArgumentKind::Block => Value::Block(word.parse().map_err(|_| invalid())?),
+                ArgumentKind::World => Value::World(word.to_string()),
+                ArgumentKind::EntityType => Value::EntityType(word.parse().map_err(|_| invalid())?),
+                ArgumentKind::Choice(choices) => Value::Choice(
+                    choices
+                        .iter()
+                        .find(|choice| **choice == word)
+                        .ok_or_else(invalid)?,
+                ),
+                ArgumentKind::Text => Value::Text(word.to_string()),
+            };
+
+            arguments.values.insert(argument.name, value);
+        }
+
+        if !rest.trim().is_empty() {
+            return Err(CommandError::TooManyArguments {
+                usage: command.usage(),
+            });
+        }
+
+        Ok((command, arguments))
+    }
+
+    /// Parse and run a command line.
+    pub fn execute(&self, world: &mut World, source: &CommandSource, line: &str) -> CommandResult {
+        let (command, arguments) = self.parse(source, line)?;
+
+        let mut context = CommandContext {
+            world,
+            source,
+            dispatcher: self,
+        };
+        (command.handler)(&mut context, &arguments)
+    }
+
+    /// Suggestions to complete the last word of a partially typed line: command names for the
+    /// first word, then values fitting the type of the argument being typed.
+    pub fn complete(&self, world: &mut World, source: &CommandSource, line: &str) -> Vec<String> {
+        let line = line.trim_start();
+        let line = line.strip_prefix('/').unwrap_or(line);
+        let (name, mut rest) = next_word(line);
+
+        if rest.is_empty() {
+            return self
+                .available(source)
+                .map(|command| command.name)
+                .filter(|command| command.starts_with(name))
+                .map(String::from)
+                .collect();
+        }
+
+        let command = match self.get(name) {
+            Some(command) if source.permission() >= command.permission => command,
+            _ => return Vec::new(),
+        };
+
+        // Skip the finished words, the last one is being typed.
+        let mut index = 0;
+        let partial = loop {
+            let (word, remaining) = next_word(rest);
+            if remaining.is_empty() && !rest.ends_with(char::is_whitespace) {
+                break word;
+            }
+            if word.is_empty() {
+                break "";
+            }
+
+            index += 1;
+            rest = remaining;
+        };
+
+        let argument = match command.arguments.get(index) {
+            Some(argument) => argument,
+            None => return Vec::new(),
+        };
+
+        let candidates: BTreeSet<String> = match argument.kind {
+            ArgumentKind::Player => world
+                .query::<&Player>()
+                .iter(world)
+                .map(|player| player.name.name.clone())
+                .collect(),
+            ArgumentKind::Coordinate => BTreeSet::from([String::from("~")]),
+            ArgumentKind::Block => world
+                .get_resource::<BlockTypes>()
+                .map(|types| types.ids().map(Identifier::to_string).collect())
+                .unwrap_or_default(),
+            ArgumentKind::World => world
+                .get_resource::<Worlds>()
+                .map(|worlds| worlds.names().map(String::from).collect())
+                .unwrap_or_default(),
+            ArgumentKind::EntityType => world
+                .get_resource::<EntityTypes>()
+                .map(|types| types.ids().map(Identifier::to_string).collect())
+                .unwrap_or_default(),
+            ArgumentKind::Choice(choices) => choices.iter().copied().map(String::from).collect(),
+            ArgumentKind::Integer | ArgumentKind::Text => BTreeSet::new(),
+        };
+
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(partial))
+            .collect()
+    }
+}
+
+/// Split the first word of a string from the rest.
+fn next_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(end) => (&s[..end], &s[end..]),
+        None => (s, ""),
+    }
+}
+
+/// Reasons a command can't be run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    UnknownCommand(String),
+    /// The source is not allowed to run the command.
+    PermissionDenied,
+    MissingArgument {
+        usage: String,
+        argument: &'static str,
+    },
+    /// An argument can't be parsed as its type.
+    InvalidArgument {
+        usage: String,
+        argument: &'static str,
+        value: String,
+    },
+    TooManyArguments {
+        usage: String,
+    },
+    /// No player with this name is online.
+    PlayerNotFound(String),
+    /// No world with this name is hosted.
+    WorldNotFound(String),
+    /// The command was parsed but couldn't be run.
+    Failed(String),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::UnknownCommand(name) => {
+                write!(
+                    f,
+                    "Unknown command {name:?}, type help for a list of commands"
+                )
+            }
+            CommandError::PermissionDenied => {
+                write!(f, "You don't have permission to use this command")
+            }
+            CommandError::MissingArgument { usage, argument } => {
+                write!(f, "Missing {argument}. Usage: {usage}")
+            }
+            CommandError::InvalidArgument {
+                usage,
+                argument,
+                value,
+            } => write!(f, "Invalid {argument} {value:?}. Usage: {usage}"),
+            CommandError::TooManyArguments { usage } => {
+                write!(f, "Too many arguments. Usage: {usage}")
+            }
+            CommandError::PlayerNotFound(name) => write!(f, "Player {name} is not online"),
+            CommandError::WorldNotFound(name) => write!(f, "There is no world named {name}"),
+            CommandError::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Command lines sent by players, run at the start of the next tick.
+#[derive(Debug, Clone, Default)]
+pub struct PendingCommands {
+    pub commands: Vec<(CommandSource, String)>,
+}
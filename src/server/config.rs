@@ -0,0 +1,325 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use log::info;
+use rand_core::{OsRng, RngCore};
+use serde_derive::{Deserialize, Serialize};
+
+pub use crate::network::{MAX_MOTD_LENGTH, MAX_WORLD_NAME_LENGTH};
+use crate::server::accounts::AuthMode;
+use crate::server::{DEFAULT_TICK_RATE, MAIN_WORLD};
+use crate::world::generator::Generator;
+
+/// Default name of the configuration file, in the directory the server is started from.
+pub const CONFIG_FILE: &str = "server.toml";
+
+/// Maximum view distance in chunks.
+pub const MAX_VIEW_DISTANCE: u32 = 32;
+/// Maximum tick rate.
+pub const MAX_TICK_RATE: u32 = 1000;
+/// Bytes sent to each client every tick unless configured otherwise.
+pub const DEFAULT_SEND_BUDGET: u32 = 32 * 1024;
+/// Tracking range in blocks unless configured otherwise.
+pub const DEFAULT_TRACKING_RANGE: u32 = 48;
+
+/// Settings of a dedicated server, read from `server.toml`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the server listens on.
+    pub bind_address: IpAddr,
+    pub port: u16,
+    /// Maximum number of players connected at the same time.
+    pub max_players: u32,
+    /// Radius in chunks around each player that is loaded and sent to it.
+    pub view_distance: u32,
+    /// Number of ticks run every second.
+    pub tick_rate: u32,
+    /// Directory the world is saved in.
+    pub world_dir: PathBuf,
+    /// Seed of the world generator.
+    pub seed: i64,
+    /// Generator of the chunks of the main world.
+    pub generator: Generator,
+    /// Message of the day shown to players.
+    pub motd: String,
+    /// Only let whitelisted players join.
+    pub whitelist: bool,
+    /// Directory of the whitelist, operator and ban lists and of the player accounts.
+    pub lists_dir: PathBuf,
+    /// Whether players need an account to join.
+    pub auth: AuthMode,
+    /// Announce the server to players on the local network.
+    pub lan: bool,
+    /// Bytes sent to each client every tick at most. What doesn't fit waits for the next ticks,
+    /// chunks first.
+    pub send_budget: u32,
+    /// Horizontal distance in blocks within which players are sent the entities around them.
+    pub tracking_range: u32,
+    /// Let every player fly. Operators can fly either way.
+    pub allow_flight: bool,
+    /// Worlds hosted besides the main one.
+    pub worlds: Vec<WorldConfig>,
+}
+
+/// A world hosted besides the main one, in `[[worlds]]` tables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldConfig {
+    /// Name players and commands refer to the world by.
+    pub name: String,
+    #[serde(default)]
+    pub generator: Generator,
+    #[serde(default)]
+    pub seed: i64,
+    /// Directory the world is saved in, `worlds/<name>` in the directory of the main world
+    /// unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+impl WorldConfig {
+    /// Directory the world is saved in, given the directory of the main world.
+    pub fn dir(&self, world_dir: &Path) -> PathBuf {
+        match &self.dir {
+            Some(dir) => dir.clone(),
+            None => world_dir.join("worlds").join(&self.name),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 25000,
+            max_players: 20,
+            view_distance: 4,
+            tick_rate: DEFAULT_TICK_RATE,
+            world_dir: PathBuf::from("world"),
+            seed: 0,
+            generator: Generator::default(),
+            motd: String::from("A yave server"),
+            whitelist: false,
+            lists_dir: PathBuf::from("."),
+            auth: AuthMode::Offline,
+            lan: false,
+            send_budget: DEFAULT_SEND_BUDGET,
+            tracking_range: DEFAULT_TRACKING_RANGE,
+            allow_flight: false,
+            worlds: Vec::new(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Read the configuration file, creating it with the default settings and a random seed if
+    /// it doesn't exist.
+    pub fn load_or_create(path: &Path) -> Result<Self, ConfigError> {
+        if !path.exists() {
+            let config = Self {
+                seed: OsRng.next_u64() as i64,
+                ..Default::default()
+            };
+            config.save(path)?;
+
+            info!("Created {} with the default settings", path.display());
+
+            return Ok(config);
+        }
+
+        let config: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        fs::write(path, toml::to_string(self)?)?;
+
+        Ok(())
+    }
+
+    /// Override settings with command line flags, given without the program name. Every
+    /// argument must be a setting flag or the value of the flag before it.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), ConfigError> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .map(String::as_str)
+                    .ok_or_else(|| ConfigError::MissingValue(arg.clone()))
+            };
+
+            match arg.as_str() {
+                "--bind" => self.bind_address = parse_arg(arg, value()?)?,
+                "--port" => self.port = parse_arg(arg, value()?)?,
+                "--max-players" => self.max_players = parse_arg(arg, value()?)?,
+                "--view-distance" => self.view_distance = parse_arg(arg, value()?)?,
+                "--tick-rate" => self.tick_rate = parse_arg(arg, value()?)?,
+                "--world" => self.world_dir = PathBuf::from(value()?),
+                "--seed" => self.seed = parse_arg(arg, value()?)?,
+                "--generator" => self.generator = parse_arg(arg, value()?)?,
+                "--motd" => self.motd = value()?.to_string(),
+                "--whitelist" => self.whitelist = true,
+                "--no-whitelist" => self.whitelist = false,
+                "--lists-dir" => self.lists_dir = PathBuf::from(value()?),
+                "--auth" => self.auth = parse_arg(arg, value()?)?,
+                "--lan" => self.lan = true,
+                "--no-lan" => self.lan = false,
+                "--send-budget" => self.send_budget = parse_arg(arg, value()?)?,
+                "--tracking-range" => self.tracking_range = parse_arg(arg, value()?)?,
+                "--allow-flight" => self.allow_flight = true,
+                "--no-flight" => self.allow_flight = false,
+                _ => return Err(ConfigError::UnknownArgument(arg.clone())),
+            }
+        }
+
+        self.validate()
+    }
+
+    /// Check every setting is in its range.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field, reason: String| Err(ConfigError::Invalid { field, reason });
+
+        if self.max_players == 0 {
+            return invalid("max_players", String::from("must be at least 1"));
+        }
+
+        if self.view_distance == 0 || self.view_distance > MAX_VIEW_DISTANCE {
+            return invalid(
+                "view_distance",
+                format!("must be between 1 and {MAX_VIEW_DISTANCE}"),
+            );
+        }
+
+        if self.tick_rate == 0 || self.tick_rate > MAX_TICK_RATE {
+            return invalid(
+                "tick_rate",
+                format!("must be between 1 and {MAX_TICK_RATE}"),
+            );
+        }
+
+        if self.send_budget == 0 {
+            return invalid("send_budget", String::from("must be at least 1"));
+        }
+
+        if self.tracking_range == 0 {
+            return invalid("tracking_range", String::from("must be at least 1"));
+        }
+
+        if self.world_dir.as_os_str().is_empty() {
+            return invalid("world_dir", String::from("must not be empty"));
+        }
+
+        if self.lists_dir.as_os_str().is_empty() {
+            return invalid("lists_dir", String::from("must not be empty"));
+        }
+
+        if self.motd.len() > MAX_MOTD_LENGTH {
+            return invalid(
+                "motd",
+                format!("must be at most {MAX_MOTD_LENGTH} bytes long"),
+            );
+        }
+
+        let mut names = vec![MAIN_WORLD];
+        for world in self.worlds.iter() {
+            let name = world.name.as_str();
+
+            // Names are used in packets and in the default directories.
+            if name.is_empty()
+                || name.len() > MAX_WORLD_NAME_LENGTH
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return invalid(
+                    "worlds",
+                    format!(
+                        "names must be 1 to {MAX_WORLD_NAME_LENGTH} letters, digits, _ or -, \
+                         not {name:?}"
+                    ),
+                );
+            }
+
+            if names.contains(&name) {
+                return invalid("worlds", format!("has {name} more than once"));
+            }
+            names.push(name);
+        }
+
+        Ok(())
+    }
+
+    /// Address the server socket is bound to.
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+}
+
+fn parse_arg<T: FromStr>(flag: &str, value: &str) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidArgument {
+        flag: flag.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// Reasons the server configuration can't be used.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file can't be read or written.
+    Io(io::Error),
+    /// The configuration file is not valid TOML or has fields of the wrong type.
+    Parse(toml::de::Error),
+    /// The configuration can't be written as TOML.
+    Serialize(toml::ser::Error),
+    /// A setting is out of its range.
+    Invalid { field: &'static str, reason: String },
+    /// A command line flag is missing its value.
+    MissingValue(String),
+    /// The value of a command line flag can't be parsed.
+    InvalidArgument { flag: String, value: String },
+    /// A command line argument is not a known flag.
+    UnknownArgument(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{e}"),
+            ConfigError::Parse(e) => write!(f, "{e}"),
+            ConfigError::Serialize(e) => write!(f, "{e}"),
+            ConfigError::Invalid { field, reason } => write!(f, "{field} {reason}"),
+            ConfigError::MissingValue(flag) => write!(f, "{flag} needs a value"),
+            ConfigError::InvalidArgument { flag, value } => {
+                write!(f, "invalid value {value:?} for {flag}")
+            }
+            ConfigError::UnknownArgument(arg) => write!(f, "unknown argument {arg}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        ConfigError::Serialize(e)
+    }
+}
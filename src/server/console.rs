@@ -0,0 +1,68 @@
+use std::io;
+use std::io::BufRead;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use log::{error, info, warn};
+
+/// Read commands from the standard input on a separate thread and send them to the server, one
+/// line at a time. The thread ends with the input or when the server is gone.
+pub fn spawn(commands: Sender<String>) {
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    error!("Cannot read the console: {e}");
+                    return;
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if commands.send(line).is_err() {
+                return;
+            }
+        }
+
+        info!("Console closed");
+    });
+}
+
+/// Stop the server with a `stop` command when the process is interrupted (Ctrl+C) or, on Unix,
+/// asked to terminate. Must be called from within a Tokio runtime.
+pub fn stop_on_signal(commands: Sender<String>) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut terminate = match signal(SignalKind::terminate()) {
+                Ok(terminate) => terminate,
+                Err(e) => {
+                    error!("Cannot listen for the terminate signal: {e}");
+                    return;
+                }
+            };
+
+            tokio::select! {
+                result = tokio::signal::ctrl_c() => if let Err(e) = result {
+                    error!("Cannot listen for Ctrl+C: {e}");
+                    return;
+                },
+                _ = terminate.recv() => {}
+            }
+        }
+
+        #[cfg(not(unix))]
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Cannot listen for Ctrl+C: {e}");
+            return;
+        }
+
+        warn!("Interrupted, stopping the server");
+        let _ = commands.send(String::from("stop"));
+    });
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy_ecs::prelude::Component;
+use serde_derive::Deserialize;
+
+use crate::assets::Identifier;
+use crate::server::storage::read_toml;
+use crate::server::Position;
+use crate::world::generator::hash;
+
+/// Directory of the entity types in an asset namespace, one TOML file per type.
+pub const ENTITIES_DIR: &str = "entities";
+/// Horizontal distance in blocks wandering entities stay within from where they were spawned.
+pub const WANDER_RADIUS: f64 = 8.;
+
+/// What an entity does on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Behavior {
+    /// Stays where it is.
+    #[default]
+    Idle,
+    /// Walks to random points around where it was spawned.
+    Wander,
+}
+
+/// Description of an entity type, read from `entities/<name>.toml` in an asset namespace.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityDescription {
+    #[serde(default)]
+    pub behavior: Behavior,
+    /// Blocks per second.
+    #[serde(default)]
+    pub speed: f64,
+}
+
+/// Types of the entities that can be spawned, by id.
+#[derive(Debug, Clone, Default)]
+pub struct EntityTypes {
+    types: HashMap<Identifier, EntityDescription>,
+}
+
+impl EntityTypes {
+    /// Read the entity types of every namespace in the assets directory.
+    pub fn load(assets: &Path) -> io::Result<Self> {
+        let mut types = Self::default();
+
+        for namespace in fs::read_dir(assets)? {
+            let namespace = namespace?;
+            let dir = namespace.path().join(ENTITIES_DIR);
+            if !dir.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(dir)? {
+                let path = file?.path();
+                if path.extension().is_none_or(|extension| extension != "toml") {
+                    continue;
+                }
+
+                let id = Identifier::new(
+                    &namespace.file_name().to_string_lossy(),
+                    &path.file_stem().unwrap().to_string_lossy(),
+                );
+                types.register(id, read_toml(&path)?);
+            }
+        }
+
+        Ok(types)
+    }
+
+    /// Add a type, replacing the one with the same id.
+    pub fn register(&mut self, id: Identifier, description: EntityDescription) {
+        self.types.insert(id, description);
+    }
+
+    pub fn get(&self, id: &Identifier) -> Option<&EntityDescription> {
+        self.types.get(id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &Identifier> {
+        self.types.keys()
+    }
+}
+
+/// Type of an entity that is not a player.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct EntityKind(pub Identifier);
+
+/// An entity walking to random points around its home.
+#[derive(Debug, Clone, Component)]
+pub struct Wander {
+    pub home: Position,
+    pub target: Position,
+    /// Blocks per second.
+    pub speed: f64,
+}
+
+impl Wander {
+    pub fn new(home: Position, speed: f64) -> Self {
+        Self {
+            home,
+            target: home,
+            speed,
+        }
+    }
+
+    /// Pick the next point to walk to. It only depends on the entity and the tick, so replays
+    /// wander the same way.
+    pub fn retarget(&mut self, id: u32, tick: u64) {
+        let random = hash(id as i64, tick as i64, 0);
+        let angle = (random & 0xffff) as f64 / 65536. * TAU;
+        let distance = ((random >> 16) & 0xffff) as f64 / 65536. * WANDER_RADIUS;
+
+        self.target = Position {
+            x: self.home.x + distance * angle.cos(),
+            y: self.home.y,
+            z: self.home.z + distance * angle.sin(),
+        };
+    }
+}
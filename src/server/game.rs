@@ -1,28 +1,152 @@
-use crate::network::{split_socket, OnlinePlayer, Packet, SocketSender};
-use crate::server::{ClientEvent, Connection, Player, PlayerName, Position};
-use crate::world::chunk::Chunk;
-use crate::world::Chunks;
+use crate::assets::{Identifier, ASSETS_DIR};
+use crate::network::auth;
+use crate::network::compression::DEFAULT_THRESHOLD;
+use crate::network::crypto::{Handshake, Role};
+use crate::network::lan::{LanAnnouncer, ANNOUNCE_INTERVAL, LAN_GROUP};
+use crate::network::replay::Replay;
+use crate::network::snapshot::{EntityState, Snapshot};
+use crate::network::transport::{Transport, QUEUE_SIZE};
+use crate::network::{
+    split, OnlinePlayer, Packet, SocketSender, MAX_MESSAGE_LENGTH, MAX_NAME_LENGTH,
+    MAX_REASON_LENGTH, MAX_STATUS_SAMPLE, PROTOCOL_VERSION,
+};
+use crate::server::access::AccessLists;
+use crate::server::accounts::{Accounts, AuthMode};
+use crate::server::block::BlockTypes;
+use crate::server::command::{CommandDispatcher, CommandResult, CommandSource, PendingCommands};
+use crate::server::config::ServerConfig;
+use crate::server::entity::{Behavior, EntityKind, EntityTypes, Wander};
+use crate::server::queue::{Priority, SendQueue, MAX_QUEUED_CHUNKS};
+use crate::server::storage::PlayerData;
+use crate::server::tick::{TickScheduler, TickStats};
+use crate::server::worlds::Worlds;
+use crate::server::{
+    valid_name, Challenge, ChatLimiter, ClientEvent, Connection, InWorld, InputQueue, LoadedChunks,
+    Login, Logins, NetworkId, NetworkIds, PermissionLevel, Player, PlayerName, Position,
+    PreviousPosition, Rotation, Running, Snapshots, StatusLimiter, Tick, TickRate, TrackedEntities,
+    Velocity, VerticalVelocity, LOGIN_TIMEOUT, MAIN_WORLD, MAX_PENDING_SESSIONS,
+    MAX_SESSIONS_PER_IP, SPAWN_POSITION,
+};
+use crate::world::physics::Body;
 use bevy_ecs::event::Events;
-use bevy_ecs::prelude::{Commands, EventReader, Query, Res, Schedule, SystemStage, World};
-use bevy_ecs::schedule::Stage;
+use bevy_ecs::prelude::{
+    Commands, Entity, EventReader, Query, Res, Schedule, SystemStage, With, World,
+};
+use bevy_ecs::schedule::{ParallelSystemDescriptorCoercion, Stage};
 use bevy_ecs::system::ResMut;
-use log::info;
-use pollster::block_on;
-use std::net::UdpSocket;
+use log::{debug, error, info, warn};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender, TrySendError};
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 use std::{io, thread};
 
-pub struct Game;
+/// Maximum number of chunks queued for a client in one tick, so joining or moving fast doesn't stall
+/// the server generating chunks.
+const MAX_CHUNKS_PER_TICK: usize = 4;
+
+/// Components of the players used when handling the packets of their clients.
+type PlayerPackets<'a> = (
+    Entity,
+    &'a Player,
+    &'a NetworkId,
+    &'a Position,
+    &'a mut InputQueue,
+    &'a Connection,
+    &'a mut Snapshots,
+    &'a mut SendQueue,
+);
+
+/// Components of the players moved by their inputs.
+type PlayerBodies<'a> = (
+    &'a InWorld,
+    &'a PermissionLevel,
+    &'a mut InputQueue,
+    &'a mut Position,
+    &'a mut VerticalVelocity,
+    &'a mut Rotation,
+);
+
+/// Components of the entities sent in snapshots. Entities that are not players have a kind.
+type SnapshotEntities<'a> = (
+    &'a NetworkId,
+    &'a InWorld,
+    &'a Position,
+    &'a Rotation,
+    &'a Velocity,
+    Option<&'a EntityKind>,
+);
+
+/// Reason given to the clients when the server stops.
+const SHUTDOWN_REASON: &str = "The server is closed";
+
+/// A running server. Ticks are run at the configured rate by `run`, or one at a time by `step`.
+pub struct Game {
+    world: World,
+    schedule: Schedule,
+    scheduler: TickScheduler,
+    commands: CommandDispatcher,
+    console: Receiver<String>,
+    console_sender: Sender<String>,
+    /// Packets received since the last tick.
+    packets: Receiver<(Packet, SocketAddr)>,
+    /// Status as of the last tick, answered by the network thread.
+    status: Arc<Mutex<Packet>>,
+    /// Thread receiving packets, until the transport is closed.
+    network: JoinHandle<()>,
+}
 
 impl Game {
-    pub fn run(port: String) -> io::Result<()> {
-        let world = Arc::new(Mutex::new(World::new()));
+    /// Set up the world and start receiving packets from the transport.
+    pub fn new(transport: impl Transport + 'static, config: ServerConfig) -> Self {
+        let tick_rate = config.tick_rate;
 
-        world
-            .lock()
-            .unwrap()
-            .insert_resource(Events::<ClientEvent>::default());
+        let mut world = World::new();
+
+        world.insert_resource(Events::<ClientEvent>::default());
+
+        world.insert_resource(TickRate(tick_rate));
+        world.insert_resource(Worlds::new(&config));
+
+        // The server still starts without its lists, but nobody can be trusted with them broken.
+        let access = AccessLists::load(&config.lists_dir).unwrap_or_else(|e| {
+            error!("Cannot read the access lists, starting with empty ones: {e}");
+            AccessLists::new(&config.lists_dir)
+        });
+        world.insert_resource(access);
+
+        let accounts = Accounts::load(&config.lists_dir).unwrap_or_else(|e| {
+            error!("Cannot read the accounts, starting without any: {e}");
+            Accounts::new(&config.lists_dir)
+        });
+        world.insert_resource(accounts);
+
+        let block_types = BlockTypes::load(Path::new(ASSETS_DIR)).unwrap_or_else(|e| {
+            error!("Cannot read the block types, starting with air only: {e}");
+            BlockTypes::default()
+        });
+        world.insert_resource(block_types);
+
+        let entity_types = EntityTypes::load(Path::new(ASSETS_DIR)).unwrap_or_else(|e| {
+            error!("Cannot read the entity types, starting without any: {e}");
+            EntityTypes::default()
+        });
+        world.insert_resource(entity_types);
+
+        if config.lan {
+            match Game::lan_announcer(&transport, &config) {
+                Ok(announcer) => world.insert_resource(announcer),
+                Err(e) => warn!("Cannot announce the server on the local network: {e}"),
+            }
+        }
+
+        world.insert_resource(config);
+        world.insert_resource(PendingCommands::default());
+        world.insert_resource(Running(true));
 
         let mut setup_schedule = Schedule::default();
 
@@ -38,145 +162,823 @@ impl Game {
         main_schedule.add_stage(
             "main_loop",
             SystemStage::parallel()
-                .with_system(Game::handle_packets)
-                .with_system(Game::update_chunks),
+                .with_system(Game::handle_logins.label("logins"))
+                .with_system(Game::handle_packets.label("packets").after("logins"))
+                .with_system(Game::handle_chat)
+                // Players are simulated once the chunks around them are loaded.
+                .with_system(Game::simulate_players.after("packets").after("chunks"))
+                .with_system(Game::wander_entities)
+                .with_system(Game::update_chunks.label("chunks")),
         );
 
-        info!("Starting server on port 25000");
+        main_schedule.add_stage(
+            "snapshots",
+            SystemStage::parallel()
+                .with_system(Game::update_velocities.label("velocities"))
+                .with_system(Game::track_entities.label("tracking"))
+                .with_system(Game::send_snapshots.after("velocities").after("tracking"))
+                .with_system(Game::send_player_states)
+                .with_system(Game::announce_lan),
+        );
 
-        let socket = UdpSocket::bind(format!("0.0.0.0:{port}"))?;
+        main_schedule.add_stage(
+            "send",
+            SystemStage::parallel().with_system(Game::send_queued),
+        );
 
-        let (sender, mut receiver) = split_socket(socket);
+        if let Ok(addr) = transport.local_addr() {
+            info!("Starting server on {addr}");
+        }
 
-        world.lock().unwrap().insert_resource(sender);
+        let (sender, mut receiver) = split(transport);
 
-        let world_clone = world.clone();
+        let mut status_sender = sender.clone();
+        world.insert_resource(sender);
 
-        thread::spawn(|| {
-            block_on(async move {
-                let world = world_clone;
+        setup_schedule.run(&mut world);
+        let status = Arc::new(Mutex::new(Game::status(&mut world, 0)));
+        let current_status = status.clone();
 
-                loop {
-                    let mut data = vec![0u8; std::mem::size_of::<Packet>()];
-                    if let Ok((size, peer)) = receiver.recv_from(&mut data) {
-                        data.resize(size, 0);
-                        if let Ok(packet) = Packet::decode(data) {
-                            let mut world = world.lock().unwrap();
-                            let mut client_events =
-                                world.get_resource_mut::<Events<ClientEvent>>().unwrap();
-                            client_events.send(ClientEvent { packet, peer });
-                        }
+        // The network thread never touches the world, ticks take the packets it received from
+        // the channel.
+        let (packet_sender, packets) = mpsc::sync_channel(QUEUE_SIZE);
+        let mut status_limiter = StatusLimiter::default();
+        let network = thread::spawn(move || loop {
+            match receiver.recv_packet_from() {
+                // Status requests are answered right away, the client doesn't join.
+                Ok((Packet::StatusRequest { token }, peer)) => {
+                    if !status_limiter.allow(peer.ip(), Instant::now()) {
+                        debug!("Ignoring a status request from {peer}, it sends too many");
+                        continue;
                     }
+
+                    let mut status = current_status.lock().unwrap().clone();
+                    if let Packet::Status { token: answer, .. } = &mut status {
+                        *answer = token;
+                    }
+                    send_now(&mut status_sender, status, &peer);
                 }
-            })
+                Ok(received) => match packet_sender.try_send(received) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => debug!("Dropping a packet, too many are waiting"),
+                    Err(TrySendError::Disconnected(_)) => break,
+                },
+                Err(e) if e.kind() == ErrorKind::ConnectionAborted => break,
+                Err(e) => debug!("Dropping invalid packet: {e}"),
+            }
         });
 
-        let mut last_time = Instant::now();
+        let (console_sender, console) = mpsc::channel();
 
-        setup_schedule.run(&mut world.lock().unwrap());
+        Self {
+            world,
+            schedule: main_schedule,
+            scheduler: TickScheduler::new(tick_rate),
+            commands: CommandDispatcher::with_builtins(),
+            console,
+            console_sender,
+            packets,
+            status,
+            network,
+        }
+    }
 
-        loop {
-            main_schedule.run(&mut world.lock().unwrap());
+    /// Run ticks at the tick rate until the server is stopped, then shut it down. An error means
+    /// the world couldn't be saved.
+    pub fn run(mut self) -> io::Result<()> {
+        while self.running() {
+            self.scheduler.wait();
+            self.step();
+        }
 
-            // Run server loop 20 times a second.
-            while Instant::now() - last_time < Duration::from_secs_f64(1. / 20.) {
-                continue;
+        self.shutdown()
+    }
+
+    /// Disconnect every client, save the world and stop receiving packets.
+    pub fn shutdown(self) -> io::Result<()> {
+        let mut world = self.world;
+
+        let peers: Vec<SocketAddr> = world
+            .query_filtered::<&Connection, With<Player>>()
+            .iter(&world)
+            .map(|connection| connection.peer)
+            .collect();
+
+        let mut sender = world.get_resource_mut::<SocketSender>().unwrap();
+        for peer in peers {
+            let reason = String::from(SHUTDOWN_REASON);
+            send_now(&mut sender, Packet::Disconnect { reason }, &peer);
+        }
+
+        let saved = Game::save(&mut world);
+        match &saved {
+            Ok((chunks, players)) => info!("Saved {chunks} chunks and {players} players"),
+            Err(e) => error!("Cannot save the world: {e}"),
+        }
+
+        world.get_resource_mut::<SocketSender>().unwrap().close();
+
+        self.network
+            .join()
+            .map_err(|_| io::Error::other("the network thread panicked"))?;
+
+        saved.map(|_saved| ())
+    }
+
+    /// Run a single tick right away.
+    pub fn step(&mut self) {
+        let start = Instant::now();
+
+        let world = &mut self.world;
+        world.get_resource_mut::<Tick>().unwrap().0 += 1;
+
+        let mut client_events = world.get_resource_mut::<Events<ClientEvent>>().unwrap();
+        for (packet, peer) in self.packets.try_iter() {
+            client_events.send(ClientEvent { packet, peer });
+        }
+
+        // Commands typed since the last tick run before anything else.
+        let mut pending: Vec<(CommandSource, String)> = self
+            .console
+            .try_iter()
+            .map(|line| (CommandSource::Console, line))
+            .collect();
+        pending.append(
+            &mut world
+                .get_resource_mut::<PendingCommands>()
+                .unwrap()
+                .commands,
+        );
+
+        for (source, line) in pending {
+            if let CommandSource::Player { name, .. } = &source {
+                info!("{name} issued server command: /{line}");
+            }
+
+            let result = self.commands.execute(world, &source, &line);
+            Game::reply(world, &source, result);
+        }
+
+        self.schedule.run(world);
+        *self.status.lock().unwrap() = Game::status(world, 0);
+
+        self.scheduler.finish_tick(start.elapsed());
+        world.insert_resource(self.scheduler.stats.clone());
+    }
+
+    /// Run ticks until every packet of a replay was received, releasing before each tick the
+    /// packets recorded during it. Returns the number of ticks run.
+    pub fn replay(&mut self, replay: &Replay) -> u64 {
+        let tick_rate = self.world.get_resource::<TickRate>().unwrap().0;
+        let interval = Duration::from_secs(1) / tick_rate;
+
+        let mut ticks = 0;
+        while !replay.finished() {
+            ticks += 1;
+            replay.advance(interval * ticks as u32);
+            self.step();
+        }
+
+        ticks
+    }
+
+    /// Number of ticks run so far.
+    pub fn tick(&self) -> u64 {
+        self.world.get_resource::<Tick>().unwrap().0
+    }
+
+    /// Durations of the last ticks.
+    pub fn stats(&self) -> &TickStats {
+        &self.scheduler.stats
+    }
+
+    /// Whether the server keeps running, until it is stopped.
+    pub fn running(&self) -> bool {
+        self.world.get_resource::<Running>().unwrap().0
+    }
+
+    /// Sender of the command lines typed in the console, run at the start of every tick.
+    pub fn console(&self) -> Sender<String> {
+        self.console_sender.clone()
+    }
+
+    /// Run a command right away as the console.
+    pub fn execute(&mut self, line: &str) -> CommandResult {
+        self.commands
+            .execute(&mut self.world, &CommandSource::Console, line)
+    }
+
+    /// Suggestions to complete a partially typed console command.
+    pub fn complete(&mut self, line: &str) -> Vec<String> {
+        self.commands
+            .complete(&mut self.world, &CommandSource::Console, line)
+    }
+
+    /// Commands that can be run from the console and the chat.
+    pub fn commands(&mut self) -> &mut CommandDispatcher {
+        &mut self.commands
+    }
+
+    /// Show the result of a command to whoever ran it, in the log for the console.
+    fn reply(world: &mut World, source: &CommandSource, result: CommandResult) {
+        match (source, result) {
+            (CommandSource::Console, Ok(message)) => {
+                for line in message.lines() {
+                    info!("{line}");
+                }
+            }
+            (CommandSource::Console, Err(e)) => warn!("{e}"),
+            (CommandSource::Player { entity, .. }, Ok(message)) => {
+                for line in message.lines() {
+                    Game::send_message(world, *entity, line);
+                }
             }
-            last_time = Instant::now();
+            (CommandSource::Player { entity, .. }, Err(e)) => {
+                Game::send_message(world, *entity, &e.to_string())
+            }
+        }
+    }
+
+    /// Status of the server, sent back to a status request.
+    pub fn status(world: &mut World, token: u64) -> Packet {
+        let mut names: Vec<String> = world
+            .query::<&Player>()
+            .iter(world)
+            .map(|player| player.name.name.clone())
+            .collect();
+        names.sort();
+
+        let config = world.get_resource::<ServerConfig>().unwrap();
+
+        Packet::Status {
+            token,
+            protocol: PROTOCOL_VERSION,
+            motd: config.motd.clone(),
+            online: names.len() as u32,
+            max_players: config.max_players,
+            sample: names.into_iter().take(MAX_STATUS_SAMPLE).collect(),
+        }
+    }
+
+    /// Announcer sending to the network of the bind address, or the default one when the server
+    /// listens on every address.
+    fn lan_announcer(
+        transport: &impl Transport,
+        config: &ServerConfig,
+    ) -> io::Result<LanAnnouncer> {
+        let interface = match config.bind_address {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+        };
+
+        LanAnnouncer::new(interface, LAN_GROUP, transport.local_addr()?.port())
+    }
+
+    /// Find an online player by name.
+    pub fn find_player(world: &mut World, name: &str) -> Option<Entity> {
+        world
+            .query::<(Entity, &Player)>()
+            .iter(world)
+            .find(|(_entity, player)| player.name.name.eq_ignore_ascii_case(name))
+            .map(|(entity, _player)| entity)
+    }
+
+    /// Send a system message to a player. Messages that don't fit in a packet are cut.
+    pub fn send_message(world: &mut World, entity: Entity, message: &str) {
+        if let Some(mut queue) = world.get_mut::<SendQueue>(entity) {
+            let message = truncate(message, MAX_MESSAGE_LENGTH).to_string();
+            queue.push(Packet::SystemMessage { message }, Priority::Message);
+        }
+    }
+
+    /// Send a system message to every player.
+    pub fn broadcast(world: &mut World, message: &str) {
+        let players: Vec<Entity> = world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(world)
+            .collect();
+
+        for entity in players {
+            Game::send_message(world, entity, message);
+        }
+    }
+
+    /// Tell a client why it is disconnected, then remove its player. Packets still queued for it
+    /// are dropped.
+    pub fn disconnect(world: &mut World, entity: Entity, reason: &str) {
+        let peer = match world.get::<Connection>(entity) {
+            Some(connection) => connection.peer,
+            None => return,
+        };
+
+        let packet = Packet::Disconnect {
+            reason: truncate(reason, MAX_REASON_LENGTH).to_string(),
+        };
+        send_now(
+            &mut world.get_resource_mut::<SocketSender>().unwrap(),
+            packet,
+            &peer,
+        );
+
+        Game::remove_player(world, entity, reason);
+    }
+
+    /// Save a player that left and remove it from the world, telling the others it left.
+    pub fn remove_player(world: &mut World, entity: Entity, reason: &str) {
+        let (name, peer) = match (world.get::<Player>(entity), world.get::<Connection>(entity)) {
+            (Some(player), Some(connection)) => (player.name.name.clone(), connection.peer),
+            _ => return,
+        };
+
+        if let Err(e) = Game::save_player(world, entity) {
+            warn!("Cannot save player {name}: {e}");
+        }
+
+        let mut sender = world.get_resource_mut::<SocketSender>().unwrap();
+        sender.set_session(peer, None);
+        sender.set_compression(peer, None);
+
+        world.despawn(entity);
+
+        Game::broadcast(world, &format!("{name} left the game"));
+
+        info!("Player {name} disconnected: {reason}");
+    }
+
+    /// Move a player to the spawn of another world. The chunks of the world it leaves are
+    /// dropped by its client, the ones around the spawn are sent from the next tick.
+    pub fn move_to_world(world: &mut World, entity: Entity, name: &str) {
+        let mut player = world.entity_mut(entity);
+
+        player.insert(InWorld(name.to_string()));
+        player.insert(SPAWN_POSITION);
+        player.insert(PreviousPosition(SPAWN_POSITION));
+        player.insert(VerticalVelocity::default());
+        player.insert(LoadedChunks::default());
+        player.insert(TrackedEntities::default());
+
+        if let Some(mut queue) = player.get_mut::<SendQueue>() {
+            queue.clear_chunks();
+            let name = name.to_string();
+            queue.push(Packet::ChangeWorld { name }, Priority::Block);
+        }
+    }
+
+    /// Spawn an entity of a registered type in a world. Returns its network id, or None if
+    /// there is no such type. Entities are not saved, they are gone when the server stops.
+    pub fn spawn_entity(
+        world: &mut World,
+        kind: &Identifier,
+        world_name: &str,
+        position: Position,
+    ) -> Option<NetworkId> {
+        let description = world
+            .get_resource::<EntityTypes>()
+            .unwrap()
+            .get(kind)?
+            .clone();
+        let id = world.get_resource_mut::<NetworkIds>().unwrap().allocate();
+
+        let mut entity = world.spawn();
+        entity
+            .insert(id)
+            .insert(EntityKind(kind.clone()))
+            .insert(InWorld(world_name.to_string()))
+            .insert(position)
+            .insert(PreviousPosition(position))
+            .insert(Rotation::default())
+            .insert(Velocity::default());
+
+        if description.behavior == Behavior::Wander {
+            entity.insert(Wander::new(position, description.speed));
+        }
+
+        Some(id)
+    }
+
+    /// Remove an entity that is not a player. Returns false if there is none with this id. The
+    /// clients tracking it are told in the next tick.
+    pub fn despawn_entity(world: &mut World, id: u32) -> bool {
+        let entity = world
+            .query_filtered::<(Entity, &NetworkId), With<EntityKind>>()
+            .iter(world)
+            .find(|(_entity, network_id)| network_id.0 == id)
+            .map(|(entity, _network_id)| entity);
+
+        match entity {
+            Some(entity) => world.despawn(entity),
+            None => false,
+        }
+    }
+
+    /// Write every loaded chunk of every world and online player to disk. Returns how many
+    /// chunks and players were saved.
+    pub fn save(world: &mut World) -> io::Result<(usize, usize)> {
+        let mut saved_chunks = 0;
+        for game_world in world.get_resource_mut::<Worlds>().unwrap().iter_mut() {
+            saved_chunks += game_world.save()?;
         }
+
+        let players: Vec<Entity> = world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(world)
+            .collect();
+        for entity in players.iter() {
+            Game::save_player(world, *entity)?;
+        }
+
+        Ok((saved_chunks, players.len()))
+    }
+
+    fn save_player(world: &mut World, entity: Entity) -> io::Result<()> {
+        let (player, position, rotation, in_world) = match (
+            world.get::<Player>(entity),
+            world.get::<Position>(entity),
+            world.get::<Rotation>(entity),
+            world.get::<InWorld>(entity),
+        ) {
+            (Some(player), Some(position), Some(rotation), Some(in_world)) => {
+                (player, position, rotation, in_world)
+            }
+            _ => return Ok(()),
+        };
+
+        world
+            .get_resource::<Worlds>()
+            .unwrap()
+            .players()
+            .save_player(
+                &player.name.name,
+                &PlayerData {
+                    position: [position.x, position.y, position.z],
+                    yaw: rotation.yaw,
+                    pitch: rotation.pitch,
+                    world: in_world.0.clone(),
+                },
+            )
     }
 
     pub fn setup(mut commands: Commands) {
-        commands.insert_resource(Chunks::default());
+        commands.insert_resource(NetworkIds::default());
+        commands.insert_resource(Tick::default());
+        commands.insert_resource(Logins::default());
+    }
+
+    /// Decide whether connecting clients may join, challenging them to prove they own their
+    /// account if the server has accounts. Clients that pass join in `handle_packets`.
+    pub fn handle_logins(
+        mut events: EventReader<ClientEvent>,
+        players: Query<(&Player, &Connection)>,
+        mut logins: ResMut<Logins>,
+        mut sender: ResMut<SocketSender>,
+        tick: Res<Tick>,
+        (config, access, accounts): (Res<ServerConfig>, Res<AccessLists>, Res<Accounts>),
+    ) {
+        let timeout = LOGIN_TIMEOUT * config.tick_rate as u64;
+        let expired: Vec<SocketAddr> = logins
+            .challenges
+            .iter()
+            .filter(|(_peer, challenge)| tick.0 >= challenge.tick + timeout)
+            .map(|(peer, _challenge)| *peer)
+            .collect();
+        for peer in expired {
+            let challenge = logins.challenges.remove(&peer).unwrap();
+            refuse(&mut sender, &challenge.login, "Login timed out");
+        }
+
+        // Sessions are only kept for clients that go on to log in.
+        let expired: Vec<SocketAddr> = logins
+            .sessions
+            .iter()
+            .filter(|(_peer, started)| tick.0 >= *started + timeout)
+            .map(|(peer, _started)| *peer)
+            .collect();
+        for peer in expired {
+            logins.sessions.remove(&peer);
+            sender.set_session(peer, None);
+        }
+
+        for event in events.iter() {
+            match &event.packet {
+                Packet::Connection { user, compression } => {
+                    // A client can only join once.
+                    if players
+                        .iter()
+                        .any(|(_player, connection)| connection.peer == event.peer)
+                    {
+                        continue;
+                    }
+                    logins.sessions.remove(&event.peer);
+
+                    let login = Login {
+                        peer: event.peer,
+                        user: user.clone(),
+                        compression: *compression,
+                    };
+
+                    if let Err(reason) = check_login(&login, &players, &logins, &config, &access) {
+                        refuse(&mut sender, &login, &reason);
+                        continue;
+                    }
+
+                    if config.auth == AuthMode::Offline {
+                        logins.accepted.push(login);
+                        continue;
+                    }
+
+                    // Names without an account are challenged too, and fail like a wrong password.
+                    let (salt, iterations) = accounts.challenge(user);
+                    let nonce = auth::random();
+                    let challenge = Packet::AuthChallenge {
+                        salt,
+                        iterations,
+                        nonce,
+                    };
+                    send_now(&mut sender, challenge, &event.peer);
+
+                    logins.challenges.insert(
+                        event.peer,
+                        Challenge {
+                            login,
+                            nonce,
+                            tick: tick.0,
+                        },
+                    );
+                }
+                Packet::AuthResponse { proof } => {
+                    let challenge = match logins.challenges.remove(&event.peer) {
+                        Some(challenge) => challenge,
+                        None => continue,
+                    };
+                    let login = challenge.login;
+
+                    let verified = accounts.get(&login.user).is_some_and(|account| {
+                        account.verify(&challenge.nonce, &login.user, proof)
+                    });
+                    if !verified {
+                        refuse(&mut sender, &login, "Wrong name or password");
+                        continue;
+                    }
+
+                    // Someone else could have joined with the name while the client answered.
+                    if let Err(reason) = check_login(&login, &players, &logins, &config, &access) {
+                        refuse(&mut sender, &login, &reason);
+                        continue;
+                    }
+
+                    logins.accepted.push(login);
+                }
+                // Players are removed with their session in `handle_packets`.
+                Packet::Disconnect { .. }
+                    if players
+                        .iter()
+                        .all(|(_player, connection)| connection.peer != event.peer) =>
+                {
+                    logins.challenges.remove(&event.peer);
+                    logins.sessions.remove(&event.peer);
+                    sender.set_session(event.peer, None);
+                }
+                _ => (),
+            }
+        }
     }
 
     pub fn handle_packets(
         mut commands: Commands,
         mut events: EventReader<ClientEvent>,
-        mut players: Query<(&Player, &mut Position, &Connection)>,
+        mut players: Query<PlayerPackets>,
         mut sender: ResMut<SocketSender>,
-        chunks: Res<Chunks>,
+        mut network_ids: ResMut<NetworkIds>,
+        mut logins: ResMut<Logins>,
+        (config, worlds, access, tick): (
+            Res<ServerConfig>,
+            Res<Worlds>,
+            Res<AccessLists>,
+            Res<Tick>,
+        ),
     ) {
+        // Clients let in by `handle_logins` join first.
+        let accepted = std::mem::take(&mut logins.accepted);
+        let joining: HashSet<SocketAddr> = accepted.iter().map(|login| login.peer).collect();
+        for Login {
+            peer,
+            user,
+            compression,
+        } in accepted
+        {
+            // Ask for compression before anything else is sent. The packets of the login are
+            // sent right away, the queue starts once the player exists.
+            if compression {
+                let threshold = DEFAULT_THRESHOLD;
+                send_now(&mut sender, Packet::Compression { threshold }, &peer);
+                sender.set_compression(peer, Some(DEFAULT_THRESHOLD));
+            }
+
+            let id = network_ids.allocate();
+
+            let welcome = Packet::Welcome {
+                id: id.0,
+                tick_rate: config.tick_rate,
+            };
+            send_now(&mut sender, welcome, &peer);
+
+            let mut online_players = Vec::new();
+            let joined = format!("{user} joined the game");
+
+            for (
+                _entity,
+                player,
+                player_id,
+                position,
+                _inputs,
+                _connection,
+                _snapshots,
+                mut queue,
+            ) in players.iter_mut()
+            {
+                let name = user.clone();
+                queue.push(Packet::PlayerJoined { id: id.0, name }, Priority::Message);
+                let message = joined.clone();
+                queue.push(Packet::SystemMessage { message }, Priority::Message);
+
+                online_players.push(OnlinePlayer {
+                    id: player_id.0,
+                    name: player.name.name.clone(),
+                    x: position.x as f32,
+                    y: position.y as f32,
+                    z: position.z as f32,
+                });
+            }
+
+            let players = Packet::OnlinePlayers {
+                players: online_players,
+            };
+            send_now(&mut sender, players, &peer);
+
+            let data = worlds.players().load_player(&user).unwrap_or_else(|e| {
+                warn!("Cannot read the saved data of player {user}: {e}");
+                None
+            });
+
+            let (position, rotation, in_world) = match data {
+                // The world of a player can be gone from the configuration since it left.
+                Some(data) if worlds.get(&data.world).is_some() => (
+                    Position {
+                        x: data.position[0],
+                        y: data.position[1],
+                        z: data.position[2],
+                    },
+                    Rotation {
+                        yaw: data.yaw,
+                        pitch: data.pitch,
+                    },
+                    data.world,
+                ),
+                Some(data) => {
+                    warn!(
+                        "Player {user} was in world {}, which is not hosted anymore",
+                        data.world
+                    );
+                    (
+                        SPAWN_POSITION,
+                        Rotation::default(),
+                        String::from(MAIN_WORLD),
+                    )
+                }
+                None => (
+                    SPAWN_POSITION,
+                    Rotation::default(),
+                    String::from(MAIN_WORLD),
+                ),
+            };
+
+            let mut queue = SendQueue::default();
+            queue.push(Packet::SystemMessage { message: joined }, Priority::Message);
+
+            commands
+                .spawn()
+                .insert(Player {
+                    name: PlayerName { name: user.clone() },
+                })
+                .insert(id)
+                .insert(InWorld(in_world))
+                .insert(position)
+                .insert(PreviousPosition(position))
+                .insert(rotation)
+                .insert(Velocity::default())
+                .insert(VerticalVelocity::default())
+                .insert(access.permission(&user))
+                .insert(ChatLimiter::default())
+                .insert(InputQueue::default())
+                .insert(Snapshots::default())
+                .insert(LoadedChunks::default())
+                .insert(TrackedEntities::default())
+                .insert(Connection { peer })
+                .insert(queue);
+
+            info!("Player {user} connected.");
+        }
+
         for event in events.iter() {
             match &event.packet {
-                Packet::Connection { user } => {
-                    let mut online_players = Vec::new();
-                    for (player, position, connection) in players.iter() {
-                        sender
-                            .send_to(event.packet.clone(), &connection.peer)
-                            .unwrap();
-
-                        online_players.push(OnlinePlayer {
-                            name: player.name.name.clone(),
-                            x: position.x as f32,
-                            y: position.y as f32,
-                            z: position.z as f32,
-                        });
+                Packet::KeyExchange { public_key } => {
+                    // A session can't be replaced, or anyone could take over the address of a
+                    // client. Only the transport drops plaintext datagrams of peers with a
+                    // session, so encrypted key exchanges are ignored here.
+                    let logged_in = joining.contains(&event.peer)
+                        || players
+                            .iter()
+                            .any(|(_, _, _, _, _, connection, _, _)| connection.peer == event.peer);
+                    if logged_in
+                        || logins.sessions.contains_key(&event.peer)
+                        || sender.has_session(&event.peer)
+                    {
+                        continue;
                     }
 
-                    sender
-                        .send_to(
-                            Packet::OnlinePlayers {
-                                players: online_players,
-                            },
-                            &event.peer,
+                    // Every session costs a key exchange and is kept until it times out, so
+                    // only a few clients can have one without being logged in.
+                    let pending: Vec<IpAddr> = logins
+                        .sessions
+                        .keys()
+                        .chain(
+                            logins
+                                .challenges
+                                .keys()
+                                .filter(|peer| sender.has_session(peer)),
                         )
-                        .unwrap();
-
-                    commands
-                        .spawn()
-                        .insert(Player {
-                            name: PlayerName { name: user.clone() },
-                        })
-                        .insert(Position {
-                            x: 0.,
-                            y: 0.,
-                            z: 10.,
-                        })
-                        .insert(Connection { peer: event.peer });
-
-                    for chunk in chunks.chunks.iter() {
-                        sender
-                            .send_to(
-                                Packet::Chunk {
-                                    x: chunk.x,
-                                    y: chunk.y,
-                                    groups: chunk.compress(),
-                                },
-                                &event.peer,
-                            )
-                            .unwrap();
+                        .map(SocketAddr::ip)
+                        .collect();
+                    let from_ip = pending.iter().filter(|ip| **ip == event.peer.ip()).count();
+                    if pending.len() >= MAX_PENDING_SESSIONS || from_ip >= MAX_SESSIONS_PER_IP {
+                        debug!("Ignoring a key exchange from {}, too many sessions are waiting for a login", event.peer);
+                        continue;
                     }
+                    logins.sessions.insert(event.peer, tick.0);
 
-                    info!("Player {user} connected.");
+                    // The session starts before the reply is sent, the client can answer with
+                    // encrypted packets as soon as it gets it. The reply itself is not encrypted.
+                    let handshake = Handshake::new();
+                    let reply = Packet::KeyExchange {
+                        public_key: handshake.public_key(),
+                    };
+                    sender.set_session(
+                        event.peer,
+                        Some(handshake.finish(*public_key, Role::Server)),
+                    );
+                    send_now(&mut sender, reply, &event.peer);
+                }
+                Packet::Disconnect { reason } => {
+                    for (
+                        entity,
+                        _player,
+                        _id,
+                        _position,
+                        _inputs,
+                        connection,
+                        _snapshots,
+                        _queue,
+                    ) in players.iter()
+                    {
+                        if connection.peer == event.peer {
+                            let reason = reason.clone();
+                            commands.add(move |world: &mut World| {
+                                Game::remove_player(world, entity, &reason)
+                            });
+                        }
+                    }
                 }
-                Packet::Movement {
-                    delta_x,
-                    delta_y,
-                    delta_z,
-                } => {
-                    for (_player, mut position, connection) in players.iter_mut() {
+                Packet::Input { sequence, input } => {
+                    for (
+                        _entity,
+                        _player,
+                        _id,
+                        _position,
+                        mut inputs,
+                        connection,
+                        _snapshots,
+                        _queue,
+                    ) in players.iter_mut()
+                    {
                         if connection.peer == event.peer {
-                            position.x = *delta_x;
-                            position.y = *delta_y;
-                            position.z = *delta_z;
+                            inputs.push(*sequence, *input, tick.0, config.tick_rate);
                         }
                     }
                 }
-                Packet::PositionRequest { name } => {
-                    for (player, position, _connection) in players.iter_mut() {
-                        if player.name.name == name.clone() {
-                            sender
-                                .send_to(
-                                    Packet::PlayerPosition {
-                                        x: position.x,
-                                        y: position.y,
-                                        z: position.z,
-                                        name: name.clone(),
-                                    },
-                                    &event.peer,
-                                )
-                                .unwrap();
+                Packet::SnapshotAck { tick } => {
+                    for (
+                        _entity,
+                        _player,
+                        _id,
+                        _position,
+                        _inputs,
+                        connection,
+                        mut snapshots,
+                        _queue,
+                    ) in players.iter_mut()
+                    {
+                        if connection.peer == event.peer {
+                            snapshots.history.acknowledge(*tick);
                         }
                     }
                 }
@@ -185,71 +987,467 @@ impl Game {
         }
     }
 
-    pub fn update_chunks(
-        mut chunks: ResMut<Chunks>,
-        players: Query<(&Player, &Position, &Connection)>,
-        mut sender: ResMut<SocketSender>,
+    /// Broadcast the chat messages of the players and queue the commands they typed, which are
+    /// run at the start of the next tick. Players sending too many messages are told to slow down.
+    pub fn handle_chat(
+        mut events: EventReader<ClientEvent>,
+        mut players: Query<(
+            Entity,
+            &Player,
+            &Connection,
+            &PermissionLevel,
+            &mut ChatLimiter,
+            &mut SendQueue,
+        )>,
+        mut pending: ResMut<PendingCommands>,
+        tick: Res<Tick>,
+        tick_rate: Res<TickRate>,
+    ) {
+        let mut messages = Vec::new();
+
+        for event in events.iter() {
+            let (line, command) = match &event.packet {
+                Packet::Command { line } => (line.as_str(), true),
+                Packet::ChatMessage { message } => match message.strip_prefix('/') {
+                    Some(line) => (line, true),
+                    None => (message.as_str(), false),
+                },
+                _ => continue,
+            };
+
+            let source = players.iter_mut().find(
+                |(_entity, _player, connection, _permission, _limiter, _queue)| {
+                    connection.peer == event.peer
+                },
+            );
+
+            let (entity, player, _connection, permission, mut limiter, mut queue) = match source {
+                Some(source) => source,
+                None => continue,
+            };
+
+            if !limiter.allow(tick.0, tick_rate.0) {
+                let message = String::from("You are sending messages too fast");
+                queue.push(Packet::SystemMessage { message }, Priority::Message);
+                continue;
+            }
+
+            if command {
+                pending.commands.push((
+                    CommandSource::Player {
+                        entity,
+                        name: player.name.name.clone(),
+                        permission: *permission,
+                    },
+                    line.to_string(),
+                ));
+                continue;
+            }
+
+            // Control characters could mess with the chat of other players or the server log.
+            let message: String = line.chars().filter(|c| !c.is_control()).collect();
+            let message = message.trim();
+            if message.is_empty() {
+                continue;
+            }
+
+            info!("<{}> {message}", player.name.name);
+            messages.push((player.name.name.clone(), message.to_string()));
+        }
+
+        for (name, message) in messages {
+            for (_entity, _player, _connection, _permission, _limiter, mut queue) in
+                players.iter_mut()
+            {
+                let chat = Packet::Chat {
+                    sender: name.clone(),
+                    message: message.clone(),
+                };
+                queue.push(chat, Priority::Message);
+            }
+        }
+    }
+
+    /// Move the players according to the inputs their clients sent and the blocks of their
+    /// world. Players that are not allowed to fly walk whatever their client asks for.
+    pub fn simulate_players(
+        tick: Res<Tick>,
+        tick_rate: Res<TickRate>,
+        config: Res<ServerConfig>,
+        worlds: Res<Worlds>,
+        mut players: Query<PlayerBodies, With<Player>>,
+    ) {
+        for (in_world, permission, mut inputs, mut position, mut velocity, mut rotation) in
+            players.iter_mut()
+        {
+            let can_fly = config.allow_flight || *permission >= PermissionLevel::Operator;
+
+            let game_world = match worlds.get(&in_world.0) {
+                Some(game_world) => game_world,
+                None => continue,
+            };
+
+            let mut body = Body {
+                position: [position.x, position.y, position.z],
+                velocity: velocity.0,
+            };
+            inputs.refill(tick.0, tick_rate.0);
+            while let Some(input) = inputs.pop() {
+                let mut input = input.clamped();
+                input.flying &= can_fly;
+                body.step(&input, &game_world.chunks);
+                rotation.yaw = input.yaw;
+                rotation.pitch = input.pitch;
+            }
+
+            let [x, y, z] = body.position;
+            *position = Position { x, y, z };
+            velocity.0 = body.velocity;
+        }
+    }
+
+    /// Walk the wandering entities toward their target, picking a new one once they reach it.
+    pub fn wander_entities(
+        tick: Res<Tick>,
+        tick_rate: Res<TickRate>,
+        mut entities: Query<(&NetworkId, &mut Position, &mut Rotation, &mut Wander)>,
+    ) {
+        for (id, mut position, mut rotation, mut wander) in entities.iter_mut() {
+            let step = wander.speed / tick_rate.0 as f64;
+            let (dx, dz) = (wander.target.x - position.x, wander.target.z - position.z);
+            let distance = (dx * dx + dz * dz).sqrt();
+
+            if distance <= step {
+                position.x = wander.target.x;
+                position.z = wander.target.z;
+                wander.retarget(id.0, tick.0);
+                continue;
+            }
+
+            position.x += dx / distance * step;
+            position.z += dz / distance * step;
+            rotation.yaw = dz.atan2(dx) as f32;
+        }
+    }
+
+    /// Tell every client about the entities coming within the tracking range of its player and
+    /// the ones leaving it or removed. Players are not tracked, clients know every player.
+    pub fn track_entities(
+        config: Res<ServerConfig>,
+        entities: Query<(&NetworkId, &EntityKind, &InWorld, &Position)>,
+        mut players: Query<
+            (&InWorld, &Position, &mut TrackedEntities, &mut SendQueue),
+            With<Player>,
+        >,
     ) {
-        let mut to_unload = vec![];
+        let range = config.tracking_range as f64;
+
+        for (in_world, position, mut tracked, mut queue) in players.iter_mut() {
+            let mut in_range = HashSet::new();
 
-        for (i, chunk) in chunks.chunks.iter().enumerate() {
-            let mut unload = true;
-            for (_player, position, _connection) in players.iter() {
-                if position.x as i64 / 16 == chunk.x && position.z as i64 / 16 == chunk.y {
-                    unload = false;
+            for (id, kind, entity_world, entity_position) in entities.iter() {
+                let (dx, dz) = (
+                    entity_position.x - position.x,
+                    entity_position.z - position.z,
+                );
+                if entity_world != in_world || dx * dx + dz * dz > range * range {
+                    continue;
                 }
 
-                if unload {
-                    to_unload.push(i);
+                in_range.insert(id.0);
+                if tracked.entities.insert(id.0) {
+                    let spawn = Packet::SpawnEntity {
+                        id: id.0,
+                        kind: kind.0.to_string(),
+                        x: entity_position.x,
+                        y: entity_position.y,
+                        z: entity_position.z,
+                    };
+                    queue.push(spawn, Priority::Block);
                 }
             }
+
+            let gone: Vec<u32> = tracked.entities.difference(&in_range).copied().collect();
+            for id in gone {
+                tracked.entities.remove(&id);
+                queue.push(Packet::DespawnEntity { id }, Priority::Block);
+            }
+        }
+    }
+
+    /// Send every client the authoritative position of its player, so it can correct its
+    /// prediction.
+    pub fn send_player_states(
+        mut players: Query<(&Position, &VerticalVelocity, &InputQueue, &mut SendQueue)>,
+    ) {
+        for (position, velocity, inputs, mut queue) in players.iter_mut() {
+            let state = Packet::PlayerState {
+                sequence: inputs.processed,
+                x: position.x,
+                y: position.y,
+                z: position.z,
+                velocity: velocity.0,
+            };
+            queue.push(state, Priority::Movement);
         }
+    }
 
-        let mut connections = Vec::new();
+    /// Announce the server on the local network every `ANNOUNCE_INTERVAL`, when enabled.
+    pub fn announce_lan(
+        announcer: Option<Res<LanAnnouncer>>,
+        tick: Res<Tick>,
+        tick_rate: Res<TickRate>,
+        config: Res<ServerConfig>,
+        players: Query<&Player>,
+    ) {
+        let announcer = match announcer {
+            Some(announcer) => announcer,
+            None => return,
+        };
 
-        for (_player, _position, connection) in players.iter() {
-            connections.push(connection);
+        let interval = (ANNOUNCE_INTERVAL.as_secs_f64() * tick_rate.0 as f64).max(1.0) as u64;
+        if !tick.0.is_multiple_of(interval) {
+            return;
         }
 
-        for i in to_unload {
-            let chunk = chunks.chunks.get(i).unwrap();
+        let announcement = Packet::LanAnnouncement {
+            protocol: PROTOCOL_VERSION,
+            motd: config.motd.clone(),
+            port: announcer.port,
+            online: players.iter().count() as u32,
+            max_players: config.max_players,
+        };
 
-            for connection in connections.iter() {
-                sender
-                    .send_to(
-                        Packet::UnloadChunk {
-                            x: chunk.x,
-                            y: chunk.y,
-                        },
-                        &connection.peer,
-                    )
-                    .unwrap();
+        if let Err(e) = announcer.announce(&announcement) {
+            debug!("Cannot announce the server on the local network: {e}");
+        }
+    }
+
+    /// Measure the velocity of every entity from how much it moved during the last tick.
+    pub fn update_velocities(
+        tick_rate: Res<TickRate>,
+        mut entities: Query<(&Position, &mut PreviousPosition, &mut Velocity)>,
+    ) {
+        let ticks_per_second = tick_rate.0 as f64;
+
+        for (position, mut previous, mut velocity) in entities.iter_mut() {
+            velocity.x = (position.x - previous.0.x) * ticks_per_second;
+            velocity.y = (position.y - previous.0.y) * ticks_per_second;
+            velocity.z = (position.z - previous.0.z) * ticks_per_second;
+
+            previous.0 = *position;
+        }
+    }
+
+    /// Send every client a snapshot of the other players in its world and of the entities it
+    /// tracks, as a delta from the last snapshot it acknowledged.
+    pub fn send_snapshots(
+        tick: Res<Tick>,
+        entities: Query<SnapshotEntities>,
+        mut clients: Query<(
+            &NetworkId,
+            &InWorld,
+            &TrackedEntities,
+            &mut Snapshots,
+            &mut SendQueue,
+        )>,
+    ) {
+        let mut players: HashMap<&str, BTreeMap<u32, EntityState>> = HashMap::new();
+        let mut others = HashMap::new();
+        for (id, in_world, position, rotation, velocity, kind) in entities.iter() {
+            let state = EntityState {
+                position: [position.x, position.y, position.z],
+                rotation: [rotation.yaw, rotation.pitch],
+                velocity: [velocity.x as f32, velocity.y as f32, velocity.z as f32],
+            };
+
+            match kind {
+                Some(_kind) => {
+                    others.insert(id.0, state);
+                }
+                None => {
+                    players
+                        .entry(in_world.0.as_str())
+                        .or_default()
+                        .insert(id.0, state);
+                }
             }
+        }
 
-            chunks.chunks.remove(i);
+        for (id, in_world, tracked, mut snapshots, mut queue) in clients.iter_mut() {
+            let mut snapshot = Snapshot {
+                tick: tick.0,
+                entities: players
+                    .get(in_world.0.as_str())
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            for entity in tracked.entities.iter() {
+                if let Some(state) = others.get(entity) {
+                    snapshot.entities.insert(*entity, *state);
+                }
+            }
+            snapshot.entities.remove(&id.0);
+
+            let baseline = snapshots.history.baseline();
+            let (deltas, removed) = snapshot.diff(baseline);
+
+            let packet = Packet::Snapshot {
+                tick: tick.0,
+                baseline: baseline.map(|baseline| baseline.tick),
+                entities: deltas,
+                removed,
+            };
+            queue.push(packet, Priority::Movement);
+
+            snapshots.history.push(snapshot);
         }
+    }
 
-        for (_player, position, _connection) in players.iter() {
-            if let None = chunks.get_chunk(position.x as i64 / 16, position.z as i64 / 16) {
-                info!("Generating chunk.");
-                let chunk = Chunk::new(position.x as i64 / 16, position.z as i64 / 16);
+    /// Queue for every client the chunks of its world within the view distance of its player,
+    /// nearest first, and unload the ones it moved away from. Chunks no player can see are
+    /// dropped.
+    pub fn update_chunks(
+        mut worlds: ResMut<Worlds>,
+        config: Res<ServerConfig>,
+        mut players: Query<(&InWorld, &Position, &mut LoadedChunks, &mut SendQueue), With<Player>>,
+    ) {
+        let radius = config.view_distance as i64;
+        let mut visible: HashMap<String, HashSet<(i64, i64)>> = HashMap::new();
 
-                for connection in connections.iter() {
-                    sender
-                        .send_to(
-                            Packet::Chunk {
-                                x: chunk.x,
-                                y: chunk.y,
-                                groups: chunk.compress(),
-                            },
-                            &connection.peer,
-                        )
-                        .unwrap();
+        for (in_world, position, mut loaded, mut queue) in players.iter_mut() {
+            let game_world = match worlds.get_mut(&in_world.0) {
+                Some(game_world) => game_world,
+                None => continue,
+            };
+            let visible = visible.entry(in_world.0.clone()).or_default();
+
+            let (center_x, center_y) = position.chunk();
+            let in_range = |(x, y): &(i64, i64)| {
+                x.abs_diff(center_x) <= radius as u64 && y.abs_diff(center_y) <= radius as u64
+            };
+
+            let out_of_range: Vec<(i64, i64)> = loaded
+                .chunks
+                .iter()
+                .filter(|chunk| !in_range(chunk))
+                .copied()
+                .collect();
+
+            for (x, y) in out_of_range {
+                // A chunk the client never got doesn't need to be unloaded.
+                if !queue.cancel_chunk(x, y) {
+                    queue.push(Packet::UnloadChunk { x, y }, Priority::Block);
+                }
+                loaded.chunks.remove(&(x, y));
+            }
+
+            let mut missing = Vec::new();
+            for x in center_x.saturating_sub(radius)..=center_x.saturating_add(radius) {
+                for y in center_y.saturating_sub(radius)..=center_y.saturating_add(radius) {
+                    visible.insert((x, y));
+
+                    if !loaded.chunks.contains(&(x, y)) {
+                        missing.push((x, y));
+                    }
                 }
-                info!("Done!");
+            }
+
+            missing.sort_by_key(|(x, y)| x.abs_diff(center_x).pow(2) + y.abs_diff(center_y).pow(2));
+
+            let room = MAX_QUEUED_CHUNKS.saturating_sub(queue.chunks());
+            for (x, y) in missing.into_iter().take(MAX_CHUNKS_PER_TICK.min(room)) {
+                let data = game_world.load_chunk(x, y).compress();
+                queue.push(Packet::Chunk { x, y, data }, Priority::Chunk);
+                loaded.chunks.insert((x, y));
+            }
+        }
+
+        let nothing = HashSet::new();
+        for game_world in worlds.iter_mut() {
+            game_world.unload_chunks(visible.get(&game_world.name).unwrap_or(&nothing));
+        }
+    }
 
-                chunks.chunks.push(chunk);
+    /// Send every client the packets queued for it that fit in its budget for this tick. A
+    /// client that can't be sent to anymore is disconnected.
+    pub fn send_queued(
+        mut commands: Commands,
+        config: Res<ServerConfig>,
+        mut clients: Query<(Entity, &Position, &Connection, &mut SendQueue)>,
+        mut sender: ResMut<SocketSender>,
+    ) {
+        for (entity, position, connection, mut queue) in clients.iter_mut() {
+            for packet in queue.take(config.send_budget, position.chunk()) {
+                if let Err(e) = sender.send_to(packet, &connection.peer) {
+                    let reason = format!("Cannot send packets: {e}");
+                    commands
+                        .add(move |world: &mut World| Game::remove_player(world, entity, &reason));
+                    break;
+                }
             }
         }
     }
 }
+
+/// Send a packet right away to a client that has no queue, because it is not a player yet or
+/// is leaving. Errors are only logged, there is no player to disconnect.
+fn send_now(sender: &mut SocketSender, packet: Packet, peer: &SocketAddr) {
+    if let Err(e) = sender.send_to(packet, peer) {
+        debug!("Cannot send a packet to {peer}: {e}");
+    }
+}
+
+/// Check a client may join: it is not banned, whitelisted if needed, its name is not taken and
+/// there is room for it.
+fn check_login(
+    login: &Login,
+    players: &Query<(&Player, &Connection)>,
+    logins: &Logins,
+    config: &ServerConfig,
+    access: &AccessLists,
+) -> Result<(), String> {
+    if !valid_name(&login.user) {
+        return Err(format!(
+            "Names are 1 to {MAX_NAME_LENGTH} letters, digits or underscores"
+        ));
+    }
+
+    access.check(&login.user, login.peer.ip(), config.whitelist)?;
+
+    // Names differing only by case would be confusing, in chat and in commands.
+    let mut online = players
+        .iter()
+        .map(|(player, _connection)| player.name.name.as_str())
+        .chain(logins.accepted.iter().map(|login| login.user.as_str()));
+    if online.any(|name| name.eq_ignore_ascii_case(&login.user)) {
+        return Err(format!("A player named {} is already online", login.user));
+    }
+
+    if players.iter().count() + logins.accepted.len() >= config.max_players as usize {
+        return Err(String::from("The server is full"));
+    }
+
+    Ok(())
+}
+
+/// Tell a client it can't join.
+fn refuse(sender: &mut SocketSender, login: &Login, reason: &str) {
+    info!("Player {} refused: {reason}", login.user);
+
+    let packet = Packet::Disconnect {
+        reason: truncate(reason, MAX_REASON_LENGTH).to_string(),
+    };
+    send_now(sender, packet, &login.peer);
+    sender.set_session(login.peer, None);
+}
+
+/// Cut a string to at most `max` bytes, on a character boundary.
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &s[..end]
+}
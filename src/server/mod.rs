@@ -1,8 +1,72 @@
-use crate::network::Packet;
+use crate::network::auth::NONCE_SIZE;
+use crate::network::snapshot::SnapshotHistory;
+use crate::network::{Packet, MAX_NAME_LENGTH};
+use crate::world::movement::{MovementInput, INPUTS_PER_SECOND};
 use bevy_ecs::prelude::Component;
-use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
 
+pub mod access;
+pub mod accounts;
+pub mod block;
+pub mod command;
+pub mod config;
+pub mod console;
+pub mod entity;
 pub mod game;
+pub mod queue;
+pub mod storage;
+pub mod tick;
+pub mod worlds;
+
+/// Number of ticks the server runs every second unless configured otherwise.
+pub const DEFAULT_TICK_RATE: u32 = 20;
+
+/// Maximum number of inputs waiting to be simulated, older ones are dropped.
+pub const MAX_QUEUED_INPUTS: usize = INPUTS_PER_SECOND as usize;
+
+/// Number of inputs a client can have simulated at once, to catch up after inputs were delayed.
+pub const INPUT_BURST: u32 = 5;
+
+/// Name of the world players join the first time.
+pub const MAIN_WORLD: &str = "overworld";
+
+/// Where players appear when they join the first time or move to another world.
+pub const SPAWN_POSITION: Position = Position {
+    x: 0.,
+    y: 16.,
+    z: 10.,
+};
+
+/// Seconds a client has to answer a login challenge.
+pub const LOGIN_TIMEOUT: u64 = 10;
+
+/// Most clients of an address that can have an encrypted session without being logged in.
+pub const MAX_SESSIONS_PER_IP: usize = 4;
+/// Most clients that can have an encrypted session without being logged in.
+pub const MAX_PENDING_SESSIONS: usize = 256;
+
+/// Number of chat messages and commands a player can send at once.
+pub const CHAT_BURST: u32 = 5;
+/// Number of chat messages and commands a player can send every second once the burst is used.
+pub const CHAT_MESSAGES_PER_SECOND: u32 = 1;
+
+/// Number of status requests an address gets answers to at once.
+pub const STATUS_BURST: u32 = 5;
+/// Number of status requests an address gets answers to every second once the burst is used.
+pub const STATUS_REQUESTS_PER_SECOND: u32 = 1;
+/// Most addresses the status limiter keeps track of. Once that many sent requests recently, new
+/// addresses get no answer until some of them are forgotten.
+pub const MAX_STATUS_PEERS: usize = 4096;
+
+/// Check a player name is made of 1 to `MAX_NAME_LENGTH` ASCII letters, digits or underscores.
+pub fn valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LENGTH).contains(&name.len())
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+}
 
 #[derive(Debug, Clone, Component)]
 pub struct PlayerName {
@@ -27,7 +91,273 @@ pub struct Position {
     pub z: f64,
 }
 
+/// Name of the world an entity is in.
+#[derive(Debug, Clone, PartialEq, Eq, Component)]
+pub struct InWorld(pub String);
+
 #[derive(Debug, Clone, Component)]
 pub struct Connection {
     pub peer: SocketAddr,
 }
+
+/// Id used to refer to an entity in packets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Component)]
+pub struct NetworkId(pub u32);
+
+/// Source of unused network ids.
+#[derive(Debug, Default)]
+pub struct NetworkIds {
+    next: u32,
+}
+
+impl NetworkIds {
+    pub fn allocate(&mut self) -> NetworkId {
+        self.next += 1;
+        NetworkId(self.next)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, Component)]
+pub struct Rotation {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// Velocity in blocks per second, measured from the position change of the last tick.
+#[derive(Debug, Copy, Clone, Default, Component)]
+pub struct Velocity {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// Vertical velocity of a player in blocks per second, from falling and jumping.
+#[derive(Debug, Copy, Clone, Default, Component)]
+pub struct VerticalVelocity(pub f64);
+
+/// Chunks sent to a client and not unloaded since.
+#[derive(Debug, Clone, Default, Component)]
+pub struct LoadedChunks {
+    pub chunks: HashSet<(i64, i64)>,
+}
+
+/// Network ids of the entities a client was told to spawn and not told to despawn since.
+#[derive(Debug, Clone, Default, Component)]
+pub struct TrackedEntities {
+    pub entities: HashSet<u32>,
+}
+
+impl Position {
+    /// Coordinates of the chunk containing the position.
+    pub fn chunk(&self) -> (i64, i64) {
+        ((self.x / 16.).floor() as i64, (self.z / 16.).floor() as i64)
+    }
+}
+
+/// Position at the end of the previous tick.
+#[derive(Debug, Copy, Clone, Component)]
+pub struct PreviousPosition(pub Position);
+
+/// Snapshots sent to a client, used as baselines for the next ones.
+#[derive(Debug, Clone, Default, Component)]
+pub struct Snapshots {
+    pub history: SnapshotHistory,
+}
+
+/// A client allowed to join.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Login {
+    pub peer: SocketAddr,
+    pub user: String,
+    pub compression: bool,
+}
+
+/// A login waiting for the client to answer its challenge.
+#[derive(Debug, Clone)]
+pub struct Challenge {
+    pub login: Login,
+    pub nonce: [u8; NONCE_SIZE],
+    /// Tick the challenge was sent at.
+    pub tick: u64,
+}
+
+/// Clients logging in. Accepted logins become players when the packets are handled.
+#[derive(Debug, Default)]
+pub struct Logins {
+    pub challenges: HashMap<SocketAddr, Challenge>,
+    pub accepted: Vec<Login>,
+    /// Peers that started an encrypted session but didn't log in yet, with the tick the session
+    /// started at.
+    pub sessions: HashMap<SocketAddr, u64>,
+}
+
+/// What a player is allowed to do. Commands need a minimum level, the console has every
+/// permission.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Component)]
+pub enum PermissionLevel {
+    #[default]
+    Player,
+    Operator,
+    Console,
+}
+
+/// Limits how fast a player can send chat messages and commands. Every message uses a token,
+/// tokens come back at `CHAT_MESSAGES_PER_SECOND` up to `CHAT_BURST`.
+#[derive(Debug, Clone, Component)]
+pub struct ChatLimiter {
+    tokens: f64,
+    last_tick: Option<u64>,
+}
+
+impl Default for ChatLimiter {
+    fn default() -> Self {
+        Self {
+            tokens: CHAT_BURST as f64,
+            last_tick: None,
+        }
+    }
+}
+
+impl ChatLimiter {
+    /// Check if a message sent during `tick` is allowed, using a token if it is.
+    pub fn allow(&mut self, tick: u64, tick_rate: u32) -> bool {
+        if let Some(last_tick) = self.last_tick {
+            let elapsed = tick.saturating_sub(last_tick) as f64 / tick_rate as f64;
+            self.tokens =
+                (self.tokens + elapsed * CHAT_MESSAGES_PER_SECOND as f64).min(CHAT_BURST as f64);
+        }
+        self.last_tick = Some(tick);
+
+        if self.tokens < 1. {
+            return false;
+        }
+
+        self.tokens -= 1.;
+        true
+    }
+}
+
+/// Limits how fast each address gets answers to status requests, like `ChatLimiter` does for chat
+/// messages. Requests are answered on the network thread, which counts time instead of ticks.
+#[derive(Debug, Default)]
+pub struct StatusLimiter {
+    peers: HashMap<IpAddr, (f64, Instant)>,
+}
+
+impl StatusLimiter {
+    /// Check if a request from `ip` received at `now` is answered, using a token if it is.
+    pub fn allow(&mut self, ip: IpAddr, now: Instant) -> bool {
+        if self.peers.len() >= MAX_STATUS_PEERS && !self.peers.contains_key(&ip) {
+            // Addresses that got all their tokens back are the same as new ones.
+            let refilled = Duration::from_secs(STATUS_BURST as u64) / STATUS_REQUESTS_PER_SECOND;
+            self.peers
+                .retain(|_ip, (_tokens, last)| now.saturating_duration_since(*last) < refilled);
+
+            if self.peers.len() >= MAX_STATUS_PEERS {
+                return false;
+            }
+        }
+
+        let (tokens, last) = self.peers.entry(ip).or_insert((STATUS_BURST as f64, now));
+        let elapsed = now.saturating_duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * STATUS_REQUESTS_PER_SECOND as f64).min(STATUS_BURST as f64);
+        *last = now;
+
+        if *tokens < 1. {
+            return false;
+        }
+
+        *tokens -= 1.;
+        true
+    }
+}
+
+/// Whether the server keeps running ticks. Cleared by the `stop` command.
+#[derive(Debug, Copy, Clone)]
+pub struct Running(pub bool);
+
+/// Number of ticks run since the server started.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Tick(pub u64);
+
+/// Number of ticks the server runs every second.
+#[derive(Debug, Copy, Clone)]
+pub struct TickRate(pub u32);
+
+/// Inputs received from a client, waiting to be simulated. Simulating an input uses a token,
+/// tokens come back at `INPUTS_PER_SECOND` up to `INPUT_BURST`, so inputs arriving in bursts are
+/// caught up with but a client sending inputs faster can't move faster.
+#[derive(Debug, Clone, Component)]
+pub struct InputQueue {
+    inputs: VecDeque<(u32, MovementInput)>,
+    /// Sequence number of the last input received.
+    received: u32,
+    /// Tick the last input was received at.
+    received_tick: Option<u64>,
+    /// Sequence number of the last input simulated.
+    pub processed: u32,
+    tokens: f64,
+    last_tick: Option<u64>,
+}
+
+impl Default for InputQueue {
+    fn default() -> Self {
+        Self {
+            inputs: VecDeque::new(),
+            received: 0,
+            received_tick: None,
+            processed: 0,
+            tokens: INPUT_BURST as f64,
+            last_tick: None,
+        }
+    }
+}
+
+impl InputQueue {
+    /// Queue an input received during `tick`, ignoring duplicates and inputs arriving out of
+    /// order. Inputs further ahead of the last one than the client could have sent since are
+    /// ignored too, or a single input with a huge sequence number would make the server ignore
+    /// every input of the client after it.
+    pub fn push(&mut self, sequence: u32, input: MovementInput, tick: u64, tick_rate: u32) {
+        let elapsed = match self.received_tick {
+            Some(received_tick) => tick.saturating_sub(received_tick),
+            None => 0,
+        };
+        let window = MAX_QUEUED_INPUTS as u64
+            + (elapsed * INPUTS_PER_SECOND as u64).div_ceil(tick_rate as u64);
+        if sequence <= self.received || (sequence - self.received) as u64 > window {
+            return;
+        }
+        self.received_tick = Some(tick);
+
+        if self.inputs.len() == MAX_QUEUED_INPUTS {
+            self.inputs.pop_front();
+        }
+
+        self.received = sequence;
+        self.inputs.push_back((sequence, input));
+    }
+
+    /// Give back the tokens earned since the last tick the queue was refilled at.
+    pub fn refill(&mut self, tick: u64, tick_rate: u32) {
+        if let Some(last_tick) = self.last_tick {
+            let elapsed = tick.saturating_sub(last_tick) as f64 / tick_rate as f64;
+            self.tokens =
+                (self.tokens + elapsed * INPUTS_PER_SECOND as f64).min(INPUT_BURST as f64);
+        }
+        self.last_tick = Some(tick);
+    }
+
+    /// Take the next input to simulate, if the client has a token left for it.
+    pub fn pop(&mut self) -> Option<MovementInput> {
+        if self.tokens < 1. {
+            return None;
+        }
+
+        let (sequence, input) = self.inputs.pop_front()?;
+        self.processed = sequence;
+        self.tokens -= 1.;
+
+        Some(input)
+    }
+}
@@ -0,0 +1,163 @@
+use std::cmp::Reverse;
+use std::mem;
+
+use bevy_ecs::prelude::Component;
+
+use crate::network::Packet;
+use crate::world::chunk::CompressedChunk;
+
+/// Maximum number of chunks waiting to be sent to a client. New chunks are only loaded for it
+/// once it received the ones before, so a slow client doesn't keep the whole view in memory.
+pub const MAX_QUEUED_CHUNKS: usize = 16;
+
+/// How urgently a packet must reach the client. Packets with a higher priority are sent first,
+/// packets with the same priority in the order they were queued, except chunks which are sent
+/// nearest first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Chunks coming into view.
+    Chunk,
+    /// Chat, system messages and the player list.
+    Message,
+    /// Changed and unloaded chunks.
+    Block,
+    /// Player states and snapshots. Only the latest one of each kind is kept.
+    Movement,
+}
+
+#[derive(Debug, Clone)]
+struct Queued {
+    priority: Priority,
+    sequence: u64,
+    /// Encoded size of the packet.
+    size: usize,
+    packet: Packet,
+}
+
+/// Packets waiting to be sent to a client, sent a few every tick within the send budget.
+#[derive(Debug, Clone, Default, Component)]
+pub struct SendQueue {
+    packets: Vec<Queued>,
+    sequence: u64,
+    /// Bytes the client can still be sent. This goes below zero after a packet bigger than what
+    /// was left, and the next ticks make up for it.
+    allowance: i64,
+}
+
+impl SendQueue {
+    /// Queue a packet to the client.
+    pub fn push(&mut self, packet: Packet, priority: Priority) {
+        if priority == Priority::Movement {
+            let kind = mem::discriminant(&packet);
+            self.packets
+                .retain(|queued| mem::discriminant(&queued.packet) != kind);
+        }
+
+        self.sequence += 1;
+        self.packets.push(Queued {
+            priority,
+            sequence: self.sequence,
+            size: encoded_size(&packet),
+            packet,
+        });
+    }
+
+    /// Replace the data of a chunk waiting to be sent. Returns false if the chunk isn't queued.
+    pub fn update_chunk(&mut self, x: i64, y: i64, data: &CompressedChunk) -> bool {
+        match self.chunk_mut(x, y) {
+            Some(queued) => {
+                queued.packet = Packet::Chunk {
+                    x,
+                    y,
+                    data: data.clone(),
+                };
+                queued.size = encoded_size(&queued.packet);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove a chunk waiting to be sent. Returns false if the chunk isn't queued.
+    pub fn cancel_chunk(&mut self, x: i64, y: i64) -> bool {
+        let before = self.packets.len();
+        self.packets.retain(|queued| {
+            !matches!(queued.packet, Packet::Chunk { x: chunk_x, y: chunk_y, .. } if (chunk_x, chunk_y) == (x, y))
+        });
+
+        self.packets.len() != before
+    }
+
+    /// Remove every chunk and chunk unload waiting to be sent, when the client is about to drop
+    /// all its chunks anyway.
+    pub fn clear_chunks(&mut self) {
+        self.packets.retain(|queued| {
+            !matches!(
+                queued.packet,
+                Packet::Chunk { .. } | Packet::UnloadChunk { .. }
+            )
+        });
+    }
+
+    /// Number of chunks waiting to be sent.
+    pub fn chunks(&self) -> usize {
+        self.packets
+            .iter()
+            .filter(|queued| matches!(queued.packet, Packet::Chunk { .. }))
+            .count()
+    }
+
+    /// Number of packets waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Take the packets to send this tick, adding `budget` bytes to what the client can be sent.
+    /// Chunks nearest to `center` go first.
+    pub fn take(&mut self, budget: u32, center: (i64, i64)) -> Vec<Packet> {
+        // What isn't used is not saved for later, or an idle client would get a burst.
+        self.allowance = (self.allowance + budget as i64).min(budget as i64);
+
+        let distance = |packet: &Packet| match packet {
+            Packet::Chunk { x, y, .. } => (x - center.0).pow(2) + (y - center.1).pow(2),
+            _ => 0,
+        };
+        self.packets.sort_by_key(|queued| {
+            (
+                Reverse(queued.priority),
+                distance(&queued.packet),
+                queued.sequence,
+            )
+        });
+
+        let mut count = 0;
+        for queued in self.packets.iter() {
+            if self.allowance <= 0 {
+                break;
+            }
+            self.allowance -= queued.size as i64;
+            count += 1;
+        }
+
+        self.packets
+            .drain(..count)
+            .map(|queued| queued.packet)
+            .collect()
+    }
+
+    fn chunk_mut(&mut self, x: i64, y: i64) -> Option<&mut Queued> {
+        self.packets.iter_mut().find(|queued| {
+            matches!(queued.packet, Packet::Chunk { x: chunk_x, y: chunk_y, .. } if (chunk_x, chunk_y) == (x, y))
+        })
+    }
+}
+
+/// Size of the packet before compression. Packets that can't be encoded count as empty, sending
+/// them fails anyway.
+fn encoded_size(packet: &Packet) -> usize {
+    packet.encoded_size().unwrap_or(0)
+}
@@ -0,0 +1,172 @@
+use std::fs::{self, File};
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::network::{decode_chunk, encode_chunk};
+use crate::server::MAIN_WORLD;
+use crate::world::chunk::Chunk;
+
+/// Saved state of a player, restored when it joins again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerData {
+    pub position: [f64; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    /// World the player was in. Players saved before there were several worlds were in the main
+    /// one.
+    #[serde(default = "main_world")]
+    pub world: String,
+}
+
+fn main_world() -> String {
+    String::from(MAIN_WORLD)
+}
+
+/// Chunks and players saved in the world directory. Chunks are stored in `chunks/` in the same
+/// format as in Chunk packets, players in `players/` as TOML.
+#[derive(Debug, Clone)]
+pub struct WorldStorage {
+    dir: PathBuf,
+}
+
+impl WorldStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read a saved chunk, or `None` if it was never saved.
+    pub fn load_chunk(&self, x: i64, y: i64) -> io::Result<Option<Chunk>> {
+        let data = match read_if_exists(&self.chunk_path(x, y))? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let data = decode_chunk(&data).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+        Ok(Some(Chunk::decompress(&data, x, y)))
+    }
+
+    pub fn save_chunk(&self, chunk: &Chunk) -> io::Result<()> {
+        let path = self.chunk_path(chunk.x, chunk.y);
+        write_file(&path, &encode_chunk(&chunk.compress())?, false)
+    }
+
+    /// Read the saved state of a player, or `None` if it never played here.
+    pub fn load_player(&self, name: &str) -> io::Result<Option<PlayerData>> {
+        let data = match read_if_exists(&self.player_path(name))? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let data =
+            String::from_utf8(data).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+        toml::from_str(&data)
+            .map(Some)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    pub fn save_player(&self, name: &str, data: &PlayerData) -> io::Result<()> {
+        let data = toml::to_string(data).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+        write_file(&self.player_path(name), data.as_bytes(), false)
+    }
+
+    fn chunk_path(&self, x: i64, y: i64) -> PathBuf {
+        self.dir.join("chunks").join(format!("{x}.{y}.chunk"))
+    }
+
+    fn player_path(&self, name: &str) -> PathBuf {
+        self.dir
+            .join("players")
+            .join(format!("{}.toml", file_name(name)))
+    }
+}
+
+/// Name of the file of a player. Names are chosen by clients, so anything that could escape the
+/// players directory or isn't portable is hex encoded.
+fn file_name(name: &str) -> String {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return name.to_string();
+    }
+
+    let hex: String = name.bytes().map(|byte| format!("{byte:02x}")).collect();
+    format!("%{hex}")
+}
+
+/// Read a TOML file, or the default value if it doesn't exist.
+pub(crate) fn read_toml<T: DeserializeOwned + Default>(path: &Path) -> io::Result<T> {
+    let data = match read_if_exists(path)? {
+        Some(data) => data,
+        None => return Ok(T::default()),
+    };
+
+    let data = String::from_utf8(data).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+    toml::from_str(&data)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, format!("{}: {e}", path.display())))
+}
+
+pub(crate) fn write_toml<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let data = toml::to_string(value).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+    write_file(path, data.as_bytes(), false)
+}
+
+/// Write a TOML file that only the owner of the server can read.
+pub(crate) fn write_private_toml<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let data = toml::to_string(value).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+    write_file(path, data.as_bytes(), true)
+}
+
+fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Write a file through a temporary one, so a crash while saving doesn't leave it truncated.
+/// A private file is restricted to its owner before anything is written to it.
+fn write_file(path: &Path, data: &[u8], private: bool) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temporary = path.with_extension("tmp");
+    let mut file = File::create(&temporary)?;
+    if private {
+        restrict(&file)?;
+    }
+    file.write_all(data)?;
+    drop(file);
+
+    fs::rename(&temporary, path)
+}
+
+#[cfg(unix)]
+fn restrict(file: &File) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    file.set_permissions(fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn restrict(_file: &File) -> io::Result<()> {
+    Ok(())
+}
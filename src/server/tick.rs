@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+/// Number of ticks the statistics are computed over.
+const STATS_WINDOW: usize = 100;
+
+/// If the server falls further behind than this, the missed ticks are skipped instead of run
+/// back to back.
+const MAX_CATCH_UP: Duration = Duration::from_secs(2);
+
+/// Minimum time between two warnings about the server being overloaded.
+const OVERLOAD_WARNING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Durations of the last ticks.
+#[derive(Debug, Clone, Default)]
+pub struct TickStats {
+    durations: VecDeque<Duration>,
+}
+
+impl TickStats {
+    pub fn record(&mut self, duration: Duration) {
+        if self.durations.len() == STATS_WINDOW {
+            self.durations.pop_front();
+        }
+
+        self.durations.push_back(duration);
+    }
+
+    /// Average milliseconds per tick.
+    pub fn average_mspt(&self) -> f64 {
+        if self.durations.is_empty() {
+            return 0.;
+        }
+
+        let total: Duration = self.durations.iter().sum();
+        total.as_secs_f64() * 1000. / self.durations.len() as f64
+    }
+
+    /// Longest tick in milliseconds.
+    pub fn max_mspt(&self) -> f64 {
+        self.durations
+            .iter()
+            .max()
+            .map_or(0., |max| max.as_secs_f64() * 1000.)
+    }
+
+    pub fn len(&self) -> usize {
+        self.durations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.durations.is_empty()
+    }
+}
+
+/// Runs ticks at a fixed rate. The scheduler sleeps until the next tick is due, and when a tick
+/// runs long the following ones start right away until the server caught up.
+#[derive(Debug, Clone)]
+pub struct TickScheduler {
+    interval: Duration,
+    next_tick: Instant,
+    last_warning: Option<Instant>,
+    pub stats: TickStats,
+}
+
+impl TickScheduler {
+    pub fn new(tick_rate: u32) -> Self {
+        assert!(tick_rate > 0, "the tick rate must be positive");
+
+        Self {
+            interval: Duration::from_secs(1) / tick_rate,
+            next_tick: Instant::now(),
+            last_warning: None,
+            stats: TickStats::default(),
+        }
+    }
+
+    /// Time a tick is supposed to take.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// How long to wait at `now` before the next tick is due. Falling too far behind skips the
+    /// missed ticks.
+    pub fn time_until_next_tick(&mut self, now: Instant) -> Duration {
+        let behind = now.saturating_duration_since(self.next_tick);
+        if behind > MAX_CATCH_UP {
+            let skipped = behind.as_nanos() / self.interval.as_nanos();
+            warn!(
+                "Can't keep up! Running {}ms behind, skipping {skipped} ticks",
+                behind.as_millis()
+            );
+            self.next_tick = now;
+        }
+
+        self.next_tick.saturating_duration_since(now)
+    }
+
+    /// Sleep until the next tick is due.
+    pub fn wait(&mut self) {
+        let sleep = self.time_until_next_tick(Instant::now());
+        if !sleep.is_zero() {
+            thread::sleep(sleep);
+        }
+    }
+
+    /// Record how long a tick took and schedule the next one.
+    pub fn finish_tick(&mut self, duration: Duration) {
+        self.stats.record(duration);
+        self.next_tick += self.interval;
+
+        if duration > self.interval
+            && self
+                .last_warning
+                .is_none_or(|last| last.elapsed() >= OVERLOAD_WARNING_INTERVAL)
+        {
+            warn!(
+                "Server overloaded: tick took {:.1}ms of {:.1}ms (average {:.1}ms, max {:.1}ms)",
+                duration.as_secs_f64() * 1000.,
+                self.interval.as_secs_f64() * 1000.,
+                self.stats.average_mspt(),
+                self.stats.max_mspt()
+            );
+            self.last_warning = Some(Instant::now());
+        }
+    }
+}
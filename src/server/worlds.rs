@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+
+use log::warn;
+
+use crate::server::config::ServerConfig;
+use crate::server::storage::WorldStorage;
+use crate::server::MAIN_WORLD;
+use crate::world::chunk::Chunk;
+use crate::world::generator::Generator;
+use crate::world::Chunks;
+
+/// A world hosted by the server, with its own chunks generated from its own seed and saved in its
+/// own directory.
+pub struct GameWorld {
+    pub name: String,
+    pub generator: Generator,
+    pub seed: i64,
+    pub storage: WorldStorage,
+    pub chunks: Chunks,
+}
+
+impl GameWorld {
+    pub fn new(name: &str, generator: Generator, seed: i64, storage: WorldStorage) -> Self {
+        Self {
+            name: name.to_string(),
+            generator,
+            seed,
+            storage,
+            chunks: Chunks::default(),
+        }
+    }
+
+    /// Make sure a chunk is in memory, reading it from disk or generating it.
+    pub fn load_chunk(&mut self, x: i64, y: i64) -> &mut Chunk {
+        if self.chunks.get_chunk(x, y).is_none() {
+            let chunk = self.read_or_generate(x, y);
+            self.chunks.chunks.push(chunk);
+        }
+
+        self.chunks.get_chunk_mut(x, y).unwrap()
+    }
+
+    /// Drop the chunks that are not visible. Modified chunks are saved before they are dropped,
+    /// or the changes would be lost.
+    pub fn unload_chunks(&mut self, visible: &HashSet<(i64, i64)>) {
+        let Chunks { chunks, modified } = &mut self.chunks;
+        let storage = &self.storage;
+
+        chunks.retain(|chunk| {
+            let position = (chunk.x, chunk.y);
+            if visible.contains(&position) {
+                return true;
+            }
+
+            if modified.remove(&position) {
+                if let Err(e) = storage.save_chunk(chunk) {
+                    warn!("Cannot save chunk {} {}: {e}", chunk.x, chunk.y);
+                }
+            }
+
+            false
+        });
+    }
+
+    /// Write every loaded chunk to disk. Returns how many chunks were saved.
+    pub fn save(&mut self) -> io::Result<usize> {
+        for chunk in self.chunks.chunks.iter() {
+            self.storage.save_chunk(chunk)?;
+        }
+        self.chunks.modified.clear();
+
+        Ok(self.chunks.chunks.len())
+    }
+
+    /// Read a chunk saved on disk, or generate it if it was never saved or can't be read.
+    fn read_or_generate(&self, x: i64, y: i64) -> Chunk {
+        match self.storage.load_chunk(x, y) {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => self.generator.generate(self.seed, x, y),
+            Err(e) => {
+                warn!(
+                    "Cannot read chunk {x} {y} of world {}, generating it again: {e}",
+                    self.name
+                );
+                self.generator.generate(self.seed, x, y)
+            }
+        }
+    }
+}
+
+/// Worlds hosted by the server, by name. There is always the main world, where players join the
+/// first time and whose directory holds the saved players.
+pub struct Worlds {
+    worlds: BTreeMap<String, GameWorld>,
+}
+
+impl Worlds {
+    /// The main world and the extra worlds of the configuration.
+    pub fn new(config: &ServerConfig) -> Self {
+        let mut worlds = BTreeMap::new();
+
+        let main = GameWorld::new(
+            MAIN_WORLD,
+            config.generator,
+            config.seed,
+            WorldStorage::new(config.world_dir.clone()),
+        );
+        worlds.insert(main.name.clone(), main);
+
+        for settings in config.worlds.iter() {
+            let world = GameWorld::new(
+                &settings.name,
+                settings.generator,
+                settings.seed,
+                WorldStorage::new(settings.dir(&config.world_dir)),
+            );
+            worlds.insert(world.name.clone(), world);
+        }
+
+        Self { worlds }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&GameWorld> {
+        self.worlds.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut GameWorld> {
+        self.worlds.get_mut(name)
+    }
+
+    pub fn main(&self) -> &GameWorld {
+        &self.worlds[MAIN_WORLD]
+    }
+
+    /// Storage the players are saved in, whatever world they are in.
+    pub fn players(&self) -> &WorldStorage {
+        &self.main().storage
+    }
+
+    /// Names of the worlds, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.worlds.keys().map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GameWorld> {
+        self.worlds.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut GameWorld> {
+        self.worlds.values_mut()
+    }
+}
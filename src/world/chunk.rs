@@ -4,6 +4,11 @@ use bevy_ecs::prelude::Component;
 
 use crate::assets::Identifier;
 
+/// Number of blocks in a chunk.
+pub const CHUNK_BLOCKS: u32 = 16 * 16 * 16;
+/// Id of the empty block, the only one that isn't solid.
+pub const AIR: &str = "base:air";
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Block {
     pub data: u16,
@@ -59,50 +64,53 @@ impl Chunk {
     }
 
     /// Compress a chunk in a smaller data structure.
-    pub fn compress(&self) -> Vec<BlockGroup> {
-        let mut groups = Vec::new();
-
-        let mut group = BlockGroup {
-            id: self.blocks.first().unwrap().id.clone(),
-            count: 0,
-        };
+    pub fn compress(&self) -> CompressedChunk {
+        let mut palette: Vec<String> = Vec::new();
+        let mut groups: Vec<BlockGroup> = Vec::new();
 
         for block in self.blocks.iter() {
-            if block.id == group.id {
-                group.count += 1;
-            } else {
-                groups.push(group);
-
-                group = BlockGroup {
-                    id: block.id.clone(),
-                    count: 1,
-                };
+            let index = match palette.iter().position(|id| *id == block.id) {
+                Some(index) => index,
+                None => {
+                    palette.push(block.id.clone());
+                    palette.len() - 1
+                }
+            } as u16;
+
+            match groups.last_mut() {
+                Some(group) if group.index == index => group.count += 1,
+                _ => groups.push(BlockGroup { index, count: 1 }),
             }
         }
 
-        groups.push(group);
-
-        groups
+        CompressedChunk { palette, groups }
     }
 
-    /// Decomrpess a chunk from a vector of BlockGroups.
-    pub fn decompress(groups: &Vec<BlockGroup>, x: i64, y: i64) -> Self {
+    /// Decomrpess a chunk from its palette and block groups.
+    pub fn decompress(data: &CompressedChunk, x: i64, y: i64) -> Self {
         let mut chunk = Self {
             blocks: Vec::new(),
             x,
             y,
         };
 
+        let palette: Vec<Identifier> = data
+            .palette
+            .iter()
+            .map(|id| Identifier::from_str(id).unwrap())
+            .collect();
+
         let mut i = 0i16;
 
-        for group in groups {
+        for group in data.groups.iter() {
             for _ in 0..group.count {
                 let z = (i / (16 * 16)) as u16;
                 let n_i = i - (z as i16 * 16 * 16);
                 let y = (n_i / 16) as u16;
                 let x = (n_i % 16) as u16;
 
-                let block = Block::new(x, y, z, Identifier::from_str(&group.id).unwrap(), true);
+                let id = &palette[group.index as usize];
+                let block = Block::new(x, y, z, id.clone(), id.to_string() != AIR);
 
                 chunk.blocks.push(block);
 
@@ -119,9 +127,17 @@ impl Chunk {
     }
 }
 
-/// Data structure used to represent a compressed chunk.
-#[derive(Debug, Clone)]
+/// A run of `count` consecutive blocks sharing the same id, stored as an index in the palette.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BlockGroup {
-    pub id: String,
-    pub count: u32,
+    pub index: u16,
+    pub count: u16,
+}
+
+/// Data structure used to represent a compressed chunk. Every block id appears once in the
+/// palette and the blocks are stored as runs of palette indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedChunk {
+    pub palette: Vec<String>,
+    pub groups: Vec<BlockGroup>,
 }
@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::assets::Identifier;
+use crate::world::chunk::{Block, Chunk};
+
+/// Width in blocks of the cells the height of the hills is picked at. Heights in between are
+/// interpolated.
+const HILL_SIZE: i64 = 8;
+/// Lowest and highest height of the hills.
+const HILL_HEIGHTS: (u64, u64) = (4, 12);
+
+/// How the chunks of a world are made the first time they are loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Generator {
+    /// Stone everywhere.
+    #[default]
+    Flat,
+    /// Hills of grass on top of stone, with air above. Their shape depends on the seed.
+    Hills,
+}
+
+impl Generator {
+    /// Generate the chunk at the given position.
+    pub fn generate(&self, seed: i64, x: i64, y: i64) -> Chunk {
+        match self {
+            Generator::Flat => Chunk::new(x, y),
+            Generator::Hills => hills(seed, x, y),
+        }
+    }
+}
+
+impl FromStr for Generator {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(Generator::Flat),
+            "hills" => Ok(Generator::Hills),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for Generator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Generator::Flat => write!(f, "flat"),
+            Generator::Hills => write!(f, "hills"),
+        }
+    }
+}
+
+fn hills(seed: i64, chunk_x: i64, chunk_y: i64) -> Chunk {
+    let mut chunk = Chunk::new(chunk_x, chunk_y);
+
+    for block in chunk.blocks.iter_mut() {
+        let (x, y, z) = (block.x() as u16, block.y() as u16, block.z() as u16);
+        let height = hill_height(seed, chunk_x * 16 + x as i64, chunk_y * 16 + z as i64) as u16;
+
+        let name = match y.cmp(&height) {
+            Ordering::Greater => "air",
+            Ordering::Equal => "grass",
+            Ordering::Less => "stone",
+        };
+
+        *block = Block::new(x, y, z, Identifier::new("base", name), name != "air");
+    }
+
+    chunk
+}
+
+/// Height of the hills at a column, interpolated between the heights picked at the corners of
+/// its cell.
+fn hill_height(seed: i64, x: i64, z: i64) -> f64 {
+    let (cell_x, cell_z) = (x.div_euclid(HILL_SIZE), z.div_euclid(HILL_SIZE));
+    let (tx, tz) = (
+        smooth(x.rem_euclid(HILL_SIZE) as f64 / HILL_SIZE as f64),
+        smooth(z.rem_euclid(HILL_SIZE) as f64 / HILL_SIZE as f64),
+    );
+
+    let corner = |dx, dz| {
+        let (low, high) = HILL_HEIGHTS;
+        (low + hash(seed, cell_x + dx, cell_z + dz) % (high - low + 1)) as f64
+    };
+
+    let north = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+    let south = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+
+    north + (south - north) * tz
+}
+
+/// Ease an interpolation factor so the hills have no sharp edges at the cell corners.
+fn smooth(t: f64) -> f64 {
+    t * t * (3. - 2. * t)
+}
+
+/// Mix a seed and a position into a random looking number, always the same for the same inputs.
+pub(crate) fn hash(seed: i64, x: i64, z: i64) -> u64 {
+    let mut value = (seed as u64)
+        ^ (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (z as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+
+    // Finalizer of SplitMix64.
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
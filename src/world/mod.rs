@@ -1,25 +1,40 @@
-use self::chunk::Chunk;
+use std::collections::HashSet;
+
+use self::chunk::{Block, Chunk};
 
 pub mod chunk;
+pub mod generator;
+pub mod movement;
+pub mod physics;
 
+#[derive(Default)]
 pub struct Chunks {
     pub chunks: Vec<Chunk>,
+    /// Chunks changed since they were last saved.
+    pub modified: HashSet<(i64, i64)>,
 }
 
 impl Chunks {
     pub fn get_chunk(&self, x: i64, y: i64) -> Option<&Chunk> {
-        for chunk in self.chunks.iter() {
-            if chunk.x == x && chunk.y == y {
-                return Some(chunk);
-            }
-        }
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.x == x && chunk.y == y)
+    }
 
-        None
+    pub fn get_chunk_mut(&mut self, x: i64, y: i64) -> Option<&mut Chunk> {
+        self.chunks
+            .iter_mut()
+            .find(|chunk| chunk.x == x && chunk.y == y)
     }
-}
 
-impl Default for Chunks {
-    fn default() -> Self {
-        Self { chunks: Vec::new() }
+    /// Block at a position in the world, if it is within the height of the world and its chunk
+    /// is loaded.
+    pub fn get_block(&self, x: i64, y: i64, z: i64) -> Option<&Block> {
+        if !(0..16).contains(&y) {
+            return None;
+        }
+
+        self.get_chunk(x.div_euclid(16), z.div_euclid(16))?
+            .get_block(x.rem_euclid(16) as u16, y as u16, z.rem_euclid(16) as u16)
     }
 }
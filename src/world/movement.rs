@@ -0,0 +1,57 @@
+use std::f32::consts::FRAC_PI_2;
+
+/// Speed of a player in blocks per second.
+pub const MOVEMENT_SPEED: f64 = 2.;
+
+/// Number of inputs clients send every second, whatever the tick rate of the server is. Every
+/// input moves the player for `1 / INPUTS_PER_SECOND` seconds.
+pub const INPUTS_PER_SECOND: u32 = 20;
+
+/// Movement input of a player during one input interval. The same input gives the same movement on the
+/// client, which predicts it, and on the server, which decides where the player really is.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MovementInput {
+    /// Forward (positive) or backward (negative) movement, from -1 to 1.
+    pub forward: f32,
+    /// Right (positive) or left (negative) movement, from -1 to 1.
+    pub right: f32,
+    /// Up (positive) or down (negative) movement, from -1 to 1. Walking players jump when it is
+    /// positive and sneak when it is negative.
+    pub up: f32,
+    /// Yaw in radians.
+    pub yaw: f32,
+    /// Pitch in radians, from -PI/2 to PI/2.
+    pub pitch: f32,
+    /// Whether the player flies instead of walking.
+    pub flying: bool,
+}
+
+impl MovementInput {
+    /// Clamp every field to its range, so a modified client can't move faster than the others.
+    pub fn clamped(&self) -> Self {
+        Self {
+            forward: self.forward.clamp(-1., 1.),
+            right: self.right.clamp(-1., 1.),
+            up: self.up.clamp(-1., 1.),
+            yaw: self.yaw,
+            pitch: self.pitch.clamp(-FRAC_PI_2, FRAC_PI_2),
+            flying: self.flying,
+        }
+    }
+}
+
+/// Move a position by one input, ignoring blocks and gravity. See `physics::Body::step`.
+pub fn step(position: [f64; 3], input: &MovementInput) -> [f64; 3] {
+    let input = input.clamped();
+    let distance = MOVEMENT_SPEED / INPUTS_PER_SECOND as f64;
+
+    let (yaw_sin, yaw_cos) = (input.yaw as f64).sin_cos();
+    let forward = input.forward as f64;
+    let right = input.right as f64;
+
+    [
+        position[0] + (yaw_cos * forward - yaw_sin * right) * distance,
+        position[1] + input.up as f64 * distance,
+        position[2] + (yaw_sin * forward + yaw_cos * right) * distance,
+    ]
+}
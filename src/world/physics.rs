@@ -0,0 +1,258 @@
+use crate::world::movement::{self, MovementInput, INPUTS_PER_SECOND};
+use crate::world::Chunks;
+
+/// Width of a player along x and z, in blocks.
+pub const PLAYER_WIDTH: f64 = 0.6;
+/// Height of a player, in blocks.
+pub const PLAYER_HEIGHT: f64 = 1.8;
+/// Height of the eyes of a player above its feet, in blocks.
+pub const EYE_HEIGHT: f64 = 1.6;
+/// Acceleration of falling bodies in blocks per second squared.
+pub const GRAVITY: f64 = 32.;
+/// Vertical velocity at the start of a jump in blocks per second, enough to jump a bit more than
+/// a block high.
+pub const JUMP_VELOCITY: f64 = 10.;
+/// Maximum falling speed in blocks per second.
+pub const MAX_FALL_SPEED: f64 = 60.;
+/// Highest ledge a walking body climbs without jumping, in blocks. Full blocks are higher, they
+/// have to be jumped on.
+pub const STEP_HEIGHT: f64 = 0.6;
+/// Fraction of the movement speed kept while sneaking.
+pub const SNEAKING_SPEED: f64 = 0.3;
+
+/// Distances smaller than this are rounding errors, boxes that close to a block only touch it.
+const EPSILON: f64 = 1e-7;
+/// Distance below a body within which a block supports it.
+const GROUND_DISTANCE: f64 = 1e-3;
+/// How much a sneaking body shortens its movement at a time until it doesn't fall off an edge.
+const EDGE_STEP: f64 = 0.05;
+
+/// Axis aligned bounding box, in world coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl Aabb {
+    pub fn new(min: [f64; 3], max: [f64; 3]) -> Self {
+        Self { min, max }
+    }
+
+    /// The same box moved by `offset`.
+    pub fn offset(&self, offset: [f64; 3]) -> Self {
+        Self {
+            min: [
+                self.min[0] + offset[0],
+                self.min[1] + offset[1],
+                self.min[2] + offset[2],
+            ],
+            max: [
+                self.max[0] + offset[0],
+                self.max[1] + offset[1],
+                self.max[2] + offset[2],
+            ],
+        }
+    }
+
+    /// Blocks the box overlaps along an axis. Blocks it only touches are left out.
+    fn cells(&self, axis: usize) -> std::ops::Range<i64> {
+        (self.min[axis] + EPSILON).floor() as i64..(self.max[axis] - EPSILON).ceil() as i64
+    }
+}
+
+/// Whether the block at a position stops bodies. There is nothing but solid ground below the
+/// world, and the blocks of chunks that aren't loaded are empty.
+pub fn solid(chunks: &Chunks, x: i64, y: i64, z: i64) -> bool {
+    y < 0
+        || chunks
+            .get_block(x, y, z)
+            .is_some_and(|block| !block.transparent())
+}
+
+/// Whether the box overlaps a solid block.
+pub fn collides(chunks: &Chunks, bounds: &Aabb) -> bool {
+    bounds.cells(0).any(|x| {
+        bounds
+            .cells(1)
+            .any(|y| bounds.cells(2).any(|z| solid(chunks, x, y, z)))
+    })
+}
+
+/// How far the box can move by `distance` along an axis before hitting a solid block.
+pub fn sweep(chunks: &Chunks, bounds: &Aabb, axis: usize, distance: f64) -> f64 {
+    let (a, b) = match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    };
+    let blocked = |cell: i64| {
+        bounds.cells(a).any(|i| {
+            bounds.cells(b).any(|j| {
+                let mut position = [0; 3];
+                position[axis] = cell;
+                position[a] = i;
+                position[b] = j;
+                solid(chunks, position[0], position[1], position[2])
+            })
+        })
+    };
+
+    if distance > 0. {
+        let first = (bounds.max[axis] - EPSILON).ceil() as i64;
+        let last = (bounds.max[axis] + distance).ceil() as i64;
+        if let Some(cell) = (first..last).find(|cell| blocked(*cell)) {
+            return cell as f64 - bounds.max[axis];
+        }
+    } else if distance < 0. {
+        let first = (bounds.min[axis] + EPSILON).floor() as i64 - 1;
+        let last = (bounds.min[axis] + distance).floor() as i64;
+        if let Some(cell) = (last..=first).rev().find(|cell| blocked(*cell)) {
+            return (cell + 1) as f64 - bounds.min[axis];
+        }
+    }
+
+    distance
+}
+
+/// Move the box as far as it can by `motion`, vertically first then along x and z, so it slides
+/// along what it hits. Returns how much it moved.
+pub fn move_box(chunks: &Chunks, bounds: &Aabb, motion: [f64; 3]) -> [f64; 3] {
+    let mut bounds = *bounds;
+    let mut moved = [0.; 3];
+
+    for axis in [1, 0, 2] {
+        moved[axis] = sweep(chunks, &bounds, axis, motion[axis]);
+
+        let mut offset = [0.; 3];
+        offset[axis] = moved[axis];
+        bounds = bounds.offset(offset);
+    }
+
+    moved
+}
+
+/// A player moved by its inputs and the blocks around it. Both the client, predicting its
+/// player, and the server step it with the same inputs and chunks, and get the same result.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Body {
+    /// Position of the feet, at the bottom center of the box.
+    pub position: [f64; 3],
+    /// Vertical velocity in blocks per second, positive upwards.
+    pub velocity: f64,
+}
+
+impl Body {
+    pub fn new(position: [f64; 3]) -> Self {
+        Self {
+            position,
+            velocity: 0.,
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let [x, y, z] = self.position;
+        let half = PLAYER_WIDTH / 2.;
+
+        Aabb::new(
+            [x - half, y, z - half],
+            [x + half, y + PLAYER_HEIGHT, z + half],
+        )
+    }
+
+    /// Whether a block right below the body supports it.
+    pub fn on_ground(&self, chunks: &Chunks) -> bool {
+        sweep(chunks, &self.bounds(), 1, -GROUND_DISTANCE) > -GROUND_DISTANCE
+    }
+
+    /// Move the body by one input. Walking bodies fall, jump when the input goes up, sneak when
+    /// it goes down and climb ledges up to `STEP_HEIGHT`. Flying ones go up and down instead.
+    pub fn step(&mut self, input: &MovementInput, chunks: &Chunks) {
+        let input = input.clamped();
+        let interval = 1. / INPUTS_PER_SECOND as f64;
+        let free = movement::step(self.position, &input);
+
+        // A body in blocks, because one was placed on it or it was teleported there, moves
+        // through them until it is out.
+        if collides(chunks, &self.bounds()) {
+            self.position = free;
+            self.velocity = 0.;
+            return;
+        }
+
+        let mut motion = [
+            free[0] - self.position[0],
+            free[1] - self.position[1],
+            free[2] - self.position[2],
+        ];
+        let on_ground = self.on_ground(chunks);
+
+        if input.flying {
+            self.velocity = 0.;
+        } else {
+            if on_ground && input.up > 0. && self.velocity <= 0. {
+                self.velocity = JUMP_VELOCITY;
+            }
+            self.velocity = (self.velocity - GRAVITY * interval).max(-MAX_FALL_SPEED);
+            motion[1] = self.velocity * interval;
+
+            if input.up < 0. {
+                motion[0] *= SNEAKING_SPEED;
+                motion[2] *= SNEAKING_SPEED;
+                if on_ground {
+                    self.keep_on_edge(&mut motion, chunks);
+                }
+            }
+        }
+
+        let bounds = self.bounds();
+        let mut moved = move_box(chunks, &bounds, motion);
+
+        if on_ground && !input.flying && (moved[0] != motion[0] || moved[2] != motion[2]) {
+            let up = sweep(chunks, &bounds, 1, STEP_HEIGHT);
+            let raised = bounds.offset([0., up, 0.]);
+            let horizontal = move_box(chunks, &raised, [motion[0], 0., motion[2]]);
+            let down = sweep(chunks, &raised.offset(horizontal), 1, -up);
+
+            let stepped = [horizontal[0], up + down, horizontal[2]];
+            if stepped[0].powi(2) + stepped[2].powi(2) > moved[0].powi(2) + moved[2].powi(2) {
+                moved = stepped;
+            }
+        }
+
+        // Landing or hitting a ceiling stops the fall or the jump.
+        if moved[1] != motion[1] {
+            self.velocity = 0.;
+        }
+
+        for (position, moved) in self.position.iter_mut().zip(moved) {
+            *position += moved;
+        }
+    }
+
+    /// Shorten the horizontal movement of a sneaking body so it doesn't leave the blocks it stands
+    /// on, one axis at a time then both.
+    fn keep_on_edge(&self, motion: &mut [f64; 3], chunks: &Chunks) {
+        let bounds = self.bounds();
+        let supported =
+            |dx: f64, dz: f64| collides(chunks, &bounds.offset([dx, -GROUND_DISTANCE, dz]));
+        let shorten = |distance: f64| {
+            if distance.abs() <= EDGE_STEP {
+                0.
+            } else {
+                distance - EDGE_STEP * distance.signum()
+            }
+        };
+
+        while motion[0] != 0. && !supported(motion[0], 0.) {
+            motion[0] = shorten(motion[0]);
+        }
+        while motion[2] != 0. && !supported(0., motion[2]) {
+            motion[2] = shorten(motion[2]);
+        }
+        while motion[0] != 0. && motion[2] != 0. && !supported(motion[0], motion[2]) {
+            motion[0] = shorten(motion[0]);
+            motion[2] = shorten(motion[2]);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::access::{AccessLists, BANNED_IPS_FILE, OPERATORS_FILE};
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::server::PermissionLevel;
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+
+fn test_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-access-{name}-{}", std::process::id()))
+}
+
+/// A server stepped by the test, keeping its world and lists in a fresh directory.
+fn start(network: &LoopbackNetwork, name: &str, whitelist: bool) -> Game {
+    let _ = fs::remove_dir_all(test_dir(name));
+
+    server(network, name, whitelist)
+}
+
+/// A server reusing the directory of an earlier one.
+fn server(network: &LoopbackNetwork, name: &str, whitelist: bool) -> Game {
+    let config = ServerConfig {
+        world_dir: test_dir(name).join("world"),
+        lists_dir: test_dir(name),
+        view_distance: 1,
+        whitelist,
+        ..Default::default()
+    };
+
+    Game::new(ChannelTransport::bind(network, SERVER).unwrap(), config)
+}
+
+fn join(network: &LoopbackNetwork, ip: [u8; 4], user: &str) -> ChannelTransport {
+    let client = ChannelTransport::bind(network, SocketAddr::from((ip, 2))).unwrap();
+    connect(&client, user);
+
+    client
+}
+
+fn connect(client: &ChannelTransport, user: &str) {
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from(user),
+                compression: false,
+            },
+            &SERVER,
+        )
+        .unwrap();
+}
+
+/// Step the server until the client is welcomed or disconnected, returning the reason of the
+/// disconnection.
+fn joined(game: &mut Game, client: &ChannelTransport) -> Result<(), String> {
+    for _ in 0..200 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            match packet {
+                Packet::Welcome { .. } => return Ok(()),
+                Packet::Disconnect { reason } => return Err(reason),
+                _ => (),
+            }
+        }
+    }
+
+    panic!("the client was neither welcomed nor disconnected");
+}
+
+/// Step the server until the client receives a packet matching the predicate.
+fn step_until(
+    game: &mut Game,
+    client: &ChannelTransport,
+    predicate: impl Fn(&Packet) -> bool,
+) -> Packet {
+    for _ in 0..200 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            if predicate(&packet) {
+                return packet;
+            }
+        }
+    }
+
+    panic!("no matching packet received");
+}
+
+#[test]
+pub fn lists_are_saved() {
+    let dir = test_dir("lists");
+    let _ = fs::remove_dir_all(&dir);
+    let ip = IpAddr::from([10, 0, 0, 7]);
+
+    let mut lists = AccessLists::load(&dir).unwrap();
+    assert!(lists.add_operator("alice").unwrap());
+    assert!(!lists.add_operator("alice").unwrap());
+    assert!(lists.add_to_whitelist("bob").unwrap());
+    lists.ban_player("mallory", "Griefing").unwrap();
+    lists.ban_ip(ip, "Spam").unwrap();
+    assert!(dir.join(OPERATORS_FILE).exists());
+
+    let mut lists = AccessLists::load(&dir).unwrap();
+    assert_eq!(lists.operators().collect::<Vec<_>>(), ["alice"]);
+    assert_eq!(lists.whitelist().collect::<Vec<_>>(), ["bob"]);
+    assert_eq!(lists.player_ban("mallory"), Some("Griefing"));
+    assert_eq!(lists.ip_ban(ip), Some("Spam"));
+    assert_eq!(lists.permission("alice"), PermissionLevel::Operator);
+    assert_eq!(lists.permission("bob"), PermissionLevel::Player);
+
+    assert!(lists.pardon_ip(ip).unwrap());
+    assert!(!lists.pardon_ip(ip).unwrap());
+    assert_eq!(AccessLists::load(&dir).unwrap().ip_ban(ip), None);
+
+    // A broken list is an error, not an empty list.
+    fs::write(dir.join(BANNED_IPS_FILE), "\"not an address\" = \"Spam\"").unwrap();
+    assert!(AccessLists::load(&dir).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn check() {
+    let mut lists = AccessLists::new(test_dir("check"));
+    let ip = IpAddr::from([10, 0, 0, 7]);
+    let other = IpAddr::from([10, 0, 0, 8]);
+
+    assert_eq!(lists.check("alice", ip, false), Ok(()));
+    assert!(lists.check("alice", ip, true).is_err());
+
+    lists.add_to_whitelist("alice").unwrap();
+    assert_eq!(lists.check("alice", ip, true), Ok(()));
+
+    // Bans apply to whitelisted players too.
+    lists.ban_ip(ip, "Spam").unwrap();
+    assert_eq!(
+        lists.check("alice", ip, true),
+        Err(String::from("Your address is banned: Spam"))
+    );
+    assert_eq!(lists.check("alice", other, true), Ok(()));
+
+    lists.ban_player("alice", "Griefing").unwrap();
+    assert_eq!(
+        lists.check("alice", other, false),
+        Err(String::from("You are banned: Griefing"))
+    );
+
+    // Names match whatever their case.
+    assert_eq!(
+        lists.check("ALICE", other, false),
+        Err(String::from("You are banned: Griefing"))
+    );
+    assert!(lists.pardon_player("Alice").unwrap());
+    assert_eq!(lists.check("aLiCe", other, true), Ok(()));
+    assert!(!lists.add_to_whitelist("Alice").unwrap());
+    assert!(lists.add_operator("Bob").unwrap());
+    assert_eq!(lists.permission("bob"), PermissionLevel::Operator);
+    assert!(lists.remove_operator("BOB").unwrap());
+    assert_eq!(lists.operators().count(), 0);
+
+    fs::remove_dir_all(test_dir("check")).unwrap();
+}
+
+#[test]
+pub fn duplicate_name() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "duplicate", false);
+
+    let alice = join(&network, [127, 0, 0, 2], "alice");
+    assert_eq!(joined(&mut game, &alice), Ok(()));
+
+    let impostor = join(&network, [127, 0, 0, 3], "alice");
+    assert_eq!(
+        joined(&mut game, &impostor),
+        Err(String::from("A player named alice is already online"))
+    );
+    let impostor = join(&network, [127, 0, 0, 4], "Alice");
+    assert_eq!(
+        joined(&mut game, &impostor),
+        Err(String::from("A player named Alice is already online"))
+    );
+    assert_eq!(
+        game.execute("list"),
+        Ok(String::from("1 of 20 players online: alice"))
+    );
+}
+
+#[test]
+pub fn invalid_names() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "names", false);
+
+    let refused = Err(String::from(
+        "Names are 1 to 32 letters, digits or underscores",
+    ));
+    for (port, name) in [(2, ""), (3, "al ice"), (4, "alice!"), (5, "élise")] {
+        let client = join(&network, [127, 0, 0, port], name);
+        assert_eq!(joined(&mut game, &client), refused);
+    }
+    let client = join(&network, [127, 0, 0, 6], &"a".repeat(33));
+    assert_eq!(joined(&mut game, &client), refused);
+
+    let client = join(&network, [127, 0, 0, 7], "Alice_2");
+    assert_eq!(joined(&mut game, &client), Ok(()));
+}
+
+#[test]
+pub fn ban_and_pardon() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "ban", false);
+
+    let alice = join(&network, [127, 0, 0, 2], "alice");
+    assert_eq!(joined(&mut game, &alice), Ok(()));
+
+    game.execute("ban alice Griefing").unwrap();
+    assert_eq!(
+        step_until(&mut game, &alice, |p| matches!(
+            p,
+            Packet::Disconnect { .. }
+        )),
+        Packet::Disconnect {
+            reason: String::from("You are banned: Griefing")
+        }
+    );
+
+    // Bans are kept across restarts.
+    drop(game);
+    let network = LoopbackNetwork::default();
+    let mut game = server(&network, "ban", false);
+
+    let alice = join(&network, [127, 0, 0, 2], "alice");
+    assert_eq!(
+        joined(&mut game, &alice),
+        Err(String::from("You are banned: Griefing"))
+    );
+
+    game.execute("pardon alice").unwrap();
+    connect(&alice, "alice");
+    assert_eq!(joined(&mut game, &alice), Ok(()));
+
+    fs::remove_dir_all(test_dir("ban")).unwrap();
+}
+
+#[test]
+pub fn ban_ip() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "ban-ip", false);
+
+    let alice = join(&network, [127, 0, 0, 2], "alice");
+    assert_eq!(joined(&mut game, &alice), Ok(()));
+
+    // Banning a player by name bans its address.
+    game.execute("ban-ip alice").unwrap();
+    step_until(&mut game, &alice, |p| {
+        matches!(p, Packet::Disconnect { .. })
+    });
+
+    // Any name from the same address is refused.
+    connect(&alice, "bob");
+    assert_eq!(
+        joined(&mut game, &alice),
+        Err(String::from(
+            "Your address is banned: Banned by an operator"
+        ))
+    );
+
+    assert!(game.execute("pardon-ip localhost").is_err());
+    game.execute("pardon-ip 127.0.0.2").unwrap();
+    connect(&alice, "bob");
+    assert_eq!(joined(&mut game, &alice), Ok(()));
+
+    fs::remove_dir_all(test_dir("ban-ip")).unwrap();
+}
+
+#[test]
+pub fn whitelist() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "whitelist", true);
+
+    let alice = join(&network, [127, 0, 0, 2], "alice");
+    assert_eq!(
+        joined(&mut game, &alice),
+        Err(String::from("You are not whitelisted on this server"))
+    );
+
+    assert!(game.execute("whitelist add").is_err());
+    game.execute("whitelist add alice").unwrap();
+    assert_eq!(
+        game.execute("whitelist list"),
+        Ok(String::from("1 whitelisted players: alice"))
+    );
+
+    connect(&alice, "alice");
+    assert_eq!(joined(&mut game, &alice), Ok(()));
+
+    fs::remove_dir_all(test_dir("whitelist")).unwrap();
+}
+
+#[test]
+pub fn operators() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "operators", false);
+
+    let alice = join(&network, [127, 0, 0, 2], "alice");
+    assert_eq!(joined(&mut game, &alice), Ok(()));
+
+    let command = |line: &str| Packet::Command {
+        line: String::from(line),
+    };
+    alice.send_to(command("save"), &SERVER).unwrap();
+    step_until(
+        &mut game,
+        &alice,
+        |p| matches!(p, Packet::SystemMessage { message } if message.contains("permission")),
+    );
+
+    // Operators get their permission right away, and when they join again.
+    game.execute("op alice").unwrap();
+    alice.send_to(command("save"), &SERVER).unwrap();
+    step_until(
+        &mut game,
+        &alice,
+        |p| matches!(p, Packet::SystemMessage { message } if message.starts_with("Saved")),
+    );
+
+    drop(game);
+    let network = LoopbackNetwork::default();
+    let mut game = server(&network, "operators", false);
+    let alice = join(&network, [127, 0, 0, 2], "alice");
+    assert_eq!(joined(&mut game, &alice), Ok(()));
+
+    alice.send_to(command("deop alice"), &SERVER).unwrap();
+    step_until(
+        &mut game,
+        &alice,
+        |p| matches!(p, Packet::SystemMessage { message } if message == "You are no longer an operator"),
+    );
+    alice.send_to(command("stop"), &SERVER).unwrap();
+    step_until(
+        &mut game,
+        &alice,
+        |p| matches!(p, Packet::SystemMessage { message } if message.contains("permission")),
+    );
+    assert!(game.running());
+
+    fs::remove_dir_all(test_dir("operators")).unwrap();
+}
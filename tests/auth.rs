@@ -0,0 +1,281 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use yave::network::auth::{self, DEFAULT_ITERATIONS};
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::accounts::{Account, Accounts, AuthMode, ACCOUNTS_FILE};
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::server::{DEFAULT_TICK_RATE, LOGIN_TIMEOUT};
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+
+/// Few rounds, so the tests don't spend their time deriving keys.
+const ITERATIONS: u32 = 100;
+
+fn test_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-auth-{name}-{}", std::process::id()))
+}
+
+/// A server with accounts, where alice has the password "hunter2".
+fn start(network: &LoopbackNetwork, name: &str) -> Game {
+    let dir = test_dir(name);
+    let _ = fs::remove_dir_all(&dir);
+
+    Accounts::new(&dir)
+        .insert("alice", Account::new("hunter2", ITERATIONS))
+        .unwrap();
+
+    let config = ServerConfig {
+        world_dir: dir.join("world"),
+        lists_dir: dir,
+        view_distance: 1,
+        auth: AuthMode::Accounts,
+        ..Default::default()
+    };
+
+    Game::new(ChannelTransport::bind(network, SERVER).unwrap(), config)
+}
+
+fn join(network: &LoopbackNetwork, port: u16, user: &str) -> ChannelTransport {
+    let client = ChannelTransport::bind(network, SocketAddr::from(([127, 0, 0, 1], port))).unwrap();
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from(user),
+                compression: false,
+            },
+            &SERVER,
+        )
+        .unwrap();
+
+    client
+}
+
+/// Step the server until the client receives a packet matching the predicate.
+fn step_until(
+    game: &mut Game,
+    client: &ChannelTransport,
+    predicate: impl Fn(&Packet) -> bool,
+) -> Packet {
+    for _ in 0..400 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            if predicate(&packet) {
+                return packet;
+            }
+        }
+    }
+
+    panic!("no matching packet received");
+}
+
+fn joined_or_refused(packet: &Packet) -> bool {
+    matches!(packet, Packet::Welcome { .. } | Packet::Disconnect { .. })
+}
+
+fn refused(reason: &str) -> Packet {
+    Packet::Disconnect {
+        reason: String::from(reason),
+    }
+}
+
+/// Answer the login challenge the client receives with a password.
+fn answer(game: &mut Game, client: &ChannelTransport, user: &str, password: &str) {
+    match step_until(game, client, |p| matches!(p, Packet::AuthChallenge { .. })) {
+        Packet::AuthChallenge {
+            salt,
+            iterations,
+            nonce,
+        } => {
+            let key = auth::derive_key(password, &salt, iterations);
+            client
+                .send_to(
+                    Packet::AuthResponse {
+                        proof: auth::prove(&key, &nonce, user),
+                    },
+                    &SERVER,
+                )
+                .unwrap();
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+pub fn proof() {
+    let salt = auth::random();
+    let nonce = auth::random();
+    let key = auth::derive_key("hunter2", &salt, ITERATIONS);
+    let stored = auth::stored_key(&key);
+    let proof = auth::prove(&key, &nonce, "alice");
+
+    assert!(auth::verify(&stored, &nonce, "alice", &proof));
+    assert!(!auth::verify(&stored, &nonce, "bob", &proof));
+    assert!(!auth::verify(&stored, &auth::random(), "alice", &proof));
+
+    let wrong = auth::derive_key("hunter3", &salt, ITERATIONS);
+    assert!(!auth::verify(
+        &stored,
+        &nonce,
+        "alice",
+        &auth::prove(&wrong, &nonce, "alice")
+    ));
+
+    // The stored key can't stand in for the password.
+    assert!(!auth::verify(
+        &stored,
+        &nonce,
+        "alice",
+        &auth::prove(&stored, &nonce, "alice")
+    ));
+}
+
+#[test]
+pub fn accounts_are_saved() {
+    let dir = test_dir("saved");
+    let _ = fs::remove_dir_all(&dir);
+
+    let mut accounts = Accounts::load(&dir).unwrap();
+    let account = Account::new("hunter2", ITERATIONS);
+    accounts.insert("alice", account.clone()).unwrap();
+
+    // Only the stored key is written, and only the owner can read it.
+    let file = fs::read_to_string(dir.join(ACCOUNTS_FILE)).unwrap();
+    assert!(!file.contains("hunter2"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let metadata = fs::metadata(dir.join(ACCOUNTS_FILE)).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+
+    let mut accounts = Accounts::load(&dir).unwrap();
+    assert_eq!(accounts.get("alice"), Some(&account));
+    assert!(accounts.remove("alice").unwrap());
+    assert!(!accounts.remove("alice").unwrap());
+    assert_eq!(Accounts::load(&dir).unwrap().names().count(), 0);
+
+    fs::write(
+        dir.join(ACCOUNTS_FILE),
+        "[alice]\nsalt = \"zz\"\niterations = 1\nkey = \"00\"\n",
+    )
+    .unwrap();
+    assert!(Accounts::load(&dir).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn unknown_names() {
+    let dir = test_dir("unknown");
+    let mut accounts = Accounts::new(&dir);
+    let account = Account::new("hunter2", ITERATIONS);
+    accounts.insert("alice", account.clone()).unwrap();
+
+    assert_eq!(
+        accounts.challenge("alice"),
+        (account.salt, account.iterations)
+    );
+
+    // Made up challenges look like the one of an account that doesn't change.
+    let (salt, iterations) = accounts.challenge("bob");
+    assert_eq!(iterations, DEFAULT_ITERATIONS);
+    assert_eq!(accounts.challenge("bob"), (salt, iterations));
+    assert_ne!(accounts.challenge("carol").0, salt);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn login() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "login");
+
+    let alice = join(&network, 2, "alice");
+    answer(&mut game, &alice, "alice", "hunter2");
+
+    assert!(matches!(
+        step_until(&mut game, &alice, joined_or_refused),
+        Packet::Welcome { .. }
+    ));
+
+    fs::remove_dir_all(test_dir("login")).unwrap();
+}
+
+#[test]
+pub fn wrong_password() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "wrong");
+
+    let alice = join(&network, 2, "alice");
+    answer(&mut game, &alice, "alice", "letmein");
+
+    assert_eq!(
+        step_until(&mut game, &alice, joined_or_refused),
+        refused("Wrong name or password")
+    );
+    assert_eq!(
+        game.execute("list"),
+        Ok(String::from("0 of 20 players online: "))
+    );
+
+    fs::remove_dir_all(test_dir("wrong")).unwrap();
+}
+
+#[test]
+pub fn no_account() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "none");
+
+    // A name without an account is challenged like any other, and can't tell it has none.
+    let bob = join(&network, 2, "bob");
+    answer(&mut game, &bob, "bob", "correct horse");
+    assert_eq!(
+        step_until(&mut game, &bob, joined_or_refused),
+        refused("Wrong name or password")
+    );
+
+    // Accounts are added from the console.
+    game.execute("account add bob correct horse").unwrap();
+    assert_eq!(
+        game.execute("account list"),
+        Ok(String::from("2 accounts: alice, bob"))
+    );
+
+    let bob = join(&network, 3, "bob");
+    answer(&mut game, &bob, "bob", "correct horse");
+    assert!(matches!(
+        step_until(&mut game, &bob, joined_or_refused),
+        Packet::Welcome { .. }
+    ));
+
+    fs::remove_dir_all(test_dir("none")).unwrap();
+}
+
+#[test]
+pub fn login_timeout() {
+    let network = LoopbackNetwork::default();
+    let mut game = start(&network, "timeout");
+
+    let alice = join(&network, 2, "alice");
+    step_until(&mut game, &alice, |p| {
+        matches!(p, Packet::AuthChallenge { .. })
+    });
+
+    for _ in 0..LOGIN_TIMEOUT * DEFAULT_TICK_RATE as u64 {
+        game.step();
+    }
+
+    assert_eq!(
+        step_until(&mut game, &alice, joined_or_refused),
+        refused("Login timed out")
+    );
+
+    fs::remove_dir_all(test_dir("timeout")).unwrap();
+}
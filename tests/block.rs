@@ -1,6 +1,9 @@
-use yave::assets::Identifier;
+use std::path::Path;
+
+use yave::assets::{Identifier, ASSETS_DIR};
 use yave::client::voxel::VoxelVertex;
-use yave::world::chunk::Block;
+use yave::server::block::BlockTypes;
+use yave::world::chunk::{Block, AIR};
 
 #[test]
 pub fn voxel_vertex() {
@@ -19,3 +22,14 @@ pub fn block_encoding() {
     assert_eq!(block.y(), 2);
     assert_eq!(block.z(), 7);
 }
+
+#[test]
+pub fn block_types() {
+    let types = BlockTypes::load(Path::new(ASSETS_DIR)).unwrap();
+    let grass = Identifier::new("base", "grass");
+
+    assert!(types.contains(&grass));
+    assert!(types.get(&grass).unwrap().solid);
+    assert!(!types.get(&AIR.parse().unwrap()).unwrap().solid);
+    assert!(!types.contains(&Identifier::new("base", "nothing")));
+}
@@ -0,0 +1,152 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use yave::client::bot::{Bot, BotStats};
+use yave::client::Credentials;
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::server::accounts::{Account, Accounts, AuthMode};
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+
+fn test_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-bot-{name}-{}", std::process::id()))
+}
+
+fn bot(network: &LoopbackNetwork, password: Option<&str>, path: Vec<[f64; 3]>) -> Bot {
+    let transport = ChannelTransport::bind(network, SocketAddr::from(([127, 0, 0, 1], 2)))
+        .unwrap()
+        .connect(SERVER);
+    let credentials = Credentials {
+        username: String::from("alice"),
+        password: password.map(String::from),
+    };
+
+    Bot::connect(transport, credentials, true, path).unwrap()
+}
+
+/// Run the server and the bot until the condition holds.
+fn run_until(game: &mut Game, bot: &mut Bot, condition: impl Fn(&Bot) -> bool) {
+    for _ in 0..500 {
+        game.step();
+        bot.update().unwrap();
+        if condition(bot) {
+            return;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    panic!("the bot never got there");
+}
+
+#[test]
+pub fn walk() {
+    let network = LoopbackNetwork::default();
+    let dir = test_dir("walk");
+    let _ = fs::remove_dir_all(&dir);
+    let config = ServerConfig {
+        world_dir: dir.join("world"),
+        lists_dir: dir.clone(),
+        view_distance: 1,
+        ..Default::default()
+    };
+    let mut game = Game::new(ChannelTransport::bind(&network, SERVER).unwrap(), config);
+
+    // Walking west from the spawn point crosses into the chunk column -1. The bot first steps
+    // east within the spawn chunk, so the view around it is loaded before it leaves.
+    let mut bot = bot(&network, None, vec![[0.5, 0., 10.], [-1., 0., 10.]]);
+    run_until(&mut game, &mut bot, |bot| {
+        bot.joined() && bot.loaded_chunks() == 9
+    });
+    run_until(&mut game, &mut bot, |bot| bot.position()[0] < -0.9);
+
+    // The chunks that came into view arrive after the ones around the spawn point.
+    run_until(&mut game, &mut bot, |bot| {
+        bot.stats().chunk_latencies.len() == 12
+    });
+    assert_eq!(bot.loaded_chunks(), 9);
+
+    let stats = bot.stats();
+    assert_eq!(stats.chunks, 12);
+    assert!(stats.snapshots > 0);
+    assert_eq!(stats.lost_snapshots(), 0);
+    assert!(stats.tick_rate().is_some());
+
+    // Nothing was saved, the server never stopped.
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+pub fn login() {
+    let network = LoopbackNetwork::default();
+    let dir = test_dir("login");
+    let _ = fs::remove_dir_all(&dir);
+    Accounts::new(&dir)
+        .insert("alice", Account::new("hunter2", 100))
+        .unwrap();
+    let config = ServerConfig {
+        world_dir: dir.join("world"),
+        lists_dir: dir.clone(),
+        view_distance: 1,
+        auth: AuthMode::Accounts,
+        ..Default::default()
+    };
+    let mut game = Game::new(ChannelTransport::bind(&network, SERVER).unwrap(), config);
+
+    let mut bot = bot(&network, Some("hunter3"), Vec::new());
+    run_until(&mut game, &mut bot, |bot| bot.disconnected().is_some());
+    assert_eq!(bot.disconnected(), Some("Wrong name or password"));
+    assert!(!bot.joined());
+    drop(bot);
+
+    let mut bot = self::bot(&network, Some("hunter2"), Vec::new());
+    run_until(&mut game, &mut bot, Bot::joined);
+    assert!(bot.id().is_some());
+
+    drop(bot);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn stats() {
+    let mut stats = BotStats {
+        chunks: 2,
+        chunk_latencies: vec![Duration::from_millis(10), Duration::from_millis(30)],
+        snapshots: 18,
+        expected_snapshots: 20,
+        ticks: 19,
+        ticks_time: Duration::from_millis(950),
+    };
+    stats.merge(&BotStats {
+        chunks: 1,
+        chunk_latencies: vec![Duration::from_millis(50)],
+        snapshots: 20,
+        expected_snapshots: 20,
+        ticks: 21,
+        ticks_time: Duration::from_millis(1050),
+    });
+
+    assert_eq!(
+        stats.average_chunk_latency(),
+        Some(Duration::from_millis(30))
+    );
+    assert_eq!(stats.max_chunk_latency(), Some(Duration::from_millis(50)));
+    assert_eq!(stats.lost_snapshots(), 2);
+    assert_eq!(
+        stats.to_string(),
+        "Chunks: 3 received, latency 30 ms average, 50 ms max\n\
+         Snapshots: 38 received, 2 lost (5.0%)\n\
+         Server ticks: 20.0 per second"
+    );
+
+    assert_eq!(
+        BotStats::default().to_string(),
+        "Chunks: 0 received\n\
+         Snapshots: 0 received, 0 lost (0.0%)\n\
+         Server ticks: unknown"
+    );
+}
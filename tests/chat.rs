@@ -0,0 +1,253 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::server::{ChatLimiter, CHAT_BURST, DEFAULT_TICK_RATE};
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+
+fn world_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-chat-{name}-{}", std::process::id()))
+}
+
+/// A server stepped by the test, with alice connected.
+fn start(name: &str, network: &LoopbackNetwork) -> (Game, ChannelTransport) {
+    let _ = fs::remove_dir_all(world_dir(name));
+    let config = ServerConfig {
+        world_dir: world_dir(name),
+        ..Default::default()
+    };
+    let game = Game::new(ChannelTransport::bind(network, SERVER).unwrap(), config);
+    let alice = join(network, 2, "alice");
+
+    (game, alice)
+}
+
+fn join(network: &LoopbackNetwork, port: u16, user: &str) -> ChannelTransport {
+    let client = ChannelTransport::bind(network, SocketAddr::from(([127, 0, 0, 1], port))).unwrap();
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from(user),
+                compression: false,
+            },
+            &SERVER,
+        )
+        .unwrap();
+
+    client
+}
+
+fn chat(client: &ChannelTransport, message: &str) {
+    client
+        .send_to(
+            Packet::ChatMessage {
+                message: String::from(message),
+            },
+            &SERVER,
+        )
+        .unwrap();
+}
+
+/// Step the server until the client receives a packet matching the predicate.
+fn step_until(
+    game: &mut Game,
+    client: &ChannelTransport,
+    predicate: impl Fn(&Packet) -> bool,
+) -> Packet {
+    for _ in 0..200 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            if predicate(&packet) {
+                return packet;
+            }
+        }
+    }
+
+    panic!("no matching packet received");
+}
+
+/// Step the server until the client receives a packet matching the predicate, and collect the
+/// chat and system messages it got until then, waiting a little more for the ones sent in the
+/// same tick.
+fn messages_until(
+    game: &mut Game,
+    client: &ChannelTransport,
+    predicate: impl Fn(&Packet) -> bool,
+) -> Vec<Packet> {
+    let mut messages = Vec::new();
+    let mut matched = false;
+
+    for _ in 0..200 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(10)) {
+            matched |= predicate(&packet);
+            if matches!(packet, Packet::Chat { .. } | Packet::SystemMessage { .. }) {
+                messages.push(packet);
+            }
+        }
+
+        if matched {
+            return messages;
+        }
+    }
+
+    panic!("no matching packet received");
+}
+
+fn system_message(message: &str) -> Packet {
+    Packet::SystemMessage {
+        message: String::from(message),
+    }
+}
+
+#[test]
+pub fn broadcast() {
+    let network = LoopbackNetwork::default();
+    let (mut game, alice) = start("broadcast", &network);
+    step_until(&mut game, &alice, |p| matches!(p, Packet::Welcome { .. }));
+    let bob = join(&network, 3, "bob");
+    step_until(&mut game, &bob, |p| matches!(p, Packet::Welcome { .. }));
+
+    // Control characters are removed, the sender gets its own message back.
+    chat(&alice, " Hello\u{7} bob ");
+
+    let expected = Packet::Chat {
+        sender: String::from("alice"),
+        message: String::from("Hello bob"),
+    };
+    assert_eq!(
+        step_until(&mut game, &bob, |p| matches!(p, Packet::Chat { .. })),
+        expected
+    );
+    assert_eq!(
+        step_until(&mut game, &alice, |p| matches!(p, Packet::Chat { .. })),
+        expected
+    );
+}
+
+#[test]
+pub fn join_and_leave() {
+    let network = LoopbackNetwork::default();
+    let (mut game, alice) = start("join_and_leave", &network);
+    assert_eq!(
+        step_until(&mut game, &alice, |p| matches!(
+            p,
+            Packet::SystemMessage { .. }
+        )),
+        system_message("alice joined the game")
+    );
+
+    let bob = join(&network, 3, "bob");
+    assert_eq!(
+        step_until(&mut game, &alice, |p| matches!(
+            p,
+            Packet::SystemMessage { .. }
+        )),
+        system_message("bob joined the game")
+    );
+
+    bob.send_to(
+        Packet::Disconnect {
+            reason: String::from("Quit"),
+        },
+        &SERVER,
+    )
+    .unwrap();
+    assert_eq!(
+        step_until(&mut game, &alice, |p| matches!(
+            p,
+            Packet::SystemMessage { .. }
+        )),
+        system_message("bob left the game")
+    );
+    assert_eq!(
+        game.execute("list"),
+        Ok(String::from("1 of 20 players online: alice"))
+    );
+}
+
+#[test]
+pub fn rate_limit() {
+    let network = LoopbackNetwork::default();
+    let (mut game, alice) = start("rate_limit", &network);
+    // Skip the join announcement.
+    step_until(&mut game, &alice, |p| {
+        matches!(p, Packet::SystemMessage { .. })
+    });
+
+    for i in 0..CHAT_BURST + 1 {
+        chat(&alice, &format!("spam {i}"));
+    }
+
+    let received = messages_until(&mut game, &alice, |p| {
+        *p == system_message("You are sending messages too fast")
+    });
+    let chats = received
+        .iter()
+        .filter(|p| matches!(p, Packet::Chat { .. }))
+        .count();
+    assert_eq!(chats as u32, CHAT_BURST);
+    assert!(!received.contains(&Packet::Chat {
+        sender: String::from("alice"),
+        message: format!("spam {CHAT_BURST}"),
+    }));
+
+    // A second later one more message is allowed.
+    for _ in 0..DEFAULT_TICK_RATE {
+        game.step();
+    }
+    chat(&alice, "sorry");
+    assert_eq!(
+        messages_until(&mut game, &alice, |p| matches!(p, Packet::Chat { .. })),
+        [Packet::Chat {
+            sender: String::from("alice"),
+            message: String::from("sorry"),
+        }]
+    );
+}
+
+#[test]
+pub fn commands_from_chat() {
+    let network = LoopbackNetwork::default();
+    let (mut game, alice) = start("commands_from_chat", &network);
+    step_until(&mut game, &alice, |p| matches!(p, Packet::Welcome { .. }));
+
+    chat(&alice, "/list");
+
+    assert_eq!(
+        step_until(&mut game, &alice, |p| matches!(
+            p,
+            Packet::SystemMessage { message } if message.contains("online")
+        )),
+        system_message("1 of 20 players online: alice")
+    );
+}
+
+#[test]
+pub fn limiter_refills() {
+    let mut limiter = ChatLimiter::default();
+
+    for _ in 0..CHAT_BURST {
+        assert!(limiter.allow(0, 20));
+    }
+    assert!(!limiter.allow(0, 20));
+
+    // Tokens come back at one per second, up to the burst.
+    assert!(!limiter.allow(10, 20));
+    assert!(limiter.allow(20, 20));
+    assert!(!limiter.allow(20, 20));
+
+    let mut limiter = ChatLimiter::default();
+    assert!(limiter.allow(0, 20));
+    for _ in 0..CHAT_BURST {
+        assert!(limiter.allow(10_000, 20));
+    }
+    assert!(!limiter.allow(10_000, 20));
+}
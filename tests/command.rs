@@ -0,0 +1,444 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy_ecs::prelude::{Entity, World};
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::command::{
+    Argument, ArgumentKind, Command, CommandDispatcher, CommandError, CommandSource, Coordinate,
+};
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::server::PermissionLevel;
+use yave::world::chunk::Chunk;
+
+fn player(permission: PermissionLevel) -> CommandSource {
+    CommandSource::Player {
+        entity: Entity::from_raw(0),
+        name: String::from("alice"),
+        permission,
+    }
+}
+
+fn world_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-command-{name}-{}", std::process::id()))
+}
+
+/// A server stepped by the test, with a connected client.
+fn start(name: &str) -> (Game, ChannelTransport, SocketAddr) {
+    let network = LoopbackNetwork::default();
+    let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+    let _ = fs::remove_dir_all(world_dir(name));
+    let config = ServerConfig {
+        world_dir: world_dir(name),
+        view_distance: 1,
+        ..Default::default()
+    };
+    let game = Game::new(ChannelTransport::bind(&network, addr).unwrap(), config);
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    connect(&client, &addr, "alice");
+
+    (game, client, addr)
+}
+
+fn connect(client: &ChannelTransport, server: &SocketAddr, user: &str) {
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from(user),
+                compression: false,
+            },
+            server,
+        )
+        .unwrap();
+}
+
+/// Step the server until the client receives a packet matching the predicate.
+fn step_until(
+    game: &mut Game,
+    client: &ChannelTransport,
+    predicate: impl Fn(&Packet) -> bool,
+) -> Packet {
+    for _ in 0..200 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            if predicate(&packet) {
+                return packet;
+            }
+        }
+    }
+
+    panic!("no matching packet received");
+}
+
+#[test]
+pub fn parse_arguments() {
+    let dispatcher = CommandDispatcher::with_builtins();
+
+    let (command, arguments) = dispatcher
+        .parse(&CommandSource::Console, "/tp alice 1.5 ~ ~-2")
+        .unwrap();
+    assert_eq!(command.name, "tp");
+    assert_eq!(arguments.player("player"), Some("alice"));
+    assert_eq!(
+        arguments.coordinate("x"),
+        Some(Coordinate {
+            relative: false,
+            value: 1.5
+        })
+    );
+    assert_eq!(arguments.coordinate("y").unwrap().resolve(10.), 10.);
+    assert_eq!(arguments.coordinate("z").unwrap().resolve(10.), 8.);
+
+    // Text arguments take the rest of the line, optional ones may be left out.
+    let (_command, arguments) = dispatcher
+        .parse(&CommandSource::Console, "kick bob  go   away ")
+        .unwrap();
+    assert_eq!(arguments.text("reason"), Some("go   away"));
+
+    let (_command, arguments) = dispatcher
+        .parse(&CommandSource::Console, "kick bob")
+        .unwrap();
+    assert_eq!(arguments.text("reason"), None);
+}
+
+#[test]
+pub fn parse_errors() {
+    let dispatcher = CommandDispatcher::with_builtins();
+    let parse = |line| dispatcher.parse(&CommandSource::Console, line).err();
+
+    assert_eq!(
+        parse("fly"),
+        Some(CommandError::UnknownCommand(String::from("fly")))
+    );
+    assert!(matches!(
+        parse("tp alice 1 2"),
+        Some(CommandError::MissingArgument { argument: "z", .. })
+    ));
+    assert!(matches!(
+        parse("tp alice 1 two 3"),
+        Some(CommandError::InvalidArgument { argument: "y", .. })
+    ));
+    assert!(matches!(
+        parse("tp alice 1 2 NaN"),
+        Some(CommandError::InvalidArgument { argument: "z", .. })
+    ));
+    assert!(matches!(
+        parse("tp alice 1e300 2 3"),
+        Some(CommandError::InvalidArgument { argument: "x", .. })
+    ));
+    assert!(matches!(
+        parse("setblock 0 0 0 stone"),
+        Some(CommandError::InvalidArgument {
+            argument: "block",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse("whitelist maybe alice"),
+        Some(CommandError::InvalidArgument {
+            argument: "action",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse("list everyone"),
+        Some(CommandError::TooManyArguments { .. })
+    ));
+}
+
+#[test]
+pub fn permissions() {
+    let dispatcher = CommandDispatcher::with_builtins();
+
+    assert!(dispatcher
+        .parse(&player(PermissionLevel::Player), "list")
+        .is_ok());
+    assert_eq!(
+        dispatcher
+            .parse(&player(PermissionLevel::Player), "stop")
+            .err(),
+        Some(CommandError::PermissionDenied)
+    );
+    assert!(dispatcher
+        .parse(&player(PermissionLevel::Operator), "stop")
+        .is_ok());
+
+    let available: Vec<&str> = dispatcher
+        .available(&player(PermissionLevel::Player))
+        .map(|command| command.name)
+        .collect();
+    assert_eq!(available, ["help", "list", "worlds"]);
+}
+
+#[test]
+pub fn register_command() {
+    let mut dispatcher = CommandDispatcher::default();
+    dispatcher.register(Command {
+        name: "add",
+        description: "Add two numbers",
+        permission: PermissionLevel::Player,
+        arguments: vec![
+            Argument::required("a", ArgumentKind::Integer),
+            Argument::optional("b", ArgumentKind::Integer),
+        ],
+        handler: |_context, arguments| {
+            let sum = arguments.integer("a").unwrap() + arguments.integer("b").unwrap_or(0);
+            Ok(sum.to_string())
+        },
+    });
+
+    assert_eq!(dispatcher.get("add").unwrap().usage(), "add <a> [b]");
+
+    let mut world = World::new();
+    let source = player(PermissionLevel::Player);
+    assert_eq!(
+        dispatcher.execute(&mut world, &source, "add 2 40"),
+        Ok(String::from("42"))
+    );
+    assert_eq!(
+        dispatcher.execute(&mut world, &source, "add -1"),
+        Ok(String::from("-1"))
+    );
+}
+
+#[test]
+#[should_panic]
+pub fn register_twice() {
+    let mut dispatcher = CommandDispatcher::with_builtins();
+    let list = dispatcher.get("list").unwrap().clone();
+    dispatcher.register(list);
+}
+
+#[test]
+pub fn complete() {
+    let dispatcher = CommandDispatcher::with_builtins();
+    let mut world = World::new();
+
+    assert_eq!(
+        dispatcher.complete(&mut world, &CommandSource::Console, "s"),
+        ["save", "say", "setblock", "stop", "summon"]
+    );
+    assert!(dispatcher
+        .complete(&mut world, &player(PermissionLevel::Player), "s")
+        .is_empty());
+    assert_eq!(
+        dispatcher.complete(&mut world, &CommandSource::Console, "/tp alice "),
+        ["~"]
+    );
+    assert!(dispatcher
+        .complete(&mut world, &CommandSource::Console, "list ")
+        .is_empty());
+    assert_eq!(
+        dispatcher.complete(&mut world, &CommandSource::Console, "whitelist "),
+        ["add", "list", "remove"]
+    );
+}
+
+#[test]
+pub fn online_players() {
+    let (mut game, client, _server) = start("complete");
+    step_until(&mut game, &client, |p| matches!(p, Packet::Welcome { .. }));
+
+    assert_eq!(
+        game.execute("list"),
+        Ok(String::from("1 of 20 players online: alice"))
+    );
+    assert_eq!(game.complete("kick a"), ["alice"]);
+    assert!(game.complete("kick b").is_empty());
+    assert_eq!(game.complete("setblock 0 0 0 base:s"), ["base:stone"]);
+    assert_eq!(
+        game.execute("tp bob 0 0 0"),
+        Err(CommandError::PlayerNotFound(String::from("bob")))
+    );
+}
+
+#[test]
+pub fn teleport() {
+    let (mut game, client, _server) = start("teleport");
+    step_until(&mut game, &client, |p| matches!(p, Packet::Welcome { .. }));
+
+    game.execute("tp alice 5 ~ ~-4").unwrap();
+
+    step_until(
+        &mut game,
+        &client,
+        |p| matches!(p, Packet::PlayerState { x, y, z, .. } if (*x, *y, *z) == (5., 16., 6.)),
+    );
+
+    // Relative coordinates stop at the edge of the world instead of overflowing chunk positions.
+    game.execute("tp alice 30000000 ~ ~").unwrap();
+    game.execute("tp alice ~1000 ~ ~").unwrap();
+    step_until(
+        &mut game,
+        &client,
+        |p| matches!(p, Packet::PlayerState { x, .. } if *x == 30_000_000.),
+    );
+}
+
+#[test]
+pub fn kick() {
+    let (mut game, client, _server) = start("kick");
+    step_until(&mut game, &client, |p| matches!(p, Packet::Welcome { .. }));
+
+    game.execute("kick alice Too loud").unwrap();
+
+    assert_eq!(
+        step_until(&mut game, &client, |p| matches!(
+            p,
+            Packet::Disconnect { .. }
+        )),
+        Packet::Disconnect {
+            reason: String::from("Too loud")
+        }
+    );
+    assert_eq!(
+        game.execute("list"),
+        Ok(String::from("0 of 20 players online: "))
+    );
+}
+
+#[test]
+pub fn say() {
+    let (mut game, client, _server) = start("say");
+    // Skip the join announcement.
+    step_until(&mut game, &client, |p| {
+        matches!(p, Packet::SystemMessage { .. })
+    });
+
+    game.execute("say Hello there").unwrap();
+
+    assert_eq!(
+        step_until(&mut game, &client, |p| matches!(
+            p,
+            Packet::SystemMessage { .. }
+        )),
+        Packet::SystemMessage {
+            message: String::from("[Server] Hello there")
+        }
+    );
+}
+
+#[test]
+pub fn setblock_resends_chunk() {
+    let (mut game, client, _server) = start("setblock");
+    step_until(&mut game, &client, |p| {
+        matches!(p, Packet::Chunk { x: -1, y: 0, .. })
+    });
+
+    game.execute("setblock -1 3 2 base:grass").unwrap();
+
+    match step_until(&mut game, &client, |p| {
+        matches!(p, Packet::Chunk { x: -1, .. })
+    }) {
+        Packet::Chunk { x, y, data } => {
+            let chunk = Chunk::decompress(&data, x, y);
+            let block = chunk.get_block(15, 3, 2).unwrap();
+            assert_eq!(block.id, "base:grass");
+            assert!(!block.transparent());
+            assert_eq!(chunk.get_block(14, 3, 2).unwrap().id, "base:stone");
+        }
+        _ => unreachable!(),
+    }
+
+    assert!(game.execute("setblock 0 16 0 base:grass").is_err());
+    // Only blocks of the assets can be placed.
+    assert!(game.execute("setblock 0 3 0 base:nothing").is_err());
+}
+
+#[test]
+pub fn save_and_restore() {
+    let (mut game, client, server) = start("save");
+    step_until(&mut game, &client, |p| matches!(p, Packet::Welcome { .. }));
+
+    game.execute("setblock 0 0 0 base:grass").unwrap();
+    game.execute("tp alice 3 4 5").unwrap();
+    // Every loaded chunk is saved, not only the modified one.
+    let saved = game.execute("save").unwrap();
+    assert!(saved.starts_with("Saved "), "{saved}");
+    assert!(saved.ends_with(" chunks and 1 players"), "{saved}");
+
+    let dir = world_dir("save");
+    assert!(dir.join("chunks").join("0.0.chunk").exists());
+    assert!(dir.join("players").join("alice.toml").exists());
+
+    // Players are saved when they leave, joining again starts where they were.
+    game.execute("tp alice 7 8 9").unwrap();
+    game.execute("kick alice").unwrap();
+    connect(&client, &server, "alice");
+
+    step_until(&mut game, &client, |p| matches!(p, Packet::Welcome { .. }));
+    step_until(
+        &mut game,
+        &client,
+        |p| matches!(p, Packet::PlayerState { x, y, z, .. } if (*x, *y, *z) == (7., 8., 9.)),
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn stop() {
+    let (mut game, _client, _server) = start("stop");
+
+    assert!(game.running());
+    game.execute("stop").unwrap();
+    assert!(!game.running());
+}
+
+#[test]
+pub fn console_and_chat() {
+    let (mut game, client, server) = start("chat");
+    // Skip the join announcement.
+    step_until(&mut game, &client, |p| {
+        matches!(p, Packet::SystemMessage { .. })
+    });
+
+    // Players can't run operator commands from the chat.
+    client
+        .send_to(
+            Packet::Command {
+                line: String::from("stop"),
+            },
+            &server,
+        )
+        .unwrap();
+    assert_eq!(
+        step_until(&mut game, &client, |p| matches!(
+            p,
+            Packet::SystemMessage { .. }
+        )),
+        Packet::SystemMessage {
+            message: CommandError::PermissionDenied.to_string()
+        }
+    );
+    assert!(game.running());
+
+    client
+        .send_to(
+            Packet::Command {
+                line: String::from("list"),
+            },
+            &server,
+        )
+        .unwrap();
+    assert_eq!(
+        step_until(&mut game, &client, |p| matches!(
+            p,
+            Packet::SystemMessage { .. }
+        )),
+        Packet::SystemMessage {
+            message: String::from("1 of 20 players online: alice")
+        }
+    );
+
+    // Console lines run at the start of the next tick.
+    game.console().send(String::from("stop")).unwrap();
+    assert!(game.running());
+    game.step();
+    assert!(!game.running());
+}
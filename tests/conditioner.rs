@@ -0,0 +1,218 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use yave::client::bot::Bot;
+use yave::client::Credentials;
+use yave::network::conditioner::{ConditionedTransport, NetworkConditions};
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+const CLIENT: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2);
+
+fn ping(sequence: u32) -> Packet {
+    Packet::Command {
+        line: format!("{sequence:04}"),
+    }
+}
+
+/// A conditioned client and a plain server on a loopback network.
+fn link(conditions: &str) -> (ConditionedTransport<ChannelTransport>, ChannelTransport) {
+    let network = LoopbackNetwork::default();
+    let server = ChannelTransport::bind(&network, SERVER).unwrap();
+    let client = ConditionedTransport::new(
+        ChannelTransport::bind(&network, CLIENT).unwrap(),
+        conditions.parse().unwrap(),
+    );
+
+    (client, server)
+}
+
+/// Packets the server receives until nothing comes for a while.
+fn receive_all(server: &ChannelTransport) -> Vec<Packet> {
+    let mut packets = Vec::new();
+    while let Ok((packet, _peer)) = server.recv_timeout(Duration::from_millis(200)) {
+        packets.push(packet);
+    }
+
+    packets
+}
+
+#[test]
+pub fn parse() {
+    let conditions: NetworkConditions =
+        "latency=100ms, jitter=20, loss=5%,reorder=0.5%,bandwidth=512kbps"
+            .parse()
+            .unwrap();
+    assert_eq!(
+        conditions,
+        NetworkConditions {
+            latency: Duration::from_millis(100),
+            jitter: Duration::from_millis(20),
+            loss: 0.05,
+            reorder: 0.005,
+            bandwidth: Some(512),
+        }
+    );
+    assert_eq!(
+        conditions.to_string(),
+        "latency=100ms,jitter=20ms,loss=5%,reorder=0.5%,bandwidth=512kbps"
+    );
+    assert_eq!(conditions.to_string().parse(), Ok(conditions));
+    assert_eq!("".parse(), Ok(NetworkConditions::default()));
+
+    assert!("latency".parse::<NetworkConditions>().is_err());
+    assert!("delay=10ms".parse::<NetworkConditions>().is_err());
+    assert!("loss=150%".parse::<NetworkConditions>().is_err());
+    assert!("jitter=-5ms".parse::<NetworkConditions>().is_err());
+    assert!("bandwidth=0".parse::<NetworkConditions>().is_err());
+
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        NetworkConditions::from_args(&args(&["yave", "--network-conditions", "loss=1%"])),
+        Ok(Some(NetworkConditions {
+            loss: 0.01,
+            ..Default::default()
+        }))
+    );
+    assert!(NetworkConditions::from_args(&args(&["yave", "--network-conditions"])).is_err());
+}
+
+#[test]
+pub fn latency() {
+    let (client, server) = link("latency=50ms");
+
+    let start = Instant::now();
+    client.send_to(ping(1), &SERVER).unwrap();
+    assert_eq!(
+        server.recv_timeout(Duration::from_secs(1)).unwrap(),
+        (ping(1), CLIENT)
+    );
+    let sent = start.elapsed();
+    assert!(sent >= Duration::from_millis(50), "{sent:?}");
+
+    // Received packets are delayed too.
+    let start = Instant::now();
+    server.send_to(ping(2), &CLIENT).unwrap();
+    assert_eq!(client.recv_from().unwrap(), (ping(2), SERVER));
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    // Without reordering the packets keep their order whatever the jitter.
+    let (client, server) = link("latency=10ms,jitter=10ms");
+    for sequence in 0..50 {
+        client.send_to(ping(sequence), &SERVER).unwrap();
+    }
+    assert_eq!(receive_all(&server), (0..50).map(ping).collect::<Vec<_>>());
+}
+
+#[test]
+pub fn loss() {
+    let (client, server) = link("loss=100%");
+    client.send_to(ping(1), &SERVER).unwrap();
+    assert!(receive_all(&server).is_empty());
+
+    let (client, server) = link("loss=50%");
+    for sequence in 0..200 {
+        client.send_to(ping(sequence), &SERVER).unwrap();
+    }
+    let received = receive_all(&server).len();
+    assert!((50..150).contains(&received), "{received} received");
+}
+
+#[test]
+pub fn reorder() {
+    let (client, server) = link("reorder=50%");
+    for sequence in 0..100 {
+        client.send_to(ping(sequence), &SERVER).unwrap();
+    }
+
+    let mut received = receive_all(&server);
+    assert_ne!(received, (0..100).map(ping).collect::<Vec<_>>());
+
+    // Nothing is lost.
+    received.sort_by_key(|packet| match packet {
+        Packet::Command { line } => line.clone(),
+        _ => unreachable!(),
+    });
+    assert_eq!(received, (0..100).map(ping).collect::<Vec<_>>());
+}
+
+#[test]
+pub fn bandwidth() {
+    // 400 kbps sends 50 kB every second.
+    let (client, server) = link("bandwidth=400kbps");
+    let line = "x".repeat(240);
+
+    let start = Instant::now();
+    for _ in 0..40 {
+        let packet = Packet::Command { line: line.clone() };
+        client.send_to(packet, &SERVER).unwrap();
+    }
+    for _ in 0..40 {
+        server.recv_timeout(Duration::from_secs(1)).unwrap();
+    }
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(180), "{elapsed:?}");
+}
+
+#[test]
+pub fn close() {
+    let (client, server) = link("latency=50ms");
+    client.send_to(ping(1), &SERVER).unwrap();
+    client.close();
+
+    // Closing doesn't lose the packets sent before.
+    assert_eq!(
+        server.recv_timeout(Duration::from_secs(1)).unwrap(),
+        (ping(1), CLIENT)
+    );
+    assert!(client.recv_from().is_err());
+}
+
+#[test]
+pub fn join() {
+    let network = LoopbackNetwork::default();
+    let dir = std::env::temp_dir().join(format!("yave-conditioner-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let config = ServerConfig {
+        world_dir: dir.join("world"),
+        lists_dir: dir.clone(),
+        view_distance: 1,
+        ..Default::default()
+    };
+    let conditions = "latency=20ms,jitter=10ms,reorder=10%".parse().unwrap();
+    let server = ConditionedTransport::new(
+        ChannelTransport::bind(&network, SERVER).unwrap(),
+        conditions,
+    );
+    let mut game = Game::new(server, config);
+
+    // An encrypted client still gets in and loads its chunks over the bad network.
+    let credentials = Credentials {
+        username: String::from("alice"),
+        password: None,
+    };
+    let transport = ChannelTransport::bind(&network, CLIENT)
+        .unwrap()
+        .connect(SERVER);
+    let mut bot = Bot::connect(transport, credentials, true, Vec::new()).unwrap();
+
+    for _ in 0..500 {
+        game.step();
+        bot.update().unwrap();
+        if bot.joined() && bot.loaded_chunks() == 9 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert!(bot.joined());
+    assert_eq!(bot.loaded_chunks(), 9);
+
+    let _ = fs::remove_dir_all(dir);
+}
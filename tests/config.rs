@@ -0,0 +1,247 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+
+use yave::server::accounts::AuthMode;
+use yave::server::config::{ConfigError, ServerConfig, WorldConfig};
+use yave::world::generator::Generator;
+
+/// Empty directory for a test, removed if it already exists.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("yave-config-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+pub fn created_on_first_start() {
+    let path = test_dir("create").join("server.toml");
+
+    let created = ServerConfig::load_or_create(&path).unwrap();
+    assert!(path.exists());
+    assert_eq!(
+        ServerConfig {
+            seed: 0,
+            ..created.clone()
+        },
+        ServerConfig::default()
+    );
+
+    // The random seed is kept.
+    assert_eq!(ServerConfig::load_or_create(&path).unwrap(), created);
+}
+
+#[test]
+pub fn missing_fields_use_defaults() {
+    let path = test_dir("partial").join("server.toml");
+    fs::write(&path, "port = 30000\nmotd = \"Hello\"\n").unwrap();
+
+    let config = ServerConfig::load_or_create(&path).unwrap();
+    assert_eq!(config.port, 30000);
+    assert_eq!(config.motd, "Hello");
+    assert_eq!(config.max_players, ServerConfig::default().max_players);
+}
+
+#[test]
+pub fn invalid_file() {
+    let dir = test_dir("invalid");
+
+    let path = dir.join("syntax.toml");
+    fs::write(&path, "port = \"not a number\"").unwrap();
+    assert!(matches!(
+        ServerConfig::load_or_create(&path),
+        Err(ConfigError::Parse(_))
+    ));
+
+    let path = dir.join("unknown.toml");
+    fs::write(&path, "prot = 25000").unwrap();
+    assert!(matches!(
+        ServerConfig::load_or_create(&path),
+        Err(ConfigError::Parse(_))
+    ));
+
+    let path = dir.join("range.toml");
+    fs::write(&path, "view_distance = 0").unwrap();
+    assert!(matches!(
+        ServerConfig::load_or_create(&path),
+        Err(ConfigError::Invalid {
+            field: "view_distance",
+            ..
+        })
+    ));
+}
+
+#[test]
+pub fn command_line_overrides() {
+    let mut config = ServerConfig::default();
+    config
+        .apply_args(&args(&[
+            "--bind",
+            "127.0.0.1",
+            "--port",
+            "30000",
+            "--max-players",
+            "5",
+            "--view-distance",
+            "8",
+            "--tick-rate",
+            "30",
+            "--world",
+            "other",
+            "--seed",
+            "-42",
+            "--generator",
+            "hills",
+            "--motd",
+            "Welcome",
+            "--whitelist",
+            "--lists-dir",
+            "lists",
+            "--auth",
+            "accounts",
+            "--lan",
+            "--send-budget",
+            "4096",
+            "--tracking-range",
+            "16",
+            "--allow-flight",
+        ]))
+        .unwrap();
+
+    assert_eq!(
+        config,
+        ServerConfig {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 30000,
+            max_players: 5,
+            view_distance: 8,
+            tick_rate: 30,
+            world_dir: PathBuf::from("other"),
+            seed: -42,
+            generator: Generator::Hills,
+            motd: String::from("Welcome"),
+            whitelist: true,
+            lists_dir: PathBuf::from("lists"),
+            auth: AuthMode::Accounts,
+            lan: true,
+            send_budget: 4096,
+            tracking_range: 16,
+            allow_flight: true,
+            worlds: Vec::new(),
+        }
+    );
+}
+
+#[test]
+pub fn invalid_arguments() {
+    let mut config = ServerConfig::default();
+
+    assert!(matches!(
+        config.apply_args(&args(&["--port", "big"])),
+        Err(ConfigError::InvalidArgument { .. })
+    ));
+    assert!(matches!(
+        config.apply_args(&args(&["--port"])),
+        Err(ConfigError::MissingValue(_))
+    ));
+    assert!(matches!(
+        config.apply_args(&args(&["--tick-rate", "0"])),
+        Err(ConfigError::Invalid {
+            field: "tick_rate",
+            ..
+        })
+    ));
+    assert!(matches!(
+        ServerConfig::default().apply_args(&args(&["--send-budget", "0"])),
+        Err(ConfigError::Invalid {
+            field: "send_budget",
+            ..
+        })
+    ));
+    assert!(matches!(
+        config.apply_args(&args(&["--prot", "30000"])),
+        Err(ConfigError::UnknownArgument(arg)) if arg == "--prot"
+    ));
+
+    // Values are never read as flags.
+    let mut config = ServerConfig::default();
+    config.apply_args(&args(&["--motd", "--lan"])).unwrap();
+    assert_eq!(config.motd, "--lan");
+    assert!(!config.lan);
+}
+
+#[test]
+pub fn worlds() {
+    let dir = test_dir("worlds");
+    let path = dir.join("server.toml");
+    fs::write(
+        &path,
+        "world_dir = \"world\"\n\
+         \n\
+         [[worlds]]\n\
+         name = \"nether\"\n\
+         generator = \"hills\"\n\
+         seed = 7\n\
+         \n\
+         [[worlds]]\n\
+         name = \"creative\"\n\
+         dir = \"creative\"\n",
+    )
+    .unwrap();
+
+    let config = ServerConfig::load_or_create(&path).unwrap();
+    assert_eq!(
+        config.worlds,
+        vec![
+            WorldConfig {
+                name: String::from("nether"),
+                generator: Generator::Hills,
+                seed: 7,
+                dir: None,
+            },
+            WorldConfig {
+                name: String::from("creative"),
+                generator: Generator::Flat,
+                seed: 0,
+                dir: Some(PathBuf::from("creative")),
+            },
+        ]
+    );
+    assert_eq!(
+        config.worlds[0].dir(&config.world_dir),
+        PathBuf::from("world/worlds/nether")
+    );
+    assert_eq!(
+        config.worlds[1].dir(&config.world_dir),
+        PathBuf::from("creative")
+    );
+
+    for invalid in ["overworld", "../escape", ""] {
+        fs::write(&path, format!("[[worlds]]\nname = {invalid:?}\n")).unwrap();
+        assert!(matches!(
+            ServerConfig::load_or_create(&path),
+            Err(ConfigError::Invalid {
+                field: "worlds",
+                ..
+            })
+        ));
+    }
+
+    fs::write(
+        &path,
+        "[[worlds]]\nname = \"nether\"\n\n[[worlds]]\nname = \"nether\"\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        ServerConfig::load_or_create(&path),
+        Err(ConfigError::Invalid {
+            field: "worlds",
+            ..
+        })
+    ));
+}
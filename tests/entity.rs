@@ -0,0 +1,187 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use yave::assets::Identifier;
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::config::{ServerConfig, WorldConfig};
+use yave::server::entity::{Behavior, EntityTypes};
+use yave::server::game::Game;
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+const CLIENT: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2);
+
+fn world_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-entity-{name}-{}", std::process::id()))
+}
+
+/// A server stepped by the test, with alice connected and welcomed.
+fn start(name: &str) -> (Game, ChannelTransport) {
+    let network = LoopbackNetwork::default();
+    let _ = fs::remove_dir_all(world_dir(name));
+    let config = ServerConfig {
+        world_dir: world_dir(name),
+        view_distance: 1,
+        tracking_range: 16,
+        worlds: vec![WorldConfig {
+            name: String::from("nether"),
+            generator: Default::default(),
+            seed: 0,
+            dir: None,
+        }],
+        ..Default::default()
+    };
+    let mut game = Game::new(ChannelTransport::bind(&network, SERVER).unwrap(), config);
+
+    let client = ChannelTransport::bind(&network, CLIENT).unwrap();
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: false,
+            },
+            &SERVER,
+        )
+        .unwrap();
+    step_until(&mut game, &client, |p| matches!(p, Packet::Welcome { .. }));
+
+    (game, client)
+}
+
+/// Step the server until the client receives a packet matching the predicate.
+fn step_until(
+    game: &mut Game,
+    client: &ChannelTransport,
+    predicate: impl Fn(&Packet) -> bool,
+) -> Packet {
+    for _ in 0..200 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            if predicate(&packet) {
+                return packet;
+            }
+        }
+    }
+
+    panic!("no matching packet received");
+}
+
+/// Step the server a few times, checking the client receives no packet matching the predicate.
+fn never(game: &mut Game, client: &ChannelTransport, predicate: impl Fn(&Packet) -> bool) {
+    for _ in 0..20 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            assert!(!predicate(&packet), "unexpected packet {packet:?}");
+        }
+    }
+}
+
+/// Positions of an entity in the snapshots received until `count` of them had it.
+fn positions(game: &mut Game, client: &ChannelTransport, id: u32, count: usize) -> Vec<[f64; 3]> {
+    let mut positions = Vec::new();
+    while positions.len() < count {
+        if let Packet::Snapshot { entities, .. } = step_until(
+            game,
+            client,
+            |p| matches!(p, Packet::Snapshot { entities, .. } if entities.iter().any(|entity| entity.id == id)),
+        ) {
+            let entity = entities.iter().find(|entity| entity.id == id).unwrap();
+            positions.push(entity.position.unwrap());
+        }
+    }
+
+    positions
+}
+
+#[test]
+pub fn registered_from_assets() {
+    let types = EntityTypes::load(Path::new("assets")).unwrap();
+    let wanderer = types.get(&Identifier::new("base", "wanderer")).unwrap();
+
+    assert_eq!(wanderer.behavior, Behavior::Wander);
+    assert!(wanderer.speed > 0.);
+    assert!(types.get(&Identifier::new("base", "stone")).is_none());
+}
+
+#[test]
+pub fn summon_and_despawn() {
+    let (mut game, client) = start("summon");
+
+    assert!(game.execute("summon base:nothing 0 0 0").is_err());
+    assert_eq!(game.complete("summon base:w"), ["base:wanderer"]);
+
+    let summoned = game.execute("summon base:wanderer 4 0 10").unwrap();
+    assert!(
+        summoned.starts_with("Summoned base:wanderer "),
+        "{summoned}"
+    );
+
+    let id = match step_until(&mut game, &client, |p| {
+        matches!(p, Packet::SpawnEntity { .. })
+    }) {
+        Packet::SpawnEntity { id, kind, x, y, z } => {
+            assert_eq!(kind, "base:wanderer");
+            assert_eq!((x, y, z), (4., 0., 10.));
+            id
+        }
+        _ => unreachable!(),
+    };
+
+    // The client never acknowledges snapshots, they all have the full state of the entity.
+    let positions = positions(&mut game, &client, id, 10);
+    assert!(positions.windows(2).any(|pair| pair[0] != pair[1]));
+    assert!(positions
+        .iter()
+        .all(|[x, _y, z]| { (x - 4.).powi(2) + (z - 10.).powi(2) <= 8f64.powi(2) + 1e-9 }));
+
+    game.execute(&format!("despawn {id}")).unwrap();
+    step_until(&mut game, &client, |p| *p == Packet::DespawnEntity { id });
+    assert!(game.execute(&format!("despawn {id}")).is_err());
+    never(
+        &mut game,
+        &client,
+        |p| matches!(p, Packet::Snapshot { entities, .. } if entities.iter().any(|entity| entity.id == id)),
+    );
+}
+
+#[test]
+pub fn tracking_range() {
+    let (mut game, client) = start("tracking");
+
+    // Too far from alice, she is only told about it once she comes close.
+    game.execute("summon base:wanderer 100 0 100").unwrap();
+    never(&mut game, &client, |p| {
+        matches!(p, Packet::SpawnEntity { .. })
+    });
+
+    game.execute("tp alice 95 0 95").unwrap();
+    let id = match step_until(&mut game, &client, |p| {
+        matches!(p, Packet::SpawnEntity { .. })
+    }) {
+        Packet::SpawnEntity { id, .. } => id,
+        _ => unreachable!(),
+    };
+
+    game.execute("tp alice 0 0 10").unwrap();
+    step_until(&mut game, &client, |p| *p == Packet::DespawnEntity { id });
+
+    // Entities of other worlds are never tracked.
+    game.execute("summon base:wanderer 0 0 10").unwrap();
+    let id = match step_until(&mut game, &client, |p| {
+        matches!(p, Packet::SpawnEntity { .. })
+    }) {
+        Packet::SpawnEntity { id, .. } => id,
+        _ => unreachable!(),
+    };
+    game.execute("world alice nether").unwrap();
+    step_until(&mut game, &client, |p| {
+        matches!(p, Packet::ChangeWorld { .. })
+    });
+    never(&mut game, &client, |p| {
+        matches!(p, Packet::SpawnEntity { .. })
+            || matches!(p, Packet::Snapshot { entities, .. } if entities.iter().any(|entity| entity.id == id))
+    });
+}
@@ -0,0 +1,110 @@
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+use yave::client::interpolation::{interpolate, tick_time, InterpolationBuffer, ServerClock};
+use yave::network::snapshot::EntityState;
+
+fn state(x: f64, velocity: f32) -> EntityState {
+    EntityState {
+        position: [x, 0., 0.],
+        rotation: [0., 0.],
+        velocity: [velocity, 0., 0.],
+    }
+}
+
+fn millis(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+pub fn interpolates_between_states() {
+    let mut buffer = InterpolationBuffer::default();
+    buffer.push(millis(0), state(0., 20.));
+    buffer.push(millis(50), state(1., 20.));
+    buffer.push(millis(100), state(3., 40.));
+
+    let max = millis(200);
+    assert_eq!(buffer.sample(millis(25), max).unwrap().position[0], 0.5);
+    assert_eq!(buffer.sample(millis(50), max).unwrap().position[0], 1.);
+    assert_eq!(buffer.sample(millis(75), max).unwrap().position[0], 2.);
+    assert_eq!(buffer.sample(millis(75), max).unwrap().velocity[0], 30.);
+}
+
+#[test]
+pub fn clamps_before_first_state() {
+    let mut buffer = InterpolationBuffer::default();
+    assert!(buffer.sample(millis(0), millis(200)).is_none());
+
+    buffer.push(millis(100), state(5., 0.));
+    assert_eq!(buffer.sample(millis(0), millis(200)), Some(state(5., 0.)));
+}
+
+#[test]
+pub fn extrapolation_limit() {
+    let mut buffer = InterpolationBuffer::default();
+    buffer.push(millis(0), state(0., 10.));
+
+    let max = millis(200);
+    assert_eq!(buffer.sample(millis(100), max).unwrap().position[0], 1.);
+    assert_eq!(buffer.sample(millis(200), max).unwrap().position[0], 2.);
+    // Past the limit the entity stops instead of flying away.
+    assert_eq!(buffer.sample(millis(1000), max).unwrap().position[0], 2.);
+}
+
+#[test]
+pub fn ignores_old_states() {
+    let mut buffer = InterpolationBuffer::default();
+    buffer.push(millis(100), state(1., 0.));
+    buffer.push(millis(50), state(100., 0.));
+    buffer.push(millis(100), state(100., 0.));
+
+    assert_eq!(buffer.len(), 1);
+    assert_eq!(buffer.sample(millis(100), millis(0)), Some(state(1., 0.)));
+}
+
+#[test]
+pub fn discards_used_states() {
+    let mut buffer = InterpolationBuffer::default();
+    for tick in 0..5 {
+        buffer.push(tick_time(tick, 20), state(tick as f64, 0.));
+    }
+
+    buffer.discard_before(millis(120));
+
+    // The state before the sampled time is kept to interpolate from.
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(
+        buffer.sample(millis(125), millis(0)).unwrap().position[0],
+        2.5
+    );
+}
+
+#[test]
+pub fn yaw_takes_shortest_path() {
+    let mut from = state(0., 0.);
+    let mut to = state(0., 0.);
+    from.rotation[0] = PI - 0.1;
+    to.rotation[0] = -PI + 0.1;
+
+    let yaw = interpolate(&from, &to, 0.5).rotation[0];
+    assert!((yaw - PI).abs() < 1e-5);
+}
+
+#[test]
+pub fn server_clock() {
+    let start = Instant::now();
+    let mut clock = ServerClock::default();
+    assert!(clock.now(start).is_none());
+
+    clock.update(tick_time(20, 20), start);
+    assert_eq!(clock.now(start + millis(100)), Some(millis(1100)));
+
+    // Jitter is smoothed out.
+    clock.update(millis(1200), start + millis(100));
+    let now = clock.now(start + millis(100)).unwrap();
+    assert!(now > millis(1100) && now < millis(1200));
+
+    // Big differences reset the clock.
+    clock.update(millis(5000), start + millis(100));
+    assert_eq!(clock.now(start + millis(100)), Some(millis(5000)));
+}
@@ -0,0 +1,141 @@
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use yave::network::lan::{DiscoveredServer, LanAnnouncer, LanDiscovery, LAN_GROUP};
+use yave::network::transport::{Transport, UdpTransport};
+use yave::network::{Packet, PROTOCOL_VERSION};
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+
+/// Group only used by one test, so tests running at the same time don't hear each other.
+fn group(port: u16) -> SocketAddrV4 {
+    SocketAddrV4::new(*LAN_GROUP.ip(), port)
+}
+
+fn announcement(motd: &str, port: u16) -> Packet {
+    Packet::LanAnnouncement {
+        protocol: PROTOCOL_VERSION,
+        motd: String::from(motd),
+        port,
+        online: 3,
+        max_players: 10,
+    }
+}
+
+/// Wait until a server listening on `port` is discovered.
+fn discovered(discovery: &LanDiscovery, port: u16, mut step: impl FnMut()) -> DiscoveredServer {
+    let start = Instant::now();
+
+    while start.elapsed() < Duration::from_secs(5) {
+        step();
+        if let Some(server) = discovery
+            .servers()
+            .into_iter()
+            .find(|server| server.addr.port() == port)
+        {
+            return server;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    panic!("no server discovered on port {port}");
+}
+
+#[test]
+pub fn discover() {
+    let group = group(25101);
+    let discovery = LanDiscovery::listen(Ipv4Addr::LOCALHOST, group).unwrap();
+    let announcer = LanAnnouncer::new(Ipv4Addr::LOCALHOST, group, 30000).unwrap();
+
+    let server = discovered(&discovery, 30000, || {
+        announcer
+            .announce(&announcement("Test server", 30000))
+            .unwrap()
+    });
+
+    // The address is the one announcements come from, with the announced port.
+    assert_eq!(server.addr, SocketAddr::from(([127, 0, 0, 1], 30000)));
+    assert_eq!(server.protocol, PROTOCOL_VERSION);
+    assert_eq!(server.motd, "Test server");
+    assert_eq!((server.online, server.max_players), (3, 10));
+
+    // Announcing again updates the server instead of adding another one.
+    announcer.announce(&announcement("Renamed", 30000)).unwrap();
+    let start = Instant::now();
+    while discovery.servers()[0].motd != "Renamed" {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(discovery.servers().len(), 1);
+}
+
+#[test]
+pub fn ignore_invalid() {
+    let group = group(25102);
+    let discovery = LanDiscovery::listen(Ipv4Addr::LOCALHOST, group).unwrap();
+    let announcer = LanAnnouncer::new(Ipv4Addr::LOCALHOST, group, 30001).unwrap();
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_multicast_loop_v4(true).unwrap();
+    let garbage = socket2::SockRef::from(&socket);
+    garbage.set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+
+    discovered(&discovery, 30001, || {
+        socket.send_to(&[0xff, 1, 2, 3], group).unwrap();
+        socket
+            .send_to(&Packet::StatusRequest { token: 1 }.encode().unwrap(), group)
+            .unwrap();
+        announcer
+            .announce(&announcement("Test server", 30001))
+            .unwrap();
+    });
+
+    assert_eq!(discovery.servers().len(), 1);
+}
+
+#[test]
+pub fn display() {
+    let server = DiscoveredServer {
+        addr: SocketAddr::from(([192, 168, 1, 20], 25000)),
+        protocol: PROTOCOL_VERSION + 1,
+        motd: String::from("Test server"),
+        online: 3,
+        max_players: 10,
+        last_seen: Instant::now(),
+    };
+
+    assert_eq!(
+        server.to_string(),
+        format!(
+            "192.168.1.20:25000: Test server (3/10 players online), incompatible protocol {}",
+            PROTOCOL_VERSION + 1
+        )
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn server_announces() {
+    let discovery = LanDiscovery::listen(Ipv4Addr::LOCALHOST, LAN_GROUP).unwrap();
+
+    let transport = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let port = transport.local_addr().unwrap().port();
+    let mut game = Game::new(
+        transport,
+        ServerConfig {
+            bind_address: Ipv4Addr::LOCALHOST.into(),
+            motd: String::from("LAN server"),
+            max_players: 5,
+            view_distance: 1,
+            world_dir: std::env::temp_dir().join(format!("yave-lan-{}", std::process::id())),
+            lan: true,
+            ..Default::default()
+        },
+    );
+
+    let server = discovered(&discovery, port, || game.step());
+
+    assert_eq!(server.addr, SocketAddr::from(([127, 0, 0, 1], port)));
+    assert_eq!(server.motd, "LAN server");
+    assert_eq!((server.online, server.max_players), (0, 5));
+}
@@ -0,0 +1,461 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::server::INPUT_BURST;
+use yave::world::movement::{MovementInput, INPUTS_PER_SECOND, MOVEMENT_SPEED};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn world_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-loopback-{name}-{}", std::process::id()))
+}
+
+fn start_server(network: &LoopbackNetwork, name: &str) -> SocketAddr {
+    start_server_with(
+        network,
+        ServerConfig {
+            world_dir: world_dir(name),
+            ..Default::default()
+        },
+    )
+}
+
+fn start_server_with(network: &LoopbackNetwork, config: ServerConfig) -> SocketAddr {
+    let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+    let server = ChannelTransport::bind(network, addr).unwrap();
+    let _ = fs::remove_dir_all(&config.world_dir);
+
+    thread::spawn(move || Game::new(server, config).run().unwrap());
+
+    addr
+}
+
+fn connect(client: &ChannelTransport, server: &SocketAddr, user: &str) {
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from(user),
+                compression: false,
+            },
+            server,
+        )
+        .unwrap();
+}
+
+/// Wait for the first packet matching the predicate, skipping the others.
+fn expect(client: &ChannelTransport, predicate: impl Fn(&Packet) -> bool) -> Packet {
+    loop {
+        let (packet, _peer) = client.recv_timeout(TIMEOUT).unwrap();
+        if predicate(&packet) {
+            return packet;
+        }
+    }
+}
+
+#[test]
+pub fn bind_twice() {
+    let network = LoopbackNetwork::default();
+    let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+
+    let _first = ChannelTransport::bind(&network, addr).unwrap();
+    assert!(ChannelTransport::bind(&network, addr).is_err());
+}
+
+#[test]
+pub fn connect_and_receive_chunks() {
+    let network = LoopbackNetwork::default();
+    let server = start_server(&network, "connect_and_receive_chunks");
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2)))
+        .unwrap()
+        .connect(server);
+
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: true,
+            },
+            &server,
+        )
+        .unwrap();
+
+    assert_eq!(
+        expect(&client, |p| matches!(p, Packet::Compression { .. })),
+        Packet::Compression { threshold: 256 }
+    );
+    assert_eq!(
+        expect(&client, |p| matches!(p, Packet::OnlinePlayers { .. })),
+        Packet::OnlinePlayers { players: vec![] }
+    );
+
+    match expect(&client, |p| matches!(p, Packet::Chunk { .. })) {
+        Packet::Chunk { x, y, .. } => assert_eq!((x, y), (0, 0)),
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+pub fn second_player_sees_first() {
+    let network = LoopbackNetwork::default();
+    let server = start_server(&network, "second_player_sees_first");
+
+    let alice = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    let bob = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 3))).unwrap();
+
+    for (client, user) in [(&alice, "alice"), (&bob, "bob")] {
+        client
+            .send_to(
+                Packet::Connection {
+                    user: String::from(user),
+                    compression: false,
+                },
+                &server,
+            )
+            .unwrap();
+        expect(client, |p| matches!(p, Packet::OnlinePlayers { .. }));
+    }
+
+    assert!(matches!(
+        expect(&alice, |p| matches!(p, Packet::PlayerJoined { .. })),
+        Packet::PlayerJoined { name, .. } if name == "bob"
+    ));
+}
+
+#[test]
+pub fn snapshots_use_acknowledged_baseline() {
+    let network = LoopbackNetwork::default();
+    let server = start_server(&network, "snapshots_use_acknowledged_baseline");
+
+    let alice = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    let bob = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 3))).unwrap();
+
+    for (client, user) in [(&alice, "alice"), (&bob, "bob")] {
+        client
+            .send_to(
+                Packet::Connection {
+                    user: String::from(user),
+                    compression: false,
+                },
+                &server,
+            )
+            .unwrap();
+        expect(client, |p| matches!(p, Packet::OnlinePlayers { .. }));
+    }
+
+    // Bob is sent in full until alice acknowledges a snapshot containing him.
+    let tick = match expect(
+        &alice,
+        |p| matches!(p, Packet::Snapshot { entities, .. } if !entities.is_empty()),
+    ) {
+        Packet::Snapshot {
+            tick,
+            baseline: None,
+            entities,
+            ..
+        } => {
+            assert_eq!(entities.len(), 1);
+            assert!(entities[0].position.is_some());
+            tick
+        }
+        packet => panic!("unexpected packet {packet:?}"),
+    };
+
+    alice
+        .send_to(Packet::SnapshotAck { tick }, &server)
+        .unwrap();
+
+    // Bob doesn't move, so once the baseline is used the snapshots are empty.
+    let packet = expect(&alice, |p| {
+        matches!(
+            p,
+            Packet::Snapshot {
+                baseline: Some(_),
+                ..
+            }
+        )
+    });
+    assert_eq!(
+        packet,
+        Packet::Snapshot {
+            tick: match packet {
+                Packet::Snapshot { tick, .. } => tick,
+                _ => unreachable!(),
+            },
+            baseline: Some(tick),
+            entities: vec![],
+            removed: vec![],
+        }
+    );
+}
+
+#[test]
+pub fn server_simulates_inputs() {
+    let network = LoopbackNetwork::default();
+    let server = start_server(&network, "server_simulates_inputs");
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: false,
+            },
+            &server,
+        )
+        .unwrap();
+    expect(&client, |p| matches!(p, Packet::OnlinePlayers { .. }));
+
+    // Out of range inputs are clamped and duplicates are simulated once.
+    let input = MovementInput {
+        forward: 1000.,
+        ..Default::default()
+    };
+    for sequence in [1, 2, 2, 3] {
+        client
+            .send_to(Packet::Input { sequence, input }, &server)
+            .unwrap();
+    }
+
+    let packet = expect(
+        &client,
+        |p| matches!(p, Packet::PlayerState { sequence, .. } if *sequence == 3),
+    );
+    let distance = 3. * MOVEMENT_SPEED / INPUTS_PER_SECOND as f64;
+    match packet {
+        Packet::PlayerState { x, y, z, .. } => {
+            assert!((x - distance).abs() < 1e-9);
+            assert_eq!((y, z), (16., 10.));
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+pub fn inputs_far_ahead() {
+    let network = LoopbackNetwork::default();
+    let server = start_server(&network, "inputs_far_ahead");
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    connect(&client, &server, "alice");
+    expect(&client, |p| matches!(p, Packet::OnlinePlayers { .. }));
+
+    // An input with a sequence number the client couldn't have reached yet doesn't make the
+    // server ignore the next ones.
+    let input = MovementInput {
+        forward: 1.,
+        ..Default::default()
+    };
+    for sequence in [u32::MAX, 1, 2, 3] {
+        client
+            .send_to(Packet::Input { sequence, input }, &server)
+            .unwrap();
+    }
+
+    expect(
+        &client,
+        |p| matches!(p, Packet::PlayerState { sequence, .. } if *sequence == 3),
+    );
+}
+
+#[test]
+pub fn inputs_faster_than_the_rate() {
+    let network = LoopbackNetwork::default();
+    let server = start_server(&network, "inputs_faster_than_the_rate");
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    connect(&client, &server, "alice");
+    expect(&client, |p| matches!(p, Packet::OnlinePlayers { .. }));
+
+    // Two seconds of inputs sent twice as fast as they should be.
+    let input = MovementInput {
+        forward: 1.,
+        ..Default::default()
+    };
+    let interval = Duration::from_secs(1) / (2 * INPUTS_PER_SECOND);
+    let start = Instant::now();
+    for sequence in 1..=4 * INPUTS_PER_SECOND {
+        client
+            .send_to(Packet::Input { sequence, input }, &server)
+            .unwrap();
+        thread::sleep(interval);
+    }
+
+    let mut x = 0.;
+    while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+        if let Packet::PlayerState { x: state, .. } = packet {
+            x = state;
+        }
+    }
+
+    // The player moved no further than inputs sent at the right rate would have moved it.
+    let earned = start.elapsed().as_secs_f64() * INPUTS_PER_SECOND as f64 + INPUT_BURST as f64;
+    assert!(x > 0.);
+    assert!(
+        x <= earned * MOVEMENT_SPEED / INPUTS_PER_SECOND as f64,
+        "{x}"
+    );
+}
+
+#[test]
+pub fn manual_steps() {
+    let network = LoopbackNetwork::default();
+    let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+    let config = ServerConfig {
+        tick_rate: 1,
+        world_dir: world_dir("manual_steps"),
+        ..Default::default()
+    };
+    let mut game = Game::new(ChannelTransport::bind(&network, addr).unwrap(), config);
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: false,
+            },
+            &addr,
+        )
+        .unwrap();
+
+    // Nothing runs on its own, the packet is only handled when the test steps the server.
+    assert!(client.recv_timeout(Duration::from_millis(100)).is_err());
+
+    let welcome = loop {
+        game.step();
+        if let Ok((packet @ Packet::Welcome { .. }, _peer)) =
+            client.recv_timeout(Duration::from_millis(10))
+        {
+            break packet;
+        }
+    };
+
+    assert!(matches!(welcome, Packet::Welcome { tick_rate: 1, .. }));
+    assert!(game.tick() > 0);
+    assert_eq!(game.stats().len() as u64, game.tick());
+}
+
+#[test]
+pub fn chunks_within_view_distance() {
+    let network = LoopbackNetwork::default();
+    let server = start_server_with(
+        &network,
+        ServerConfig {
+            view_distance: 1,
+            world_dir: world_dir("chunks_within_view_distance"),
+            ..Default::default()
+        },
+    );
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    connect(&client, &server, "alice");
+
+    let mut chunks = Vec::new();
+    while chunks.len() < 9 {
+        if let Packet::Chunk { x, y, .. } = expect(&client, |p| matches!(p, Packet::Chunk { .. })) {
+            chunks.push((x, y));
+        }
+    }
+
+    // The chunk the player is in comes first.
+    assert_eq!(chunks[0], (0, 0));
+    chunks.sort();
+    chunks.dedup();
+    assert_eq!(chunks.len(), 9);
+    assert!(chunks
+        .iter()
+        .all(|(x, y)| (-1..=1).contains(x) && (-1..=1).contains(y)));
+
+    // Player states keep coming, but no more chunks.
+    let end = Instant::now() + Duration::from_millis(300);
+    while Instant::now() < end {
+        let (packet, _peer) = client.recv_timeout(TIMEOUT).unwrap();
+        assert!(!matches!(packet, Packet::Chunk { .. }));
+    }
+}
+
+#[test]
+pub fn full_server() {
+    let network = LoopbackNetwork::default();
+    let server = start_server_with(
+        &network,
+        ServerConfig {
+            max_players: 1,
+            world_dir: world_dir("full_server"),
+            ..Default::default()
+        },
+    );
+
+    let alice = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    let bob = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 3))).unwrap();
+
+    connect(&alice, &server, "alice");
+    expect(&alice, |p| matches!(p, Packet::Welcome { .. }));
+
+    connect(&bob, &server, "bob");
+    assert!(matches!(
+        expect(&bob, |p| matches!(
+            p,
+            Packet::Disconnect { .. } | Packet::Welcome { .. }
+        )),
+        Packet::Disconnect { .. }
+    ));
+}
+
+/// Highest a player that flies up for two seconds gets.
+fn fly_up(name: &str, allow_flight: bool) -> f64 {
+    let network = LoopbackNetwork::default();
+    let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+    let config = ServerConfig {
+        world_dir: world_dir(name),
+        allow_flight,
+        ..Default::default()
+    };
+    let _ = fs::remove_dir_all(&config.world_dir);
+    let mut game = Game::new(ChannelTransport::bind(&network, addr).unwrap(), config);
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    connect(&client, &addr, "alice");
+    loop {
+        game.step();
+        if let Ok((Packet::Welcome { .. }, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            break;
+        }
+    }
+
+    let input = MovementInput {
+        up: 1.,
+        flying: true,
+        ..Default::default()
+    };
+    let mut highest = f64::MIN;
+    for sequence in 1..=2 * INPUTS_PER_SECOND {
+        client
+            .send_to(Packet::Input { sequence, input }, &addr)
+            .unwrap();
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            if let Packet::PlayerState { y, .. } = packet {
+                highest = highest.max(y);
+            }
+        }
+    }
+
+    highest
+}
+
+#[test]
+pub fn flying_needs_permission() {
+    // Players that may not fly jump instead, whatever their client says.
+    assert!(fly_up("flying_denied", false) < 18.);
+    assert!(fly_up("flying_allowed", true) > 19.);
+}
@@ -0,0 +1,412 @@
+use std::fs;
+
+use yave::network::compression;
+use yave::network::error::DecodeError;
+use yave::network::snapshot::EntityDelta;
+use yave::network::{
+    decode_chunk, encode_chunk, OnlinePlayer, Packet, MAX_CHAT_LENGTH, MAX_MESSAGE_LENGTH,
+    MAX_NAME_LENGTH, MAX_PACKET_SIZE, MAX_STATUS_SAMPLE,
+};
+use yave::world::chunk::{BlockGroup, Chunk, CompressedChunk};
+
+const CORPUS: &str = "fuzz/corpus/packet_decode";
+
+fn corpus(name: &str) -> Vec<u8> {
+    fs::read(format!("{CORPUS}/{name}")).unwrap()
+}
+
+#[test]
+pub fn corpus_round_trip() {
+    for entry in fs::read_dir(CORPUS).unwrap() {
+        let data = fs::read(entry.unwrap().path()).unwrap();
+
+        if let Ok(packet) = Packet::decode(&data) {
+            assert_eq!(packet.encode().unwrap(), data);
+            assert_eq!(packet.encoded_size().unwrap(), data.len());
+        }
+    }
+}
+
+#[test]
+pub fn corpus_valid_packets() {
+    assert_eq!(
+        Packet::decode(&corpus("connection")),
+        Ok(Packet::Connection {
+            user: String::from("singleplayer"),
+            compression: true,
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("online_players")),
+        Ok(Packet::OnlinePlayers {
+            players: vec![
+                OnlinePlayer {
+                    id: 1,
+                    name: String::from("alice"),
+                    x: 1.,
+                    y: 2.,
+                    z: 3.,
+                },
+                OnlinePlayer {
+                    id: 2,
+                    name: String::from("bob"),
+                    x: 0.,
+                    y: 0.,
+                    z: 10.,
+                },
+            ]
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("chunk")),
+        Ok(Packet::Chunk {
+            x: 0,
+            y: 0,
+            data: CompressedChunk {
+                palette: vec![String::from("base:stone"), String::from("base:grass")],
+                groups: vec![
+                    BlockGroup {
+                        index: 0,
+                        count: 4000
+                    },
+                    BlockGroup {
+                        index: 1,
+                        count: 96
+                    },
+                ],
+            },
+        })
+    );
+}
+
+#[test]
+pub fn corpus_commands() {
+    assert_eq!(
+        Packet::decode(&corpus("command")),
+        Ok(Packet::Command {
+            line: String::from("tp alice 1 ~ ~-2"),
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("system_message")),
+        Ok(Packet::SystemMessage {
+            message: String::from("[Server] Hello there"),
+        })
+    );
+
+    let packet = Packet::SystemMessage {
+        message: "a".repeat(MAX_MESSAGE_LENGTH + 1),
+    };
+    assert!(packet.encode().is_err());
+    assert!(packet.encoded_size().is_err());
+}
+
+#[test]
+pub fn corpus_chat() {
+    assert_eq!(
+        Packet::decode(&corpus("chat_message")),
+        Ok(Packet::ChatMessage {
+            message: String::from("Hello everyone"),
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("chat")),
+        Ok(Packet::Chat {
+            sender: String::from("alice"),
+            message: String::from("Hello everyone"),
+        })
+    );
+
+    let packet = Packet::ChatMessage {
+        message: "a".repeat(MAX_CHAT_LENGTH + 1),
+    };
+    assert!(packet.encode().is_err());
+}
+
+#[test]
+pub fn corpus_auth() {
+    let mut salt = [0u8; 16];
+    for (i, byte) in salt.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    assert_eq!(
+        Packet::decode(&corpus("auth_challenge")),
+        Ok(Packet::AuthChallenge {
+            salt,
+            iterations: 100_000,
+            nonce: [0xab; 32],
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("auth_response")),
+        Ok(Packet::AuthResponse { proof: [0x5a; 32] })
+    );
+
+    let truncated = &corpus("auth_response")[..20];
+    assert_eq!(Packet::decode(truncated), Err(DecodeError::Truncated));
+}
+
+#[test]
+pub fn corpus_status() {
+    assert_eq!(
+        Packet::decode(&corpus("status_request")),
+        Ok(Packet::StatusRequest { token: 42 })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("status")),
+        Ok(Packet::Status {
+            token: 42,
+            protocol: 1,
+            motd: String::from("A yave server"),
+            online: 2,
+            max_players: 20,
+            sample: vec![String::from("alice"), String::from("bob")],
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("status_huge_sample")),
+        Err(DecodeError::TooLong {
+            field: "sample",
+            len: MAX_STATUS_SAMPLE as u64 + 1,
+            max: MAX_STATUS_SAMPLE as u64,
+        })
+    );
+}
+
+#[test]
+pub fn corpus_lan_announcement() {
+    assert_eq!(
+        Packet::decode(&corpus("lan_announcement")),
+        Ok(Packet::LanAnnouncement {
+            protocol: 1,
+            motd: String::from("A yave server"),
+            port: 25000,
+            online: 2,
+            max_players: 20,
+        })
+    );
+}
+
+#[test]
+pub fn corpus_change_world() {
+    assert_eq!(
+        Packet::decode(&corpus("change_world")),
+        Ok(Packet::ChangeWorld {
+            name: String::from("nether"),
+        })
+    );
+}
+
+#[test]
+pub fn corpus_entities() {
+    assert_eq!(
+        Packet::decode(&corpus("spawn_entity")),
+        Ok(Packet::SpawnEntity {
+            id: 7,
+            kind: String::from("base:wanderer"),
+            x: 1.5,
+            y: 4.,
+            z: -2.25,
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("despawn_entity")),
+        Ok(Packet::DespawnEntity { id: 7 })
+    );
+}
+
+#[test]
+pub fn corpus_malicious_packets() {
+    assert_eq!(
+        Packet::decode(&corpus("huge_length")),
+        Err(DecodeError::TooLong {
+            field: "user",
+            len: u64::MAX,
+            max: MAX_NAME_LENGTH as u64,
+        })
+    );
+    assert!(matches!(
+        Packet::decode(&corpus("huge_player_count")),
+        Err(DecodeError::TooLong {
+            field: "players",
+            ..
+        })
+    ));
+    assert!(matches!(
+        Packet::decode(&corpus("huge_palette")),
+        Err(DecodeError::TooLong {
+            field: "palette",
+            ..
+        })
+    ));
+    assert!(matches!(
+        Packet::decode(&corpus("huge_group_count")),
+        Err(DecodeError::TooLong {
+            field: "groups",
+            ..
+        })
+    ));
+    assert_eq!(
+        Packet::decode(&corpus("invalid_utf8")),
+        Err(DecodeError::InvalidUtf8 { field: "user" })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("invalid_bool")),
+        Err(DecodeError::InvalidBool {
+            field: "compression"
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("trailing_bytes")),
+        Err(DecodeError::TrailingBytes(1))
+    );
+    assert_eq!(
+        Packet::decode(&corpus("truncated")),
+        Err(DecodeError::Truncated)
+    );
+    assert_eq!(
+        Packet::decode(&corpus("key_exchange_truncated")),
+        Err(DecodeError::Truncated)
+    );
+    assert_eq!(
+        Packet::decode(&corpus("empty")),
+        Err(DecodeError::Truncated)
+    );
+    assert_eq!(
+        Packet::decode(&corpus("nan")),
+        Err(DecodeError::NonFinite { field: "forward" })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("unknown_id")),
+        Err(DecodeError::UnknownPacket(255))
+    );
+    assert_eq!(
+        Packet::decode(&corpus("chunk_too_few_blocks")),
+        Err(DecodeError::InvalidChunkSize(1))
+    );
+    assert!(matches!(
+        Packet::decode(&corpus("chunk_overflowing_count")),
+        Err(DecodeError::InvalidChunkSize(_))
+    ));
+    assert_eq!(
+        Packet::decode(&corpus("snapshot_invalid_flags")),
+        Err(DecodeError::InvalidFlags {
+            field: "delta",
+            flags: 0x8
+        })
+    );
+    assert_eq!(
+        Packet::decode(&corpus("chunk_invalid_palette_index")),
+        Err(DecodeError::InvalidPaletteIndex(1))
+    );
+    assert_eq!(
+        Packet::decode(&corpus("chunk_invalid_identifier")),
+        Err(DecodeError::InvalidIdentifier(String::from("stone")))
+    );
+}
+
+#[test]
+pub fn corpus_snapshot_delta() {
+    assert_eq!(
+        Packet::decode(&corpus("snapshot_delta")),
+        Ok(Packet::Snapshot {
+            tick: 41,
+            baseline: Some(40),
+            entities: vec![EntityDelta {
+                id: 8,
+                position: Some([4., 5., 6.]),
+                rotation: None,
+                velocity: None,
+            }],
+            removed: vec![7],
+        })
+    );
+}
+
+#[test]
+pub fn encode_rejects_oversized_fields() {
+    let packet = Packet::Connection {
+        user: "a".repeat(MAX_NAME_LENGTH + 1),
+        compression: false,
+    };
+
+    assert!(packet.encode().is_err());
+}
+
+#[test]
+pub fn generated_chunk_round_trip() {
+    let chunk = Chunk::new(3, -7);
+    let packet = Packet::Chunk {
+        x: chunk.x,
+        y: chunk.y,
+        data: chunk.compress(),
+    };
+
+    assert_eq!(Packet::decode(&packet.encode().unwrap()), Ok(packet));
+}
+
+#[test]
+pub fn stored_chunk_round_trip() {
+    let data = Chunk::new(0, 0).compress();
+    let bytes = encode_chunk(&data).unwrap();
+
+    assert_eq!(decode_chunk(&bytes), Ok(data));
+    assert_eq!(
+        decode_chunk(&[bytes.as_slice(), &[0]].concat()),
+        Err(DecodeError::TrailingBytes(1))
+    );
+}
+
+#[test]
+pub fn chunk_palette_round_trip() {
+    let mut chunk = Chunk::new(0, 0);
+    for block in chunk.blocks.iter_mut().skip(100).step_by(7) {
+        block.id = String::from("base:grass");
+    }
+
+    let data = chunk.compress();
+
+    assert_eq!(data.palette.len(), 2);
+    assert_eq!(Chunk::decompress(&data, 0, 0).blocks, chunk.blocks);
+}
+
+#[test]
+pub fn compressed_frames() {
+    let packet = Packet::Chunk {
+        x: 0,
+        y: 0,
+        data: Chunk::new(0, 0).compress(),
+    };
+    let encoded = packet.encode().unwrap();
+
+    let raw = compression::compress(&encoded, None).unwrap();
+    assert_eq!(raw[0], compression::FRAME_RAW);
+    assert_eq!(compression::decompress(&raw), Ok(encoded.clone()));
+
+    let small = Packet::UnloadChunk { x: 0, y: 0 }.encode().unwrap();
+    let below_threshold = compression::compress(&small, Some(64)).unwrap();
+    assert_eq!(below_threshold[0], compression::FRAME_RAW);
+
+    let compressed = compression::compress(&encoded, Some(0)).unwrap();
+    assert_eq!(compressed[0], compression::FRAME_COMPRESSED);
+    assert_eq!(compression::decompress(&compressed), Ok(encoded));
+}
+
+#[test]
+pub fn compressed_frame_bomb() {
+    let bomb = compression::compress(&vec![0u8; MAX_PACKET_SIZE * 4], Some(0)).unwrap();
+
+    assert!(matches!(
+        compression::decompress(&bomb),
+        Err(DecodeError::TooLong { .. })
+    ));
+    assert_eq!(
+        compression::decompress(&[compression::FRAME_COMPRESSED, 1, 2, 3]),
+        Err(DecodeError::InvalidCompression)
+    );
+    assert_eq!(
+        compression::decompress(&[0xff]),
+        Err(DecodeError::UnknownFrame(0xff))
+    );
+}
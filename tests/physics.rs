@@ -0,0 +1,226 @@
+use yave::assets::Identifier;
+use yave::world::chunk::{Block, BlockGroup, Chunk, CompressedChunk, AIR};
+use yave::world::movement::{MovementInput, INPUTS_PER_SECOND, MOVEMENT_SPEED};
+use yave::world::physics::{Body, PLAYER_WIDTH};
+use yave::world::Chunks;
+
+const DISTANCE: f64 = MOVEMENT_SPEED / INPUTS_PER_SECOND as f64;
+
+/// A world of empty chunks around the origin, with stone at the given blocks.
+fn world(stone: &[(i64, i64, i64)]) -> Chunks {
+    let air = CompressedChunk {
+        palette: vec![String::from(AIR)],
+        groups: vec![BlockGroup {
+            index: 0,
+            count: 4096,
+        }],
+    };
+
+    let mut chunks = Chunks::default();
+    for x in -1..=1 {
+        for y in -1..=1 {
+            chunks.chunks.push(Chunk::decompress(&air, x, y));
+        }
+    }
+
+    for &(x, y, z) in stone {
+        let chunk = chunks
+            .get_chunk_mut(x.div_euclid(16), z.div_euclid(16))
+            .unwrap();
+        let (x, y, z) = (x.rem_euclid(16) as u16, y as u16, z.rem_euclid(16) as u16);
+        chunk.blocks[(z * 16 * 16 + y * 16 + x) as usize] =
+            Block::new(x, y, z, Identifier::new("base", "stone"), true);
+    }
+
+    chunks
+}
+
+/// A floor of stone at `y` from -8 to 8 on x and z.
+fn floor(y: i64) -> Vec<(i64, i64, i64)> {
+    (-8..8)
+        .flat_map(|x| (-8..8).map(move |z| (x, y, z)))
+        .collect()
+}
+
+fn walk(yaw: f32) -> MovementInput {
+    MovementInput {
+        forward: 1.,
+        yaw,
+        ..Default::default()
+    }
+}
+
+fn steps(body: &mut Body, input: MovementInput, chunks: &Chunks, count: usize) {
+    for _ in 0..count {
+        body.step(&input, chunks);
+    }
+}
+
+#[test]
+pub fn falls_and_lands() {
+    let chunks = world(&floor(3));
+    let mut body = Body::new([0.5, 10., 0.5]);
+
+    body.step(&MovementInput::default(), &chunks);
+    assert!(body.position[1] < 10.);
+    assert!(body.velocity < 0.);
+
+    steps(&mut body, MovementInput::default(), &chunks, 40);
+    assert_eq!(body.position[1], 4.);
+    assert_eq!(body.velocity, 0.);
+    assert!(body.on_ground(&chunks));
+}
+
+#[test]
+pub fn slides_along_walls() {
+    // A wall along z at x = 2, the body walks into it diagonally.
+    let mut stone = floor(0);
+    stone.extend((-8..8).flat_map(|z| [(2, 1, z), (2, 2, z)]));
+    let chunks = world(&stone);
+    let mut body = Body::new([0.5, 1., 0.5]);
+
+    steps(&mut body, walk(std::f32::consts::FRAC_PI_4), &chunks, 40);
+
+    // Stopped by the wall on x, still moving along it on z.
+    assert!((body.position[0] - (2. - PLAYER_WIDTH / 2.)).abs() < 1e-9);
+    assert!(body.position[2] > 0.5 + 20. * DISTANCE * 0.7);
+    assert_eq!(body.position[1], 1.);
+}
+
+#[test]
+pub fn lands_on_ledges() {
+    // A ledge two blocks high.
+    let mut stone = floor(0);
+    stone.extend((2..6).flat_map(|x| (-8..8).flat_map(move |z| [(x, 1, z), (x, 2, z)])));
+    let chunks = world(&stone);
+
+    // Walking doesn't climb blocks and jumping doesn't go high enough, the ledge stops the body.
+    let mut body = Body::new([0.5, 1., 0.5]);
+    steps(&mut body, walk(0.), &chunks, 20);
+    assert!((body.position[0] - (2. - PLAYER_WIDTH / 2.)).abs() < 1e-9);
+    assert_eq!(body.position[1], 1.);
+
+    let jump = MovementInput { up: 1., ..walk(0.) };
+    steps(&mut body, jump, &chunks, 20);
+    assert!(body.position[0] < 2.);
+
+    // Falling on its edge, the body lands on it even if only a bit of it is above the ledge.
+    let mut body = Body::new([1.8, 6., 0.5]);
+    steps(&mut body, MovementInput::default(), &chunks, 40);
+    assert_eq!(body.position[1], 3.);
+    assert!(body.on_ground(&chunks));
+
+    steps(&mut body, walk(0.), &chunks, 10);
+    assert_eq!(body.position[1], 3.);
+    assert!(body.position[0] > 2.5);
+}
+
+#[test]
+pub fn jumps_up_single_blocks() {
+    let mut stone = floor(0);
+    stone.extend((2..8).flat_map(|x| (-8..8).map(move |z| (x, 1, z))));
+    let chunks = world(&stone);
+
+    // A block is higher than a step, walking into it stops the body.
+    let mut body = Body::new([0.5, 1., 0.5]);
+    steps(&mut body, walk(0.), &chunks, 20);
+    assert_eq!(body.position[1], 1.);
+    assert!((body.position[0] - (2. - PLAYER_WIDTH / 2.)).abs() < 1e-9);
+
+    let jump = MovementInput { up: 1., ..walk(0.) };
+    steps(&mut body, jump, &chunks, 20);
+    steps(&mut body, walk(0.), &chunks, 20);
+    assert_eq!(body.position[1], 2.);
+    assert!(body.position[0] > 2.);
+}
+
+#[test]
+pub fn jumps_from_the_ground_only() {
+    let chunks = world(&floor(0));
+    let jump = MovementInput {
+        up: 1.,
+        ..Default::default()
+    };
+
+    let mut body = Body::new([0.5, 1., 0.5]);
+    body.step(&jump, &chunks);
+    assert!(body.position[1] > 1.);
+    assert!(body.velocity > 0.);
+
+    // Holding jump in the air doesn't go higher.
+    let mut highest = body.position[1];
+    for _ in 0..20 {
+        body.step(&jump, &chunks);
+        highest = highest.max(body.position[1]);
+    }
+    assert!(highest > 2. && highest < 2.5, "{highest}");
+
+    let mut body = Body::new([0.5, 5., 0.5]);
+    body.step(&jump, &chunks);
+    assert!(body.position[1] < 5.);
+}
+
+#[test]
+pub fn sneaking_stays_on_edges() {
+    // A platform ending at x = 2 over a drop.
+    let stone: Vec<_> = (-8..2)
+        .flat_map(|x| (-8..8).map(move |z| (x, 3, z)))
+        .collect();
+    let chunks = world(&stone);
+    let sneak = MovementInput {
+        up: -1.,
+        ..walk(0.)
+    };
+
+    let mut body = Body::new([0.5, 4., 0.5]);
+    steps(&mut body, sneak, &chunks, 200);
+    assert_eq!(body.position[1], 4.);
+    assert!(body.position[0] > 2. - PLAYER_WIDTH / 2.);
+    assert!(body.position[0] < 2. + PLAYER_WIDTH / 2.);
+
+    // Without sneaking it falls off.
+    steps(&mut body, walk(0.), &chunks, 20);
+    assert!(body.position[1] < 4.);
+}
+
+#[test]
+pub fn flies_through_the_air() {
+    let chunks = world(&floor(0));
+    let up = MovementInput {
+        up: 1.,
+        flying: true,
+        ..Default::default()
+    };
+
+    let mut body = Body::new([0.5, 1., 0.5]);
+    steps(&mut body, up, &chunks, 10);
+    assert!((body.position[1] - (1. + 10. * DISTANCE)).abs() < 1e-9);
+
+    // Flying bodies don't fall, but blocks still stop them.
+    let hover = MovementInput {
+        flying: true,
+        ..Default::default()
+    };
+    steps(&mut body, hover, &chunks, 10);
+    assert!((body.position[1] - (1. + 10. * DISTANCE)).abs() < 1e-9);
+    let down = MovementInput {
+        up: -1.,
+        flying: true,
+        ..Default::default()
+    };
+    steps(&mut body, down, &chunks, 40);
+    assert_eq!(body.position[1], 1.);
+}
+
+#[test]
+pub fn stuck_bodies_move_freely() {
+    // A block placed on a body doesn't trap it.
+    let mut stone = floor(0);
+    stone.extend([(0, 1, 0), (0, 2, 0), (1, 1, 0), (1, 2, 0)]);
+    let chunks = world(&stone);
+    let mut body = Body::new([0.5, 1., 0.5]);
+
+    steps(&mut body, walk(0.), &chunks, 20);
+
+    assert!((body.position[0] - (0.5 + 20. * DISTANCE)).abs() < 1e-9);
+}
@@ -0,0 +1,64 @@
+use std::f32::consts::FRAC_PI_2;
+
+use winit::event::{ElementState, VirtualKeyCode};
+use yave::client::player::{Look, MovementMode, PlayerInput};
+
+#[test]
+pub fn keys_stay_held() {
+    let mut input = PlayerInput::new(0.01);
+    let look = Look::default();
+
+    input.process_keyboard(VirtualKeyCode::W, ElementState::Pressed);
+    input.process_keyboard(VirtualKeyCode::D, ElementState::Pressed);
+
+    // Frames without keyboard events keep moving the player.
+    for _ in 0..3 {
+        let movement = input.input(&look, MovementMode::Walking);
+        assert_eq!((movement.forward, movement.right), (1., 1.));
+    }
+
+    input.process_keyboard(VirtualKeyCode::W, ElementState::Released);
+    input.process_keyboard(VirtualKeyCode::S, ElementState::Pressed);
+    input.process_keyboard(VirtualKeyCode::LShift, ElementState::Pressed);
+    let movement = input.input(&look, MovementMode::Flying);
+    assert_eq!(
+        (movement.forward, movement.right, movement.up),
+        (-1., 1., -1.)
+    );
+    assert!(movement.flying);
+}
+
+#[test]
+pub fn mouse_turns_once() {
+    let mut input = PlayerInput::new(0.01);
+    let mut look = Look::default();
+
+    // Every motion of the frame counts, and only once.
+    input.process_mouse(10., 0.);
+    input.process_mouse(5., -20.);
+    input.turn(&mut look);
+    assert!((look.yaw - 0.15).abs() < 1e-6);
+    assert!((look.pitch - 0.2).abs() < 1e-6);
+
+    input.turn(&mut look);
+    assert!((look.yaw - 0.15).abs() < 1e-6);
+
+    // The player can't look further than straight up.
+    input.process_mouse(0., -1000.);
+    input.turn(&mut look);
+    assert_eq!(look.pitch, FRAC_PI_2);
+
+    let movement = input.input(&look, MovementMode::Walking);
+    assert_eq!((movement.yaw, movement.pitch), (look.yaw, look.pitch));
+}
+
+#[test]
+pub fn movement_mode_toggles() {
+    let mut mode = MovementMode::default();
+    assert_eq!(mode, MovementMode::Walking);
+
+    mode.toggle();
+    assert_eq!(mode, MovementMode::Flying);
+    mode.toggle();
+    assert_eq!(mode, MovementMode::Walking);
+}
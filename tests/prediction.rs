@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use yave::client::prediction::Prediction;
+use yave::world::movement::{step, MovementInput, INPUTS_PER_SECOND, MOVEMENT_SPEED};
+use yave::world::physics::Body;
+use yave::world::Chunks;
+
+const TICK: Duration = Duration::from_millis(1000 / INPUTS_PER_SECOND as u64);
+const DISTANCE: f64 = MOVEMENT_SPEED / INPUTS_PER_SECOND as f64;
+
+fn forward() -> MovementInput {
+    MovementInput {
+        forward: 1.,
+        ..Default::default()
+    }
+}
+
+fn assert_close(a: [f64; 3], b: [f64; 3]) {
+    for axis in 0..3 {
+        assert!((a[axis] - b[axis]).abs() < 1e-9, "{a:?} != {b:?}");
+    }
+}
+
+#[test]
+pub fn step_follows_yaw() {
+    assert_close(step([0., 0., 0.], &forward()), [DISTANCE, 0., 0.]);
+
+    let input = MovementInput {
+        right: 1.,
+        up: -1.,
+        ..Default::default()
+    };
+    assert_close(step([0., 0., 0.], &input), [0., -DISTANCE, DISTANCE]);
+}
+
+#[test]
+pub fn step_clamps_input() {
+    let input = MovementInput {
+        forward: 1000.,
+        ..Default::default()
+    };
+    assert_close(step([0., 0., 0.], &input), [DISTANCE, 0., 0.]);
+}
+
+#[test]
+pub fn one_input_per_tick() {
+    // Nothing is loaded, the player walks on the bottom of the world.
+    let chunks = Chunks::default();
+    let mut prediction = Prediction::new([0., 0., 0.]);
+
+    assert!(prediction.update(TICK / 2, forward(), &chunks).is_empty());
+    let inputs = prediction.update(TICK * 2, forward(), &chunks);
+    assert_eq!(
+        inputs
+            .iter()
+            .map(|(sequence, _input)| *sequence)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_close(prediction.position(), [2. * DISTANCE, 0., 0.]);
+    assert_eq!(prediction.pending(), 2);
+
+    // A long frame doesn't send a burst of inputs.
+    assert_eq!(
+        prediction
+            .update(Duration::from_secs(10), forward(), &chunks)
+            .len(),
+        5
+    );
+}
+
+#[test]
+pub fn reconcile_replays_pending_inputs() {
+    let chunks = Chunks::default();
+    let mut prediction = Prediction::new([0., 0., 0.]);
+    for _ in 0..5 {
+        prediction.apply(forward(), &chunks);
+    }
+
+    // The server simulated the first two inputs, but started from somewhere else.
+    prediction.reconcile(2, Body::new([10., 0., 2. * DISTANCE]), &chunks);
+
+    assert_eq!(prediction.pending(), 3);
+    assert_close(
+        prediction.position(),
+        [10. + 3. * DISTANCE, 0., 2. * DISTANCE],
+    );
+}
+
+#[test]
+pub fn reconcile_ignores_stale_states() {
+    let chunks = Chunks::default();
+    let mut prediction = Prediction::new([0., 0., 0.]);
+    for _ in 0..3 {
+        prediction.apply(forward(), &chunks);
+    }
+
+    prediction.reconcile(3, Body::new([3. * DISTANCE, 0., 0.]), &chunks);
+    prediction.reconcile(1, Body::new([100., 0., 0.]), &chunks);
+    prediction.reconcile(10, Body::new([100., 0., 0.]), &chunks);
+
+    assert_eq!(prediction.pending(), 0);
+    assert_close(prediction.position(), [3. * DISTANCE, 0., 0.]);
+}
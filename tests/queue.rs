@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::server::queue::{Priority, SendQueue};
+use yave::world::chunk::Chunk;
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+const ALICE: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2);
+
+fn chunk(x: i64, y: i64) -> Packet {
+    Packet::Chunk {
+        x,
+        y,
+        data: Chunk::new(x, y).compress(),
+    }
+}
+
+fn message(message: &str) -> Packet {
+    Packet::SystemMessage {
+        message: String::from(message),
+    }
+}
+
+fn state(sequence: u32) -> Packet {
+    Packet::PlayerState {
+        sequence,
+        x: 0.,
+        y: 0.,
+        z: 0.,
+        velocity: 0.,
+    }
+}
+
+#[test]
+pub fn priorities() {
+    let mut queue = SendQueue::default();
+    queue.push(chunk(3, 0), Priority::Chunk);
+    queue.push(chunk(1, 1), Priority::Chunk);
+    queue.push(message("first"), Priority::Message);
+    queue.push(Packet::UnloadChunk { x: 9, y: 9 }, Priority::Block);
+    queue.push(message("second"), Priority::Message);
+    queue.push(state(1), Priority::Movement);
+    // Only the latest player state is worth sending.
+    queue.push(state(2), Priority::Movement);
+
+    assert_eq!(queue.len(), 6);
+    assert_eq!(
+        queue.take(u32::MAX, (2, 0)),
+        vec![
+            state(2),
+            Packet::UnloadChunk { x: 9, y: 9 },
+            message("first"),
+            message("second"),
+            // Nearest first from the chunk the player is in.
+            chunk(3, 0),
+            chunk(1, 1),
+        ]
+    );
+    assert!(queue.is_empty());
+}
+
+#[test]
+pub fn budget() {
+    let size = chunk(0, 0).encode().unwrap().len() as u32;
+
+    let mut queue = SendQueue::default();
+    for x in 0..4 {
+        queue.push(chunk(x, 0), Priority::Chunk);
+    }
+
+    // The packet going over the budget is still sent, the next tick makes up for it.
+    assert_eq!(queue.take(size + 1, (0, 0)), vec![chunk(0, 0), chunk(1, 0)]);
+    assert_eq!(queue.take(size + 1, (0, 0)), vec![chunk(2, 0)]);
+    assert_eq!(queue.take(size + 1, (0, 0)), vec![chunk(3, 0)]);
+    assert_eq!(queue.chunks(), 0);
+}
+
+#[test]
+pub fn chunks() {
+    let mut queue = SendQueue::default();
+    queue.push(chunk(0, 0), Priority::Chunk);
+    queue.push(chunk(1, 0), Priority::Chunk);
+    assert_eq!(queue.chunks(), 2);
+
+    assert!(queue.cancel_chunk(1, 0));
+    assert!(!queue.cancel_chunk(1, 0));
+
+    let mut changed = Chunk::new(0, 0);
+    changed.blocks.clear();
+    let data = changed.compress();
+    assert!(queue.update_chunk(0, 0, &data));
+    assert!(!queue.update_chunk(5, 5, &data));
+
+    assert_eq!(
+        queue.take(u32::MAX, (0, 0)),
+        vec![Packet::Chunk { x: 0, y: 0, data }]
+    );
+}
+
+/// Transport failing to send to the peers in the set.
+struct FailingTransport {
+    transport: ChannelTransport,
+    failing: Arc<Mutex<HashSet<SocketAddr>>>,
+}
+
+impl Transport for FailingTransport {
+    fn send_to(&self, packet: Packet, addr: &SocketAddr) -> io::Result<()> {
+        if self.failing.lock().unwrap().contains(addr) {
+            return Err(io::Error::from(ErrorKind::ConnectionRefused));
+        }
+
+        self.transport.send_to(packet, addr)
+    }
+
+    fn recv_from(&self) -> io::Result<(Packet, SocketAddr)> {
+        self.transport.recv_from()
+    }
+
+    fn close(&self) {
+        self.transport.close()
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.peer_addr()
+    }
+}
+
+fn config(name: &str, send_budget: u32) -> ServerConfig {
+    let dir = std::env::temp_dir().join(format!("yave-queue-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    ServerConfig {
+        world_dir: dir.join("world"),
+        lists_dir: dir,
+        view_distance: 1,
+        send_budget,
+        ..Default::default()
+    }
+}
+
+fn join(network: &LoopbackNetwork) -> ChannelTransport {
+    let alice = ChannelTransport::bind(network, ALICE).unwrap();
+    alice
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: false,
+            },
+            &SERVER,
+        )
+        .unwrap();
+
+    alice
+}
+
+/// Packets received by the client during one tick.
+fn step(game: &mut Game, client: &ChannelTransport) -> Vec<Packet> {
+    game.step();
+
+    let mut packets = Vec::new();
+    while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(5)) {
+        packets.push(packet);
+    }
+
+    packets
+}
+
+/// Step until the client joined.
+fn welcome(game: &mut Game, client: &ChannelTransport) {
+    for _ in 0..200 {
+        let packets = step(game, client);
+        if packets
+            .iter()
+            .any(|packet| matches!(packet, Packet::Welcome { .. }))
+        {
+            return;
+        }
+    }
+
+    panic!("the client never joined");
+}
+
+#[test]
+pub fn chunks_after_movement() {
+    let network = LoopbackNetwork::default();
+    let config = config("order", 1024);
+    let dir = config.lists_dir.clone();
+    let mut game = Game::new(ChannelTransport::bind(&network, SERVER).unwrap(), config);
+    let alice = join(&network);
+
+    welcome(&mut game, &alice);
+    let packets = step(&mut game, &alice);
+
+    // The player state goes before the chunks, the one the player stands in first.
+    let state = packets
+        .iter()
+        .position(|packet| matches!(packet, Packet::PlayerState { .. }))
+        .unwrap();
+    let first_chunk = packets
+        .iter()
+        .position(|packet| matches!(packet, Packet::Chunk { .. }))
+        .unwrap();
+    assert!(state < first_chunk);
+    assert!(matches!(
+        packets[first_chunk],
+        Packet::Chunk { x: 0, y: 0, .. }
+    ));
+
+    // The budget spreads the chunks over several ticks, but they all arrive.
+    let mut chunks = packets
+        .iter()
+        .filter(|packet| matches!(packet, Packet::Chunk { .. }))
+        .count();
+    assert!(chunks < 9);
+    for _ in 0..20 {
+        chunks += step(&mut game, &alice)
+            .iter()
+            .filter(|packet| matches!(packet, Packet::Chunk { .. }))
+            .count();
+    }
+    assert_eq!(chunks, 9);
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+pub fn send_error_disconnects() {
+    let network = LoopbackNetwork::default();
+    let config = config("error", 32 * 1024);
+    let dir = config.lists_dir.clone();
+    let failing = Arc::new(Mutex::new(HashSet::new()));
+    let transport = FailingTransport {
+        transport: ChannelTransport::bind(&network, SERVER).unwrap(),
+        failing: failing.clone(),
+    };
+    let mut game = Game::new(transport, config);
+
+    let alice = join(&network);
+    welcome(&mut game, &alice);
+    assert_eq!(
+        game.execute("list"),
+        Ok(String::from("1 of 20 players online: alice"))
+    );
+
+    // The server keeps running without the client it can't send to.
+    failing.lock().unwrap().insert(ALICE);
+    step(&mut game, &alice);
+    step(&mut game, &alice);
+    assert_eq!(
+        game.execute("list"),
+        Ok(String::from("0 of 20 players online: "))
+    );
+
+    let _ = fs::remove_dir_all(dir);
+}
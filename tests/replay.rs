@@ -0,0 +1,248 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use yave::client::bot::Bot;
+use yave::client::Credentials;
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::record::{Direction, Recording, RecordingTransport};
+use yave::network::replay::ReplayTransport;
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::world::movement::MovementInput;
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+const CLIENT: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2);
+
+fn test_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-replay-{name}-{}", std::process::id()))
+}
+
+fn config(dir: &Path) -> ServerConfig {
+    ServerConfig {
+        world_dir: dir.join("world"),
+        lists_dir: dir.to_path_buf(),
+        view_distance: 1,
+        ..Default::default()
+    }
+}
+
+/// Position of the last PlayerState packet.
+fn last_position<'a>(packets: impl Iterator<Item = &'a Packet>) -> Option<[f64; 3]> {
+    packets
+        .filter_map(|packet| match packet {
+            Packet::PlayerState { x, y, z, .. } => Some([*x, *y, *z]),
+            _ => None,
+        })
+        .last()
+}
+
+/// Record a server while alice joins and walks north for 20 inputs.
+fn record_server(dir: &Path) -> Recording {
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let path = dir.join("server.rec");
+
+    let network = LoopbackNetwork::default();
+    let transport =
+        RecordingTransport::new(ChannelTransport::bind(&network, SERVER).unwrap(), &path);
+    let mut game = Game::new(transport.unwrap(), config(&dir.join("recorded")));
+
+    let alice = ChannelTransport::bind(&network, CLIENT).unwrap();
+    alice
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: false,
+            },
+            &SERVER,
+        )
+        .unwrap();
+
+    let mut step_until = |predicate: &dyn Fn(&Packet) -> bool| {
+        for _ in 0..200 {
+            game.step();
+            while let Ok((packet, _peer)) = alice.recv_timeout(Duration::from_millis(1)) {
+                if predicate(&packet) {
+                    return;
+                }
+            }
+        }
+        panic!("no matching packet received");
+    };
+    step_until(&|packet| matches!(packet, Packet::Welcome { .. }));
+
+    let input = MovementInput {
+        forward: 1.,
+        ..Default::default()
+    };
+    for sequence in 1..=20 {
+        alice
+            .send_to(Packet::Input { sequence, input }, &SERVER)
+            .unwrap();
+    }
+
+    // Run until the server simulated every input.
+    step_until(&|packet| matches!(packet, Packet::PlayerState { sequence: 20, .. }));
+
+    Recording::read(&path).unwrap()
+}
+
+#[test]
+pub fn record() {
+    let dir = test_dir("record");
+    let recording = record_server(&dir);
+
+    assert_eq!(recording.local, SERVER);
+    assert_eq!(recording.peer, None);
+
+    let first = &recording.packets[0];
+    assert_eq!(first.direction, Direction::Received);
+    assert_eq!(first.peer, CLIENT);
+    assert_eq!(
+        first.packet,
+        Packet::Connection {
+            user: String::from("alice"),
+            compression: false,
+        }
+    );
+    assert_eq!(recording.received().count(), 21);
+    assert!(recording
+        .sent()
+        .any(|packet| matches!(packet.packet, Packet::Welcome { .. })));
+    assert!(recording
+        .packets
+        .windows(2)
+        .all(|pair| pair[0].time <= pair[1].time));
+
+    // A recording cut in the middle of a packet ends at the last complete one.
+    let path = dir.join("server.rec");
+    let data = fs::read(&path).unwrap();
+    fs::write(&path, &data[..data.len() - 3]).unwrap();
+    assert_eq!(
+        Recording::read(&path).unwrap().packets.len(),
+        recording.packets.len() - 1
+    );
+
+    fs::write(&path, b"not a recording").unwrap();
+    assert_eq!(
+        Recording::read(&path).unwrap_err().kind(),
+        ErrorKind::InvalidData
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn replay_server() {
+    let dir = test_dir("server");
+    let recording = record_server(&dir);
+    let recorded = last_position(recording.sent().map(|packet| &packet.packet));
+
+    // Replaying twice gives the same movement, and the player ends where it did when recorded.
+    let mut positions = Vec::new();
+    for run in 0..2 {
+        let transport = ReplayTransport::new(&recording);
+        let replay = transport.replay();
+        let mut game = Game::new(transport, config(&dir.join(format!("replay{run}"))));
+
+        assert!(game.replay(&replay) > 0);
+        for _ in 0..20 {
+            game.step();
+        }
+
+        let sent = replay.take_sent();
+        assert!(sent.iter().all(|(_packet, peer)| *peer == CLIENT));
+        assert_eq!(
+            game.execute("list"),
+            Ok(String::from("1 of 20 players online: alice"))
+        );
+
+        let states: Vec<Packet> = sent
+            .into_iter()
+            .map(|(packet, _peer)| packet)
+            .filter(|packet| matches!(packet, Packet::PlayerState { .. }))
+            .collect();
+        positions.push(states);
+    }
+
+    assert_eq!(positions[0], positions[1]);
+    assert_eq!(last_position(positions[0].iter()), recorded);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn replay_client() {
+    let dir = test_dir("client");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("client.rec");
+
+    let network = LoopbackNetwork::default();
+    let mut game = Game::new(
+        ChannelTransport::bind(&network, SERVER).unwrap(),
+        config(&dir),
+    );
+
+    let credentials = || Credentials {
+        username: String::from("alice"),
+        password: None,
+    };
+    let transport = RecordingTransport::new(
+        ChannelTransport::bind(&network, CLIENT)
+            .unwrap()
+            .connect(SERVER),
+        &path,
+    )
+    .unwrap();
+    let mut bot = Bot::connect(transport, credentials(), false, Vec::new()).unwrap();
+
+    for _ in 0..200 {
+        game.step();
+        bot.update().unwrap();
+        if bot.joined() && bot.loaded_chunks() == 9 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(bot.loaded_chunks(), 9);
+    drop(bot);
+
+    // A client fed with the recording gets to the same state without a server.
+    let recording = Recording::read(&path).unwrap();
+    assert_eq!(recording.peer, Some(SERVER));
+
+    let transport = ReplayTransport::new(&recording);
+    let replay = transport.replay();
+    let mut bot = Bot::connect(transport, credentials(), false, Vec::new()).unwrap();
+    for _ in 0..200 {
+        replay.advance(replay.duration());
+        bot.update().unwrap();
+        if replay.finished() {
+            break;
+        }
+    }
+
+    // The replayed bot sends its inputs at its own pace, the packets still waiting for them are
+    // released once they waited long enough.
+    replay.advance(replay.duration() + Duration::from_secs(1));
+    bot.update().unwrap();
+
+    assert!(bot.joined());
+    assert_eq!(bot.loaded_chunks(), 9);
+    assert_eq!(bot.stats().chunks, 9);
+
+    // The replayed client answered like the recorded one.
+    assert!(replay
+        .take_sent()
+        .iter()
+        .any(|(packet, peer)| { *peer == SERVER && matches!(packet, Packet::Connection { .. }) }));
+
+    fs::remove_dir_all(dir).unwrap();
+}
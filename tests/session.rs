@@ -0,0 +1,296 @@
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use yave::network::crypto::{Handshake, Role, Session, FRAME_ENCRYPTED};
+use yave::network::error::DecodeError;
+use yave::network::transport::{Transport, UdpTransport};
+use yave::network::{split, split_socket, Packet, SocketReceiver, SocketSender};
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::server::MAX_SESSIONS_PER_IP;
+
+fn sessions() -> (Session, Session) {
+    let client = Handshake::new();
+    let server = Handshake::new();
+    let client_key = client.public_key();
+
+    (
+        client.finish(server.public_key(), Role::Client),
+        server.finish(client_key, Role::Server),
+    )
+}
+
+#[test]
+pub fn seal_and_open() {
+    let (mut client, mut server) = sessions();
+
+    let datagram = client.seal(b"hello");
+    assert_eq!(datagram[0], FRAME_ENCRYPTED);
+    assert_eq!(server.open(&datagram), Ok(b"hello".to_vec()));
+
+    let datagram = server.seal(b"world");
+    assert_eq!(client.open(&datagram), Ok(b"world".to_vec()));
+}
+
+#[test]
+pub fn directions_use_different_keys() {
+    let (mut client, _server) = sessions();
+
+    // A datagram reflected back to its sender must not be accepted.
+    let datagram = client.seal(b"hello");
+    assert_eq!(client.open(&datagram), Err(DecodeError::Unauthenticated));
+}
+
+#[test]
+pub fn replayed_datagrams() {
+    let (mut client, mut server) = sessions();
+
+    let first = client.seal(b"first");
+    let second = client.seal(b"second");
+
+    assert!(server.open(&second).is_ok());
+    assert!(server.open(&first).is_ok());
+    assert_eq!(server.open(&first), Err(DecodeError::Replayed(0)));
+    assert_eq!(server.open(&second), Err(DecodeError::Replayed(1)));
+
+    let old = client.seal(b"old");
+    for _ in 0..100 {
+        server.open(&client.seal(b"new")).unwrap();
+    }
+    assert_eq!(server.open(&old), Err(DecodeError::Replayed(2)));
+}
+
+#[test]
+pub fn tampered_datagrams() {
+    let (mut client, mut server) = sessions();
+    let (_, mut other) = sessions();
+
+    let mut datagram = client.seal(b"hello");
+    let last = datagram.len() - 1;
+    datagram[last] ^= 1;
+    assert_eq!(server.open(&datagram), Err(DecodeError::Unauthenticated));
+
+    // Changing the nonce must fail authentication too.
+    let mut datagram = client.seal(b"hello");
+    datagram[8] ^= 1;
+    assert_eq!(server.open(&datagram), Err(DecodeError::Unauthenticated));
+
+    let datagram = client.seal(b"hello");
+    assert_eq!(other.open(&datagram), Err(DecodeError::Unauthenticated));
+    assert!(server.open(&datagram).is_ok());
+
+    assert_eq!(server.open(&[0, 1, 2]), Err(DecodeError::Unencrypted));
+    assert_eq!(
+        server.open(&[FRAME_ENCRYPTED, 0]),
+        Err(DecodeError::Truncated)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn loopback_session() {
+    let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client_socket
+        .connect(server_socket.local_addr().unwrap())
+        .unwrap();
+    let client_addr = client_socket.local_addr().unwrap();
+
+    let (mut server_sender, mut server_receiver) = split_socket(server_socket);
+    let (mut client_sender, mut client_receiver) = split_socket(client_socket);
+
+    let handshake = Handshake::new();
+    client_sender
+        .send(Packet::KeyExchange {
+            public_key: handshake.public_key(),
+        })
+        .unwrap();
+
+    let client_key = match server_receiver.recv_packet_from().unwrap() {
+        (Packet::KeyExchange { public_key }, peer) if peer == client_addr => public_key,
+        packet => panic!("unexpected packet {packet:?}"),
+    };
+
+    let reply = Handshake::new();
+    server_sender
+        .send_to(
+            Packet::KeyExchange {
+                public_key: reply.public_key(),
+            },
+            &client_addr,
+        )
+        .unwrap();
+    server_sender.set_session(client_addr, Some(reply.finish(client_key, Role::Server)));
+
+    let server_key = match client_receiver.recv_packet_from().unwrap() {
+        (Packet::KeyExchange { public_key }, _) => public_key,
+        packet => panic!("unexpected packet {packet:?}"),
+    };
+    let server_addr = client_sender.peer_addr().unwrap();
+    client_sender.set_session(
+        server_addr,
+        Some(handshake.finish(server_key, Role::Client)),
+    );
+
+    let connection = Packet::Connection {
+        user: String::from("alice"),
+        compression: false,
+    };
+    client_sender.send(connection.clone()).unwrap();
+    assert_eq!(
+        server_receiver.recv_packet_from().unwrap(),
+        (connection, client_addr)
+    );
+
+    // Plaintext from a peer with a session is what a spoofed datagram looks like.
+    client_sender.set_session(server_addr, None);
+    client_sender.send(Packet::SnapshotAck { tick: 1 }).unwrap();
+    let error = server_receiver.recv_packet_from().unwrap_err();
+    assert_eq!(
+        error
+            .into_inner()
+            .unwrap()
+            .downcast::<DecodeError>()
+            .unwrap(),
+        Box::new(DecodeError::Unencrypted)
+    );
+}
+
+/// A server running on its own thread, refusing everyone if `max_players` is 0.
+fn start_server(name: &str, max_players: u32) -> SocketAddr {
+    let transport = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let addr = transport.local_addr().unwrap();
+    let config = ServerConfig {
+        max_players,
+        view_distance: 1,
+        world_dir: std::env::temp_dir().join(format!("yave-session-{name}-{}", std::process::id())),
+        ..Default::default()
+    };
+    let game = Game::new(transport, config);
+    thread::spawn(move || game.run());
+
+    addr
+}
+
+/// Run the client side of a test, failing instead of waiting forever for a missing reply.
+fn within_timeout(test: impl FnOnce() + Send + 'static) {
+    let (done, finished) = mpsc::channel();
+    thread::spawn(move || {
+        test();
+        done.send(()).unwrap();
+    });
+
+    finished.recv_timeout(Duration::from_secs(5)).unwrap();
+}
+
+/// Wait for the first packet matching the predicate, skipping the others.
+fn expect(receiver: &mut SocketReceiver, predicate: impl Fn(&Packet) -> bool) -> Packet {
+    loop {
+        if let Ok((packet, _peer)) = receiver.recv_packet_from() {
+            if predicate(&packet) {
+                return packet;
+            }
+        }
+    }
+}
+
+/// Send a key exchange and finish it with the public key the server replies with.
+fn exchange_keys(sender: &mut SocketSender, receiver: &mut SocketReceiver) -> Session {
+    let handshake = Handshake::new();
+    sender
+        .send(Packet::KeyExchange {
+            public_key: handshake.public_key(),
+        })
+        .unwrap();
+
+    match expect(receiver, |p| matches!(p, Packet::KeyExchange { .. })) {
+        Packet::KeyExchange { public_key } => handshake.finish(public_key, Role::Client),
+        _ => unreachable!(),
+    }
+}
+
+fn connection() -> Packet {
+    Packet::Connection {
+        user: String::from("alice"),
+        compression: false,
+    }
+}
+
+/// Start a key exchange with the server, finishing it if the server replies in time.
+fn try_exchange_keys(client: &UdpTransport, server: SocketAddr) -> Option<Session> {
+    let handshake = Handshake::new();
+    client
+        .send_to(
+            Packet::KeyExchange {
+                public_key: handshake.public_key(),
+            },
+            &server,
+        )
+        .unwrap();
+
+    loop {
+        match client.recv_timeout(Duration::from_millis(200)) {
+            Ok((Packet::KeyExchange { public_key }, _peer)) => {
+                return Some(handshake.finish(public_key, Role::Client))
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut => return None,
+            _ => (),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn session_not_replaced() {
+    let server = start_server("replaced", 20);
+    let client = UdpTransport::connect(server).unwrap();
+
+    // Once a session started, plaintext key exchanges from the address are dropped, so a datagram
+    // spoofing the address of a client can't take its session over.
+    let session = try_exchange_keys(&client, server).unwrap();
+    assert!(try_exchange_keys(&client, server).is_none());
+
+    client.set_session(server, Some(session));
+    client.send_to(connection(), &server).unwrap();
+    let welcome = (0..100).any(|_| {
+        matches!(
+            client.recv_timeout(Duration::from_secs(1)),
+            Ok((Packet::Welcome { .. }, _))
+        )
+    });
+    assert!(welcome);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pending_sessions_per_address() {
+    let server = start_server("pending", 20);
+
+    // Clients that don't log in keep their session until it times out, but only a few of them.
+    let clients: Vec<UdpTransport> = (0..=MAX_SESSIONS_PER_IP)
+        .map(|_| UdpTransport::connect(server).unwrap())
+        .collect();
+    for client in &clients[..MAX_SESSIONS_PER_IP] {
+        assert!(try_exchange_keys(client, server).is_some());
+    }
+    assert!(try_exchange_keys(&clients[MAX_SESSIONS_PER_IP], server).is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn session_dropped_when_refused() {
+    let server = start_server("refused", 0);
+    let (mut sender, mut receiver) = split(UdpTransport::connect(server).unwrap());
+
+    within_timeout(move || {
+        let session = exchange_keys(&mut sender, &mut receiver);
+        sender.set_session(server, Some(session));
+        sender.send(connection()).unwrap();
+        expect(&mut receiver, |p| matches!(p, Packet::Disconnect { .. }));
+
+        // The server forgot the session right after refusing, plaintext is accepted again.
+        thread::sleep(Duration::from_millis(100));
+        sender.set_session(server, None);
+        sender.send(connection()).unwrap();
+        expect(&mut receiver, |p| matches!(p, Packet::Disconnect { .. }));
+    });
+}
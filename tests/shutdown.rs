@@ -0,0 +1,114 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+
+fn world_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-shutdown-{name}-{}", std::process::id()))
+}
+
+/// A server with a connected client, stepped until the client receives a packet matching the
+/// predicate.
+fn start(name: &str, predicate: impl Fn(&Packet) -> bool) -> (Game, ChannelTransport, Packet) {
+    let network = LoopbackNetwork::default();
+    let addr = SocketAddr::from(([127, 0, 0, 1], 1));
+    let _ = fs::remove_dir_all(world_dir(name));
+    let config = ServerConfig {
+        world_dir: world_dir(name),
+        view_distance: 1,
+        ..Default::default()
+    };
+    let mut game = Game::new(ChannelTransport::bind(&network, addr).unwrap(), config);
+
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: false,
+            },
+            &addr,
+        )
+        .unwrap();
+
+    for _ in 0..200 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            if predicate(&packet) {
+                return (game, client, packet);
+            }
+        }
+    }
+
+    panic!("no matching packet received");
+}
+
+#[test]
+pub fn shutdown_saves_and_disconnects() {
+    let (mut game, client, chunk) = start("save", |p| matches!(p, Packet::Chunk { .. }));
+    let (x, y) = match chunk {
+        Packet::Chunk { x, y, .. } => (x, y),
+        _ => unreachable!(),
+    };
+    game.execute("tp alice 1 2 3").unwrap();
+    game.execute("stop").unwrap();
+
+    game.shutdown().unwrap();
+
+    let mut reason = None;
+    while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(10)) {
+        if let Packet::Disconnect { reason: r } = packet {
+            reason = Some(r);
+        }
+    }
+    assert_eq!(reason.as_deref(), Some("The server is closed"));
+
+    let dir = world_dir("save");
+    assert!(dir.join("chunks").join(format!("{x}.{y}.chunk")).exists());
+    assert!(dir.join("players").join("alice.toml").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn run_returns_after_stop() {
+    let (game, _client, _welcome) = start("run", |p| matches!(p, Packet::Welcome { .. }));
+    let console = game.console();
+
+    let server = thread::spawn(move || game.run());
+    console.send(String::from("stop")).unwrap();
+
+    server.join().unwrap().unwrap();
+
+    fs::remove_dir_all(world_dir("run")).unwrap();
+}
+
+#[test]
+pub fn close_wakes_up_receiver() {
+    let network = LoopbackNetwork::default();
+    let transport =
+        ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 1))).unwrap();
+
+    thread::scope(|scope| {
+        let receiver = scope.spawn(|| transport.recv_from());
+        thread::sleep(Duration::from_millis(10));
+        transport.close();
+
+        let error = receiver.join().unwrap().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ConnectionAborted);
+    });
+
+    // Later receives fail right away.
+    assert_eq!(
+        transport.recv_from().unwrap_err().kind(),
+        ErrorKind::ConnectionAborted
+    );
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use yave::network::snapshot::{EntityState, Snapshot, SnapshotHistory, SNAPSHOT_HISTORY};
+
+fn state(x: f64) -> EntityState {
+    EntityState {
+        position: [x, 0., 0.],
+        rotation: [0., 0.],
+        velocity: [0., 0., 0.],
+    }
+}
+
+fn snapshot(tick: u64, entities: &[(u32, EntityState)]) -> Snapshot {
+    Snapshot {
+        tick,
+        entities: entities.iter().copied().collect::<BTreeMap<_, _>>(),
+    }
+}
+
+#[test]
+pub fn full_snapshot() {
+    let current = snapshot(1, &[(1, state(1.)), (2, state(2.))]);
+    let (deltas, removed) = current.diff(None);
+
+    assert_eq!(deltas.len(), 2);
+    assert!(deltas.iter().all(|delta| delta.position.is_some()
+        && delta.rotation.is_some()
+        && delta.velocity.is_some()));
+    assert!(removed.is_empty());
+    assert_eq!(Snapshot::apply(1, None, &deltas, &removed), Some(current));
+}
+
+#[test]
+pub fn delta_snapshot() {
+    let baseline = snapshot(1, &[(1, state(1.)), (2, state(2.)), (3, state(3.))]);
+    let current = snapshot(2, &[(1, state(1.)), (2, state(5.)), (4, state(4.))]);
+
+    let (deltas, removed) = current.diff(Some(&baseline));
+
+    // Entity 1 didn't change, entity 2 only moved and entity 4 is new.
+    assert_eq!(deltas.len(), 2);
+    assert_eq!(deltas[0].id, 2);
+    assert!(deltas[0].rotation.is_none() && deltas[0].velocity.is_none());
+    assert_eq!(deltas[1].id, 4);
+    assert!(deltas[1].rotation.is_some());
+    assert_eq!(removed, vec![3]);
+
+    assert_eq!(
+        Snapshot::apply(2, Some(&baseline), &deltas, &removed),
+        Some(current)
+    );
+}
+
+#[test]
+pub fn partial_delta_without_baseline() {
+    let baseline = snapshot(1, &[(1, state(1.))]);
+    let current = snapshot(2, &[(1, state(2.))]);
+    let (deltas, removed) = current.diff(Some(&baseline));
+
+    assert_eq!(Snapshot::apply(2, None, &deltas, &removed), None);
+}
+
+#[test]
+pub fn history() {
+    let mut history = SnapshotHistory::default();
+    for tick in 0..SNAPSHOT_HISTORY as u64 + 5 {
+        history.push(snapshot(tick, &[]));
+    }
+
+    assert!(history.get(0).is_none());
+    assert!(history.get(5).is_some());
+    assert_eq!(history.latest().unwrap().tick, SNAPSHOT_HISTORY as u64 + 4);
+
+    assert!(history.baseline().is_none());
+    history.acknowledge(10);
+    history.acknowledge(8);
+    assert_eq!(history.baseline().unwrap().tick, 10);
+
+    // Acknowledging a snapshot that fell out of the history means a full snapshot is needed.
+    let mut history = SnapshotHistory::default();
+    history.acknowledge(3);
+    assert!(history.baseline().is_none());
+}
@@ -0,0 +1,178 @@
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use yave::client::status::{self, ServerStatus};
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::{Transport, UdpTransport};
+use yave::network::{
+    compression, Packet, MAX_MOTD_LENGTH, MAX_NAME_LENGTH, MAX_STATUS_SAMPLE, PROTOCOL_VERSION,
+    STATUS_REQUEST_SIZE,
+};
+use yave::server::config::ServerConfig;
+use yave::server::game::Game;
+use yave::server::{StatusLimiter, STATUS_BURST, STATUS_REQUESTS_PER_SECOND};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        motd: String::from("Test server"),
+        max_players: 5,
+        view_distance: 1,
+        world_dir: std::env::temp_dir().join(format!("yave-status-{}", std::process::id())),
+        ..Default::default()
+    }
+}
+
+#[test]
+pub fn query() {
+    let network = LoopbackNetwork::default();
+    let server = SocketAddr::from(([127, 0, 0, 1], 1));
+    let mut game = Game::new(ChannelTransport::bind(&network, server).unwrap(), config());
+
+    let alice = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 2))).unwrap();
+    alice
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: false,
+            },
+            &server,
+        )
+        .unwrap();
+    // Step until alice joined, the network thread may not have received her yet.
+    let joined = (0..200).any(|_| {
+        game.step();
+        matches!(
+            alice.recv_timeout(Duration::from_millis(1)),
+            Ok((Packet::Welcome { .. }, _))
+        )
+    });
+    assert!(joined);
+
+    // The server answers without running a tick.
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 3))).unwrap();
+    let status = status::query(&client, server, TIMEOUT).unwrap();
+
+    assert_eq!(status.protocol, PROTOCOL_VERSION);
+    assert!(status.compatible());
+    assert_eq!(status.motd, "Test server");
+    assert_eq!((status.online, status.max_players), (1, 5));
+    assert_eq!(status.sample, ["alice"]);
+
+    // Asking for the status doesn't join.
+    game.step();
+    assert_eq!(
+        game.execute("list"),
+        Ok(String::from("1 of 5 players online: alice"))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn query_udp() {
+    let transport = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let addr = transport.local_addr().unwrap();
+    let _game = Game::new(transport, config());
+
+    let status = status::query_addr(addr, TIMEOUT).unwrap();
+    assert_eq!(status.motd, "Test server");
+    assert_eq!(status.online, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_amplification() {
+    let transport = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let addr = transport.local_addr().unwrap();
+    let _game = Game::new(transport, config());
+
+    // Even the biggest answer is smaller than the request.
+    let status = Packet::Status {
+        token: 0,
+        protocol: PROTOCOL_VERSION,
+        motd: "m".repeat(MAX_MOTD_LENGTH),
+        online: 0,
+        max_players: 0,
+        sample: vec!["n".repeat(MAX_NAME_LENGTH); MAX_STATUS_SAMPLE],
+    };
+    let request = Packet::StatusRequest { token: 1 }.encode().unwrap();
+    assert_eq!(request.len(), STATUS_REQUEST_SIZE);
+    assert!(status.encode().unwrap().len() <= request.len());
+
+    // Compressed requests are not answered.
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let compressed = compression::compress(&request, Some(0)).unwrap();
+    socket.send_to(&compressed, addr).unwrap();
+
+    // Past the burst an address gets no more answers.
+    let raw = compression::compress(&request, None).unwrap();
+    for _ in 0..2 * STATUS_BURST {
+        socket.send_to(&raw, addr).unwrap();
+    }
+
+    let mut buffer = [0u8; STATUS_REQUEST_SIZE];
+    let mut answers = 0;
+    socket
+        .set_read_timeout(Some(Duration::from_millis(200)))
+        .unwrap();
+    while socket.recv(&mut buffer).is_ok() {
+        answers += 1;
+    }
+    assert_eq!(answers, STATUS_BURST);
+}
+
+#[test]
+pub fn limiter() {
+    let mut limiter = StatusLimiter::default();
+    let start = Instant::now();
+    let alice = IpAddr::from([10, 0, 0, 1]);
+    let bob = IpAddr::from([10, 0, 0, 2]);
+
+    for _ in 0..STATUS_BURST {
+        assert!(limiter.allow(alice, start));
+    }
+    assert!(!limiter.allow(alice, start));
+    assert!(limiter.allow(bob, start));
+
+    let later = start + Duration::from_secs(1) / STATUS_REQUESTS_PER_SECOND;
+    assert!(limiter.allow(alice, later));
+    assert!(!limiter.allow(alice, later));
+}
+
+#[test]
+pub fn timeout() {
+    let network = LoopbackNetwork::default();
+    let client = ChannelTransport::bind(&network, SocketAddr::from(([127, 0, 0, 1], 3))).unwrap();
+
+    let error = status::query(
+        &client,
+        SocketAddr::from(([127, 0, 0, 1], 1)),
+        Duration::from_millis(50),
+    )
+    .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::TimedOut);
+}
+
+#[test]
+pub fn display() {
+    let status = ServerStatus {
+        protocol: PROTOCOL_VERSION + 1,
+        motd: String::from("Test server"),
+        online: 14,
+        max_players: 20,
+        sample: vec![String::from("alice"), String::from("bob")],
+        latency: Duration::from_millis(12),
+    };
+
+    assert_eq!(
+        status.to_string(),
+        format!(
+            "Test server\n\
+             14/20 players online: alice, bob and 12 more\n\
+             Protocol {}, incompatible with this client ({PROTOCOL_VERSION})\n\
+             Latency 12 ms",
+            PROTOCOL_VERSION + 1
+        )
+    );
+}
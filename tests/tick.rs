@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+
+use yave::server::tick::{TickScheduler, TickStats};
+
+#[test]
+pub fn stats() {
+    let mut stats = TickStats::default();
+    assert_eq!(stats.average_mspt(), 0.);
+    assert_eq!(stats.max_mspt(), 0.);
+
+    stats.record(Duration::from_millis(10));
+    stats.record(Duration::from_millis(30));
+    assert_eq!(stats.average_mspt(), 20.);
+    assert_eq!(stats.max_mspt(), 30.);
+
+    // Only the recent ticks are kept.
+    for _ in 0..1000 {
+        stats.record(Duration::from_millis(5));
+    }
+    assert_eq!(stats.average_mspt(), 5.);
+    assert_eq!(stats.max_mspt(), 5.);
+}
+
+#[test]
+pub fn sleeps_until_next_tick() {
+    let mut scheduler = TickScheduler::new(20);
+    assert_eq!(scheduler.interval(), Duration::from_millis(50));
+
+    let start = Instant::now();
+    scheduler.finish_tick(Duration::from_millis(10));
+
+    let sleep = scheduler.time_until_next_tick(start);
+    assert!(sleep > Duration::ZERO && sleep <= Duration::from_millis(50));
+    assert!(scheduler.time_until_next_tick(start + Duration::from_millis(60)) == Duration::ZERO);
+}
+
+#[test]
+pub fn catches_up_after_long_ticks() {
+    let mut scheduler = TickScheduler::new(20);
+    let start = Instant::now();
+
+    // The first tick took three intervals, the ones that were due meanwhile run right away.
+    scheduler.finish_tick(Duration::from_millis(150));
+    let now = start + Duration::from_millis(150);
+    for _ in 0..3 {
+        assert_eq!(scheduler.time_until_next_tick(now), Duration::ZERO);
+        scheduler.finish_tick(Duration::ZERO);
+    }
+    assert!(scheduler.time_until_next_tick(now) > Duration::ZERO);
+}
+
+#[test]
+pub fn skips_ticks_when_far_behind() {
+    let mut scheduler = TickScheduler::new(20);
+    let now = Instant::now() + Duration::from_secs(60);
+
+    assert_eq!(scheduler.time_until_next_tick(now), Duration::ZERO);
+    scheduler.finish_tick(Duration::ZERO);
+
+    // Instead of running the 1200 missed ticks, the next one is a normal interval later.
+    let sleep = scheduler.time_until_next_tick(now);
+    assert!(sleep > Duration::from_millis(40) && sleep <= Duration::from_millis(50));
+}
+
+#[test]
+#[should_panic]
+pub fn zero_tick_rate() {
+    TickScheduler::new(0);
+}
@@ -0,0 +1,133 @@
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use yave::network::transport::{Transport, UdpTransport, QUEUE_SIZE};
+use yave::network::Packet;
+use yave::world::chunk::Chunk;
+
+fn ping(sequence: u32) -> Packet {
+    Packet::Command {
+        line: format!("{sequence:04}"),
+    }
+}
+
+/// Two transports on localhost, the client connected to the server.
+fn pair() -> (UdpTransport, UdpTransport) {
+    let server = UdpTransport::bind("127.0.0.1:0").unwrap();
+    let client = UdpTransport::connect(server.local_addr().unwrap()).unwrap();
+
+    (client, server)
+}
+
+#[test]
+pub fn needs_runtime() {
+    let error = UdpTransport::bind("127.0.0.1:0").err().unwrap();
+    assert_eq!(error.to_string(), "UDP transports need a tokio runtime");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn round_trip() {
+    let (client, server) = pair();
+    let client_addr = client.local_addr().unwrap();
+
+    client
+        .send_to(ping(1), &client.peer_addr().unwrap())
+        .unwrap();
+    assert_eq!(
+        server.recv_timeout(Duration::from_secs(1)).unwrap(),
+        (ping(1), client_addr)
+    );
+
+    // Compressed packets come back the same.
+    server.set_compression(client_addr, Some(0));
+    client.set_compression(server.local_addr().unwrap(), Some(0));
+    let chunk = Packet::Chunk {
+        x: 1,
+        y: -1,
+        data: Chunk::new(1, -1).compress(),
+    };
+    server.send_to(chunk.clone(), &client_addr).unwrap();
+    assert_eq!(
+        client.recv_timeout(Duration::from_secs(1)).unwrap().0,
+        chunk
+    );
+
+    assert_eq!(
+        client
+            .recv_timeout(Duration::from_millis(50))
+            .unwrap_err()
+            .kind(),
+        ErrorKind::TimedOut
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unread_packets_are_dropped() {
+    let (client, server) = pair();
+    let server_addr = server.local_addr().unwrap();
+
+    // Sends fail rather than wait once the outgoing queue is full.
+    for sequence in 0..2 * QUEUE_SIZE as u32 {
+        match client.send_to(ping(sequence), &server_addr) {
+            Ok(()) => {}
+            Err(e) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+        }
+    }
+    thread::sleep(Duration::from_millis(200));
+
+    let mut received = 0;
+    while server.recv_timeout(Duration::from_millis(50)).is_ok() {
+        received += 1;
+    }
+    assert!(received > 0);
+    assert!(received <= QUEUE_SIZE, "{received}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn many_packets() {
+    let (client, server) = pair();
+    let server_addr = server.local_addr().unwrap();
+
+    // Sending only queues the datagrams, the packets all arrive in order on localhost.
+    for sequence in 0..200 {
+        client.send_to(ping(sequence), &server_addr).unwrap();
+        if sequence % 50 == 49 {
+            for expected in sequence - 49..=sequence {
+                let (packet, _peer) = server.recv_timeout(Duration::from_secs(1)).unwrap();
+                assert_eq!(packet, ping(expected));
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn close() {
+    let (client, server) = pair();
+    let client = Arc::new(client);
+
+    // Closing wakes up a thread waiting for a packet.
+    let receiver = client.clone();
+    let waiting = thread::spawn(move || receiver.recv_from());
+    thread::sleep(Duration::from_millis(50));
+
+    client
+        .send_to(ping(1), &server.local_addr().unwrap())
+        .unwrap();
+    client.close();
+    assert_eq!(
+        waiting.join().unwrap().unwrap_err().kind(),
+        ErrorKind::ConnectionAborted
+    );
+    assert_eq!(
+        client.recv_from().unwrap_err().kind(),
+        ErrorKind::ConnectionAborted
+    );
+
+    // The packet sent before closing still leaves.
+    assert_eq!(
+        server.recv_timeout(Duration::from_secs(1)).unwrap().0,
+        ping(1)
+    );
+}
@@ -0,0 +1,197 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use yave::network::loopback::{ChannelTransport, LoopbackNetwork};
+use yave::network::transport::Transport;
+use yave::network::Packet;
+use yave::server::command::CommandError;
+use yave::server::config::{ServerConfig, WorldConfig};
+use yave::server::game::Game;
+use yave::world::generator::Generator;
+
+const SERVER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+
+fn world_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("yave-worlds-{name}-{}", std::process::id()))
+}
+
+/// A server with a hilly nether besides the main world.
+fn start(name: &str, network: &LoopbackNetwork) -> Game {
+    let _ = fs::remove_dir_all(world_dir(name));
+    let config = ServerConfig {
+        world_dir: world_dir(name),
+        view_distance: 1,
+        worlds: vec![WorldConfig {
+            name: String::from("nether"),
+            generator: Generator::Hills,
+            seed: 3,
+            dir: None,
+        }],
+        ..Default::default()
+    };
+
+    Game::new(ChannelTransport::bind(network, SERVER).unwrap(), config)
+}
+
+fn connect(network: &LoopbackNetwork, port: u16, user: &str) -> ChannelTransport {
+    let client = ChannelTransport::bind(network, SocketAddr::from(([127, 0, 0, 1], port))).unwrap();
+    client
+        .send_to(
+            Packet::Connection {
+                user: String::from(user),
+                compression: false,
+            },
+            &SERVER,
+        )
+        .unwrap();
+
+    client
+}
+
+/// Step the server until the client receives a packet matching the predicate.
+fn step_until(
+    game: &mut Game,
+    client: &ChannelTransport,
+    predicate: impl Fn(&Packet) -> bool,
+) -> Packet {
+    for _ in 0..200 {
+        game.step();
+        while let Ok((packet, _peer)) = client.recv_timeout(Duration::from_millis(1)) {
+            if predicate(&packet) {
+                return packet;
+            }
+        }
+    }
+
+    panic!("no matching packet received");
+}
+
+#[test]
+pub fn generators() {
+    let flat = Generator::Flat.generate(5, 2, -3);
+    assert!(flat.blocks.iter().all(|block| block.id == "base:stone"));
+
+    // The same seed gives the same hills, another seed other hills.
+    let hills = Generator::Hills.generate(5, 2, -3);
+    assert_eq!(
+        hills.compress(),
+        Generator::Hills.generate(5, 2, -3).compress()
+    );
+    assert!((0..8).any(|x| {
+        Generator::Hills.generate(6, x, -3).compress()
+            != Generator::Hills.generate(5, x, -3).compress()
+    }));
+
+    // Every column is stone under grass under air.
+    for x in 0..16 {
+        for z in 0..16 {
+            let column: Vec<&str> = (0..16)
+                .map(|y| hills.get_block(x, y, z).unwrap().id.as_str())
+                .collect();
+            let top = column.iter().position(|id| *id == "base:grass").unwrap();
+
+            assert!(column[..top].iter().all(|id| *id == "base:stone"));
+            assert!(column[top + 1..].iter().all(|id| *id == "base:air"));
+            assert!(hills.get_block(x, top as u16 + 1, z).unwrap().transparent());
+        }
+    }
+
+    assert_eq!("hills".parse(), Ok(Generator::Hills));
+    assert_eq!(Generator::Flat.to_string(), "flat");
+}
+
+#[test]
+pub fn move_between_worlds() {
+    let network = LoopbackNetwork::default();
+    let mut game = start("move", &network);
+    let alice = connect(&network, 2, "alice");
+    let bob = connect(&network, 3, "bob");
+
+    let id = match step_until(&mut game, &alice, |p| matches!(p, Packet::Welcome { .. })) {
+        Packet::Welcome { id, .. } => id,
+        _ => unreachable!(),
+    };
+    step_until(
+        &mut game,
+        &bob,
+        |p| matches!(p, Packet::Snapshot { entities, .. } if entities.iter().any(|entity| entity.id == id)),
+    );
+
+    assert_eq!(
+        game.execute("world alice nowhere"),
+        Err(CommandError::WorldNotFound(String::from("nowhere")))
+    );
+    assert!(game.execute("world alice overworld").is_err());
+    assert_eq!(game.complete("world alice n"), ["nether"]);
+
+    game.execute("world alice nether").unwrap();
+    assert_eq!(
+        game.execute("worlds"),
+        Ok(String::from("nether: 1 players\noverworld: 1 players"))
+    );
+
+    // Alice drops her chunks and gets the ones of the nether around the spawn.
+    step_until(&mut game, &alice, |p| {
+        *p == Packet::ChangeWorld {
+            name: String::from("nether"),
+        }
+    });
+    match step_until(&mut game, &alice, |p| matches!(p, Packet::Chunk { .. })) {
+        Packet::Chunk { x, y, data } => {
+            assert_eq!(data, Generator::Hills.generate(3, x, y).compress());
+        }
+        _ => unreachable!(),
+    }
+
+    // Bob doesn't see her anymore. He never acknowledges snapshots, they all have every entity.
+    step_until(&mut game, &bob, |p| match p {
+        Packet::Snapshot { entities, .. } => entities.iter().all(|entity| entity.id != id),
+        _ => false,
+    });
+
+    // Blocks are placed in the world of whoever places them.
+    game.execute("setblock 0 0 0 base:grass").unwrap();
+    game.execute("save").unwrap();
+    let dir = world_dir("move");
+    assert!(dir.join("chunks").join("0.0.chunk").exists());
+    assert!(dir
+        .join("worlds")
+        .join("nether")
+        .join("chunks")
+        .join("0.0.chunk")
+        .exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+pub fn world_restored() {
+    let network = LoopbackNetwork::default();
+    let mut game = start("restore", &network);
+    let alice = connect(&network, 2, "alice");
+    step_until(&mut game, &alice, |p| matches!(p, Packet::Welcome { .. }));
+
+    game.execute("world alice nether").unwrap();
+    game.execute("kick alice").unwrap();
+    assert!(game.execute("world alice overworld").is_err());
+
+    // Joining again starts in the world the player left from.
+    alice
+        .send_to(
+            Packet::Connection {
+                user: String::from("alice"),
+                compression: false,
+            },
+            &SERVER,
+        )
+        .unwrap();
+    step_until(&mut game, &alice, |p| matches!(p, Packet::Welcome { .. }));
+    assert_eq!(
+        game.execute("worlds"),
+        Ok(String::from("nether: 1 players\noverworld: 0 players"))
+    );
+
+    fs::remove_dir_all(world_dir("restore")).unwrap();
+}